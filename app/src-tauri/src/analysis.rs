@@ -0,0 +1,155 @@
+//! Pre-analysis of a source before it is encoded: black bars (`cropdetect`) and interlacing
+//! (`idet`) over a few sampled stretches of the video, both in the same decode. Results
+//! are cached per file (and its size and mtime) so the row's analysis and the encode agree and the file is only
+//! decoded once.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use crate::video::{job_key, VideoInfo};
+
+/// Stretches sampled across the video, and the length of each
+const SAMPLES: usize = 5;
+const SAMPLE_SECONDS: f64 = 2.0;
+/// Bars thinner than this share of the frame are left alone
+const MIN_CROP_RATIO: f64 = 0.02;
+/// Share of idet's decided frames from which a source counts as interlaced
+const INTERLACED_RATIO: f64 = 0.5;
+
+#[derive(Clone, Default)]
+pub struct Analysis {
+    pub crop: Option<String>, // "w:h:x:y", None if there are no bars
+    pub interlaced_ratio: Option<f64>, // Share of frames idet found interlaced
+}
+
+impl Analysis {
+    /// Whether the source should be deinterlaced: idet's verdict, or the container's field
+    /// order when idet decided nothing
+    pub fn is_interlaced(&self, info: &VideoInfo) -> bool {
+        match self.interlaced_ratio {
+            Some(ratio) => ratio >= INTERLACED_RATIO,
+            None => info.field_order.as_deref().is_some_and(|f| matches!(f, "tt" | "bb" | "tb" | "bt")),
+        }
+    }
+}
+
+/// What one sample found
+#[derive(Default)]
+struct Sample {
+    picture: Option<(u32, u32, u32, u32)>, // cropdetect's w, h, x, y
+    interlaced: u64, // idet multi-frame TFF + BFF
+    progressive: u64,
+}
+
+/// Job key plus size and mtime, so a file replaced under the same name is analyzed again
+type CacheKey = (String, Option<(u64, u64)>);
+
+static CACHE: Mutex<Option<HashMap<CacheKey, Analysis>>> = Mutex::new(None);
+
+/// Analyze `input_path`. For the crop, each sample reports the area it found picture in and
+/// the union of all samples is kept, so a dark scene never crops into the picture.
+pub fn analyze(ffmpeg_path: &str, input_path: &str, info: &VideoInfo) -> Analysis {
+    let key = (job_key(input_path), crate::metacache::fingerprint(std::path::Path::new(input_path)));
+    if let Some(cached) = CACHE.lock().ok().and_then(|g| g.as_ref()?.get(&key).cloned()) {
+        return cached;
+    }
+
+    // Union of the picture areas as (x1, y1, x2, y2)
+    let mut area: Option<(u32, u32, u32, u32)> = None;
+    let (mut interlaced, mut progressive) = (0, 0);
+    for start in sample_starts(info.duration_sec) {
+        let sample = run_sample(ffmpeg_path, input_path, start);
+        interlaced += sample.interlaced;
+        progressive += sample.progressive;
+        let Some((w, h, x, y)) = sample.picture else { continue };
+        area = Some(match area {
+            Some((x1, y1, x2, y2)) => (x1.min(x), y1.min(y), x2.max(x + w), y2.max(y + h)),
+            None => (x, y, x + w, y + h),
+        });
+    }
+
+    // Samples are decoded upright, so a quarter-turned source is measured with w and h swapped
+    let mut dims = info.resolution.split('x').filter_map(|p| p.parse::<u32>().ok());
+    let frame = dims.next().zip(dims.next())
+        .map(|(w, h)| if matches!(info.rotation, Some(90) | Some(270)) { (h, w) } else { (w, h) });
+    let crop = area.zip(frame).and_then(|((x1, y1, x2, y2), (width, height))| {
+        let (w, h) = ((x2 - x1).min(width), (y2 - y1).min(height));
+        let removed_w = 1.0 - w as f64 / width as f64;
+        let removed_h = 1.0 - h as f64 / height as f64;
+        (w > 0 && h > 0 && (removed_w >= MIN_CROP_RATIO || removed_h >= MIN_CROP_RATIO))
+            .then(|| format!("{}:{}:{}:{}", w, h, x1, y1))
+    });
+    let decided = interlaced + progressive;
+    let analysis = Analysis {
+        crop,
+        interlaced_ratio: (decided > 0).then(|| interlaced as f64 / decided as f64),
+    };
+    println!(
+        "[INFO] Analysis of {}: crop {}, {} interlaced",
+        input_path,
+        analysis.crop.as_deref().unwrap_or("none"),
+        analysis.interlaced_ratio.map_or("?".to_string(), |r| format!("{:.0}%", r * 100.0))
+    );
+    if let Ok(mut guard) = CACHE.lock() {
+        guard.get_or_insert_with(HashMap::new).insert(key, analysis.clone());
+    }
+    analysis
+}
+
+/// Deinterlacing filter for `filter` ("yadif" or "bwdif"): one frame per frame, every
+/// frame deinterlaced since the flags of interlaced sources are often wrong
+pub fn deinterlace_filter(filter: &str) -> Option<String> {
+    matches!(filter, "yadif" | "bwdif").then(|| format!("{}=mode=send_frame:parity=auto:deint=all", filter))
+}
+
+/// Start times of the samples, spread evenly between the ends of the video
+fn sample_starts(duration_sec: f64) -> Vec<f64> {
+    if duration_sec <= SAMPLE_SECONDS * SAMPLES as f64 {
+        return vec![0.0];
+    }
+    (1..=SAMPLES).map(|i| duration_sec * i as f64 / (SAMPLES + 1) as f64).collect()
+}
+
+/// Decode one sample through idet and cropdetect
+fn run_sample(ffmpeg_path: &str, input_path: &str, start: f64) -> Sample {
+    let args = [
+        "-hide_banner", "-nostats",
+        "-ss", &format!("{:.2}", start),
+        "-t", &format!("{:.0}", SAMPLE_SECONDS),
+        "-i", input_path,
+        "-an", "-sn",
+        "-vf", "idet,cropdetect=limit=24:round=2:reset=0",
+        "-f", "null", "-",
+    ];
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(args).stdout(Stdio::null()).stderr(Stdio::piped());
+    let output = match command.output() {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("[WARNING] Analysis failed for {}: {}", input_path, e);
+            return Sample::default();
+        }
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut sample = Sample::default();
+
+    // Last crop=w:h:x:y cropdetect settled on
+    if let Some((_, value)) = stderr.rsplit_once("crop=") {
+        let mut parts = value.split_whitespace().next().unwrap_or("").split(':').filter_map(|p| p.parse::<u32>().ok());
+        sample.picture = parts.next().zip(parts.next()).zip(parts.next().zip(parts.next()))
+            .map(|((w, h), (x, y))| (w, h, x, y));
+    }
+    // "Multi frame detection: TFF:   120 BFF:     0 Progressive:     3 Undetermined:    2"
+    if let Some(line) = stderr.lines().rev().find(|l| l.contains("Multi frame detection:")) {
+        let count = |label: &str| -> u64 {
+            line.split(label).nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0)
+        };
+        sample.interlaced = count("TFF:") + count("BFF:");
+        sample.progressive = count("Progressive:");
+    }
+    sample
+}
@@ -0,0 +1,282 @@
+//! Originals that a job replaces: kept as `<file>.bak` next to the source, as a timestamped
+//! copy in a `.backup` folder (purged after a retention time) or in the OS trash, and put
+//! back by `restore_original`. Also deletes originals once their outputs are done.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::video::{job_key, resolves_to_same_file, CompressionConfig};
+
+/// Folder next to the source that "folder" backups go into
+pub const BACKUP_DIR: &str = ".backup";
+
+/// A replacement made in this session, so it can be undone even when the output got a
+/// different name (replace-original with another container)
+struct Replacement {
+    source: String,
+    output: String,
+    backup: Option<String>, // None = moved to the OS trash
+}
+
+static REPLACEMENTS: Mutex<Vec<Replacement>> = Mutex::new(Vec::new());
+
+/// Output path for replace-original mode: the source's own name, with the extension of
+/// the output the job would otherwise have written
+pub fn replacement_path(input_path: &str, output_path: &str) -> String {
+    match Path::new(output_path).extension() {
+        Some(ext) => Path::new(input_path).with_extension(ext).to_string_lossy().to_string(),
+        None => input_path.to_string(),
+    }
+}
+
+/// First free backup name for `path`: `<file>.bak`, then `<file>.bak2`, ...
+fn backup_path_for(path: &str) -> String {
+    let mut candidate = format!("{}.bak", path);
+    let mut n = 2;
+    while Path::new(&candidate).exists() {
+        candidate = format!("{}.bak{}", path, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Most recent `.bak`/`.bak2`/... next to `path`
+fn latest_bak_for(path: &str) -> Option<String> {
+    let mut latest = None;
+    let mut candidate = format!("{}.bak", path);
+    let mut n = 2;
+    while Path::new(&candidate).exists() {
+        latest = Some(candidate);
+        candidate = format!("{}.bak{}", path, n);
+        n += 1;
+    }
+    latest
+}
+
+/// `.backup` folder entries of `path` (`<name>.<unix secs>`) with their timestamps
+fn folder_backups_for(path: &str) -> Vec<(u64, PathBuf)> {
+    let source = Path::new(path);
+    let (Some(dir), Some(name)) = (source.parent(), source.file_name()) else { return Vec::new() };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = std::fs::read_dir(dir.join(BACKUP_DIR)) else { return Vec::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let file_name = e.file_name().to_string_lossy().to_string();
+            let secs = file_name.strip_prefix(&prefix)?.parse::<u64>().ok()?;
+            Some((secs, e.path()))
+        })
+        .collect()
+}
+
+/// Most recent backup of `path` made by a replacement, `.bak` first, then the `.backup` folder
+pub fn latest_backup_for(path: &str) -> Option<String> {
+    latest_bak_for(path).or_else(|| {
+        folder_backups_for(path)
+            .into_iter()
+            .max_by_key(|(secs, _)| *secs)
+            .map(|(_, p)| p.to_string_lossy().to_string())
+    })
+}
+
+/// Move the source out of the way before its replacement is renamed into place. Returns
+/// the `.bak` path, which `finish_backup` then moves to the configured location.
+pub fn move_aside(input_path: &str) -> Result<String, String> {
+    let backup = backup_path_for(input_path);
+    if let Err(e) = crate::filelock::retry_if_locked(input_path, || std::fs::rename(input_path, &backup)) {
+        eprintln!("[ERROR] Failed to back up source '{}' to '{}': {}", input_path, backup, e);
+        return Err(crate::filelock::describe("Failed to back up source file", &e));
+    }
+    println!("[INFO] Source backed up to {}", backup);
+    Ok(backup)
+}
+
+/// Once the output is in place: move the `.bak` from `move_aside` to `backup_location` and
+/// remember the replacement for `restore_original`. Returns where the original can still
+/// be read (None once it is in the trash).
+pub fn finish_backup(input_path: &str, output_path: &str, aside: String, config: &CompressionConfig) -> Option<String> {
+    let backup = match config.backup_location.as_str() {
+        "folder" => Some(move_to_backup_folder(input_path, &aside, config.backup_retention_hours)),
+        // The post-encode VMAF still needs the original, so it stays a `.bak` until
+        // trash_after_vmaf (VMAF search modes score during the search instead)
+        "trash" if config.enable_vmaf && !config.uses_vmaf_search() => {
+            println!("[INFO] VMAF is enabled, keeping the original of {} as {} instead of trashing it", input_path, aside);
+            Some(aside)
+        }
+        "trash" => match trash::delete(&aside) {
+            Ok(()) => {
+                println!("[INFO] Original of {} moved to the trash", input_path);
+                None
+            }
+            Err(e) => {
+                eprintln!("[WARNING] Could not move {} to the trash, keeping it: {}", aside, e);
+                Some(aside)
+            }
+        },
+        _ => Some(aside),
+    };
+    if let Ok(mut replacements) = REPLACEMENTS.lock() {
+        replacements.push(Replacement {
+            source: input_path.to_string(),
+            output: output_path.to_string(),
+            backup: backup.clone(),
+        });
+    }
+    backup
+}
+
+/// Move a `.bak` that finish_backup kept for the post-encode VMAF to the trash once the
+/// VMAF is done with it. Only backups recorded as replacements are touched. Returns
+/// whether it went to the trash.
+pub fn trash_after_vmaf(backup: &str, config: &CompressionConfig) -> bool {
+    if config.backup_location != "trash" {
+        return false;
+    }
+    let Ok(mut replacements) = REPLACEMENTS.lock() else { return false };
+    let Some(replacement) = replacements.iter_mut().rev().find(|r| r.backup.as_deref() == Some(backup)) else {
+        return false;
+    };
+    match trash::delete(backup) {
+        Ok(()) => {
+            println!("[INFO] VMAF done, original of {} moved to the trash", replacement.source);
+            replacement.backup = None;
+            true
+        }
+        Err(e) => {
+            eprintln!("[WARNING] Could not move {} to the trash, keeping it: {}", backup, e);
+            false
+        }
+    }
+}
+
+/// `.backup/<name>.<unix secs>` next to the source; older copies past the retention time
+/// are purged on the way. Falls back to the `.bak` if the folder can't be used.
+fn move_to_backup_folder(input_path: &str, aside: &str, retention_hours: u32) -> String {
+    let source = Path::new(input_path);
+    let (Some(dir), Some(name)) = (source.parent(), source.file_name()) else { return aside.to_string() };
+    let folder = dir.join(BACKUP_DIR);
+    if let Err(e) = std::fs::create_dir_all(&folder) {
+        eprintln!("[WARNING] Could not create {:?}, keeping {}: {}", folder, aside, e);
+        return aside.to_string();
+    }
+
+    let now = crate::sidecar::now_unix_secs();
+    if retention_hours > 0 {
+        for (secs, old) in folder_backups_for(input_path) {
+            if now.saturating_sub(secs) > retention_hours as u64 * 3600 {
+                match std::fs::remove_file(&old) {
+                    Ok(()) => println!("[INFO] Purged expired backup {:?}", old),
+                    Err(e) => eprintln!("[WARNING] Could not purge expired backup {:?}: {}", old, e),
+                }
+            }
+        }
+    }
+
+    let target = folder.join(format!("{}.{}", name.to_string_lossy(), now));
+    match std::fs::rename(aside, &target) {
+        Ok(()) => target.to_string_lossy().to_string(),
+        Err(e) => {
+            eprintln!("[WARNING] Could not move {} into {:?}, keeping it: {}", aside, folder, e);
+            aside.to_string()
+        }
+    }
+}
+
+/// Undo a replacement: put the backup of `path` (the source or the output that replaced
+/// it) back and drop the compressed file along with its sidecar. Only replacements this
+/// session recorded are undone, so a caller can't get an arbitrary file moved over `path`.
+/// Returns the backup that was restored.
+pub fn restore_original(path: &str) -> Result<String, String> {
+    let key = job_key(path);
+    let recorded = REPLACEMENTS.lock().ok().and_then(|replacements| {
+        replacements
+            .iter()
+            .rev()
+            .find(|r| job_key(&r.source) == key || job_key(&r.output) == key)
+            .map(|r| (r.source.clone(), r.output.clone(), r.backup.clone()))
+    });
+    let (source, output, backup) = match recorded {
+        Some((source, output, Some(backup))) => (source, output, backup),
+        Some((source, _, None)) => {
+            return Err(format!("The original of {} is in the trash; restore it from there", source));
+        }
+        None => return Err(format!("No replacement of {} was recorded in this session", path)),
+    };
+
+    // Keep the compressed file until the backup is in place, so a failed move loses nothing
+    let aside = format!("{}.restoring", output);
+    let had_output = Path::new(&output).exists();
+    if had_output {
+        std::fs::rename(&output, &aside).map_err(|e| format!("Failed to move compressed file aside: {}", e))?;
+    }
+    if let Err(e) = std::fs::rename(&backup, &source) {
+        if had_output {
+            let _ = std::fs::rename(&aside, &output);
+        }
+        eprintln!("[ERROR] Failed to restore '{}' from '{}': {}", source, backup, e);
+        return Err(format!("Failed to restore backup: {}", e));
+    }
+    if had_output {
+        if let Err(e) = std::fs::remove_file(&aside) {
+            eprintln!("[WARNING] Restored {} but could not delete the compressed copy {}: {}", source, aside, e);
+        }
+    }
+
+    // The sidecar describes the compressed file that is now gone
+    let sidecar = crate::sidecar::sidecar_path(&output);
+    if sidecar.exists() {
+        let _ = std::fs::remove_file(&sidecar);
+    }
+    if let Ok(mut replacements) = REPLACEMENTS.lock() {
+        replacements.retain(|r| job_key(&r.source) != job_key(&source));
+    }
+
+    println!("[INFO] Restored original {} from {}", source, backup);
+    Ok(backup)
+}
+
+/// Source of a finished job to delete, with the output it was compressed to
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishedOriginal {
+    pub path: String,
+    pub output: String,
+}
+
+/// An original that `delete_originals` left in place
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Delete the sources of finished jobs, to the OS trash (recycle bin) or permanently.
+/// Sources of jobs that are still running (`is_busy`) are refused, and so are sources that
+/// are their own output (a different spelling of the same path). Returns what failed.
+pub fn delete_originals(originals: &[FinishedOriginal], use_trash: bool, is_busy: impl Fn(&str) -> bool) -> Vec<DeleteFailure> {
+    let mut failures = Vec::new();
+    for FinishedOriginal { path, output } in originals {
+        let result = if is_busy(path) {
+            Err("a job is still using this file".to_string())
+        } else if resolves_to_same_file(path, output) {
+            Err("this file is also the output".to_string())
+        } else if !Path::new(path).is_file() {
+            Err("not a file".to_string())
+        } else if use_trash {
+            trash::delete(path).map_err(|e| e.to_string())
+        } else {
+            crate::filelock::retry_if_locked(path, || std::fs::remove_file(path))
+                .map_err(|e| crate::filelock::describe("Failed to delete", &e))
+        };
+        match result {
+            Ok(()) => println!("[INFO] Deleted original {}{}", path, if use_trash { " (to trash)" } else { "" }),
+            Err(error) => {
+                eprintln!("[ERROR] Could not delete original {}: {}", path, error);
+                failures.push(DeleteFailure { path: path.clone(), error });
+            }
+        }
+    }
+    failures
+}
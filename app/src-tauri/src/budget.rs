@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// A job still to run, as the UI's queue sees it (last = lowest priority)
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetJob {
+    pub path: String,
+    pub duration_sec: f64,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetProposal {
+    pub action: String, // "fasterPreset", "hardwareEncoder" or "defer"
+    pub estimated_secs: f64, // Remaining time if the action is taken
+    pub paths: Vec<String>, // Files to defer (defer only)
+}
+
+/// Sent as `budget-status` whenever the estimate changes
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub deadline_ms: u64,
+    pub available_secs: f64,
+    pub remaining_jobs: usize,
+    // None until the first job of the batch has finished
+    pub estimated_remaining_secs: Option<f64>,
+    pub fits: Option<bool>,
+    pub proposals: Vec<BudgetProposal>,
+}
+
+struct Budget {
+    deadline_ms: u64,
+    started_ms: u64,
+    done_media_secs: f64,
+    video_encoder: String,
+    remaining: Vec<BudgetJob>,
+}
+
+static BUDGET: Mutex<Option<Budget>> = Mutex::new(None);
+
+// Rough speed-ups used for the proposals
+const FASTER_PRESET_TIME: f64 = 0.6;
+const HARDWARE_ENCODER_TIME: f64 = 0.25;
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Set the batch deadline (Unix ms, 0 = no budget) and the jobs still to run.
+/// `new_batch` restarts the throughput measurement.
+pub fn set_budget(app: &AppHandle, deadline_ms: u64, remaining: Vec<BudgetJob>, video_encoder: String, new_batch: bool) {
+    let Ok(mut guard) = BUDGET.lock() else { return };
+    if deadline_ms == 0 {
+        *guard = None;
+        return;
+    }
+    match guard.as_mut() {
+        Some(budget) if !new_batch => {
+            budget.deadline_ms = deadline_ms;
+            budget.remaining = remaining;
+            budget.video_encoder = video_encoder;
+        }
+        _ => {
+            *guard = Some(Budget { deadline_ms, started_ms: now_ms(), done_media_secs: 0.0, video_encoder, remaining });
+        }
+    }
+    if let Some(budget) = guard.as_ref() {
+        let _ = app.emit("budget-status", evaluate(budget));
+    }
+}
+
+/// Count a finished job's media duration towards the batch throughput
+pub fn record_job(app: &AppHandle, input_path: &str, duration_sec: f64) {
+    let Ok(mut guard) = BUDGET.lock() else { return };
+    let Some(budget) = guard.as_mut() else { return };
+    budget.done_media_secs += duration_sec.max(0.0);
+    let key = crate::video::job_key(input_path);
+    budget.remaining.retain(|j| crate::video::job_key(&j.path) != key);
+    let _ = app.emit("budget-status", evaluate(budget));
+}
+
+/// Compare the remaining media time, at the throughput seen so far in this batch (media
+/// seconds per wall second, so parallel jobs count), with the time left until the deadline
+fn evaluate(budget: &Budget) -> BudgetStatus {
+    let now = now_ms();
+    let available_secs = budget.deadline_ms.saturating_sub(now) as f64 / 1000.0;
+    let elapsed_secs = now.saturating_sub(budget.started_ms) as f64 / 1000.0;
+    let throughput = (budget.done_media_secs > 0.0 && elapsed_secs > 0.0)
+        .then(|| budget.done_media_secs / elapsed_secs);
+    let remaining_media: f64 = budget.remaining.iter().map(|j| j.duration_sec).sum();
+    let estimate = throughput.map(|t| remaining_media / t);
+
+    let mut proposals = Vec::new();
+    if let (Some(estimate), Some(throughput)) = (estimate, throughput) {
+        if estimate > available_secs {
+            proposals.push(BudgetProposal {
+                action: "fasterPreset".to_string(),
+                estimated_secs: estimate * FASTER_PRESET_TIME,
+                paths: Vec::new(),
+            });
+            if !crate::video::is_hardware_encoder(&budget.video_encoder) {
+                proposals.push(BudgetProposal {
+                    action: "hardwareEncoder".to_string(),
+                    estimated_secs: estimate * HARDWARE_ENCODER_TIME,
+                    paths: Vec::new(),
+                });
+            }
+            // Drop jobs from the end of the queue until the rest fits
+            let mut kept_media = remaining_media;
+            let mut deferred = Vec::new();
+            for job in budget.remaining.iter().rev() {
+                if kept_media / throughput <= available_secs {
+                    break;
+                }
+                kept_media -= job.duration_sec;
+                deferred.push(job.path.clone());
+            }
+            deferred.reverse();
+            proposals.push(BudgetProposal {
+                action: "defer".to_string(),
+                estimated_secs: kept_media / throughput,
+                paths: deferred,
+            });
+        }
+    }
+
+    BudgetStatus {
+        deadline_ms: budget.deadline_ms,
+        available_secs,
+        remaining_jobs: budget.remaining.len(),
+        estimated_remaining_secs: estimate,
+        fits: estimate.map(|e| e <= available_secs),
+        proposals,
+    }
+}
@@ -0,0 +1,163 @@
+//! CRF search results kept across runs, persisted as `crf-search-cache.json` in the app
+//! cache dir. A result is reused when the file (size, mtime) and the settings the search
+//! depends on are unchanged, so re-running a batch after a crash or an unrelated settings
+//! change skips searches that were already done.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::video::job_key;
+
+/// What a search result depends on besides the file itself
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchKey {
+    pub encoder: String,
+    pub target_vmaf: f32,
+    pub preset: String, // Preset the samples were encoded with, "" = encoder default
+    pub pooling: String, // vmaf_search_pooling
+    pub vmaf_neg: bool,
+    /// Hash of everything else the search depends on (see video::search_settings_hash)
+    #[serde(default)]
+    pub settings: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedSearch {
+    pub path: String,
+    pub size: u64,
+    pub modified_ms: u64,
+    #[serde(flatten)]
+    pub key: SearchKey,
+    pub crf: f32,
+    pub vmaf: f64,
+    pub cached_at: u64, // Unix seconds
+}
+
+/// On-disk layout of the cache file
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct CacheFile {
+    entries: Vec<CachedSearch>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheFileRef<'a> {
+    entries: &'a [CachedSearch],
+}
+
+struct Store {
+    file: Option<PathBuf>,
+    entries: Vec<CachedSearch>,
+    save_pending: bool,
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+/// Entries beyond this drop the oldest
+const MAX_ENTRIES: usize = 10_000;
+/// Changes are written out this long after the first unsaved one, together
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+pub fn load(file: Option<PathBuf>) {
+    let entries = file.as_ref()
+        .and_then(|f| crate::schema::load_file::<CacheFile>(crate::schema::Kind::CrfSearchCache, f))
+        .unwrap_or_default()
+        .entries;
+    println!("[INFO] CRF search cache: {} entries", entries.len());
+    if let Ok(mut guard) = STORE.lock() {
+        *guard = Some(Store { file, entries, save_pending: false });
+    }
+}
+
+fn fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_millis() as u64))
+}
+
+/// Cached (crf, vmaf) of `path` for `key` if the file is unchanged
+pub fn get(path: &str, key: &SearchKey) -> Option<(f32, f64)> {
+    let (size, modified_ms) = fingerprint(Path::new(path))?;
+    let job = job_key(path);
+    let guard = STORE.lock().ok()?;
+    guard.as_ref()?.entries.iter()
+        .find(|e| job_key(&e.path) == job && e.size == size && e.modified_ms == modified_ms && e.key == *key)
+        .map(|e| (e.crf, e.vmaf))
+}
+
+/// Remember a finished search, replacing an older result for the same file and settings
+pub fn put(path: &str, key: SearchKey, crf: f32, vmaf: f64) {
+    let Some((size, modified_ms)) = fingerprint(Path::new(path)) else { return };
+    let Ok(mut guard) = STORE.lock() else { return };
+    let Some(store) = guard.as_mut() else { return };
+    let job = job_key(path);
+    store.entries.retain(|e| !(job_key(&e.path) == job && e.key == key));
+    store.entries.push(CachedSearch {
+        path: path.to_string(),
+        size,
+        modified_ms,
+        key,
+        crf,
+        vmaf,
+        cached_at: crate::sidecar::now_unix_secs(),
+    });
+    if store.entries.len() > MAX_ENTRIES {
+        let excess = store.entries.len() - MAX_ENTRIES;
+        store.entries.drain(..excess);
+    }
+    schedule_save(store);
+}
+
+/// Every cached result, newest first
+pub fn list() -> Vec<CachedSearch> {
+    let mut entries = STORE.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.entries.clone()))
+        .unwrap_or_default();
+    entries.reverse();
+    entries
+}
+
+/// Forget the results for `path`, or all of them. Returns how many were removed.
+pub fn clear(path: Option<&str>) -> Result<usize, String> {
+    let mut guard = STORE.lock().map_err(|e| e.to_string())?;
+    let Some(store) = guard.as_mut() else { return Ok(0) };
+    let before = store.entries.len();
+    match path {
+        Some(path) => {
+            let job = job_key(path);
+            store.entries.retain(|e| job_key(&e.path) != job);
+        }
+        None => store.entries.clear(),
+    }
+    let removed = before - store.entries.len();
+    schedule_save(store);
+    println!("[INFO] CRF search cache: removed {} entries", removed);
+    Ok(removed)
+}
+
+/// Save the cache shortly, once for any number of changes until then
+fn schedule_save(store: &mut Store) {
+    if store.file.is_none() || store.save_pending {
+        return;
+    }
+    store.save_pending = true;
+    std::thread::spawn(|| {
+        std::thread::sleep(SAVE_DELAY);
+        let snapshot = STORE.lock().ok().and_then(|mut guard| {
+            let store = guard.as_mut()?;
+            store.save_pending = false;
+            Some((store.file.clone()?, store.entries.clone()))
+        });
+        let Some((file, entries)) = snapshot else { return };
+        let result = crate::schema::write(&CacheFileRef { entries: &entries })
+            .and_then(|json| crate::schema::save_file(&file, &json));
+        if let Err(e) = result {
+            eprintln!("[WARNING] Could not save the CRF search cache: {}", e);
+        }
+    });
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Numbers from the encoder's end-of-run summary on stderr (libx264, libx265, SVT-AV1),
+/// kept with the result for tuning presets
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderStats {
+    pub preset: Option<String>, // -preset of the encode, the fps depends on it
+    pub avg_qp: Option<f64>,
+    pub i_frames: Option<u64>,
+    pub p_frames: Option<u64>,
+    pub b_frames: Option<u64>,
+    pub b_frame_ratio: Option<f64>, // B-frames / all frames
+    pub encode_fps: Option<f64>,    // Encoder-reported, else frames / wall time
+}
+
+/// Lines worth keeping for `parse`; the rest of stderr is progress and stream info
+pub fn is_summary_line(line: &str) -> bool {
+    line.contains("Avg QP")
+        || line.contains("Average Speed")
+        || (line.contains("encoded ") && line.contains(" frames in "))
+}
+
+/// Value after `key`, up to the next space or comma
+fn number_after(line: &str, key: &str) -> Option<f64> {
+    let rest = line[line.find(key)? + key.len()..].trim_start();
+    let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// Build the stats from the kept summary lines. `encode_args` supplies the preset and
+/// `elapsed_secs` the fallback fps for encoders that don't report one (x264).
+pub fn parse(lines: &[String], encode_args: &[String], elapsed_secs: f64) -> Option<EncoderStats> {
+    let mut stats = EncoderStats {
+        preset: encode_args.iter()
+            .position(|a| a == "-preset")
+            .and_then(|i| encode_args.get(i + 1))
+            .cloned(),
+        ..Default::default()
+    };
+    let mut weighted_qp = 0.0;
+    let mut overall_qp = None;
+
+    for line in lines {
+        // x264 "frame I:5     Avg QP:20.10  size: ..." / x265 "frame I:      5, Avg QP:22.48  kb/s: ..."
+        for (kind, slot) in [("frame I:", &mut stats.i_frames), ("frame P:", &mut stats.p_frames), ("frame B:", &mut stats.b_frames)] {
+            if line.contains(kind) {
+                if let (Some(count), Some(qp)) = (number_after(line, kind), number_after(line, "Avg QP:")) {
+                    *slot = Some(count as u64);
+                    weighted_qp += count * qp;
+                }
+            }
+        }
+        // x265 "encoded 500 frames in 20.12s (24.85 fps), 1234.56 kb/s, Avg QP:26.11"
+        if line.contains(" frames in ") {
+            stats.encode_fps = number_after(line, "(");
+            overall_qp = number_after(line, "Avg QP:");
+        }
+        // SVT-AV1 "Average Speed:          24.850 fps"
+        if line.contains("Average Speed:") {
+            stats.encode_fps = number_after(line, "Average Speed:");
+        }
+    }
+
+    let frames = stats.i_frames.unwrap_or(0) + stats.p_frames.unwrap_or(0) + stats.b_frames.unwrap_or(0);
+    if frames > 0 {
+        stats.avg_qp = overall_qp.or(Some(weighted_qp / frames as f64));
+        stats.b_frame_ratio = Some(stats.b_frames.unwrap_or(0) as f64 / frames as f64);
+        if stats.encode_fps.is_none() && elapsed_secs > 0.0 {
+            stats.encode_fps = Some(frames as f64 / elapsed_secs);
+        }
+    } else {
+        stats.avg_qp = overall_qp;
+    }
+
+    if stats.avg_qp.is_none() && stats.encode_fps.is_none() {
+        return None;
+    }
+    Some(stats)
+}
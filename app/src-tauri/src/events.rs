@@ -0,0 +1,88 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::video::ProgressPayload;
+
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// When set, progress updates are coalesced into `queue-snapshot` events instead of
+/// being emitted one by one as `video-progress`
+static BATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Paths in first-update order, and the latest update per path
+type Pending = (Vec<String>, HashMap<String, ProgressPayload>);
+
+/// Updates not sent yet
+static PENDING: Mutex<Option<Pending>> = Mutex::new(None);
+
+static EPOCH: Mutex<u64> = Mutex::new(0);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshot {
+    pub epoch: u64, // Increments per snapshot; a gap means the UI missed one
+    pub monotonic_ms: u64,
+    pub updates: Vec<ProgressPayload>, // Only jobs that changed since the previous snapshot
+}
+
+/// Switch between per-update `video-progress` events and batched snapshots. The UI turns
+/// batching on for huge queues, where thousands of events per second would stall it.
+pub fn set_batching(app: &AppHandle, enabled: bool) {
+    BATCHING.store(enabled, Ordering::SeqCst);
+    if !enabled {
+        // Send what's still buffered rather than leave it behind
+        flush(app);
+    }
+}
+
+/// Report job progress, directly or through the next snapshot
+pub fn emit_progress(app: &AppHandle, payload: ProgressPayload) {
+    if !BATCHING.load(Ordering::SeqCst) {
+        let _ = app.emit("video-progress", payload);
+        return;
+    }
+    if let Ok(mut guard) = PENDING.lock() {
+        let (order, latest) = guard.get_or_insert_with(Default::default);
+        match latest.get_mut(&payload.path) {
+            Some(previous) => {
+                // A later update without output info mustn't drop the one that had it
+                let output_info = payload.output_info.clone().or(previous.output_info.take());
+                *previous = ProgressPayload { output_info, ..payload };
+            }
+            None => {
+                order.push(payload.path.clone());
+                latest.insert(payload.path.clone(), payload);
+            }
+        }
+    }
+}
+
+/// Emit the buffered updates as one `queue-snapshot`; call every SNAPSHOT_INTERVAL
+pub fn flush(app: &AppHandle) {
+    let updates: Vec<ProgressPayload> = match PENDING.lock() {
+        Ok(mut guard) => match guard.take() {
+            Some((order, mut latest)) => order.iter().filter_map(|p| latest.remove(p)).collect(),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    if updates.is_empty() {
+        return;
+    }
+    let epoch = match EPOCH.lock() {
+        Ok(mut e) => {
+            *e += 1;
+            *e
+        }
+        Err(_) => 0,
+    };
+    let _ = app.emit("queue-snapshot", QueueSnapshot {
+        epoch,
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        updates,
+    });
+}
@@ -0,0 +1,73 @@
+//! Files locked by another program (a player, a sync client) on Windows: detection,
+//! retries with backoff, and the "File in use" error that makes queues defer a job.
+
+use std::io;
+use std::time::Duration;
+
+/// Status and error prefix for a job whose input stayed locked
+pub const FILE_IN_USE: &str = "File in use";
+
+/// How often a locked job is moved to the end of the queue before it counts as failed
+pub const MAX_DEFERRALS: u32 = 3;
+
+/// Waits between attempts on a locked file (about 15 s in total)
+const BACKOFF_MS: [u64; 5] = [500, 1000, 2000, 4000, 8000];
+
+/// ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION: another process has the file open
+/// without sharing. Other platforms don't have mandatory locks.
+pub fn is_sharing_violation(e: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        matches!(e.raw_os_error(), Some(32) | Some(33))
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Run `op`, retrying with backoff while it fails with a sharing violation
+pub fn retry_if_locked<T>(what: &str, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delays = BACKOFF_MS.iter();
+    loop {
+        match op() {
+            Err(e) if is_sharing_violation(&e) => match delays.next() {
+                Some(ms) => {
+                    println!("[INFO] {} is locked by another program, retrying in {} ms", what, ms);
+                    std::thread::sleep(Duration::from_millis(*ms));
+                }
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Wait (with backoff) until `path` can be opened for reading. Only a persistent lock is
+/// an error; anything else is left for ffmpeg to report.
+pub fn wait_until_readable(path: &str) -> Result<(), String> {
+    match retry_if_locked(path, || std::fs::File::open(path)) {
+        Err(e) if is_sharing_violation(&e) => Err(format!("{}: {}", FILE_IN_USE, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `path` is locked right now (no retries)
+pub fn is_locked(path: &str) -> bool {
+    matches!(std::fs::File::open(path), Err(e) if is_sharing_violation(&e))
+}
+
+/// Error message for a failed file operation, marked as "File in use" for lock errors
+pub fn describe(context: &str, e: &io::Error) -> String {
+    if is_sharing_violation(e) {
+        format!("{}: {}: {}", FILE_IN_USE, context, e)
+    } else {
+        format!("{}: {}", context, e)
+    }
+}
+
+/// Errors that should defer the job rather than fail it
+pub fn is_file_in_use_error(error: &str) -> bool {
+    error.starts_with(FILE_IN_USE)
+}
@@ -0,0 +1,120 @@
+//! Kill switch for GPU paths that break on particular NVIDIA driver versions (libvmaf_cuda,
+//! NVENC B-frames). Jobs ask here before using them and fall back to CPU VMAF / `-bf 0`,
+//! logging the reason once per session.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
+use crate::video::CompressionConfig;
+
+pub const VMAF_CUDA: &str = "vmafCuda";
+pub const NVENC_BFRAMES: &str = "nvencBframes";
+
+/// Driver range in which a feature is avoided
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuQuirkRule {
+    pub feature: String, // VMAF_CUDA or NVENC_BFRAMES
+    #[serde(default)]
+    pub min_driver: String, // First affected version, "" = everything older too
+    #[serde(default)]
+    pub max_driver: String, // First good version, "" = not fixed yet
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A rule that matches the installed driver
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveQuirk {
+    pub feature: String,
+    pub driver_version: String,
+    pub reason: String,
+}
+
+static LOGGED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Ranges shipped with the app; `gpu_blacklist` in the settings adds to them
+fn builtin_rules() -> Vec<GpuQuirkRule> {
+    // CUDA 12 minimum driver, per platform
+    let cuda12 = if cfg!(windows) { "527.41" } else { "525.60.13" };
+    vec![GpuQuirkRule {
+        feature: VMAF_CUDA.to_string(),
+        min_driver: String::new(),
+        max_driver: cuda12.to_string(),
+        reason: format!("libvmaf_cuda from CUDA 12 builds of ffmpeg needs driver {} or newer", cuda12),
+    }]
+}
+
+/// Installed NVIDIA driver version, read once through NVML (None without an NVIDIA GPU)
+pub fn driver_version() -> Option<String> {
+    static VERSION: OnceLock<Option<String>> = OnceLock::new();
+    VERSION
+        .get_or_init(|| {
+            if cfg!(target_os = "macos") {
+                return None;
+            }
+            nvml_wrapper::Nvml::init().ok()?.sys_driver_version().ok()
+        })
+        .clone()
+}
+
+/// "535.104.05" -> [535, 104, 5]
+fn parse_version(version: &str) -> Vec<u32> {
+    version.split('.').map(|p| p.trim().parse().unwrap_or(0)).collect()
+}
+
+fn matches(rule: &GpuQuirkRule, driver: &[u32]) -> bool {
+    (rule.min_driver.is_empty() || driver >= parse_version(&rule.min_driver).as_slice())
+        && (rule.max_driver.is_empty() || driver < parse_version(&rule.max_driver).as_slice())
+}
+
+/// Rules that apply to this machine's driver, unless the blacklist is switched off
+pub fn active_quirks(config: &CompressionConfig) -> Vec<ActiveQuirk> {
+    if config.ignore_gpu_blacklist {
+        return Vec::new();
+    }
+    let Some(version) = driver_version() else { return Vec::new() };
+    let driver = parse_version(&version);
+    builtin_rules()
+        .iter()
+        .chain(config.gpu_blacklist.iter())
+        .filter(|rule| matches(rule, &driver))
+        .map(|rule| ActiveQuirk {
+            feature: rule.feature.clone(),
+            driver_version: version.clone(),
+            reason: rule.reason.clone(),
+        })
+        .collect()
+}
+
+/// Whether `feature` must be avoided on this driver; logs the reason the first time
+pub fn blocks(config: &CompressionConfig, feature: &str) -> bool {
+    let Some(quirk) = active_quirks(config).into_iter().find(|q| q.feature == feature) else { return false };
+    if let Ok(mut guard) = LOGGED.lock() {
+        if guard.get_or_insert_with(HashSet::new).insert(feature.to_string()) {
+            println!("[WARNING] Not using {} on NVIDIA driver {}: {}", feature, quirk.driver_version, quirk.reason);
+        }
+    }
+    true
+}
+
+/// Encode args with B-frames turned off for NVENC when the driver is blacklisted for them.
+/// Call before the output path is appended.
+pub fn with_bframe_quirk(config: &CompressionConfig, v_enc: &str, args: Vec<String>) -> Vec<String> {
+    if !v_enc.contains("nvenc") || !blocks(config, NVENC_BFRAMES) {
+        return args;
+    }
+    let mut out = Vec::with_capacity(args.len() + 2);
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-bf" {
+            iter.next();
+            continue;
+        }
+        out.push(arg);
+    }
+    out.extend(["-bf".to_string(), "0".to_string()]);
+    out
+}
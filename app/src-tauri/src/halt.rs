@@ -0,0 +1,99 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchHaltedPayload {
+    pub error_class: String, // "encoder_missing", "disk_full", "permission_denied", "ffmpeg_missing"
+    pub diagnosis: String,
+    pub consecutive_failures: u32,
+    pub last_error: String,
+}
+
+/// Error class of the current failure streak and its length
+static STREAK: Mutex<Option<(&'static str, u32)>> = Mutex::new(None);
+
+/// Failures that will hit every remaining file the same way. Anything else (a broken
+/// source, a bad sample) is file-specific and doesn't count towards a halt.
+fn classify_error(error: &str) -> Option<&'static str> {
+    let lower = error.to_lowercase();
+    if ["no space left", "not enough space", "disk full", "os error 28", "os error 112"].iter().any(|p| lower.contains(p)) {
+        Some("disk_full")
+    } else if ["permission denied", "access is denied", "os error 13", "os error 5)"].iter().any(|p| lower.contains(p)) {
+        Some("permission_denied")
+    } else if ["unknown encoder", "encoder not found", "no nvenc capable devices", "no capable devices found", "cannot load nvcuda", "cannot load libcuda"].iter().any(|p| lower.contains(p)) {
+        Some("encoder_missing")
+    } else if lower.contains("failed to spawn") || lower.contains("failed to run ffmpeg") {
+        Some("ffmpeg_missing")
+    } else {
+        None
+    }
+}
+
+fn diagnosis_for(class: &str) -> &'static str {
+    match class {
+        "disk_full" => "The output drive is out of space.",
+        "permission_denied" => "The output folder (or the sources) can't be written to.",
+        "encoder_missing" => "The selected encoder is not available on this system.",
+        "ffmpeg_missing" => "ffmpeg could not be started.",
+        _ => "",
+    }
+}
+
+/// Count a failed job. Once `threshold` jobs in a row have failed with the same systemic
+/// error class, emit `batch-halted` so the queue stops, and start counting afresh in case
+/// the user resumes (0 disables halting). Returns whether the batch was halted.
+pub fn record_failure(app: &AppHandle, error: &str, threshold: u32) -> bool {
+    let class = match classify_error(error) {
+        Some(c) => c,
+        None => {
+            record_success();
+            return false;
+        }
+    };
+    let count = {
+        let mut guard = match STREAK.lock() {
+            Ok(g) => g,
+            Err(_) => return false,
+        };
+        let count = match *guard {
+            Some((prev, n)) if prev == class => n + 1,
+            _ => 1,
+        };
+        let halt = threshold > 0 && count >= threshold;
+        *guard = if halt { None } else { Some((class, count)) };
+        if !halt {
+            return false;
+        }
+        count
+    };
+
+    let diagnosis = diagnosis_for(class);
+    eprintln!("[ERROR] Halting batch after {} consecutive '{}' failures: {}", count, class, diagnosis);
+    let _ = app.emit("batch-halted", BatchHaltedPayload {
+        error_class: class.to_string(),
+        diagnosis: diagnosis.to_string(),
+        consecutive_failures: count,
+        last_error: error.to_string(),
+    });
+    true
+}
+
+/// A job finished; any failure streak is over
+pub fn record_success() {
+    if let Ok(mut guard) = STREAK.lock() {
+        *guard = None;
+    }
+}
+
+/// A CRF search succeeded: ffmpeg and the encoder work, so a streak of those failures is
+/// over. Disk and permission failures on the output only show in the encode, so theirs
+/// carries on until a job finishes.
+pub fn record_search_success() {
+    if let Ok(mut guard) = STREAK.lock() {
+        if guard.is_some_and(|(class, _)| class == "encoder_missing" || class == "ffmpeg_missing") {
+            *guard = None;
+        }
+    }
+}
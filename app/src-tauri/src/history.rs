@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Finished jobs kept in the history; older ones are dropped
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum JobOutcome {
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// One finished job, with the exact ffmpeg command line of every pass it ran
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub finished_ms: u64,
+    pub input_path: String,
+    pub output_path: String,
+    pub duration_sec: f64,
+    pub outcome: JobOutcome,
+    pub error: Option<String>,
+    pub output_size: Option<u64>,
+    // In the order they ran; a job that fell back to another encoder lists both attempts
+    pub ffmpeg_commands: Vec<String>,
+}
+
+/// On-disk layout of the history file
+#[derive(Serialize, Deserialize, Default)]
+struct HistoryFile {
+    jobs: Vec<HistoryEntry>,
+}
+
+/// The report written by export(): the history plus what produced it
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryReport<'a> {
+    app_version: &'a str,
+    generated_ms: u64,
+    jobs: &'a [HistoryEntry],
+}
+
+struct Store {
+    file: Option<PathBuf>,
+    jobs: Vec<HistoryEntry>,
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+/// Commands of jobs still running, by job key
+static RUNNING: Mutex<Option<HashMap<String, Vec<String>>>> = Mutex::new(None);
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Load `job-history.json` from the app data dir
+pub fn load(file: Option<PathBuf>) {
+    let jobs = file.as_ref()
+        .and_then(|f| crate::schema::load_file::<HistoryFile>(crate::schema::Kind::JobHistory, f))
+        .unwrap_or_default()
+        .jobs;
+    if let Ok(mut guard) = STORE.lock() {
+        *guard = Some(Store { file, jobs });
+    }
+}
+
+/// Note an ffmpeg command line as a job runs it
+pub fn record_command(input_path: &str, command: String) {
+    if let Ok(mut guard) = RUNNING.lock() {
+        guard.get_or_insert_with(HashMap::new)
+            .entry(crate::video::job_key(input_path))
+            .or_default()
+            .push(command);
+    }
+}
+
+/// Add a finished job, with the commands recorded for it, and save the history
+pub fn finish(input_path: &str, output_path: &str, duration_sec: f64, result: &Result<(), String>) {
+    let ffmpeg_commands = RUNNING.lock().ok()
+        .and_then(|mut g| g.as_mut().and_then(|running| running.remove(&crate::video::job_key(input_path))))
+        .unwrap_or_default();
+    let (outcome, error) = match result {
+        Ok(()) => (JobOutcome::Done, None),
+        Err(e) if crate::video::is_cancelled(e) => (JobOutcome::Cancelled, None),
+        Err(e) => (JobOutcome::Failed, Some(e.clone())),
+    };
+    let entry = HistoryEntry {
+        finished_ms: now_ms(),
+        input_path: input_path.to_string(),
+        output_path: output_path.to_string(),
+        duration_sec,
+        outcome,
+        error,
+        output_size: result.as_ref().ok().and_then(|_| std::fs::metadata(output_path).ok()).map(|m| m.len()),
+        ffmpeg_commands,
+    };
+
+    let Ok(mut guard) = STORE.lock() else { return };
+    let store = guard.get_or_insert_with(|| Store { file: None, jobs: Vec::new() });
+    store.jobs.push(entry);
+    if store.jobs.len() > MAX_ENTRIES {
+        let excess = store.jobs.len() - MAX_ENTRIES;
+        store.jobs.drain(..excess);
+    }
+    if let Some(file) = &store.file {
+        let saved = crate::schema::write(&HistoryFile { jobs: store.jobs.clone() })
+            .and_then(|json| crate::schema::save_file(file, &json));
+        if let Err(e) = saved {
+            eprintln!("[WARNING] Failed to save job history: {}", e);
+        }
+    }
+}
+
+/// All finished jobs, oldest first
+pub fn list() -> Vec<HistoryEntry> {
+    STORE.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.jobs.clone()))
+        .unwrap_or_default()
+}
+
+pub fn clear() -> Result<(), String> {
+    let mut guard = STORE.lock().map_err(|e| e.to_string())?;
+    let Some(store) = guard.as_mut() else { return Ok(()) };
+    store.jobs.clear();
+    if let Some(file) = &store.file {
+        crate::schema::save_file(file, &crate::schema::write(&HistoryFile::default())?)?;
+    }
+    Ok(())
+}
+
+/// Write the history as a JSON report (for bug reports and reproducing results)
+pub fn export(path: &str, app_version: &str) -> Result<usize, String> {
+    let jobs = list();
+    let report = HistoryReport { app_version, generated_ms: now_ms(), jobs: &jobs };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write report: {}", e))?;
+    println!("[INFO] Exported {} jobs to {}", jobs.len(), path);
+    Ok(jobs.len())
+}
@@ -1,38 +1,104 @@
+mod monitor;
+mod analysis;
+mod backup;
+mod budget;
+mod crfcache;
+mod encstats;
+mod events;
+mod filelock;
+mod gpuquirks;
+mod halt;
+mod history;
+mod loudness;
+mod manifest;
+mod metacache;
+mod naming;
+mod nvenc;
+mod phase;
+mod presets;
+mod pool;
+mod pooling;
+mod process;
+mod queue;
+mod reconcile;
+mod remux;
+mod schema;
+mod scratch;
+mod service;
+mod settings;
+mod sidecar;
+mod svtav1;
+mod throttle;
+mod thermal;
+mod tiers;
+mod timeline;
+mod transfer;
+mod tuning;
 mod video;
+mod vmafmodels;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Emitter, State, Manager}; // Manager added for manage()
-use sysinfo::{System};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
 use nvml_wrapper::Nvml;
 use std::time::Duration;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::process::Command; // For cancel command
 
 #[derive(serde::Serialize, Clone)]
 struct SystemStats {
     cpu_usage: f32,
     memory_usage: f32,
     gpu_usage: f32,
+    monotonic_ms: u64,
 }
 
+/// Stats emission interval while ffmpeg jobs are running, and while idle
+const STATS_INTERVAL_ACTIVE: Duration = Duration::from_millis(500);
+const STATS_INTERVAL_IDLE: Duration = Duration::from_millis(2000);
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 struct ProcessingState {
     pids: Arc<Mutex<HashMap<String, u32>>>,
     cancelled_paths: Arc<Mutex<HashSet<String>>>,
     vmaf_state: Arc<Mutex<video::VmafState>>,
+    job_queue: Arc<Mutex<queue::JobQueue>>,
+}
+
+const FFMPEG_BIN: &str = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+const FFPROBE_BIN: &str = if cfg!(windows) { "ffprobe.exe" } else { "ffprobe" };
+
+/// Package-manager install of `bin`. Apps started from Finder don't get the shell's
+/// PATH, so Homebrew binaries aren't found by name on macOS.
+fn system_binary(bin: &str) -> Option<String> {
+    let dirs: &[&str] = if cfg!(target_os = "macos") {
+        &["/opt/homebrew/bin", "/usr/local/bin", "/opt/local/bin"]
+    } else {
+        &[]
+    };
+    dirs.iter()
+        .map(|d| Path::new(d).join(bin))
+        .find(|p| p.is_file())
+        .map(|p| p.to_string_lossy().to_string())
 }
 
-// Helper function to resolve FFmpeg path from bundled resources
+// Helper function to resolve FFmpeg path: user setting, then bundled resources
 fn resolve_ffmpeg_path(app: &AppHandle) -> String {
     println!("[DEBUG] Resolving ffmpeg path...");
+
+    if let Some(custom) = settings::custom_ffmpeg_path() {
+        println!("[DEBUG] Using configured ffmpeg at: {}", custom);
+        return custom;
+    }
     
     // Try bundled resource directory first
     if let Ok(resource_dir) = app.path().resource_dir() {
         println!("[DEBUG] Resource dir: {:?}", resource_dir);
         // Try multiple possible paths in bundled resources
         let bundled_candidates = vec![
-            resource_dir.join("ffmpeg/bin/ffmpeg.exe"),  // Standard: bin subdirectory
-            resource_dir.join("ffmpeg/ffmpeg.exe"),       // Alternative: direct in ffmpeg dir
+            resource_dir.join("ffmpeg/bin").join(FFMPEG_BIN),  // Standard: bin subdirectory
+            resource_dir.join("ffmpeg").join(FFMPEG_BIN),       // Alternative: direct in ffmpeg dir
         ];
         
         for bundled_path in bundled_candidates {
@@ -47,7 +113,7 @@ fn resolve_ffmpeg_path(app: &AppHandle) -> String {
     }
 
     // Fallback 1: Try relative path from dev build
-    let ffmpeg_rel = PathBuf::from("../ffmpeg/bin/ffmpeg.exe");
+    let ffmpeg_rel = PathBuf::from("../ffmpeg/bin").join(FFMPEG_BIN);
     println!("[DEBUG] Checking relative path: {:?}", ffmpeg_rel);
     if ffmpeg_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&ffmpeg_rel) {
@@ -59,7 +125,7 @@ fn resolve_ffmpeg_path(app: &AppHandle) -> String {
     }
 
     // Fallback 2: Try project root relative path
-    let root_rel = PathBuf::from("ffmpeg/bin/ffmpeg.exe");
+    let root_rel = PathBuf::from("ffmpeg/bin").join(FFMPEG_BIN);
     println!("[DEBUG] Checking root relative path: {:?}", root_rel);
     if root_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&root_rel) {
@@ -71,7 +137,7 @@ fn resolve_ffmpeg_path(app: &AppHandle) -> String {
     }
 
     // Fallback 3: Try one more level up
-    let up_rel = PathBuf::from("../../ffmpeg/bin/ffmpeg.exe");
+    let up_rel = PathBuf::from("../../ffmpeg/bin").join(FFMPEG_BIN);
     println!("[DEBUG] Checking ../../ relative path: {:?}", up_rel);
     if up_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&up_rel) {
@@ -84,9 +150,9 @@ fn resolve_ffmpeg_path(app: &AppHandle) -> String {
 
     // Fallback 4: Try alternative paths (direct ffmpeg folder)
     let alt_paths = vec![
-        PathBuf::from("ffmpeg/ffmpeg.exe"),
-        PathBuf::from("../ffmpeg/ffmpeg.exe"),
-        PathBuf::from("../../ffmpeg/ffmpeg.exe"),
+        PathBuf::from("ffmpeg").join(FFMPEG_BIN),
+        PathBuf::from("../ffmpeg").join(FFMPEG_BIN),
+        PathBuf::from("../../ffmpeg").join(FFMPEG_BIN),
     ];
     
     for alt_path in alt_paths {
@@ -101,22 +167,32 @@ fn resolve_ffmpeg_path(app: &AppHandle) -> String {
         }
     }
 
+    if let Some(path) = system_binary(FFMPEG_BIN) {
+        println!("[DEBUG] Found ffmpeg at: {}", path);
+        return path;
+    }
+
     // Last resort: hope it's in PATH
     println!("[DEBUG] FFmpeg not found, using PATH");
-    "ffmpeg.exe".to_string()
+    FFMPEG_BIN.to_string()
 }
 
-// Helper function to resolve FFprobe path from bundled resources
+// Helper function to resolve FFprobe path: user setting, then bundled resources
 fn resolve_ffprobe_path(app: &AppHandle) -> String {
     println!("[DEBUG] Resolving ffprobe path...");
+
+    if let Some(custom) = settings::custom_ffprobe_path() {
+        println!("[DEBUG] Using configured ffprobe at: {}", custom);
+        return custom;
+    }
     
     // Try bundled resource directory first
     if let Ok(resource_dir) = app.path().resource_dir() {
         println!("[DEBUG] Resource dir: {:?}", resource_dir);
         // Try multiple possible paths in bundled resources
         let bundled_candidates = vec![
-            resource_dir.join("ffmpeg/bin/ffprobe.exe"),  // Standard: bin subdirectory
-            resource_dir.join("ffmpeg/ffprobe.exe"),       // Alternative: direct in ffmpeg dir
+            resource_dir.join("ffmpeg/bin").join(FFPROBE_BIN),  // Standard: bin subdirectory
+            resource_dir.join("ffmpeg").join(FFPROBE_BIN),       // Alternative: direct in ffmpeg dir
         ];
         
         for bundled_path in bundled_candidates {
@@ -131,7 +207,7 @@ fn resolve_ffprobe_path(app: &AppHandle) -> String {
     }
 
     // Fallback 1: Try relative path from dev build
-    let ffprobe_rel = PathBuf::from("../ffmpeg/bin/ffprobe.exe");
+    let ffprobe_rel = PathBuf::from("../ffmpeg/bin").join(FFPROBE_BIN);
     println!("[DEBUG] Checking relative path: {:?}", ffprobe_rel);
     if ffprobe_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&ffprobe_rel) {
@@ -143,7 +219,7 @@ fn resolve_ffprobe_path(app: &AppHandle) -> String {
     }
 
     // Fallback 2: Try project root relative path
-    let root_rel = PathBuf::from("ffmpeg/bin/ffprobe.exe");
+    let root_rel = PathBuf::from("ffmpeg/bin").join(FFPROBE_BIN);
     println!("[DEBUG] Checking root relative path: {:?}", root_rel);
     if root_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&root_rel) {
@@ -155,7 +231,7 @@ fn resolve_ffprobe_path(app: &AppHandle) -> String {
     }
 
     // Fallback 3: Try one more level up
-    let up_rel = PathBuf::from("../../ffmpeg/bin/ffprobe.exe");
+    let up_rel = PathBuf::from("../../ffmpeg/bin").join(FFPROBE_BIN);
     println!("[DEBUG] Checking ../../ relative path: {:?}", up_rel);
     if up_rel.exists() {
         if let Ok(canonical) = std::fs::canonicalize(&up_rel) {
@@ -168,9 +244,9 @@ fn resolve_ffprobe_path(app: &AppHandle) -> String {
 
     // Fallback 4: Try alternative paths (direct ffmpeg folder)
     let alt_paths = vec![
-        PathBuf::from("ffmpeg/ffprobe.exe"),
-        PathBuf::from("../ffmpeg/ffprobe.exe"),
-        PathBuf::from("../../ffmpeg/ffprobe.exe"),
+        PathBuf::from("ffmpeg").join(FFPROBE_BIN),
+        PathBuf::from("../ffmpeg").join(FFPROBE_BIN),
+        PathBuf::from("../../ffmpeg").join(FFPROBE_BIN),
     ];
     
     for alt_path in alt_paths {
@@ -185,9 +261,99 @@ fn resolve_ffprobe_path(app: &AppHandle) -> String {
         }
     }
 
+    if let Some(path) = system_binary(FFPROBE_BIN) {
+        println!("[DEBUG] Found ffprobe at: {}", path);
+        return path;
+    }
+
     // Last resort: hope it's in PATH
     println!("[DEBUG] FFprobe not found, using PATH");
-    "ffprobe.exe".to_string()
+    FFPROBE_BIN.to_string()
+}
+
+/// The quality tiers the UI offers as presets
+#[tauri::command]
+async fn get_quality_tiers() -> Result<Vec<tiers::QualityTier>, String> {
+    Ok(tiers::TIERS.to_vec())
+}
+
+#[tauri::command]
+async fn get_settings() -> Result<settings::BackendSettings, String> {
+    Ok(settings::get())
+}
+
+#[tauri::command]
+async fn set_settings(settings: settings::BackendSettings) -> Result<(), String> {
+    settings::set(settings)
+}
+
+#[tauri::command]
+async fn list_presets() -> Result<Vec<presets::Preset>, String> {
+    Ok(presets::list())
+}
+
+#[tauri::command]
+async fn save_preset(name: String, config: video::CompressionConfig) -> Result<(), String> {
+    presets::save(&name, config)
+}
+
+#[tauri::command]
+async fn delete_preset(name: String) -> Result<(), String> {
+    presets::delete(&name)
+}
+
+/// Finished jobs with the ffmpeg command lines they ran, oldest first
+#[tauri::command]
+async fn list_job_history() -> Result<Vec<history::HistoryEntry>, String> {
+    Ok(history::list())
+}
+
+#[tauri::command]
+async fn clear_job_history() -> Result<(), String> {
+    history::clear()
+}
+
+/// Write the job history as a JSON report to `path`; returns the number of jobs
+#[tauri::command]
+async fn export_job_history(app: AppHandle, path: String) -> Result<usize, String> {
+    history::export(&path, &app.package_info().version.to_string())
+}
+
+fn service_paths(app: &AppHandle) -> service::ServicePaths {
+    service::ServicePaths::new(app.path().app_config_dir().ok(), app.path().app_data_dir().ok())
+}
+
+#[tauri::command]
+async fn get_service_config(app: AppHandle) -> Result<service::ServiceConfig, String> {
+    Ok(service::load_config(&service_paths(&app)))
+}
+
+#[tauri::command]
+async fn set_service_config(app: AppHandle, config: service::ServiceConfig) -> Result<(), String> {
+    service::save_config(&service_paths(&app), config)
+}
+
+/// Status of the background service, None if it never ran
+#[tauri::command]
+async fn get_service_status(app: AppHandle) -> Result<Option<service::ServiceStatus>, String> {
+    Ok(service::status(&service_paths(&app)))
+}
+
+#[tauri::command]
+async fn stop_service(app: AppHandle) -> Result<(), String> {
+    service::request_stop(&service_paths(&app))
+}
+
+/// Register the service to start at logon, and start it
+#[tauri::command]
+async fn install_service() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(service::install).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn uninstall_service(app: AppHandle) -> Result<(), String> {
+    let _ = service::request_stop(&service_paths(&app));
+    tauri::async_runtime::spawn_blocking(service::uninstall).await.map_err(|e| e.to_string())?
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -226,14 +392,27 @@ fn get_background_image(app: AppHandle) -> Option<Vec<u8>> {
     None
 }
 
+/// Scans take an optional filter, which probes every found file (see ScanFilter)
 #[tauri::command]
-async fn scan_directory(path: String) -> Result<video::ScanResult, String> {
-    Ok(video::scan_videos(&path))
+async fn scan_directory(app: AppHandle, path: String, filter: Option<video::ScanFilter>, scan_id: u64) -> Result<(), String> {
+    // Returns right away; the results arrive as `scan-progress` events
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        video::stream_scan(&app, &path, filter.as_ref(), &ffprobe_path, scan_id);
+    });
+    Ok(())
 }
 
 #[tauri::command]
-async fn scan_multiple_paths(paths: Vec<String>) -> Result<video::ScanResult, String> {
-    Ok(video::scan_multiple_paths(paths))
+async fn scan_multiple_paths(app: AppHandle, paths: Vec<String>, filter: Option<video::ScanFilter>) -> Result<video::ScanResult, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = video::scan_multiple_paths(paths);
+        match filter {
+            Some(filter) => video::filter_scan(result, &filter, &ffprobe_path),
+            None => result,
+        }
+    }).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -247,6 +426,32 @@ async fn get_video_metadata(app: AppHandle, path: String) -> Result<video::Video
     video::get_metadata(&path, &ffprobe_path)
 }
 
+#[tauri::command]
+async fn get_video_metadata_quick(app: AppHandle, path: String) -> Result<video::VideoInfo, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    video::get_metadata_quick(&path, &ffprobe_path)
+}
+
+/// Probe plus pre-analysis (black bars, interlacing); decodes a few samples of the video
+#[tauri::command]
+async fn analyze_video(app: AppHandle, path: String) -> Result<video::VideoInfo, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || video::analyze_video(&ffmpeg_path, &ffprobe_path, &path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Probe many files at once; results arrive as `metadata-progress` events
+#[tauri::command]
+async fn get_video_metadata_batch(app: AppHandle, paths: Vec<String>, parallelism: u32, batch_id: u64) -> Result<(), String> {
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        video::probe_metadata_batch(&app, paths, parallelism as usize, &ffprobe_path, batch_id);
+    });
+    Ok(())
+}
+
 #[tauri::command]
 async fn detect_encoders(app: AppHandle) -> Result<video::DetectionReport, String> {
     let ffmpeg_path = resolve_ffmpeg_path(&app);
@@ -267,72 +472,471 @@ async fn start_processing(
     let cancelled_paths = state.cancelled_paths.clone();
     let vmaf_state = state.vmaf_state.clone();
 
+    let halt_threshold = config.halt_after_failures;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = video::process_video(app.clone(), &ffmpeg_path, input_path.clone(), output_path, config, duration_sec, pids, cancelled_paths, vmaf_state);
+        match &result {
+            Ok(()) => halt::record_success(),
+            Err(e) => {
+                phase::emit_job_failed(&app, &input_path, e);
+                if !video::is_cancelled(e) {
+                    halt::record_failure(&app, e, halt_threshold);
+                }
+            }
+        }
+        result
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Run a batch described by a JSON job manifest (paths + config + output template)
+#[tauri::command]
+async fn submit_manifest(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    json: String,
+) -> Result<manifest::ManifestResult, String> {
+    let job_manifest = manifest::parse_manifest(&json)?;
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    let vmaf_state = state.vmaf_state.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        manifest::run_manifest(&app, job_manifest, &ffmpeg_path, &ffprobe_path, pids, cancelled_paths, vmaf_state)
+    }).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn enqueue_job(
+    state: State<'_, ProcessingState>,
+    input_path: String,
+    output_path: String,
+    config: video::CompressionConfig,
+    duration_sec: f64
+) -> Result<queue::QueuedJob, String> {
+    let mut job_queue = state.job_queue.lock().map_err(|e| e.to_string())?;
+    Ok(job_queue.enqueue(input_path, output_path, config, duration_sec))
+}
+
+#[tauri::command]
+async fn list_jobs(state: State<'_, ProcessingState>) -> Result<Vec<queue::QueuedJob>, String> {
+    let job_queue = state.job_queue.lock().map_err(|e| e.to_string())?;
+    Ok(job_queue.list())
+}
+
+/// Encode a folder of images, or a file the probe reported as "Image sequence", at `fps`
+#[tauri::command]
+async fn encode_image_sequence(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    input_path: String,
+    output_path: String,
+    fps: f64,
+    config: video::CompressionConfig
+) -> Result<(), String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        video::encode_image_sequence(app, &ffmpeg_path, input_path, output_path, fps, config, pids, cancelled_paths)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Pull the audio track(s) of a video out into mp3/aac/flac/opus files next to it
+#[tauri::command]
+async fn extract_audio(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    input_path: String,
+    format: String
+) -> Result<Vec<String>, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        video::extract_audio(app, &ffmpeg_path, input_path, &format, pids)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Encode a short window of a file with the current settings, next to a reference cut of
+/// the same window, for an A/B comparison before the batch
+#[tauri::command]
+async fn preview_encode(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    path: String,
+    start: f64,
+    duration: f64,
+    config: video::CompressionConfig
+) -> Result<video::PreviewClip, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        video::encode_preview(&app, &ffmpeg_path, &path, start, duration, &config, &pids, &cancelled_paths)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Delete the clips of a preview (shredded with `shred`, like other scratch files)
+#[tauri::command]
+async fn discard_preview(paths: Vec<String>, shred: bool) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || video::discard_previews(&paths, shred))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn requeue_with_changes(
+    state: State<'_, ProcessingState>,
+    job_id: u64,
+    overrides: serde_json::Value
+) -> Result<queue::QueuedJob, String> {
+    let mut job_queue = state.job_queue.lock().map_err(|e| e.to_string())?;
+    job_queue.requeue_with_changes(job_id, &overrides)
+}
+
+#[tauri::command]
+async fn remove_job(state: State<'_, ProcessingState>, id: u64) -> Result<(), String> {
+    let mut job_queue = state.job_queue.lock().map_err(|e| e.to_string())?;
+    job_queue.remove(id)
+}
+
+/// Work through the pending jobs of the persistent queue, including ones restored
+/// after a restart, `maxConcurrentEncodes` at a time. Stops when the queue is empty
+/// or the batch halts. Returns the number of jobs run.
+#[tauri::command]
+async fn run_job_queue(app: AppHandle, state: State<'_, ProcessingState>) -> Result<usize, String> {
+    let job_queue = state.job_queue.clone();
+    {
+        let mut q = job_queue.lock().map_err(|e| e.to_string())?;
+        if q.runner_active {
+            return Err("The job queue is already running".to_string());
+        }
+        q.runner_active = true;
+    }
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    let vmaf_state = state.vmaf_state.clone();
+
+    // Worker count comes from the pending jobs' settings; each encode still takes a slot
+    let workers = job_queue.lock().map_err(|e| e.to_string())?
+        .list().iter()
+        .filter(|j| j.status == queue::QueuedJobStatus::Pending)
+        .map(|j| j.config.job_workers())
+        .max()
+        .unwrap_or(1);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let processed = std::sync::atomic::AtomicUsize::new(0);
+        let halted = std::sync::atomic::AtomicBool::new(false);
+        pool::run_workers(workers, || loop {
+            if halted.load(std::sync::atomic::Ordering::SeqCst) {
+                break;
+            }
+            let job = match job_queue.lock() {
+                Ok(mut q) => q.start_next(),
+                Err(_) => None,
+            };
+            let Some(job) = job else { break };
+
+            let halt_threshold = job.config.halt_after_failures;
+            let result = video::process_video(
+                app.clone(), &ffmpeg_path, job.input_path.clone(), job.output_path.clone(), job.config,
+                job.duration_sec, pids.clone(), cancelled_paths.clone(), vmaf_state.clone(),
+            );
+            processed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let deferred = match job_queue.lock() {
+                Ok(mut q) => q.finish(job.id, &result),
+                Err(_) => false,
+            };
+            if deferred {
+                continue;
+            }
+            match &result {
+                Ok(()) => halt::record_success(),
+                Err(e) => {
+                    phase::emit_job_failed(&app, &job.input_path, e);
+                    if !video::is_cancelled(e) && halt::record_failure(&app, e, halt_threshold) {
+                        halted.store(true, std::sync::atomic::Ordering::SeqCst);
+                    }
+                }
+            }
+        });
+        if let Ok(mut q) = job_queue.lock() {
+            q.runner_active = false;
+        }
+        processed.into_inner()
+    }).await.map_err(|e| e.to_string())
+}
+
+/// CRF search + compression of one file under a single job id, so a cancel or pause
+/// applies to whichever phase is running (see video::begin_pipeline)
+#[tauri::command]
+async fn run_vmaf_pipeline(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    input_path: String,
+    output_path: String,
+    config: video::CompressionConfig,
+    duration_sec: f64
+) -> Result<(), String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    let vmaf_state = state.vmaf_state.clone();
+
+    let halt_threshold = config.halt_after_failures;
+    let pipeline = video::begin_pipeline(&input_path, &cancelled_paths)?;
+
     tauri::async_runtime::spawn_blocking(move || {
-        video::process_video(app, &ffmpeg_path, input_path, output_path, config, duration_sec, pids, cancelled_paths, vmaf_state)
+        let result = video::process_video(app.clone(), &ffmpeg_path, input_path.clone(), output_path, config, duration_sec, pids, cancelled_paths, vmaf_state);
+        drop(pipeline);
+        match &result {
+            Ok(()) => halt::record_success(),
+            Err(e) => {
+                phase::emit_job_failed(&app, &input_path, e);
+                if !video::is_cancelled(e) {
+                    halt::record_failure(&app, e, halt_threshold);
+                }
+            }
+        }
+        result
     }).await.map_err(|e| e.to_string())?
 }
 
+/// Coalesce progress updates into queue-snapshot events (for very large queues)
+#[tauri::command]
+async fn set_event_batching(app: AppHandle, enabled: bool) -> Result<(), String> {
+    events::set_batching(&app, enabled);
+    Ok(())
+}
+
+/// Emergency pause on GPU overheating / pegged CPU (limit in °C, 0 = default)
+#[tauri::command]
+async fn set_thermal_guard(app: AppHandle, enabled: bool, gpu_max_temp: u32) -> Result<(), String> {
+    thermal::configure(&app, enabled, gpu_max_temp);
+    Ok(())
+}
+
 #[tauri::command]
 async fn cancel_processing(
     app: AppHandle,
     state: State<'_, ProcessingState>,
     path: String
 ) -> Result<(), String> {
+    let key = video::job_key(&path);
     let pid_opt = {
         let map = state.pids.lock().map_err(|e| e.to_string())?;
-        map.get(&path).cloned()
+        map.get(&key).cloned()
     };
 
+    throttle::clear_job_speed_limit(&path);
+    video::set_user_paused(&path, false);
+
     if let Some(pid) = pid_opt {
         // Mark as cancelled BEFORE killing
         if let Ok(mut set) = state.cancelled_paths.lock() {
-            set.insert(path.clone());
+            set.insert(key.clone());
         }
 
-        #[cfg(target_os = "windows")]
-        {
-             use std::os::windows::process::CommandExt;
-             let _ = Command::new("taskkill")
-                .args(&["/F", "/PID", &pid.to_string()])
-                .creation_flags(0x08000000)
-                .output()
-                .map_err(|e| e.to_string())?;
+        if !process::kill(pid) {
+            eprintln!("[WARNING] Failed to kill ffmpeg process {} for {}", pid, path);
         }
-        #[cfg(not(target_os = "windows"))]
-        {
-             let _ = Command::new("kill")
-                .args(&["-9", &pid.to_string()])
-                .output()
-                .map_err(|e| e.to_string())?;
+    } else if video::is_waiting_for_slot(&path) || video::is_pipeline_active(&path) {
+        // Waiting for an encode/verification slot, or a pipeline between its CRF search and
+        // the encode: it gives up on its next check
+        if let Ok(mut set) = state.cancelled_paths.lock() {
+            set.insert(key.clone());
         }
     }
 
     // Connect to VMAF State to remove from queue if present
-    let mut removed_from_queue = false;
-    {
-        if let Ok(mut v_state) = state.vmaf_state.lock() {
-             if let Some(pos) = v_state.queue.iter().position(|t| t.input_path == path) {
-                 v_state.queue.remove(pos);
-                 removed_from_queue = true;
-             }
-        }
-    }
+    let removed_from_queue = state.vmaf_state.lock()
+        .map(|mut v_state| v_state.remove_task(&path).is_some())
+        .unwrap_or(false);
 
     if removed_from_queue {
         // Emit Done status as requested (Status Done, No VMAF)
-        let _ = app.emit("video-progress", video::ProgressPayload {
+        events::emit_progress(&app, video::ProgressPayload {
              path: path.clone(),
              progress: 100,
              status: "Done".to_string(),
              speed: 0.0,
              bitrate_kbps: 0.0,
              output_info: None, // Or we could try to fetch it, but None implies no update to info
+             monotonic_ms: monitor::monotonic_ms(),
+             vmaf_segment: None,
         });
     }
 
     Ok(())
 }
 
+/// Suspend the job's running ffmpeg process in place (SIGSTOP / NtSuspendProcess)
+#[tauri::command]
+async fn pause_processing(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    path: String
+) -> Result<(), String> {
+    let pid = {
+        let map = state.pids.lock().map_err(|e| e.to_string())?;
+        map.get(&video::job_key(&path)).cloned()
+    }.ok_or_else(|| format!("No running process for {}", path))?;
+
+    if !process::suspend(pid) {
+        return Err(format!("Failed to suspend ffmpeg (pid {})", pid));
+    }
+    video::set_user_paused(&path, true);
+    println!("[INFO] Paused ffmpeg for {}", path);
+
+    // The UI keeps the current progress for "Paused"
+    events::emit_progress(&app, video::ProgressPayload {
+        path,
+        progress: 0,
+        status: "Paused".to_string(),
+        speed: 0.0,
+        bitrate_kbps: 0.0,
+        output_info: None,
+        monotonic_ms: monitor::monotonic_ms(),
+        vmaf_segment: None,
+    });
+    Ok(())
+}
+
+/// Resume a job suspended with pause_processing. ffmpeg's next progress line
+/// restores the normal status.
+#[tauri::command]
+async fn resume_processing(
+    state: State<'_, ProcessingState>,
+    path: String
+) -> Result<(), String> {
+    let pid = {
+        let map = state.pids.lock().map_err(|e| e.to_string())?;
+        map.get(&video::job_key(&path)).cloned()
+    }.ok_or_else(|| format!("No running process for {}", path))?;
+
+    video::set_user_paused(&path, false);
+    if !process::resume(pid) {
+        return Err(format!("Failed to resume ffmpeg (pid {})", pid));
+    }
+    println!("[INFO] Resumed ffmpeg for {}", path);
+    Ok(())
+}
+
+/// Slow a running job down to `factor` (0-1] of its normal speed without cancelling it
+#[tauri::command]
+async fn set_job_speed_limit(
+    state: State<'_, ProcessingState>,
+    job_id: String,
+    factor: f32,
+) -> Result<(), String> {
+    throttle::set_job_speed_limit(&job_id, factor, state.pids.clone())
+}
+
+/// Pre-flight: which of the planned outputs would replace their own source file
+#[tauri::command]
+async fn check_overwrite_hazards(mappings: Vec<video::OutputMapping>) -> Result<Vec<String>, String> {
+    Ok(video::find_overwrite_hazards(&mappings))
+}
+
+/// Spot-check a finished batch with segment VMAF on a random sample of outputs.
+/// `audit_key` (e.g. "audit:1") cancels it through cancel_processing.
+#[tauri::command]
+async fn audit_batch(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    audit_key: String,
+    mappings: Vec<video::OutputMapping>,
+    sample_count: usize,
+    threshold: f64,
+    config: video::CompressionConfig,
+) -> Result<video::AuditReport, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let pids = state.pids.clone();
+    let cancelled_paths = state.cancelled_paths.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        video::audit_outputs(&app, &ffmpeg_path, &audit_key, mappings, sample_count, threshold, &config, &pids, &cancelled_paths)
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// Delete the originals of finished jobs (to the recycle bin with `use_trash`). Takes a
+/// batch of sources with their outputs and returns the ones that could not be deleted.
+#[tauri::command]
+async fn delete_original(
+    state: State<'_, ProcessingState>,
+    originals: Vec<backup::FinishedOriginal>,
+    use_trash: bool
+) -> Result<Vec<backup::DeleteFailure>, String> {
+    let pids = state.pids.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        backup::delete_originals(&originals, use_trash, |path| {
+            pids.lock().map(|p| p.contains_key(&video::job_key(path))).unwrap_or(true)
+        })
+    }).await.map_err(|e| e.to_string())
+}
+
+/// Canonical form of `path` (see video::job_key), to tell whether two paths are one file
+#[tauri::command]
+async fn job_key(path: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || video::job_key(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Batch pre-filter: finish the jobs whose source is already in one of the skipped codecs
+#[tauri::command]
+async fn find_codec_skips(app: AppHandle, jobs: Vec<video::CodecSkipJob>) -> Result<Vec<video::CodecSkip>, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    tauri::async_runtime::spawn_blocking(move || video::find_codec_skips(&app, &ffmpeg_path, jobs))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Streams of a batch that its target containers can't hold, checked before a copy-mode
+/// run or container change
+#[tauri::command]
+async fn preflight_remux(app: AppHandle, paths: Vec<String>, config: video::CompressionConfig) -> Result<Vec<remux::RemuxReport>, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || remux::preflight(&paths, &config, &ffprobe_path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Originals under `scan_root` whose outputs under `output_root` verified, for cleanup
+#[tauri::command]
+async fn reconcile_outputs(app: AppHandle, scan_root: String, output_root: String) -> Result<reconcile::ReconcileReport, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    let ffprobe_path = resolve_ffprobe_path(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        reconcile::reconcile_outputs(&scan_root, &output_root, &ffmpeg_path, &ffprobe_path)
+    }).await.map_err(|e| e.to_string())
+}
+
+/// GPU features the driver blacklist turns off on this machine, with the reasons
+#[tauri::command]
+async fn get_gpu_quirks(config: video::CompressionConfig) -> Result<Vec<gpuquirks::ActiveQuirk>, String> {
+    tauri::async_runtime::spawn_blocking(move || gpuquirks::active_quirks(&config))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Undo a replacement of the source by moving its backup back
+#[tauri::command]
+async fn restore_original(path: String) -> Result<String, String> {
+    backup::restore_original(&path)
+}
+
+/// Pre-flight: unreadable inputs and output directories that can't be written
+#[tauri::command]
+async fn check_access(mappings: Vec<video::OutputMapping>) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || video::find_access_problems(&mappings))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn clear_cancelled_paths(
     state: State<'_, ProcessingState>,
@@ -353,6 +957,119 @@ async fn clear_crf_history(
     Ok(())
 }
 
+/// Cached CRF search results, newest first
+#[tauri::command]
+async fn list_crf_cache() -> Result<Vec<crfcache::CachedSearch>, String> {
+    Ok(crfcache::list())
+}
+
+/// Forget cached CRF search results for `path`, or all of them; returns how many
+#[tauri::command]
+async fn clear_crf_cache(path: Option<String>) -> Result<usize, String> {
+    crfcache::clear(path.as_deref())
+}
+
+/// Drop all cached ffprobe results; returns how many there were
+#[tauri::command]
+async fn clear_metadata_cache() -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(metacache::clear).await.map_err(|e| e.to_string())?
+}
+
+/// VMAF of any two files the user picks, e.g. to check an encode made elsewhere
+#[tauri::command]
+async fn compare_files(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    reference: String,
+    distorted: String,
+    options: video::CompressionConfig
+) -> Result<video::Comparison, String> {
+    let task = video::VmafTask {
+        app: app.clone(),
+        input_path: distorted.clone(),
+        ffmpeg_path: resolve_ffmpeg_path(&app),
+        ffprobe_path: resolve_ffprobe_path(&app),
+        reference_path: reference,
+        distorted_path: distorted,
+        config: options,
+        duration_sec: 0.0,
+        pids: state.pids.clone(),
+        cancelled_paths: state.cancelled_paths.clone(),
+        output_video_info: None,
+    };
+    tauri::async_runtime::spawn_blocking(move || video::compare_files(task))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Where each VMAF model was found, if anywhere
+#[tauri::command]
+async fn list_vmaf_models(app: AppHandle) -> Result<Vec<vmafmodels::ModelStatus>, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    tauri::async_runtime::spawn_blocking(move || vmafmodels::status(&app, &ffmpeg_path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Download the official VMAF models that can't be found into the app data dir
+#[tauri::command]
+async fn download_vmaf_models(app: AppHandle) -> Result<Vec<vmafmodels::ModelStatus>, String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    tauri::async_runtime::spawn_blocking(move || vmafmodels::download_missing(&app, &ffmpeg_path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Running and waiting VMAF tasks, running ones first
+#[tauri::command]
+async fn list_vmaf_queue(state: State<'_, ProcessingState>) -> Result<Vec<video::VmafQueueEntry>, String> {
+    let v_state = state.vmaf_state.lock().map_err(|e| e.to_string())?;
+    Ok(v_state.entries())
+}
+
+/// Move a waiting VMAF task to `position` in the queue (0 = next)
+#[tauri::command]
+async fn reorder_vmaf_task(
+    state: State<'_, ProcessingState>,
+    path: String,
+    position: usize
+) -> Result<(), String> {
+    let mut v_state = state.vmaf_state.lock().map_err(|e| e.to_string())?;
+    v_state.move_task(&path, position)
+}
+
+/// Drop a waiting VMAF task; its file ends as Done without a VMAF score
+#[tauri::command]
+async fn remove_vmaf_task(
+    app: AppHandle,
+    state: State<'_, ProcessingState>,
+    path: String
+) -> Result<(), String> {
+    let task = state.vmaf_state.lock().map_err(|e| e.to_string())?
+        .remove_task(&path)
+        .ok_or_else(|| format!("{} is not waiting for VMAF", path))?;
+    phase::emit_job_phase(&app, &task.input_path, phase::JobPhase::Done);
+    events::emit_progress(&app, video::ProgressPayload {
+        path: task.input_path,
+        progress: 100,
+        status: "Done".to_string(),
+        speed: 0.0,
+        bitrate_kbps: 0.0,
+        output_info: None,
+        monotonic_ms: monitor::monotonic_ms(),
+        vmaf_segment: None,
+    });
+    Ok(())
+}
+
+/// Per-second VMAF of an output whose per-frame scores were kept (`keepVmafLog`)
+#[tauri::command]
+async fn get_vmaf_timeline(path: String) -> Result<timeline::VmafTimeline, String> {
+    tauri::async_runtime::spawn_blocking(move || timeline::timeline(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 async fn compute_vmaf(
     app: AppHandle,
@@ -387,13 +1104,15 @@ async fn compute_vmaf(
         v_state.queue.push_back(task);
     }
 
-    let _ = app.emit("video-progress", video::ProgressPayload {
+    events::emit_progress(&app, video::ProgressPayload {
         path: input_path.clone(),
         progress: 100,
         status: "Waiting for VMAF".to_string(),
         speed: 0.0,
         bitrate_kbps: 0.0,
         output_info: None,
+        monotonic_ms: monitor::monotonic_ms(),
+        vmaf_segment: None,
     });
 
     video::schedule_next_vmaf(state.vmaf_state.clone());
@@ -415,16 +1134,39 @@ async fn run_crf_search_command(
     let vmaf_state = state.vmaf_state.clone();
 
     tauri::async_runtime::spawn_blocking(move || {
-        video::run_crf_search(
-            app,
+        let result = video::run_crf_search(
+            app.clone(),
             &ffmpeg_path,
-            input_path,
+            input_path.clone(),
             &config,
             duration_sec,
             pids,
             cancelled_paths,
             vmaf_state
-        )
+        );
+        match &result {
+            Ok(_) => halt::record_search_success(),
+            Err(e) => {
+                throttle::clear_job_speed_limit(&input_path);
+                phase::emit_job_failed(&app, &input_path, e);
+                if !video::is_cancelled(e) {
+                    halt::record_failure(&app, e, config.halt_after_failures);
+                }
+            }
+        }
+        result
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// One-frame trial encode with the batch's settings, run before a batch starts
+#[tauri::command]
+async fn validate_batch_config(
+    app: AppHandle,
+    config: video::CompressionConfig,
+) -> Result<(), String> {
+    let ffmpeg_path = resolve_ffmpeg_path(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        video::validate_encoding_config(&ffmpeg_path, &config)
     }).await.map_err(|e| e.to_string())?
 }
 
@@ -445,11 +1187,12 @@ async fn run_compression_command(
     let cancelled_paths = state.cancelled_paths.clone();
     let vmaf_state = state.vmaf_state.clone();
 
+    let halt_threshold = config.halt_after_failures;
     tauri::async_runtime::spawn_blocking(move || {
-        video::run_ffmpeg_compression_task(
-            app,
+        let result = video::run_ffmpeg_compression_task(
+            app.clone(),
             &ffmpeg_path,
-            input_path,
+            input_path.clone(),
             output_path,
             config,
             duration_sec,
@@ -458,32 +1201,96 @@ async fn run_compression_command(
             vmaf_state,
             vmaf_derived_crf,
             vmaf_search_score
-        )
+        );
+        match &result {
+            Ok(()) => halt::record_success(),
+            Err(e) => {
+                phase::emit_job_failed(&app, &input_path, e);
+                if !video::is_cancelled(e) {
+                    halt::record_failure(&app, e, halt_threshold);
+                }
+            }
+        }
+        result
     }).await.map_err(|e| e.to_string())?
 }
 
+/// Set or clear ("deadline_ms" 0) the batch time budget. The UI calls this when a batch
+/// starts (`new_batch`) and whenever its queue changes; progress comes as `budget-status`.
+#[tauri::command]
+fn set_batch_budget(
+    app: AppHandle,
+    deadline_ms: u64,
+    remaining: Vec<budget::BudgetJob>,
+    video_encoder: String,
+    new_batch: bool
+) {
+    budget::set_budget(&app, deadline_ms, remaining, video_encoder, new_batch);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
+            let settings_file = app.path().app_config_dir().ok().and_then(|dir| {
+                std::fs::create_dir_all(&dir).ok()?;
+                Some(dir.join("settings.json"))
+            });
+            settings::load(settings_file);
+            presets::load(app.path().app_config_dir().ok().map(|dir| dir.join("presets.json")));
+            crfcache::load(app.path().app_cache_dir().ok().and_then(|dir| {
+                std::fs::create_dir_all(&dir).ok()?;
+                Some(dir.join("crf-search-cache.json"))
+            }));
+            metacache::load(app.path().app_cache_dir().ok().and_then(|dir| {
+                std::fs::create_dir_all(&dir).ok()?;
+                Some(dir.join("metadata-cache.json"))
+            }));
+            scratch::init(app.path().app_cache_dir().ok().map(|dir| dir.join("scratch")));
+            std::thread::spawn(video::discard_stale_previews);
+
+            history::load(app.path().app_data_dir().ok().and_then(|dir| {
+                std::fs::create_dir_all(&dir).ok()?;
+                Some(dir.join("job-history.json"))
+            }));
+            let queue_file = app.path().app_data_dir().ok().and_then(|dir| {
+                std::fs::create_dir_all(&dir).ok()?;
+                Some(dir.join("jobs.json"))
+            });
             app.manage(ProcessingState {
                 pids: Arc::new(Mutex::new(HashMap::new())),
                 cancelled_paths: Arc::new(Mutex::new(HashSet::new())),
                 vmaf_state: Arc::new(Mutex::new(video::VmafState {
                     queue: std::collections::VecDeque::new(),
-                    running_task: None,
+                    running_tasks: Vec::new(),
                     crf_history: HashMap::new(),
                 })),
+                job_queue: Arc::new(Mutex::new(queue::JobQueue::load(queue_file))),
+            });
+
+            // Sends batched progress (a no-op unless batching is on)
+            let snapshot_handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(events::SNAPSHOT_INTERVAL);
+                events::flush(&snapshot_handle);
             });
 
             let handle = app.handle().clone();
-            
+            let monitored_pids = app.state::<ProcessingState>().pids.clone();
+            monitor::monotonic_ms(); // Start the clock
+
             // Start resource monitoring thread
             std::thread::spawn(move || {
                 let mut sys = System::new_all();
-                let nvml = Nvml::init().ok();
+                // No NVIDIA driver (AMD/Intel, macOS): GPU stats just stay at 0
+                let nvml = if cfg!(target_os = "macos") { None } else {
+                    Nvml::init().map_err(|e| println!("[INFO] NVML unavailable, GPU usage not reported: {}", e)).ok()
+                };
+                let mut on_battery = monitor::detect_on_battery();
+                let mut last_battery_check = std::time::Instant::now();
+                let mut next_tick = std::time::Instant::now();
                 
                 loop {
                     sys.refresh_cpu_all();
@@ -497,8 +1304,11 @@ pub fn run() {
                     let mut graphics_usage = 0.0;
                     let mut encoder_usage = 0.0;
                     let mut decoder_usage = 0.0;
+                    let mut gpu_temperature = None;
                     if let Some(ref n) = nvml {
-                        if let Ok(device) = n.device_by_index(0) {
+                        if let Ok(device) = n.device_by_index(settings::gpu_index()) {
+                            gpu_temperature = device.temperature(TemperatureSensor::Gpu).ok();
+
                             if let Ok(utilization) = device.utilization_rates() {
                                 graphics_usage = utilization.gpu as f32;
                             }
@@ -516,35 +1326,140 @@ pub fn run() {
                     // choose the maximum of graphics (3D), encoder and decoder utilizations
                     let gpu_usage = graphics_usage.max(encoder_usage).max(decoder_usage);
 
+                    // Power source changes rarely and is slower to query (pmset on macOS)
+                    if last_battery_check.elapsed() >= BATTERY_CHECK_INTERVAL {
+                        on_battery = monitor::detect_on_battery();
+                        last_battery_check = std::time::Instant::now();
+                    }
+                    monitor::record(monitor::Snapshot {
+                        gpu_usage,
+                        available_memory: sys.available_memory(),
+                        on_battery,
+                    });
+
                     let stats = SystemStats {
                         cpu_usage,
                         memory_usage,
                         gpu_usage,
+                        monotonic_ms: monitor::monotonic_ms(),
                     };
                     
                     let _ = handle.emit("system-stats", stats);
-                    
-                    std::thread::sleep(Duration::from_millis(1000));
+
+                    // The guard reacts to other programs' load, not to the app's own ffmpeg
+                    let own_pids: Vec<Pid> = monitored_pids.lock()
+                        .map(|p| p.values().map(|pid| Pid::from_u32(*pid)).collect())
+                        .unwrap_or_default();
+                    let own_cpu = if own_pids.is_empty() {
+                        0.0
+                    } else {
+                        sys.refresh_processes_specifics(ProcessesToUpdate::Some(&own_pids), true, ProcessRefreshKind::nothing().with_cpu());
+                        own_pids.iter().filter_map(|pid| sys.process(*pid)).map(|p| p.cpu_usage()).sum::<f32>()
+                            / sys.cpus().len().max(1) as f32
+                    };
+                    thermal::check(&handle, gpu_temperature, (cpu_usage - own_cpu).max(0.0), &monitored_pids);
+
+                    // Schedule from the previous tick rather than sleeping a fixed time after
+                    // the (variable) sampling work, so samples don't drift
+                    let busy = monitored_pids.lock().map(|p| !p.is_empty()).unwrap_or(false);
+                    next_tick += if busy { STATS_INTERVAL_ACTIVE } else { STATS_INTERVAL_IDLE };
+                    let now = std::time::Instant::now();
+                    if next_tick > now {
+                        std::thread::sleep(next_tick - now);
+                    } else {
+                        next_tick = now; // Fell behind (e.g. system sleep); don't burst to catch up
+                    }
                 }
             });
+
+            // `--service`: no window, just the watch folders until a stop is requested
+            if service::requested() {
+                let service_handle = app.handle().clone();
+                let state = app.state::<ProcessingState>();
+                let (pids, cancelled_paths, vmaf_state) = (state.pids.clone(), state.cancelled_paths.clone(), state.vmaf_state.clone());
+                std::thread::spawn(move || {
+                    let binaries = || (resolve_ffmpeg_path(&service_handle), resolve_ffprobe_path(&service_handle));
+                    service::run(&service_handle, service_paths(&service_handle), binaries, pids, cancelled_paths, vmaf_state);
+                    service_handle.exit(0);
+                });
+            } else if let Some(window) = app.get_webview_window("main") {
+                // The window starts hidden so the service never flashes one
+                window.show()?;
+            }
             
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet,
+            get_settings,
+            get_quality_tiers,
+            set_batch_budget,
+            set_settings,
+            list_presets,
+            save_preset,
+            delete_preset,
             get_background_image,
             scan_directory,
             scan_multiple_paths,
             categorize_paths,
             get_video_metadata,
+            get_video_metadata_quick,
+            get_video_metadata_batch,
+            analyze_video,
             detect_encoders,
             start_processing,
             cancel_processing,
+            pause_processing,
+            set_event_batching,
+            set_thermal_guard,
+            resume_processing,
+            set_job_speed_limit,
+            check_overwrite_hazards,
+            check_access,
+            restore_original,
+            get_gpu_quirks,
+            delete_original,
+            job_key,
+            find_codec_skips,
+            preflight_remux,
+            reconcile_outputs,
+            audit_batch,
+            submit_manifest,
+            enqueue_job,
+            list_jobs,
+            remove_job,
+            run_job_queue,
+            requeue_with_changes,
+            encode_image_sequence,
+            extract_audio,
+            preview_encode,
+            discard_preview,
             clear_cancelled_paths,
             clear_crf_history,
+            list_crf_cache,
+            clear_crf_cache,
+            clear_metadata_cache,
             compute_vmaf,
+            compare_files,
+            get_vmaf_timeline,
+            list_vmaf_queue,
+            list_vmaf_models,
+            download_vmaf_models,
+            reorder_vmaf_task,
+            remove_vmaf_task,
             run_crf_search_command,
-            run_compression_command
+            run_compression_command,
+            run_vmaf_pipeline,
+            validate_batch_config,
+            get_service_config,
+            set_service_config,
+            get_service_status,
+            stop_service,
+            install_service,
+            uninstall_service,
+            list_job_history,
+            clear_job_history,
+            export_job_history
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
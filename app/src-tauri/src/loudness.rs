@@ -0,0 +1,81 @@
+//! Two-pass EBU R128 loudness normalization with ffmpeg's `loudnorm`: each audio track
+//! that goes into the output is measured first, then normalized with its own measured
+//! values during the encode.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+/// True peak and loudness range targets that go with the integrated target
+const TRUE_PEAK: f32 = -1.5;
+const LOUDNESS_RANGE: f32 = 11.0;
+
+/// First-pass `loudnorm` measurement, as printed with `print_format=json` (ffmpeg prints
+/// the numbers as strings)
+#[derive(Debug, Deserialize)]
+pub struct Measurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+/// Measure the loudness of audio track `track` (`0:a:N`) of `input_path` against
+/// `target_lufs` (EBU R128, e.g. -23 or -16). This decodes the whole audio track; the
+/// ffmpeg is listed in `pids` under the job so it can be cancelled and paused.
+pub fn measure(
+    ffmpeg_path: &str,
+    input_path: &str,
+    track: usize,
+    target_lufs: f32,
+    pids: &Arc<Mutex<HashMap<String, u32>>>,
+) -> Result<Measurement, String> {
+    let filter = format!("loudnorm=I={}:TP={}:LRA={}:print_format=json", target_lufs, TRUE_PEAK, LOUDNESS_RANGE);
+    let map = format!("0:a:{}", track);
+    let args = [
+        "-hide_banner", "-nostats",
+        "-i", input_path,
+        "-map", &map,
+        "-af", &filter,
+        "-f", "null", "-",
+    ];
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(args).stdout(Stdio::null()).stderr(Stdio::piped());
+    let child = command.spawn().map_err(|e| format!("Failed to run loudness measurement: {}", e))?;
+    let key = crate::video::job_key(input_path);
+    if let Ok(mut map) = pids.lock() {
+        map.insert(key.clone(), child.id());
+    }
+    let output = child.wait_with_output();
+    if let Ok(mut map) = pids.lock() {
+        map.remove(&key);
+    }
+    let output = output.map_err(|e| format!("Failed to run loudness measurement: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Loudness measurement failed: {}", stderr.lines().last().unwrap_or("").trim()));
+    }
+
+    // The JSON block is the last thing loudnorm logs
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let start = stderr.rfind('{').ok_or("No loudness measurement in the ffmpeg output")?;
+    let end = stderr[start..].find('}').map(|i| start + i + 1).ok_or("Truncated loudness measurement")?;
+    let measurement: Measurement = serde_json::from_str(&stderr[start..end])
+        .map_err(|e| format!("Failed to parse loudness measurement: {}", e))?;
+    println!(
+        "[INFO] Loudness of {} (audio {}): {} LUFS, true peak {} dBTP, range {} LU",
+        input_path, track, measurement.input_i, measurement.input_tp, measurement.input_lra
+    );
+    Ok(measurement)
+}
+
+/// Second-pass filter: normalize to `target_lufs` using the measured values, in linear
+/// mode so the dynamics are left alone where possible
+pub fn filter(target_lufs: f32, m: &Measurement) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+        target_lufs, TRUE_PEAK, LOUDNESS_RANGE, m.input_i, m.input_tp, m.input_lra, m.input_thresh, m.target_offset
+    )
+}
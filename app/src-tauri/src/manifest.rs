@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+
+use crate::video::{self, CompressionConfig, VmafState};
+use crate::{filelock, halt, phase};
+
+/// A batch described as JSON, for scripted pipelines that don't go through the GUI
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobManifest {
+    pub paths: Vec<String>, // Files or folders (scanned recursively)
+    pub config: CompressionConfig,
+    // Output path per file, e.g. "D:/out/{name}.{codec}.mkv". `{dir}`, `{name}` and `{ext}`
+    // refer to the source file; the naming tokens (`{height}`, `{codec}`, ...) also work.
+    pub output_template: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFailure {
+    pub input_path: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestResult {
+    pub submitted: usize,
+    pub succeeded: Vec<String>, // Output paths
+    pub failed: Vec<ManifestFailure>,
+    pub scan_errors: Vec<String>,
+    pub halted: bool, // Stopped early by halt_after_failures; the remaining files were not run
+}
+
+pub fn parse_manifest(json: &str) -> Result<JobManifest, String> {
+    let manifest: JobManifest = serde_json::from_str(json)
+        .map_err(|e| format!("Invalid job manifest: {}", e))?;
+    if manifest.paths.is_empty() {
+        return Err("Invalid job manifest: no paths given".to_string());
+    }
+    if manifest.output_template.trim().is_empty() {
+        return Err("Invalid job manifest: outputTemplate is empty".to_string());
+    }
+    Ok(manifest)
+}
+
+/// Fill in the source-file placeholders of the output template
+pub fn output_path_for(template: &str, input_path: &str) -> String {
+    let path = Path::new(input_path);
+    let dir = path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    template
+        .replace("{dir}", &dir)
+        .replace("{name}", &name)
+        .replace("{ext}", &ext)
+}
+
+/// Scan the manifest's paths and compress every video found, `maxConcurrentEncodes` at a
+/// time, with the same progress/phase events as GUI jobs
+pub fn run_manifest(
+    app: &AppHandle,
+    manifest: JobManifest,
+    ffmpeg_path: &str,
+    ffprobe_path: &str,
+    pids: Arc<Mutex<HashMap<String, u32>>>,
+    cancelled_paths: Arc<Mutex<HashSet<String>>>,
+    vmaf_state: Arc<Mutex<VmafState>>,
+) -> ManifestResult {
+    let scan = video::scan_multiple_paths(manifest.paths);
+    let result = ManifestResult {
+        submitted: scan.videos.len(),
+        scan_errors: scan.errors,
+        ..Default::default()
+    };
+    println!("[INFO] Job manifest: {} files submitted", result.submitted);
+
+    // Files with the times they were deferred because they were in use
+    let files: Mutex<VecDeque<(video::VideoInfo, u32)>> = Mutex::new(scan.videos.into_iter().map(|f| (f, 0)).collect());
+    let result = Mutex::new(result);
+    let halted = AtomicBool::new(false);
+    crate::pool::run_workers(manifest.config.job_workers(), || loop {
+        if halted.load(Ordering::SeqCst) {
+            break;
+        }
+        let next = files.lock().ok().and_then(|mut f| f.pop_front());
+        let Some((file, deferrals)) = next else { break };
+        let output_path = output_path_for(&manifest.output_template, &file.path);
+        let duration_sec = video::get_metadata_quick(&file.path, ffprobe_path)
+            .map(|info| info.duration_sec)
+            .unwrap_or(0.0);
+
+        let outcome = video::process_video(
+            app.clone(), ffmpeg_path, file.path.clone(), output_path.clone(), manifest.config.clone(),
+            duration_sec, pids.clone(), cancelled_paths.clone(), vmaf_state.clone(),
+        );
+        if let Err(e) = &outcome {
+            if filelock::is_file_in_use_error(e) && deferrals < filelock::MAX_DEFERRALS {
+                println!("[INFO] Deferring {} to the end of the manifest: {}", file.path, e);
+                if let Ok(mut f) = files.lock() {
+                    f.push_back((file, deferrals + 1));
+                }
+                continue;
+            }
+        }
+        let mut result = match result.lock() {
+            Ok(r) => r,
+            Err(_) => break,
+        };
+        match outcome {
+            Ok(()) => {
+                halt::record_success();
+                result.succeeded.push(output_path);
+            }
+            Err(e) => {
+                phase::emit_job_failed(app, &file.path, &e);
+                if !crate::video::is_cancelled(&e) && halt::record_failure(app, &e, manifest.config.halt_after_failures) {
+                    halted.store(true, Ordering::SeqCst);
+                }
+                eprintln!("[ERROR] Manifest job failed for {}: {}", file.path, e);
+                result.failed.push(ManifestFailure { input_path: file.path, error: e });
+            }
+        }
+    });
+
+    let mut result = result.into_inner().unwrap_or_default();
+    result.halted = halted.into_inner();
+    println!("[INFO] Job manifest finished: {} done, {} failed", result.succeeded.len(), result.failed.len());
+    result
+}
@@ -0,0 +1,162 @@
+//! ffprobe results kept across runs, keyed by path and invalidated by size or mtime
+//! changes, persisted as `metadata-cache.json` in the app cache dir. Rescanning a library
+//! then only probes new and changed files.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::video::{job_key, VideoInfo};
+
+/// A probe result with the file state it belongs to
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CachedProbe {
+    size: u64,
+    modified_ms: u64,
+    full: bool, // Full probe; a quick one only answers quick lookups
+    cached_at: u64, // Unix seconds, for eviction
+    info: VideoInfo,
+}
+
+/// Fields the probe fills in; bump when it extracts more, so older entries are re-probed
+const PROBE_VERSION: u32 = 3;
+
+/// On-disk layout of the cache file
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct CacheFile {
+    #[serde(default)]
+    probe_version: u32,
+    entries: HashMap<String, CachedProbe>, // Job key -> probe
+}
+
+/// Borrowed CacheFile, to serialize a snapshot without copying it again
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheFileRef<'a> {
+    probe_version: u32,
+    entries: &'a HashMap<String, CachedProbe>,
+}
+
+struct Store {
+    file: Option<PathBuf>,
+    entries: HashMap<String, CachedProbe>,
+    dirty: bool,
+    last_save: Instant,
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+/// Held from taking a snapshot until it is written, so saves land in order; STORE is only
+/// locked for the snapshot itself
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Entries beyond this drop the oldest tenth
+const MAX_ENTRIES: usize = 100_000;
+/// New entries are written out at most this often (and by `flush`)
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+pub fn load(file: Option<PathBuf>) {
+    let entries = file.as_ref()
+        .and_then(|f| crate::schema::load_file::<CacheFile>(crate::schema::Kind::MetadataCache, f))
+        .filter(|c| c.probe_version == PROBE_VERSION)
+        .unwrap_or_default()
+        .entries;
+    println!("[INFO] Metadata cache: {} entries", entries.len());
+    if let Ok(mut guard) = STORE.lock() {
+        *guard = Some(Store { file, entries, dirty: false, last_save: Instant::now() });
+    }
+}
+
+/// Size and mtime (ms) that a cached probe of `path` must match
+pub(crate) fn fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_millis() as u64))
+}
+
+/// Cached probe of `path` if the file is unchanged; `full` asks for a full probe
+pub fn get(path: &Path, full: bool) -> Option<VideoInfo> {
+    let (size, modified_ms) = fingerprint(path)?;
+    let guard = STORE.lock().ok()?;
+    let entry = guard.as_ref()?.entries.get(&job_key(&path.to_string_lossy()))?;
+    (entry.size == size && entry.modified_ms == modified_ms && (entry.full || !full)).then(|| entry.info.clone())
+}
+
+/// Remember a successful probe of `path`
+pub fn put(path: &Path, full: bool, info: &VideoInfo) {
+    let Some((size, modified_ms)) = fingerprint(path) else { return };
+    let Ok(mut guard) = STORE.lock() else { return };
+    let Some(store) = guard.as_mut() else { return };
+    let key = job_key(&path.to_string_lossy());
+    // Don't let a quick probe replace a full one of the same file
+    if !full && store.entries.get(&key).is_some_and(|e| e.full && e.size == size && e.modified_ms == modified_ms) {
+        return;
+    }
+    store.entries.insert(key, CachedProbe {
+        size,
+        modified_ms,
+        full,
+        cached_at: crate::sidecar::now_unix_secs(),
+        info: info.clone(),
+    });
+    if store.entries.len() > MAX_ENTRIES {
+        // By count: entries cached in the same second all share a timestamp
+        let mut by_age: Vec<(u64, String)> = store.entries.iter().map(|(k, e)| (e.cached_at, k.clone())).collect();
+        by_age.sort_unstable();
+        for (_, key) in by_age.into_iter().take(MAX_ENTRIES / 10) {
+            store.entries.remove(&key);
+        }
+    }
+    store.dirty = true;
+    let due = store.last_save.elapsed() >= SAVE_INTERVAL;
+    drop(guard);
+    if due {
+        save(false);
+    }
+}
+
+/// Write pending entries now (end of a scan or metadata batch)
+pub fn flush() {
+    save(true);
+}
+
+/// Forget every cached probe, on disk too. Returns how many there were.
+pub fn clear() -> Result<usize, String> {
+    let _saving = SAVE_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut guard = STORE.lock().map_err(|e| e.to_string())?;
+    let Some(store) = guard.as_mut() else { return Ok(0) };
+    let count = store.entries.len();
+    store.entries.clear();
+    if let Some(file) = &store.file {
+        if file.exists() {
+            std::fs::remove_file(file).map_err(|e| format!("Failed to delete the metadata cache: {}", e))?;
+        }
+    }
+    store.dirty = false;
+    println!("[INFO] Metadata cache cleared ({} entries)", count);
+    Ok(count)
+}
+
+/// Write out the entries if they changed. Serializing and writing happen outside STORE so
+/// probes aren't held up; `wait` = false skips the save when another one is running
+/// (the entries stay dirty for the next one).
+fn save(wait: bool) {
+    let saving = if wait { SAVE_LOCK.lock().ok() } else { SAVE_LOCK.try_lock().ok() };
+    let Some(_saving) = saving else { return };
+    let snapshot = {
+        let Ok(mut guard) = STORE.lock() else { return };
+        let Some(store) = guard.as_mut().filter(|s| s.dirty) else { return };
+        store.last_save = Instant::now();
+        store.dirty = false;
+        store.file.clone().map(|file| (file, store.entries.clone()))
+    };
+    let Some((file, entries)) = snapshot else { return };
+    let result = crate::schema::write(&CacheFileRef { probe_version: PROBE_VERSION, entries: &entries })
+        .and_then(|json| crate::schema::save_file(&file, &json));
+    if let Err(e) = result {
+        eprintln!("[WARNING] Could not save the metadata cache: {}", e);
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Latest sample taken by the resource monitoring thread, so processing code
+/// can react to system load without polling the OS itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Snapshot {
+    pub gpu_usage: f32,
+    pub available_memory: u64, // bytes, 0 = not sampled yet
+    pub on_battery: bool,
+}
+
+static LATEST: Mutex<Snapshot> = Mutex::new(Snapshot {
+    gpu_usage: 0.0,
+    available_memory: 0,
+    on_battery: false,
+});
+
+pub fn record(snapshot: Snapshot) {
+    if let Ok(mut latest) = LATEST.lock() {
+        *latest = snapshot;
+    }
+}
+
+pub fn latest() -> Snapshot {
+    LATEST.lock().map(|s| *s).unwrap_or_default()
+}
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Milliseconds since the app started, from a monotonic clock. Stamped on system-stats
+/// and video-progress events so they can be lined up without wall-clock jumps.
+pub fn monotonic_ms() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// Whether the machine is currently running from battery (false on desktops
+/// or when it can't be determined)
+pub fn detect_on_battery() -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+        let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+            return false;
+        }
+        // 0 = offline, 1 = online, 255 = unknown
+        status.ACLineStatus == 0
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains("'Battery Power'"))
+            .unwrap_or(false)
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        // On AC if any mains adapter reports online; otherwise on battery if a battery is discharging
+        let entries = match std::fs::read_dir("/sys/class/power_supply") {
+            Ok(e) => e,
+            Err(_) => return false,
+        };
+        let mut discharging = false;
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default().trim().to_string();
+            match read("type").as_str() {
+                "Mains" | "USB" if read("online") == "1" => return false,
+                "Battery" if read("status") == "Discharging" => discharging = true,
+                _ => {}
+            }
+        }
+        discharging
+    }
+}
+
+/// Seconds since the last keyboard/mouse input anywhere on the system, or None where
+/// that can't be read (Linux, where it depends on the display server)
+pub fn user_idle_secs() -> Option<u64> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::SystemInformation::GetTickCount;
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+        let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+        if unsafe { GetLastInputInfo(&mut info) } == 0 {
+            return None;
+        }
+        Some((unsafe { GetTickCount() }.wrapping_sub(info.dwTime) / 1000) as u64)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // "HIDIdleTime" = <nanoseconds>
+        let output = std::process::Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let line = text.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+        let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+        Some(nanos / 1_000_000_000)
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        None
+    }
+}
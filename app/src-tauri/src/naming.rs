@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::video::{job_key, resolves_to_same_file, CompressionConfig};
+
+/// Normalized output names handed out this session (output key -> input key), so two
+/// sources that normalize to the same name don't overwrite each other
+static CLAIMED_NAMES: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// Values for the `{token}` placeholders allowed in output file names
+/// (typically via the suffix, e.g. `.{height}p.{codec}.VMAF{vmaf_target}`)
+struct NamingTokens {
+    source_codec: String,
+    audio_lang: String,
+    height: String,
+    fps: String,
+}
+
+/// Replace naming tokens in the file name part of `output_path` with values probed from
+/// `input_path` and taken from `config`. Paths without tokens are returned unchanged
+/// without probing. Unknown tokens are left as-is.
+///
+/// Supported: `{source_codec}`, `{codec}` (output encoder), `{audio_lang}`, `{height}`,
+/// `{width}`, `{fps}`, `{vmaf_target}`.
+///
+/// The file name is then normalized per `filename_normalization`.
+pub fn resolve_output_path(ffprobe_path: &str, input_path: &str, output_path: &str, config: &CompressionConfig) -> String {
+    let resolved = resolve_tokens(ffprobe_path, input_path, output_path, config);
+    normalize_file_name(input_path, &resolved, &config.filename_normalization)
+}
+
+fn resolve_tokens(ffprobe_path: &str, input_path: &str, output_path: &str, config: &CompressionConfig) -> String {
+    let _spawn = crate::process::job_scope(config);
+    let path = Path::new(output_path);
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) if n.contains('{') => n,
+        _ => return output_path.to_string(),
+    };
+
+    let (tokens, width) = match probe_tokens(ffprobe_path, input_path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("[WARNING] Could not probe naming tokens for {}: {}", input_path, e);
+            (NamingTokens {
+                source_codec: "unknown".to_string(),
+                audio_lang: "und".to_string(),
+                height: "0".to_string(),
+                fps: "0".to_string(),
+            }, "0".to_string())
+        }
+    };
+
+    let codec = if config.compression_mode == "copy" {
+        tokens.source_codec.clone()
+    } else {
+        short_codec_name(&config.video_encoder)
+    };
+
+    let resolved = file_name
+        .replace("{source_codec}", &sanitize(&tokens.source_codec))
+        .replace("{codec}", &sanitize(&codec))
+        .replace("{audio_lang}", &sanitize(&tokens.audio_lang))
+        .replace("{height}", &tokens.height)
+        .replace("{width}", &width)
+        .replace("{fps}", &tokens.fps)
+        .replace("{vmaf_target}", &format!("{}", config.target_vmaf));
+
+    let resolved_path = path.with_file_name(resolved).to_string_lossy().to_string();
+    println!("[INFO] Output name resolved: {} -> {}", output_path, resolved_path);
+    resolved_path
+}
+
+fn probe_tokens(ffprobe_path: &str, input_path: &str) -> Result<(NamingTokens, String), String> {
+    let mut command = crate::process::ffprobe_command(ffprobe_path);
+    command.args([
+        "-v", "quiet",
+        "-print_format", "json",
+        "-show_entries", "stream=codec_type,codec_name,width,height,avg_frame_rate:stream_tags=language",
+        input_path,
+    ]);
+
+
+    let output = command.output().map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe exited with status: {:?}", output.status));
+    }
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe json: {}", e))?;
+    let streams = parsed.get("streams").and_then(|v| v.as_array()).ok_or("No streams info")?;
+
+    let video = streams.iter().find(|s| s["codec_type"].as_str() == Some("video"));
+    let audio = streams.iter().find(|s| s["codec_type"].as_str() == Some("audio"));
+
+    let tokens = NamingTokens {
+        source_codec: video
+            .and_then(|v| v["codec_name"].as_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        audio_lang: audio
+            .and_then(|a| a["tags"]["language"].as_str())
+            .unwrap_or("und")
+            .to_string(),
+        height: video.and_then(|v| v["height"].as_u64()).unwrap_or(0).to_string(),
+        fps: video
+            .and_then(|v| v["avg_frame_rate"].as_str())
+            .map(format_frame_rate)
+            .unwrap_or_else(|| "0".to_string()),
+    };
+    let width = video.and_then(|v| v["width"].as_u64()).unwrap_or(0).to_string();
+    Ok((tokens, width))
+}
+
+/// "30000/1001" -> "29.97", "25/1" -> "25"
+fn format_frame_rate(rate: &str) -> String {
+    let mut parts = rate.split('/');
+    let num: f64 = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0.0);
+    let den: f64 = parts.next().and_then(|d| d.parse().ok()).unwrap_or(1.0);
+    if den == 0.0 {
+        return "0".to_string();
+    }
+    let fps = num / den;
+    if (fps - fps.round()).abs() < 0.01 {
+        format!("{}", fps.round() as u64)
+    } else {
+        format!("{:.2}", fps)
+    }
+}
+
+/// Encoder name as a codec label for file names: "libsvtav1" -> "AV1", "hevc_nvenc" -> "HEVC"
+fn short_codec_name(encoder: &str) -> String {
+    let encoder = if encoder.is_empty() { "libx264" } else { encoder };
+    if encoder.contains("av1") {
+        "AV1".to_string()
+    } else if encoder.contains("265") || encoder.contains("hevc") {
+        "HEVC".to_string()
+    } else if encoder.contains("264") || encoder.contains("h264") {
+        "H264".to_string()
+    } else if encoder.contains("vp9") {
+        "VP9".to_string()
+    } else {
+        encoder.to_string()
+    }
+}
+
+/// Keep probed values from introducing path separators or characters Windows rejects
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect()
+}
+
+/// Normalize the file name of `output_path` for filesystems and services that mangle
+/// non-ASCII names: "nfc" composes it (macOS hands out decomposed names), "ascii"
+/// also transliterates it ("Café Ünïcödé 東京" -> "Cafe Unicode Dong Jing"). Names
+/// that end up taken by another source or by an existing file get " (2)", " (3)", ... appended.
+fn normalize_file_name(input_path: &str, output_path: &str, mode: &str) -> String {
+    if mode != "nfc" && mode != "ascii" {
+        return output_path.to_string();
+    }
+    let path = Path::new(output_path);
+    let (Some(stem), ext) = (path.file_stem().map(|s| s.to_string_lossy()), path.extension().map(|e| e.to_string_lossy())) else {
+        return output_path.to_string();
+    };
+    let mut stem: String = stem.nfc().collect();
+    if mode == "ascii" {
+        stem = sanitize(&deunicode::deunicode(&stem)).trim().to_string();
+        if stem.is_empty() {
+            stem = "video".to_string();
+        }
+    }
+    let with_stem = |stem: &str| match &ext {
+        Some(ext) => path.with_file_name(format!("{}.{}", stem, ext)),
+        None => path.with_file_name(stem),
+    };
+
+    let input_key = job_key(input_path);
+    let Ok(mut guard) = CLAIMED_NAMES.lock() else { return with_stem(&stem).to_string_lossy().to_string() };
+    let claimed = guard.get_or_insert_with(HashMap::new);
+    let mut candidate = with_stem(&stem).to_string_lossy().to_string();
+    let mut n = 2;
+    loop {
+        let key = job_key(&candidate);
+        let taken_in_session = claimed.get(&key).is_some_and(|owner| *owner != input_key);
+        // Outputs of earlier runs say in their sidecar which source they came from. Any other
+        // file already at a name that normalization produced belongs to someone else; the
+        // unnormalized name is left to the overwrite policy.
+        let taken_on_disk = !taken_in_session && match crate::sidecar::read_sidecar(&candidate) {
+            Some(r) => job_key(&r.source_path) != input_key,
+            None => candidate != output_path && Path::new(&candidate).exists() && !resolves_to_same_file(input_path, &candidate),
+        };
+        if !taken_in_session && !taken_on_disk {
+            claimed.insert(key, input_key);
+            break;
+        }
+        candidate = with_stem(&format!("{} ({})", stem, n)).to_string_lossy().to_string();
+        n += 1;
+    }
+    if candidate != output_path {
+        println!("[INFO] Output name normalized: {} -> {}", output_path, candidate);
+    }
+    candidate
+}
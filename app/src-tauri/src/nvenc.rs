@@ -0,0 +1,93 @@
+//! Typed NVENC options (preset, tuning, rate control, multipass, AQ, lookahead), checked
+//! here instead of being passed through as free-text custom params. Empty fields take a
+//! default that suits the compression mode.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NvencOptions {
+    #[serde(default)]
+    pub preset: String, // "p1" (fastest) .. "p7" (best)
+    #[serde(default)]
+    pub tune: String, // "hq", "ll" (low latency), "ull", "lossless"
+    #[serde(default)]
+    pub rate_control: String, // "vbr" or "cbr" (bitrate modes only)
+    #[serde(default)]
+    pub multipass: String, // "disabled", "qres", "fullres"
+    #[serde(default)]
+    pub spatial_aq: Option<bool>,
+    #[serde(default)]
+    pub temporal_aq: Option<bool>,
+    #[serde(default)]
+    pub lookahead: Option<u32>, // Frames
+}
+
+pub const PRESETS: &[&str] = &["p1", "p2", "p3", "p4", "p5", "p6", "p7"];
+const TUNES: &[&str] = &["hq", "ll", "ull", "lossless"];
+const MULTIPASS: &[&str] = &["disabled", "qres", "fullres"];
+const MAX_LOOKAHEAD: u32 = 32;
+
+/// Whether the mode targets a bitrate (rather than a constant quality)
+fn bitrate_mode(mode: &str) -> bool {
+    matches!(mode, "bitrate" | "percent" | "vmaf_abr")
+}
+
+/// Check the options for `mode`; nothing to check for other encoders
+pub fn validate(options: Option<&NvencOptions>, v_enc: &str, mode: &str) -> Result<(), String> {
+    let Some(o) = options.filter(|_| v_enc.contains("nvenc")) else { return Ok(()) };
+    if !o.preset.is_empty() && !PRESETS.contains(&o.preset.as_str()) {
+        return Err(format!("NVENC preset must be p1-p7, not '{}'", o.preset));
+    }
+    if !o.tune.is_empty() && !TUNES.contains(&o.tune.as_str()) {
+        return Err(format!("NVENC tuning must be one of {}, not '{}'", TUNES.join("/"), o.tune));
+    }
+    match o.rate_control.as_str() {
+        "" | "vbr" => {}
+        "cbr" if bitrate_mode(mode) => {}
+        "cbr" => return Err("NVENC CBR needs a bitrate mode; quality (CQ) modes use VBR".to_string()),
+        other => return Err(format!("NVENC rate control must be vbr or cbr, not '{}'", other)),
+    }
+    if !o.multipass.is_empty() && !MULTIPASS.contains(&o.multipass.as_str()) {
+        return Err(format!("NVENC multipass must be one of {}, not '{}'", MULTIPASS.join("/"), o.multipass));
+    }
+    if o.lookahead.is_some_and(|l| l > MAX_LOOKAHEAD) {
+        return Err(format!("NVENC lookahead must be 0-{} frames", MAX_LOOKAHEAD));
+    }
+    if o.temporal_aq == Some(true) && v_enc.contains("av1") {
+        return Err("av1_nvenc has no temporal AQ".to_string());
+    }
+    Ok(())
+}
+
+/// Encoder options for an NVENC encode in `mode`. With `auto_tuned` the content-based
+/// tuning already chose AQ and lookahead, so only explicitly set values override it.
+pub fn args(options: Option<&NvencOptions>, v_enc: &str, mode: &str, auto_tuned: bool) -> Vec<String> {
+    let Some(o) = options.filter(|_| v_enc.contains("nvenc")) else { return Vec::new() };
+    if !matches!(mode, "crf" | "vmaf" | "bitrate" | "percent" | "vmaf_abr") {
+        return Vec::new();
+    }
+    let or = |value: &str, default: &str| if value.is_empty() { default.to_string() } else { value.to_string() };
+    let mut args = vec![
+        "-preset".to_string(), or(&o.preset, "p5"),
+        "-tune".to_string(), or(&o.tune, "hq"),
+        "-rc".to_string(), or(&o.rate_control, "vbr"),
+        // Full-resolution first pass pays off when bits must be spread to hit a target
+        "-multipass".to_string(), or(&o.multipass, if bitrate_mode(mode) { "fullres" } else { "qres" }),
+    ];
+
+    let spatial_aq = o.spatial_aq.or((!auto_tuned).then_some(true));
+    // Temporal AQ isn't available on av1_nvenc
+    let temporal_aq = o.temporal_aq.or((!auto_tuned && !v_enc.contains("av1")).then_some(true));
+    let lookahead = o.lookahead.or((!auto_tuned).then_some(if bitrate_mode(mode) { 32 } else { 20 }));
+    if let Some(on) = spatial_aq {
+        args.extend(["-spatial-aq".to_string(), (on as u8).to_string()]);
+    }
+    if let Some(on) = temporal_aq {
+        args.extend(["-temporal-aq".to_string(), (on as u8).to_string()]);
+    }
+    if let Some(frames) = lookahead {
+        args.extend(["-rc-lookahead".to_string(), frames.to_string()]);
+    }
+    args
+}
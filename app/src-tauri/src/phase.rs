@@ -0,0 +1,89 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// Stage of a job. Emitted as `job-phase-changed` alongside the free-form status
+/// strings of `video-progress`, so the UI and logs can time each stage.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum JobPhase {
+    Queued, // Accepted, waiting for memory/eco start conditions
+    Probing,
+    Searching, // VMAF-guided CRF search
+    Pass1,
+    Pass2,
+    Encoding, // Single-pass encode or stream copy
+    Verifying,
+    Moving, // Backup of an overwritten source, temp -> final rename
+    #[serde(rename = "VMAF")]
+    Vmaf,
+    Done,
+    Skipped,
+    Error,
+    Cancelled,
+}
+
+impl JobPhase {
+    fn is_terminal(self) -> bool {
+        matches!(self, JobPhase::Done | JobPhase::Skipped | JobPhase::Error | JobPhase::Cancelled)
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobPhasePayload {
+    pub path: String,
+    pub phase: JobPhase,
+    pub timestamp_ms: u64, // Unix milliseconds
+    pub previous_phase: Option<JobPhase>,
+    pub previous_phase_ms: Option<u64>, // Time spent in previous_phase
+}
+
+/// Current phase and its start time per job (input path)
+static CURRENT: Mutex<Option<HashMap<String, (JobPhase, u64)>>> = Mutex::new(None);
+
+fn now_unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Move a job into `phase`. Re-entering the current phase is ignored; terminal
+/// phases end the job's timeline.
+pub fn emit_job_phase(app: &AppHandle, path: &str, phase: JobPhase) {
+    let now = now_unix_millis();
+    let key = crate::video::job_key(path);
+    let previous = {
+        let mut guard = match CURRENT.lock() {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+        let jobs = guard.get_or_insert_with(HashMap::new);
+        let previous = jobs.get(&key).cloned();
+        if matches!(previous, Some((p, _)) if p == phase) {
+            return;
+        }
+        if phase.is_terminal() {
+            jobs.remove(&key);
+        } else {
+            jobs.insert(key, (phase, now));
+        }
+        previous
+    };
+
+    println!("[INFO] {} -> {:?}", path, phase);
+    let _ = app.emit("job-phase-changed", JobPhasePayload {
+        path: path.to_string(),
+        phase,
+        timestamp_ms: now,
+        previous_phase: previous.map(|(p, _)| p),
+        previous_phase_ms: previous.map(|(_, started)| now.saturating_sub(started)),
+    });
+}
+
+/// Terminal phase for a job that returned an error
+pub fn emit_job_failed(app: &AppHandle, path: &str, error: &str) {
+    let phase = if crate::video::is_cancelled(error) { JobPhase::Cancelled } else { JobPhase::Error };
+    emit_job_phase(app, path, phase);
+}
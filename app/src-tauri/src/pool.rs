@@ -0,0 +1,60 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// A counted set of slots shared by all jobs
+pub struct SlotPool {
+    active: Mutex<u32>,
+    freed: Condvar,
+}
+
+/// Encodes (`max_concurrent_encodes`)
+pub static ENCODE: SlotPool = SlotPool::new();
+/// Output verification after the encode (`max_concurrent_verifications`), so a long
+/// check doesn't keep the next encode waiting
+pub static VERIFY: SlotPool = SlotPool::new();
+
+/// One slot of a pool; released when dropped
+pub struct Slot(&'static SlotPool);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.0.active.lock() {
+            *active = active.saturating_sub(1);
+        }
+        self.0.freed.notify_all();
+    }
+}
+
+impl SlotPool {
+    const fn new() -> Self {
+        SlotPool { active: Mutex::new(0), freed: Condvar::new() }
+    }
+
+    /// Take a slot if fewer than `limit` are in use, waiting up to `timeout` for one to
+    /// free up (0 = no limit). Callers loop on `None` so they can check for cancellation
+    /// in between.
+    pub fn acquire(&'static self, limit: u32, timeout: Duration) -> Option<Slot> {
+        let guard = self.active.lock().ok()?;
+        let (mut active, _) = self.freed
+            .wait_timeout_while(guard, timeout, |active| limit > 0 && *active >= limit)
+            .ok()?;
+        if limit > 0 && *active >= limit {
+            return None;
+        }
+        *active += 1;
+        Some(Slot(self))
+    }
+}
+
+/// Run `worker` on `count` threads (at least one) and wait for all of them. Workers pull
+/// their jobs from shared state; the encode slots keep the actual encodes within the limit.
+pub fn run_workers<F>(count: u32, worker: F)
+where
+    F: Fn() + Sync,
+{
+    std::thread::scope(|scope| {
+        for _ in 0..count.max(1) {
+            scope.spawn(&worker);
+        }
+    });
+}
@@ -0,0 +1,70 @@
+//! VMAF pooling beyond the mean: harmonic mean, low percentiles and the minimum, computed
+//! from the per-frame scores of libvmaf's JSON log. The mean hides short stretches of bad
+//! frames; the low percentiles show them.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VmafPooling {
+    pub harmonic_mean: f64,
+    pub p5: f64, // 5th percentile ("5% lows")
+    pub p1: f64, // 1st percentile ("1% lows")
+    pub min: f64,
+}
+
+/// Per-frame VMAF scores of a libvmaf JSON log (`frames[].metrics.vmaf`)
+pub fn frame_scores(log: &serde_json::Value) -> Vec<f64> {
+    log.get("frames")
+        .and_then(|f| f.as_array())
+        .map(|frames| {
+            frames.iter()
+                .filter_map(|f| f.get("metrics")?.get("vmaf")?.as_f64())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn mean(scores: &[f64]) -> f64 {
+    scores.iter().sum::<f64>() / scores.len().max(1) as f64
+}
+
+/// libvmaf's harmonic mean: shifted by one so a zero score doesn't blow up
+fn harmonic_mean(scores: &[f64]) -> f64 {
+    scores.len() as f64 / scores.iter().map(|s| 1.0 / (s + 1.0)).sum::<f64>() - 1.0
+}
+
+/// Nearest-rank percentile of `sorted` (ascending)
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn pool(scores: &[f64]) -> Option<VmafPooling> {
+    if scores.is_empty() {
+        return None;
+    }
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(VmafPooling {
+        harmonic_mean: harmonic_mean(scores),
+        p5: percentile(&sorted, 5.0),
+        p1: percentile(&sorted, 1.0),
+        min: sorted[0],
+    })
+}
+
+/// Score for `method` ("harmonic_mean", "p5", "p1", "min"; anything else is the mean)
+pub fn pooled(scores: &[f64], method: &str) -> Option<f64> {
+    if method.is_empty() || method == "mean" {
+        return (!scores.is_empty()).then(|| mean(scores));
+    }
+    let pooling = pool(scores)?;
+    Some(match method {
+        "harmonic_mean" => pooling.harmonic_mean,
+        "p5" => pooling.p5,
+        "p1" => pooling.p1,
+        "min" => pooling.min,
+        _ => mean(scores),
+    })
+}
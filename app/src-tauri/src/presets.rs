@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::video::CompressionConfig;
+
+/// A named, complete CompressionConfig ("Archive AV1", "Fast NVENC", ...), persisted as
+/// `presets.json` in the app config dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub name: String,
+    pub config: CompressionConfig,
+}
+
+struct Store {
+    file: Option<PathBuf>,
+    presets: Vec<Preset>,
+}
+
+/// On-disk layout of the presets file
+#[derive(Serialize, Deserialize, Default)]
+struct PresetsFile {
+    presets: Vec<Preset>,
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+pub fn load(file: Option<PathBuf>) {
+    let presets = file.as_ref()
+        .and_then(|f| crate::schema::load_file::<PresetsFile>(crate::schema::Kind::Presets, f))
+        .unwrap_or_default()
+        .presets;
+    if let Ok(mut guard) = STORE.lock() {
+        *guard = Some(Store { file, presets });
+    }
+}
+
+/// All presets, sorted by name
+pub fn list() -> Vec<Preset> {
+    STORE.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.presets.clone()))
+        .unwrap_or_default()
+}
+
+/// Add a preset, replacing one with the same name (case-insensitive)
+pub fn save(name: &str, config: CompressionConfig) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Preset name is empty".to_string());
+    }
+    update(|presets| {
+        presets.retain(|p| !p.name.eq_ignore_ascii_case(name));
+        presets.push(Preset { name: name.to_string(), config });
+        presets.sort_by_key(|p| p.name.to_lowercase());
+        Ok(())
+    })?;
+    println!("[INFO] Saved preset '{}'", name);
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), String> {
+    update(|presets| {
+        let count = presets.len();
+        presets.retain(|p| p.name != name);
+        if presets.len() == count {
+            return Err(format!("Preset '{}' not found", name));
+        }
+        Ok(())
+    })?;
+    println!("[INFO] Deleted preset '{}'", name);
+    Ok(())
+}
+
+/// Change the presets and write them to disk; nothing changes if either step fails
+fn update(change: impl FnOnce(&mut Vec<Preset>) -> Result<(), String>) -> Result<(), String> {
+    let mut guard = STORE.lock().map_err(|e| e.to_string())?;
+    let store = guard.get_or_insert_with(|| Store { file: None, presets: Vec::new() });
+    let mut presets = store.presets.clone();
+    change(&mut presets)?;
+    if let Some(file) = &store.file {
+        let json = crate::schema::write(&PresetsFile { presets: presets.clone() })?;
+        crate::schema::save_file(file, &json).map_err(|e| format!("Failed to save presets: {}", e))?;
+    }
+    store.presets = presets;
+    Ok(())
+}
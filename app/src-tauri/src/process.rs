@@ -0,0 +1,170 @@
+//! Small OS helpers for controlling already-spawned ffmpeg processes
+//! (priority, suspend/resume and kill), and the builders every ffmpeg/ffprobe
+//! command starts from.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::video::CompressionConfig;
+
+#[cfg(windows)]
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+#[cfg(windows)]
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, SetPriorityClass, TerminateProcess, BELOW_NORMAL_PRIORITY_CLASS,
+    PROCESS_SET_INFORMATION, PROCESS_SUSPEND_RESUME, PROCESS_TERMINATE,
+};
+
+#[cfg(windows)]
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtSuspendProcess(process: HANDLE) -> i32;
+    fn NtResumeProcess(process: HANDLE) -> i32;
+}
+
+/// Run `f` with a process handle opened with `access`, closing it afterwards
+#[cfg(windows)]
+fn with_process_handle(pid: u32, access: u32, f: impl FnOnce(HANDLE) -> bool) -> bool {
+    unsafe {
+        let handle = OpenProcess(access, 0, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let ok = f(handle);
+        CloseHandle(handle);
+        ok
+    }
+}
+
+/// Drop a process to below-normal priority so it yields to interactive apps
+pub fn lower_priority(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        with_process_handle(pid, PROCESS_SET_INFORMATION, |h| unsafe {
+            SetPriorityClass(h, BELOW_NORMAL_PRIORITY_CLASS) != 0
+        })
+    }
+    #[cfg(unix)]
+    {
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, 10) == 0 }
+    }
+}
+
+/// Freeze a process in place; it keeps its memory and open files
+pub fn suspend(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        with_process_handle(pid, PROCESS_SUSPEND_RESUME, |h| unsafe { NtSuspendProcess(h) >= 0 })
+    }
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) == 0 }
+    }
+}
+
+pub fn resume(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        with_process_handle(pid, PROCESS_SUSPEND_RESUME, |h| unsafe { NtResumeProcess(h) >= 0 })
+    }
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGCONT) == 0 }
+    }
+}
+
+/// Force-terminate a process (no chance to clean up, like `kill -9`)
+pub fn kill(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        with_process_handle(pid, PROCESS_TERMINATE, |h| unsafe { TerminateProcess(h, 1) != 0 })
+    }
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) == 0 }
+    }
+}
+
+/// Environment and global ffmpeg flags of the job running on this thread
+#[derive(Clone)]
+struct JobSpawnOptions {
+    env: BTreeMap<String, String>,
+    global_flags: Vec<String>,
+}
+
+thread_local! {
+    static JOB_SPAWN: RefCell<Option<JobSpawnOptions>> = const { RefCell::new(None) };
+}
+
+/// While alive, commands built on this thread get the job's `env` and `global_flags`.
+/// Dropping it restores what was set before, so scopes can nest.
+pub struct JobScope(Option<JobSpawnOptions>);
+
+impl Drop for JobScope {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        JOB_SPAWN.with(|s| *s.borrow_mut() = previous);
+    }
+}
+
+pub fn job_scope(config: &CompressionConfig) -> JobScope {
+    let options = JobSpawnOptions {
+        env: config.env.clone(),
+        global_flags: config.global_flags.iter()
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect(),
+    };
+    JobScope(JOB_SPAWN.with(|s| s.borrow_mut().replace(options)))
+}
+
+/// Working directory for children: the app's own, unless it has gone away (a packaged
+/// build started from a since-deleted folder), then the temp dir. Relative paths in
+/// user input (manifest paths, filter arguments) keep resolving the same way.
+fn spawn_dir() -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Options every child gets: no console window on Windows, no stdin (a GUI app's
+/// stdin may be invalid, and ffmpeg would otherwise poll it for key presses) and a
+/// working directory that exists
+pub fn tool_command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    command.stdin(Stdio::null()).current_dir(spawn_dir());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+    command
+}
+
+/// ffmpeg command with -nostdin and the current job's environment and global flags
+pub fn ffmpeg_command(program: &str) -> Command {
+    let mut command = tool_command(program);
+    command.arg("-nostdin");
+    JOB_SPAWN.with(|s| {
+        if let Some(options) = s.borrow().as_ref() {
+            command.envs(&options.env)
+                .args(options.global_flags.iter().filter(|f| *f != "-nostdin"));
+        }
+    });
+    command
+}
+
+/// ffprobe command with the current job's environment. The global flags are left out:
+/// ffprobe rejects most ffmpeg-only ones (-nostdin, -xerror).
+pub fn ffprobe_command(program: &str) -> Command {
+    let mut command = tool_command(program);
+    JOB_SPAWN.with(|s| {
+        if let Some(options) = s.borrow().as_ref() {
+            command.envs(&options.env);
+        }
+    });
+    command
+}
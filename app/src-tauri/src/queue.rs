@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::video::CompressionConfig;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum QueuedJobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJob {
+    pub id: u64,
+    pub input_path: String,
+    pub output_path: String,
+    pub config: CompressionConfig,
+    pub duration_sec: f64,
+    pub status: QueuedJobStatus,
+    pub error: Option<String>,
+    // Times the job went back to the end of the queue because its file was in use
+    #[serde(default)]
+    pub deferrals: u32,
+}
+
+/// On-disk layout of the queue file
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct QueueFile {
+    next_id: u64,
+    jobs: Vec<QueuedJob>,
+}
+
+/// Backend job queue, saved to `jobs.json` in the app data dir after every change so a
+/// batch interrupted by closing the app (or a crash/reboot) can be resumed.
+pub struct JobQueue {
+    file: Option<PathBuf>,
+    next_id: u64,
+    jobs: Vec<QueuedJob>,
+    pub runner_active: bool, // A run_job_queue call is working through the queue
+}
+
+impl JobQueue {
+    /// Load the saved queue. Jobs that were running when the app went away are pending
+    /// again; their partial output is overwritten when they rerun.
+    pub fn load(file: Option<PathBuf>) -> Self {
+        let mut saved: QueueFile = file.as_ref()
+            .and_then(|f| crate::schema::load_file(crate::schema::Kind::JobQueue, f))
+            .unwrap_or_default();
+
+        let mut interrupted = 0;
+        for job in saved.jobs.iter_mut().filter(|j| j.status == QueuedJobStatus::Running) {
+            job.status = QueuedJobStatus::Pending;
+            interrupted += 1;
+        }
+        if !saved.jobs.is_empty() {
+            println!("[INFO] Restored job queue: {} jobs ({} interrupted)", saved.jobs.len(), interrupted);
+        }
+
+        JobQueue { file, next_id: saved.next_id, jobs: saved.jobs, runner_active: false }
+    }
+
+    fn save(&self) {
+        let Some(file) = &self.file else { return };
+        let data = QueueFile { next_id: self.next_id, jobs: self.jobs.clone() };
+        let result = crate::schema::write(&data)
+            .and_then(|json| {
+                // Write then rename, so a crash mid-write can't leave a truncated queue
+                let tmp = file.with_extension("json.tmp");
+                std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+                std::fs::rename(&tmp, file).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("[ERROR] Failed to save job queue: {}", e);
+        }
+    }
+
+    pub fn enqueue(&mut self, input_path: String, output_path: String, config: CompressionConfig, duration_sec: f64) -> QueuedJob {
+        self.next_id += 1;
+        let job = QueuedJob {
+            id: self.next_id,
+            input_path,
+            output_path,
+            config,
+            duration_sec,
+            status: QueuedJobStatus::Pending,
+            error: None,
+            deferrals: 0,
+        };
+        self.jobs.push(job.clone());
+        self.save();
+        job
+    }
+
+    /// Queue a copy of a finished, failed or cancelled job with some settings changed.
+    /// `overrides` maps CompressionConfig fields (by their JSON names) and `outputPath` to
+    /// new values; `{"delta": n}` adjusts a number instead, e.g. `{"targetCRF": {"delta": -2}}`.
+    pub fn requeue_with_changes(&mut self, id: u64, overrides: &serde_json::Value) -> Result<QueuedJob, String> {
+        let job = self.jobs.iter().find(|j| j.id == id)
+            .ok_or_else(|| format!("No queued job with id {}", id))?;
+        if matches!(job.status, QueuedJobStatus::Pending | QueuedJobStatus::Running) {
+            return Err(format!("Job {} hasn't finished yet", id));
+        }
+        let overrides = overrides.as_object().ok_or("Overrides must be an object")?;
+
+        let mut output_path = job.output_path.clone();
+        let mut config = serde_json::to_value(&job.config).map_err(|e| e.to_string())?;
+        let fields = config.as_object_mut().ok_or("Job config is not an object")?;
+        for (key, value) in overrides {
+            if key == "outputPath" {
+                output_path = value.as_str().ok_or("outputPath must be a string")?.to_string();
+                continue;
+            }
+            let current = fields.get_mut(key).ok_or_else(|| format!("Unknown setting '{}'", key))?;
+            match value.get("delta").and_then(|d| d.as_f64()) {
+                Some(delta) => {
+                    let number = current.as_f64().ok_or_else(|| format!("'{}' is not a number", key))?;
+                    // Keep integer fields integers
+                    *current = if current.is_f64() {
+                        serde_json::json!(number + delta)
+                    } else {
+                        serde_json::json!((number + delta).round().max(0.0) as u64)
+                    };
+                }
+                None => *current = value.clone(),
+            }
+        }
+        let config: CompressionConfig = serde_json::from_value(config)
+            .map_err(|e| format!("Invalid overrides: {}", e))?;
+
+        let (input_path, duration_sec) = (job.input_path.clone(), job.duration_sec);
+        let new_job = self.enqueue(input_path, output_path, config, duration_sec);
+        println!("[INFO] Job {} requeued as job {} with {} change(s)", id, new_job.id, overrides.len());
+        Ok(new_job)
+    }
+
+    pub fn list(&self) -> Vec<QueuedJob> {
+        self.jobs.clone()
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<(), String> {
+        let index = self.jobs.iter().position(|j| j.id == id)
+            .ok_or_else(|| format!("No queued job with id {}", id))?;
+        if self.jobs[index].status == QueuedJobStatus::Running {
+            return Err("Job is running; cancel it first".to_string());
+        }
+        self.jobs.remove(index);
+        self.save();
+        Ok(())
+    }
+
+    /// Take the next pending job and mark it running
+    pub fn start_next(&mut self) -> Option<QueuedJob> {
+        let job = self.jobs.iter_mut().find(|j| j.status == QueuedJobStatus::Pending)?;
+        job.status = QueuedJobStatus::Running;
+        let job = job.clone();
+        self.save();
+        Some(job)
+    }
+
+    /// Record a job's result. A job whose file was in use goes back to the end of the
+    /// queue (up to MAX_DEFERRALS times); returns true in that case.
+    pub fn finish(&mut self, id: u64, result: &Result<(), String>) -> bool {
+        if let Err(e) = result {
+            let index = self.jobs.iter().position(|j| j.id == id);
+            if let Some(index) = index.filter(|&i| {
+                crate::filelock::is_file_in_use_error(e) && self.jobs[i].deferrals < crate::filelock::MAX_DEFERRALS
+            }) {
+                let mut job = self.jobs.remove(index);
+                job.status = QueuedJobStatus::Pending;
+                job.deferrals += 1;
+                job.error = Some(e.clone());
+                println!("[INFO] Job {} deferred to the end of the queue ({}/{}): {}", id, job.deferrals, crate::filelock::MAX_DEFERRALS, e);
+                self.jobs.push(job);
+                self.save();
+                return true;
+            }
+        }
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            match result {
+                Ok(()) => {
+                    job.status = QueuedJobStatus::Done;
+                    job.error = None;
+                }
+                Err(e) => {
+                    job.status = if crate::video::is_cancelled(e) { QueuedJobStatus::Cancelled } else { QueuedJobStatus::Failed };
+                    job.error = Some(e.clone());
+                }
+            }
+            self.save();
+        }
+        false
+    }
+}
@@ -0,0 +1,156 @@
+//! Post-batch cleanup: match the outputs under an output folder to their originals through
+//! the sidecars, check each output again and list the originals that are safe to delete.
+//! Nothing is deleted here; the UI confirms and sends the list to `delete_original`.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::sidecar::SidecarRecord;
+use crate::video::{self, job_key};
+
+/// Output duration must reach this share of the source's (unless the encode was trimmed)
+const MIN_DURATION_RATIO: f64 = 0.98;
+
+/// An original whose output checked out
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileMatch {
+    pub source_path: String,
+    pub output_path: String,
+    pub source_size: u64,
+    pub output_size: u64,
+    pub vmaf: Option<f64>,
+}
+
+/// An output that was found but whose original must stay
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileProblem {
+    pub source_path: String,
+    pub output_path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub safe_to_delete: Vec<ReconcileMatch>,
+    pub reclaimable_bytes: u64, // Sum of the source sizes in safe_to_delete
+    pub problems: Vec<ReconcileProblem>,
+    // Further outputs of an original that is already matched (older runs, other settings)
+    pub duplicate_outputs: Vec<String>,
+    // Videos under the output folder without a sidecar; can't be tied to an original
+    pub unmatched_outputs: Vec<String>,
+}
+
+/// Walk `output_root` for finished outputs (sidecar result "Done") whose source lies under
+/// `scan_root`. The newest output per source is verified: it must be unchanged since the
+/// job (size recorded in the sidecar), decode cleanly and be about as long as the source,
+/// and the source must still be the file that was compressed.
+pub fn reconcile_outputs(scan_root: &str, output_root: &str, ffmpeg_path: &str, ffprobe_path: &str) -> ReconcileReport {
+    let mut report = ReconcileReport::default();
+    let scan_root = canonical(scan_root);
+
+    // Source key -> sidecars of its outputs, newest first
+    let mut by_source: HashMap<String, Vec<SidecarRecord>> = HashMap::new();
+    for entry in WalkDir::new(output_root).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || !video::is_video_file(path) {
+            continue;
+        }
+        let output = path.to_string_lossy().to_string();
+        let Some(record) = crate::sidecar::read_sidecar(&output) else {
+            report.unmatched_outputs.push(output);
+            continue;
+        };
+        // Replaced in place, or pointing at a copy that was moved; nothing to clean up
+        if record.result != "Done" || job_key(&record.source_path) == job_key(&output) {
+            continue;
+        }
+        if !Path::new(&canonical(&record.source_path)).starts_with(&scan_root) {
+            continue;
+        }
+        // The record's own output path is stale if the folder was moved; trust where it is now
+        let record = SidecarRecord { output_path: output, ..record };
+        by_source.entry(job_key(&record.source_path)).or_default().push(record);
+    }
+
+    for mut records in by_source.into_values() {
+        records.sort_by_key(|r| std::cmp::Reverse(r.finished_at));
+        let record = records.remove(0);
+        report.duplicate_outputs.extend(records.into_iter().map(|r| r.output_path));
+
+        match check(&record, ffmpeg_path, ffprobe_path) {
+            Ok(output_size) => {
+                report.reclaimable_bytes += record.source_size;
+                report.safe_to_delete.push(ReconcileMatch {
+                    source_path: record.source_path,
+                    output_path: record.output_path,
+                    source_size: record.source_size,
+                    output_size,
+                    vmaf: record.vmaf,
+                });
+            }
+            Err(reason) => {
+                eprintln!("[WARNING] Keeping {}: {}", record.source_path, reason);
+                report.problems.push(ReconcileProblem {
+                    source_path: record.source_path,
+                    output_path: record.output_path,
+                    reason,
+                });
+            }
+        }
+    }
+
+    report.safe_to_delete.sort_by(|a, b| a.source_path.cmp(&b.source_path));
+    report.problems.sort_by(|a, b| a.source_path.cmp(&b.source_path));
+    println!(
+        "[INFO] Reconcile: {} original(s) safe to delete ({} bytes), {} kept, {} output(s) without sidecar",
+        report.safe_to_delete.len(),
+        report.reclaimable_bytes,
+        report.problems.len(),
+        report.unmatched_outputs.len()
+    );
+    report
+}
+
+/// Verify one source/output pair; returns the output size
+fn check(record: &SidecarRecord, ffmpeg_path: &str, ffprobe_path: &str) -> Result<u64, String> {
+    let source_size = std::fs::metadata(&record.source_path)
+        .map_err(|_| "original no longer exists".to_string())?
+        .len();
+    if source_size != record.source_size {
+        return Err("original changed since it was compressed".to_string());
+    }
+    let output_size = std::fs::metadata(&record.output_path)
+        .map_err(|e| format!("output unreadable: {}", e))?
+        .len();
+    if output_size != record.output_size {
+        return Err("output changed since it was written".to_string());
+    }
+
+    video::verify_video(ffmpeg_path, &record.output_path)?;
+
+    let trimmed = record.ffmpeg_commands.iter().any(|c| {
+        c.split_whitespace().any(|a| a == "-t" || a == "-to" || a == "-ss" || a == "-frames:v" || a.contains("trim="))
+    });
+    if !trimmed {
+        let source = video::get_video_info(Path::new(&record.source_path), ffprobe_path)?;
+        let output = video::get_video_info(Path::new(&record.output_path), ffprobe_path)?;
+        if source.duration_sec > 0.0 && output.duration_sec < source.duration_sec * MIN_DURATION_RATIO {
+            return Err(format!(
+                "output is {:.1} s long, original {:.1} s",
+                output.duration_sec, source.duration_sec
+            ));
+        }
+    }
+    Ok(output_size)
+}
+
+fn canonical(path: &str) -> String {
+    std::fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
@@ -0,0 +1,164 @@
+//! Preflight for stream copies and container changes: check every probed stream against
+//! what the target container can hold, so a PGS track or FLAC audio headed for MP4 is
+//! reported up front with a fix instead of failing late inside ffmpeg.
+
+use serde::Serialize;
+
+use crate::video::{self, CompressionConfig, VideoInfo};
+
+/// A stream the target container can't take
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Incompatibility {
+    pub stream: String, // "v:0", "a:1", "s:0"
+    pub codec: String,
+    pub reason: String,
+    pub fix: String,
+    // ffmpeg would fail on it; otherwise the stream is only left out of the output
+    pub blocking: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemuxReport {
+    pub path: String,
+    pub container: String,
+    pub incompatibilities: Vec<Incompatibility>,
+}
+
+/// Video codecs a container takes as a stream copy (None = no restriction known)
+fn container_video_codecs(container: &str) -> Option<&'static [&'static str]> {
+    match container {
+        "mp4" | "m4v" => Some(&["h264", "hevc", "av1", "vp9", "mpeg4", "mpeg2video", "mpeg1video"]),
+        "mov" => Some(&["h264", "hevc", "av1", "prores", "mpeg4", "mjpeg", "dnxhd", "mpeg2video"]),
+        "webm" => Some(&["vp8", "vp9", "av1"]),
+        _ => None,
+    }
+}
+
+/// Audio codecs a container takes as a stream copy (None = no restriction known)
+fn container_audio_codecs(container: &str) -> Option<&'static [&'static str]> {
+    match container {
+        "mp4" | "m4v" => Some(&["aac", "mp3", "mp2", "ac3", "eac3", "alac", "opus"]),
+        "mov" => Some(&["aac", "mp3", "ac3", "eac3", "alac", "pcm_s16le", "pcm_s16be", "pcm_s24le", "pcm_s24be"]),
+        "webm" => Some(&["opus", "vorbis"]),
+        _ => None,
+    }
+}
+
+/// Audio encoder to suggest for a container
+fn suggested_audio(container: &str) -> &'static str {
+    if container == "webm" { "Opus" } else { "AAC" }
+}
+
+/// Check the streams of `info` against `container` for the way `config` would write them:
+/// copied streams must fit the container as they are, re-encoded ones through the
+/// configured encoders. Custom commands are the user's own business and aren't checked.
+pub fn check(info: &VideoInfo, config: &CompressionConfig, container: &str) -> Vec<Incompatibility> {
+    let mut found = Vec::new();
+    if config.compression_mode == "custom" {
+        return found;
+    }
+    let copy_mode = config.compression_mode == "copy";
+    let copy_audio = copy_mode || config.audio_encoder == "copy";
+    let upper = container.to_uppercase();
+
+    if copy_mode {
+        if let Some(allowed) = container_video_codecs(container) {
+            if !allowed.contains(&info.encoder.as_str()) {
+                found.push(Incompatibility {
+                    stream: "v:0".to_string(),
+                    codec: info.encoder.clone(),
+                    reason: format!("{} can't hold {} video", upper, info.encoder),
+                    fix: "Re-encode the video (leave copy mode) or output MKV".to_string(),
+                    blocking: true,
+                });
+            }
+        }
+    } else {
+        let v_enc = if config.video_encoder.is_empty() { "libx264" } else { config.video_encoder.as_str() };
+        let a_enc = if config.audio_encoder.is_empty() || copy_audio { "aac" } else { config.audio_encoder.as_str() };
+        if let Err(reason) = video::check_container_compatibility(container, v_enc, a_enc) {
+            found.push(Incompatibility {
+                stream: "encoder".to_string(),
+                codec: format!("{} / {}", v_enc, a_enc),
+                reason,
+                fix: format!("Pick encoders {} supports or output MKV", upper),
+                blocking: true,
+            });
+        }
+    }
+
+    if copy_audio {
+        if let Some(allowed) = container_audio_codecs(container) {
+            for (i, track) in info.audio_tracks.iter().flatten().enumerate() {
+                if !allowed.contains(&track.codec.as_str()) {
+                    found.push(Incompatibility {
+                        stream: format!("a:{}", i),
+                        codec: track.codec.clone(),
+                        reason: format!("{} can't hold {} audio", upper, track.codec),
+                        fix: format!("Re-encode the audio to {} or output MKV", suggested_audio(container)),
+                        blocking: true,
+                    });
+                }
+            }
+        }
+    }
+
+    if config.subtitle_mode != "drop" {
+        // ffmpeg's own default ("" mode) also converts text subtitles to the container's
+        let planned = matches!(config.subtitle_mode.as_str(), "copy" | "convert") && !video::user_maps_streams(config);
+        let convertible = matches!(config.subtitle_mode.as_str(), "convert" | "")
+            && video::container_text_subtitle(container).is_some();
+        for (i, track) in info.subtitle_tracks.iter().flatten().enumerate() {
+            if video::container_holds_subtitle(container, &track.codec) {
+                continue;
+            }
+            let text = video::TEXT_SUBTITLE_CODECS.contains(&track.codec.as_str());
+            if text && convertible {
+                continue;
+            }
+            let fix = if text && video::container_text_subtitle(container).is_some() {
+                "Set subtitles to convert, or output MKV".to_string()
+            } else {
+                "Output MKV, burn the subtitle in, or drop subtitles".to_string()
+            };
+            found.push(Incompatibility {
+                stream: format!("s:{}", i),
+                codec: track.codec.clone(),
+                reason: format!("{} can't hold {} subtitles", upper, track.codec),
+                fix,
+                // The copy/convert plan leaves such tracks out; without one ffmpeg trips over them
+                blocking: !planned,
+            });
+        }
+    }
+    found
+}
+
+/// Preflight every file of a batch against the container it would be written to; files
+/// without problems (or that can't be probed) are left out
+pub fn preflight(paths: &[String], config: &CompressionConfig, ffprobe_path: &str) -> Vec<RemuxReport> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let info = video::get_metadata_quick(path, ffprobe_path).ok().filter(|i| i.status == "Pending")?;
+            let container = config.output_format_for(path);
+            let incompatibilities = check(&info, config, &container);
+            (!incompatibilities.is_empty()).then(|| RemuxReport {
+                path: path.clone(),
+                container,
+                incompatibilities,
+            })
+        })
+        .collect()
+}
+
+/// One-line summary of the blocking problems, for a job error
+pub fn blocking_summary(incompatibilities: &[Incompatibility]) -> Option<String> {
+    let blocking: Vec<String> = incompatibilities.iter()
+        .filter(|i| i.blocking)
+        .map(|i| format!("{} ({}): {}. {}", i.stream, i.codec, i.reason, i.fix))
+        .collect();
+    (!blocking.is_empty()).then(|| blocking.join("; "))
+}
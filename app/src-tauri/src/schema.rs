@@ -0,0 +1,278 @@
+//! Versions of the files the app writes (settings, presets, the job queue, sidecars).
+//! Each file carries a `schemaVersion`; older files are upgraded one version at a time
+//! when read, and files that can't be read are moved aside rather than overwritten.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Version written by this build. Bump it together with a new arm in `upgrade_step`
+/// (and `upgrade_config` if CompressionConfig changed incompatibly).
+pub const SCHEMA_VERSION: u64 = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Settings,
+    Presets,
+    JobQueue,
+    Sidecar,
+    MetadataCache,
+    CrfSearchCache,
+    Service,
+    ServiceStatus,
+    JobHistory,
+}
+
+/// Parse `json` as a file of `kind`, upgrading it to the current version first.
+/// Fails for malformed files and for files written by a newer version.
+pub fn read<T: DeserializeOwned>(kind: Kind, json: &str) -> Result<T, String> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    // Version 0 = written before versioning
+    let version = value.get("schemaVersion").and_then(Value::as_u64).unwrap_or(0);
+    if version > SCHEMA_VERSION {
+        return Err(format!("written by a newer version of the app (schema {}, this build reads up to {})", version, SCHEMA_VERSION));
+    }
+    for from in version..SCHEMA_VERSION {
+        upgrade_step(kind, from, &mut value);
+        println!("[INFO] Upgraded {:?} data from schema {} to {}", kind, from, from + 1);
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Serialize `data` (a struct) with the current `schemaVersion` added
+pub fn write<T: Serialize>(data: &T) -> Result<String, String> {
+    let mut value = serde_json::to_value(data).map_err(|e| e.to_string())?;
+    match value.as_object_mut() {
+        Some(object) => {
+            object.insert("schemaVersion".to_string(), Value::from(SCHEMA_VERSION));
+        }
+        None => return Err("Only objects can carry a schema version".to_string()),
+    }
+    serde_json::to_string_pretty(&value).map_err(|e| e.to_string())
+}
+
+/// Write one of the app's own files: to `<name>.tmp` first, then renamed over the old file,
+/// so a crash mid-write can't leave it truncated
+pub fn save_file(file: &Path, json: &str) -> Result<(), String> {
+    let tmp = file.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, file).map_err(|e| e.to_string())
+}
+
+/// Load one of the app's own files. A file that exists but can't be read is renamed to
+/// `<name>.unreadable-<unix secs>` so the next save doesn't destroy what's in it.
+pub fn load_file<T: DeserializeOwned>(kind: Kind, file: &Path) -> Option<T> {
+    let json = std::fs::read_to_string(file).ok()?;
+    match read(kind, &json) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            let kept = format!("{}.unreadable-{}", file.display(), crate::sidecar::now_unix_secs());
+            match std::fs::rename(file, &kept) {
+                Ok(()) => eprintln!("[WARNING] Could not read {:?} ({}); kept it as {}", file, e, kept),
+                Err(re) => eprintln!("[WARNING] Could not read {:?} ({}) or move it aside: {}", file, e, re),
+            }
+            None
+        }
+    }
+}
+
+/// Upgrade `value` from version `from` to `from + 1`
+fn upgrade_step(kind: Kind, from: u64, value: &mut Value) {
+    // 0 -> 1: presets.json was a bare array of presets
+    if let (Kind::Presets, 0) = (kind, from) {
+        if value.is_array() {
+            *value = serde_json::json!({ "presets": value.take() });
+        }
+    }
+
+    for config in configs_mut(kind, value) {
+        upgrade_config(from, config);
+    }
+}
+
+/// The CompressionConfig objects stored in a file of `kind`
+fn configs_mut(kind: Kind, value: &mut Value) -> Vec<&mut Value> {
+    let list_key = match kind {
+        Kind::Settings | Kind::MetadataCache | Kind::CrfSearchCache
+        | Kind::Service | Kind::ServiceStatus | Kind::JobHistory => return Vec::new(),
+        Kind::Sidecar => return value.get_mut("config").into_iter().collect(),
+        Kind::Presets => "presets",
+        Kind::JobQueue => "jobs",
+    };
+    match value.get_mut(list_key).and_then(Value::as_array_mut) {
+        Some(items) => items.iter_mut().filter_map(|item| item.get_mut("config")).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Upgrade a stored CompressionConfig from version `from` to `from + 1`. Fields added
+/// with `#[serde(default)]` need no step; renames and changed meanings do.
+fn upgrade_config(from: u64, config: &mut Value) {
+    // 0 -> 1: unchanged, version 1 only started recording the version
+    // 1 -> 2: encoder options moved out of the custom params
+    if from == 1 {
+        move_nvenc_params(config);
+        move_svtav1_preset(config);
+    }
+}
+
+/// 1 -> 2: a libsvtav1 `-preset` in the custom params becomes the entry's typed preset
+/// (it came after the typed one, so it wins). Values that aren't a preset number stay.
+fn move_svtav1_preset(config: &mut Value) {
+    let Some(encoders) = config.get_mut("availableVideoEncoders").and_then(Value::as_array_mut) else { return };
+    for encoder in encoders.iter_mut() {
+        if !encoder.get("value").and_then(Value::as_str).is_some_and(|v| v.contains("libsvtav1")) {
+            continue;
+        }
+        let Some(params) = encoder.get_mut("customParams") else { continue };
+        let original = params.clone();
+        let Some(taken) = take_param(params, "-preset") else { continue };
+        let Ok(preset) = taken.parse::<u8>() else {
+            *params = original;
+            continue;
+        };
+        let Some(object) = encoder.as_object_mut() else { continue };
+        let svtav1 = object.entry("svtav1").or_insert_with(|| serde_json::json!({}));
+        if svtav1.is_null() {
+            *svtav1 = serde_json::json!({});
+        }
+        if let Some(svtav1) = svtav1.as_object_mut() {
+            svtav1.insert("preset".to_string(), Value::from(preset));
+        }
+    }
+}
+
+/// 1 -> 2: NVENC `-preset`/`-tune` written into an encoder's custom params become the typed
+/// `nvenc` options. They came after the typed ones on the command line, so they win here too;
+/// the selected encoder's entry counts over the other NVENC entries.
+fn move_nvenc_params(config: &mut Value) {
+    let selected = config.get("videoEncoder").and_then(Value::as_str).unwrap_or("").to_string();
+    let Some(encoders) = config.get_mut("availableVideoEncoders").and_then(Value::as_array_mut) else { return };
+    let mut found: Vec<(bool, &str, String)> = Vec::new();
+    for encoder in encoders.iter_mut() {
+        let value = encoder.get("value").and_then(Value::as_str).unwrap_or("").to_string();
+        if !value.contains("nvenc") {
+            continue;
+        }
+        let Some(params) = encoder.get_mut("customParams") else { continue };
+        for flag in ["preset", "tune"] {
+            if let Some(taken) = take_param(params, &format!("-{}", flag)) {
+                found.push((value == selected, flag, taken));
+            }
+        }
+    }
+    if found.is_empty() {
+        return;
+    }
+    // Selected encoder last, so its values are the ones kept
+    found.sort_by_key(|(selected, _, _)| *selected);
+    let Some(object) = config.as_object_mut() else { return };
+    let nvenc = object.entry("nvenc").or_insert_with(|| serde_json::json!({}));
+    if nvenc.is_null() {
+        *nvenc = serde_json::json!({});
+    }
+    if let Some(nvenc) = nvenc.as_object_mut() {
+        for (_, flag, value) in found {
+            nvenc.insert(flag.to_string(), Value::from(value));
+        }
+    }
+}
+
+/// Remove every `flag value` pair from stored custom params (a list of strings holding
+/// one or more arguments each). Returns the last value, the one ffmpeg used.
+fn take_param(params: &mut Value, flag: &str) -> Option<String> {
+    let items = params.as_array_mut()?;
+    let mut taken = None;
+    let mut value_next = false;
+    for item in items.iter_mut() {
+        let Some(text) = item.as_str() else { continue };
+        let mut kept: Vec<&str> = Vec::new();
+        for token in text.split_whitespace() {
+            if value_next {
+                taken = Some(token.to_string());
+                value_next = false;
+            } else if token == flag {
+                value_next = true;
+            } else {
+                kept.push(token);
+            }
+        }
+        *item = Value::from(kept.join(" "));
+    }
+    items.retain(|item| item.as_str() != Some(""));
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, take_param, write, Kind, SCHEMA_VERSION};
+    use serde_json::{json, Value};
+
+    /// Read as `kind`, write back and read again: the second read must change nothing
+    fn round_trip(kind: Kind, stored: Value) -> Value {
+        let upgraded: Value = read(kind, &stored.to_string()).unwrap();
+        let written = write(&upgraded).unwrap();
+        let reread: Value = read(kind, &written).unwrap();
+        let mut expected = upgraded.clone();
+        expected["schemaVersion"] = Value::from(SCHEMA_VERSION);
+        assert_eq!(reread, expected);
+        upgraded
+    }
+
+    #[test]
+    fn take_param_removes_every_pair_and_returns_the_last_value() {
+        let mut params = json!(["-preset p4 -rc vbr", "-preset", "p7", "-preset"]);
+        assert_eq!(take_param(&mut params, "-preset").as_deref(), Some("p7"));
+        assert_eq!(params, json!(["-rc vbr"]));
+
+        let mut params = json!(["-rc vbr"]);
+        assert_eq!(take_param(&mut params, "-preset"), None);
+        assert_eq!(params, json!(["-rc vbr"]));
+    }
+
+    #[test]
+    fn v1_nvenc_params_become_typed_options_for_the_selected_encoder() {
+        let config = json!({
+            "videoEncoder": "hevc_nvenc",
+            "availableVideoEncoders": [
+                { "value": "h264_nvenc", "customParams": ["-preset p1 -tune ll"] },
+                { "value": "hevc_nvenc", "customParams": ["-preset p6", "-b_ref_mode middle"] },
+            ],
+            "nvenc": { "preset": "p4", "tune": "hq", "multipass": "qres" },
+        });
+        let upgraded = round_trip(Kind::Sidecar, json!({ "schemaVersion": 1, "config": config }));
+        let config = &upgraded["config"];
+        assert_eq!(config["nvenc"], json!({ "preset": "p6", "tune": "ll", "multipass": "qres" }));
+        assert_eq!(config["availableVideoEncoders"][0]["customParams"], json!([]));
+        assert_eq!(config["availableVideoEncoders"][1]["customParams"], json!(["-b_ref_mode middle"]));
+    }
+
+    #[test]
+    fn v1_svtav1_preset_becomes_the_typed_preset() {
+        let presets = json!({
+            "schemaVersion": 1,
+            "presets": [{ "name": "AV1", "config": { "availableVideoEncoders": [
+                { "value": "libsvtav1", "customParams": ["-preset 8 -svtav1-params tune=0"], "svtav1": null },
+                { "value": "libsvtav1", "customParams": ["-preset fast"] },
+            ] } }],
+        });
+        let upgraded = round_trip(Kind::Presets, presets);
+        let encoders = &upgraded["presets"][0]["config"]["availableVideoEncoders"];
+        assert_eq!(encoders[0]["svtav1"], json!({ "preset": 8 }));
+        assert_eq!(encoders[0]["customParams"], json!(["-svtav1-params tune=0"]));
+        // Not a preset number: left where it was
+        assert_eq!(encoders[1]["customParams"], json!(["-preset fast"]));
+        assert!(encoders[1].get("svtav1").is_none());
+    }
+
+    #[test]
+    fn current_version_files_are_not_touched() {
+        let config = json!({
+            "videoEncoder": "hevc_nvenc",
+            "availableVideoEncoders": [{ "value": "hevc_nvenc", "customParams": ["-preset p6"] }],
+        });
+        let upgraded = round_trip(Kind::Sidecar, json!({ "schemaVersion": SCHEMA_VERSION, "config": config.clone() }));
+        assert_eq!(upgraded["config"], config);
+    }
+}
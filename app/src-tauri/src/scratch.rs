@@ -0,0 +1,82 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const SHRED_CHUNK: usize = 1024 * 1024;
+
+/// Private scratch directory inside the app's per-user cache dir (set at startup)
+static PRIVATE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn init(dir: Option<PathBuf>) {
+    if let Ok(mut guard) = PRIVATE_DIR.lock() {
+        *guard = dir;
+    }
+}
+
+/// Directory for sample segments and other scratch files. With `secure` set they go into
+/// a folder of the app's per-user cache dir that only the current user can open instead
+/// of the shared temp dir. Fails rather than falling back to a shared location when that
+/// folder can't be created or is not private (owned by someone else, or open to others).
+pub fn scratch_dir(secure: bool) -> Result<PathBuf, String> {
+    if !secure {
+        return Ok(std::env::temp_dir());
+    }
+    let dir = PRIVATE_DIR.lock().ok().and_then(|g| g.clone())
+        .ok_or("No private scratch directory is available")?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Could not create private scratch dir {:?}: {}", dir, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let metadata = std::fs::symlink_metadata(&dir).map_err(|e| e.to_string())?;
+        let uid = unsafe { libc::getuid() };
+        if !metadata.is_dir() || metadata.uid() != uid {
+            return Err(format!("Private scratch dir {:?} is not a directory owned by the current user", dir));
+        }
+        if metadata.mode() & 0o077 != 0 {
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+                .map_err(|e| format!("Could not restrict permissions of {:?}: {}", dir, e))?;
+        }
+    }
+    Ok(dir)
+}
+
+/// Numbers scratch files, so two created in the same microsecond don't collide
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// Path for a new scratch file `{prefix}_{time}_{n}.{extension}` in scratch_dir(secure).
+/// Only the path: the caller (or ffmpeg) creates the file and removes it with
+/// remove_scratch_file.
+pub fn scratch_file(secure: bool, prefix: &str, extension: &str) -> Result<PathBuf, String> {
+    let micros = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_micros())
+        .unwrap_or(0);
+    let n = NEXT_FILE.fetch_add(1, Ordering::SeqCst);
+    Ok(scratch_dir(secure)?.join(format!("{}_{}_{}.{}", prefix, micros, n, extension)))
+}
+
+/// Delete a scratch file. With `shred` set its contents are overwritten with zeros and
+/// flushed to disk first, so deleted samples/partial outputs can't be recovered from the
+/// free space. (On SSDs and copy-on-write filesystems this is best effort.)
+pub fn remove_scratch_file(path: &Path, shred: bool) {
+    if shred {
+        if let Err(e) = overwrite_with_zeros(path) {
+            eprintln!("[WARNING] Could not shred {:?}: {}", path, e);
+        }
+    }
+    let _ = std::fs::remove_file(path);
+}
+
+fn overwrite_with_zeros(path: &Path) -> std::io::Result<()> {
+    let len = std::fs::metadata(path)?.len();
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = vec![0u8; SHRED_CHUNK];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(SHRED_CHUNK as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
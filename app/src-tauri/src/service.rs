@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tauri::AppHandle;
+
+use crate::manifest::{self, JobManifest};
+use crate::video::VmafState;
+
+/// Command-line flag that starts the app as the background watch-folder service
+pub const SERVICE_ARG: &str = "--service";
+#[cfg(any(windows, target_os = "linux"))]
+const TASK_NAME: &str = "Video Compressor Service";
+#[cfg(target_os = "linux")]
+const UNIT_NAME: &str = "video-compressor.service";
+
+/// A folder the service watches; new videos in it are compressed with a saved preset
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolder {
+    pub path: String,
+    pub preset: String, // Name of a preset in presets.json
+    // Same placeholders as a job manifest's outputTemplate, e.g. "{dir}/done/{name}.mkv"
+    pub output_template: String,
+}
+
+/// What the service watches, persisted as `service.json` in the app config dir. The
+/// service reads it again on every poll, so changes apply without a restart.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceConfig {
+    #[serde(default)]
+    pub watch_folders: Vec<WatchFolder>,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    30
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        ServiceConfig { watch_folders: Vec::new(), poll_interval_secs: default_poll_interval() }
+    }
+}
+
+/// Written by the service after every poll (`service-state.json` in the app data dir) so
+/// the GUI can show what it is doing, and read back on start so handled files stay handled
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceStatus {
+    pub pid: u32,
+    pub started_ms: u64,
+    pub updated_ms: u64,
+    pub poll_interval_secs: u64,
+    pub processed: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+    pub halted: bool, // Stopped submitting after halt_after_failures; restart to resume
+    pub current: Vec<String>,
+    // Sources that were compressed (or failed) and the outputs written, never picked up again
+    #[serde(default)]
+    pub handled: Vec<String>,
+    #[serde(default, skip_deserializing)]
+    pub running: bool, // Filled in for the GUI: the service process is still alive
+}
+
+/// Paths the service uses, from the app dirs
+pub struct ServicePaths {
+    pub config: Option<PathBuf>,
+    pub state: Option<PathBuf>,
+    pub stop_request: Option<PathBuf>,
+    pub presets: Option<PathBuf>,
+}
+
+impl ServicePaths {
+    pub fn new(config_dir: Option<PathBuf>, data_dir: Option<PathBuf>) -> Self {
+        ServicePaths {
+            config: config_dir.as_ref().map(|dir| dir.join("service.json")),
+            state: data_dir.as_ref().map(|dir| dir.join("service-state.json")),
+            stop_request: data_dir.map(|dir| dir.join("service-stop")),
+            presets: config_dir.map(|dir| dir.join("presets.json")),
+        }
+    }
+}
+
+/// Whether this process was started as the service
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == SERVICE_ARG)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn load_config(paths: &ServicePaths) -> ServiceConfig {
+    paths.config.as_ref()
+        .and_then(|f| crate::schema::load_file(crate::schema::Kind::Service, f))
+        .unwrap_or_default()
+}
+
+/// Validate and save the watch folders
+pub fn save_config(paths: &ServicePaths, mut config: ServiceConfig) -> Result<(), String> {
+    config.poll_interval_secs = config.poll_interval_secs.clamp(5, 3600);
+    let presets = crate::presets::list();
+    for folder in config.watch_folders.iter_mut() {
+        folder.path = folder.path.trim().to_string();
+        folder.output_template = folder.output_template.trim().to_string();
+        if !Path::new(&folder.path).is_dir() {
+            return Err(format!("Watch folder not found: {}", folder.path));
+        }
+        if folder.output_template.is_empty() {
+            return Err(format!("No output template for {}", folder.path));
+        }
+        if !presets.iter().any(|p| p.name == folder.preset) {
+            return Err(format!("Preset '{}' not found", folder.preset));
+        }
+    }
+    let file = paths.config.as_ref().ok_or("No app config dir")?;
+    crate::schema::save_file(file, &crate::schema::write(&config)?)
+}
+
+/// Last status the service wrote, with whether it is still running
+pub fn status(paths: &ServicePaths) -> Option<ServiceStatus> {
+    let mut status: ServiceStatus = paths.state.as_ref()
+        .and_then(|f| crate::schema::load_file(crate::schema::Kind::ServiceStatus, f))?;
+    // A service that stopped cleanly clears updated_ms; one that crashed leaves a dead pid
+    let pid = Pid::from_u32(status.pid);
+    let mut sys = System::new();
+    sys.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    status.running = status.updated_ms != 0 && sys.process(pid).is_some();
+    status.handled.clear();
+    Some(status)
+}
+
+/// Ask a running service to exit after its current files
+pub fn request_stop(paths: &ServicePaths) -> Result<(), String> {
+    let file = paths.stop_request.as_ref().ok_or("No app data dir")?;
+    std::fs::write(file, b"").map_err(|e| format!("Failed to signal the service: {}", e))
+}
+
+fn save_status(paths: &ServicePaths, status: &ServiceStatus) {
+    let Some(file) = &paths.state else { return };
+    if let Err(e) = crate::schema::write(status).and_then(|json| crate::schema::save_file(file, &json)) {
+        eprintln!("[WARNING] Failed to save service status: {}", e);
+    }
+}
+
+/// Size and modification time, to tell when a file has stopped growing
+fn fingerprint(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+/// Run the service until a stop is requested: poll the watch folders, and compress every
+/// video that is new, unchanged since the previous poll and not in use, with the folder's
+/// preset. Returns when the stop file appears.
+pub fn run(
+    app: &AppHandle,
+    paths: ServicePaths,
+    binaries: impl Fn() -> (String, String),
+    pids: Arc<Mutex<HashMap<String, u32>>>,
+    cancelled_paths: Arc<Mutex<HashSet<String>>>,
+    vmaf_state: Arc<Mutex<VmafState>>,
+) {
+    if let Some(stop) = &paths.stop_request {
+        let _ = std::fs::remove_file(stop);
+    }
+    let saved: ServiceStatus = paths.state.as_ref()
+        .and_then(|f| crate::schema::load_file(crate::schema::Kind::ServiceStatus, f))
+        .unwrap_or_default();
+    let mut handled: HashSet<String> = saved.handled.into_iter().collect();
+    let mut status = ServiceStatus {
+        pid: std::process::id(),
+        started_ms: now_ms(),
+        ..Default::default()
+    };
+    // Fingerprints from the previous poll; a file is ready once it matches
+    let mut seen: HashMap<PathBuf, (u64, SystemTime)> = HashMap::new();
+    println!("[INFO] Service started ({} files already handled)", handled.len());
+
+    loop {
+        if paths.stop_request.as_ref().is_some_and(|f| f.exists()) {
+            break;
+        }
+        let config = load_config(&paths);
+        status.poll_interval_secs = config.poll_interval_secs;
+
+        if !status.halted {
+            // Presets may have been edited in the GUI since the last poll
+            crate::presets::load(paths.presets.clone());
+            let presets = crate::presets::list();
+            let mut current_seen = HashMap::new();
+            for folder in &config.watch_folders {
+                let Some(preset) = presets.iter().find(|p| p.name == folder.preset) else {
+                    status.last_error = Some(format!("Preset '{}' not found for {}", folder.preset, folder.path));
+                    continue;
+                };
+
+                let mut ready = Vec::new();
+                for entry in walkdir::WalkDir::new(&folder.path).into_iter().filter_map(|e| e.ok()) {
+                    let path = entry.path();
+                    let key = path.to_string_lossy().to_string();
+                    if handled.contains(&key) || !crate::video::is_video_file(path) {
+                        continue;
+                    }
+                    // Already compressed by an earlier run (before the handled list existed)
+                    if !preset.config.replace_original
+                        && Path::new(&manifest::output_path_for(&folder.output_template, &key)).exists()
+                    {
+                        continue;
+                    }
+                    let Some(print) = fingerprint(path) else { continue };
+                    if seen.get(path) == Some(&print) && !crate::filelock::is_locked(&key) {
+                        ready.push(key);
+                    } else {
+                        current_seen.insert(path.to_path_buf(), print);
+                    }
+                }
+                if ready.is_empty() {
+                    continue;
+                }
+
+                println!("[INFO] Service: {} new files in {}", ready.len(), folder.path);
+                status.current = ready.clone();
+                status.updated_ms = now_ms();
+                save_status(&paths, &status);
+
+                let (ffmpeg_path, ffprobe_path) = binaries();
+                let job_manifest = JobManifest {
+                    paths: ready.clone(),
+                    config: preset.config.clone(),
+                    output_template: folder.output_template.clone(),
+                };
+                let result = manifest::run_manifest(
+                    app, job_manifest, &ffmpeg_path, &ffprobe_path,
+                    pids.clone(), cancelled_paths.clone(), vmaf_state.clone(),
+                );
+                status.processed += result.succeeded.len() as u64;
+                status.failed += result.failed.len() as u64;
+                if let Some(failure) = result.failed.last() {
+                    status.last_error = Some(format!("{}: {}", failure.input_path, failure.error));
+                }
+                // Failed files aren't retried on every poll; files a halt kept from running
+                // are picked up again after a restart
+                handled.extend(result.failed.into_iter().map(|f| f.input_path));
+                if result.halted {
+                    status.halted = true;
+                    eprintln!("[WARNING] Service halted after repeated failures");
+                } else {
+                    handled.extend(ready);
+                }
+                // Outputs written into a watched folder aren't sources
+                handled.extend(result.succeeded);
+                status.current.clear();
+                if status.halted {
+                    break;
+                }
+            }
+            seen = current_seen;
+        }
+
+        status.handled = handled.iter().cloned().collect();
+        status.updated_ms = now_ms();
+        save_status(&paths, &status);
+        // Sleep in short steps so a stop request is seen quickly
+        let deadline = std::time::Instant::now() + Duration::from_secs(status.poll_interval_secs.max(5));
+        while std::time::Instant::now() < deadline {
+            if paths.stop_request.as_ref().is_some_and(|f| f.exists()) {
+                break;
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    if let Some(stop) = &paths.stop_request {
+        let _ = std::fs::remove_file(stop);
+    }
+    status.updated_ms = 0;
+    save_status(&paths, &status);
+    println!("[INFO] Service stopped: {} compressed, {} failed", status.processed, status.failed);
+}
+
+/// Start the service at logon: a scheduled task on Windows, a systemd user unit on Linux.
+/// The service hosts the app's runtime, so it runs in the user's session rather than as a
+/// system service.
+pub fn install() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    install_for(&exe)
+}
+
+#[cfg(windows)]
+fn install_for(exe: &Path) -> Result<(), String> {
+    let action = format!("\"{}\" {}", exe.display(), SERVICE_ARG);
+    run_tool("schtasks", &["/Create", "/TN", TASK_NAME, "/TR", &action, "/SC", "ONLOGON", "/F"])?;
+    run_tool("schtasks", &["/Run", "/TN", TASK_NAME])
+}
+
+#[cfg(target_os = "linux")]
+fn install_for(exe: &Path) -> Result<(), String> {
+    let unit = format!(
+        "[Unit]\nDescription={}\nAfter=graphical-session.target\nPartOf=graphical-session.target\n\n\
+         [Service]\nExecStart=\"{}\" {}\nRestart=on-failure\n\n\
+         [Install]\nWantedBy=graphical-session.target\n",
+        TASK_NAME, exe.display(), SERVICE_ARG,
+    );
+    let file = unit_file()?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&file, unit).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+    run_tool("systemctl", &["--user", "daemon-reload"])?;
+    run_tool("systemctl", &["--user", "enable", "--now", UNIT_NAME])
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn install_for(_exe: &Path) -> Result<(), String> {
+    Err("Installing the service is not supported on this platform; run the app with --service instead".to_string())
+}
+
+/// Undo install(). A running service is asked to stop too.
+#[cfg(windows)]
+pub fn uninstall() -> Result<(), String> {
+    run_tool("schtasks", &["/Delete", "/TN", TASK_NAME, "/F"])
+}
+
+#[cfg(target_os = "linux")]
+pub fn uninstall() -> Result<(), String> {
+    run_tool("systemctl", &["--user", "disable", "--now", UNIT_NAME])?;
+    let file = unit_file()?;
+    std::fs::remove_file(&file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+    run_tool("systemctl", &["--user", "daemon-reload"])
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn uninstall() -> Result<(), String> {
+    Err("Installing the service is not supported on this platform".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn unit_file() -> Result<PathBuf, String> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or("HOME is not set")?;
+    Ok(config_home.join("systemd").join("user").join(UNIT_NAME))
+}
+
+#[cfg(any(windows, target_os = "linux"))]
+fn run_tool(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = crate::process::tool_command(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
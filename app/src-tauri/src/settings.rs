@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Backend settings that must be known before any command runs, persisted as
+/// `settings.json` in the app config dir. (Encoding options stay in the frontend's
+/// AppSettings and arrive with each command.)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendSettings {
+    // User-chosen binaries; empty = bundled copy / PATH
+    #[serde(default)]
+    pub ffmpeg_path: String,
+    #[serde(default)]
+    pub ffprobe_path: String,
+    // NVIDIA GPU for NVENC encodes, CUDA VMAF and the stats readout (NVML/CUDA index)
+    #[serde(default)]
+    pub gpu_index: u32,
+}
+
+struct Store {
+    file: Option<PathBuf>,
+    settings: BackendSettings,
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+pub fn load(file: Option<PathBuf>) {
+    let settings = file.as_ref()
+        .and_then(|f| crate::schema::load_file(crate::schema::Kind::Settings, f))
+        .unwrap_or_default();
+    if let Ok(mut guard) = STORE.lock() {
+        *guard = Some(Store { file, settings });
+    }
+}
+
+pub fn get() -> BackendSettings {
+    STORE.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.settings.clone()))
+        .unwrap_or_default()
+}
+
+/// Validate and store new settings, writing them to disk
+pub fn set(mut settings: BackendSettings) -> Result<(), String> {
+    settings.ffmpeg_path = settings.ffmpeg_path.trim().to_string();
+    settings.ffprobe_path = settings.ffprobe_path.trim().to_string();
+    for (name, path) in [("ffmpeg", &settings.ffmpeg_path), ("ffprobe", &settings.ffprobe_path)] {
+        if !path.is_empty() && !Path::new(path).is_file() {
+            return Err(format!("{} not found at {}", name, path));
+        }
+    }
+
+    let mut guard = STORE.lock().map_err(|e| e.to_string())?;
+    let store = guard.get_or_insert_with(|| Store { file: None, settings: BackendSettings::default() });
+    if let Some(file) = &store.file {
+        let json = crate::schema::write(&settings)?;
+        crate::schema::save_file(file, &json).map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+    println!("[INFO] Settings updated: ffmpeg='{}', ffprobe='{}', GPU {}", settings.ffmpeg_path, settings.ffprobe_path, settings.gpu_index);
+    store.settings = settings;
+    Ok(())
+}
+
+/// User-configured ffmpeg binary, if set and still present
+pub fn custom_ffmpeg_path() -> Option<String> {
+    existing(get().ffmpeg_path)
+}
+
+/// User-configured ffprobe binary, if set and still present
+pub fn custom_ffprobe_path() -> Option<String> {
+    existing(get().ffprobe_path)
+}
+
+/// Configured NVIDIA GPU index
+pub fn gpu_index() -> u32 {
+    get().gpu_index
+}
+
+fn existing(path: String) -> Option<String> {
+    if path.is_empty() {
+        return None;
+    }
+    if Path::new(&path).is_file() {
+        Some(path)
+    } else {
+        eprintln!("[WARNING] Configured binary {} no longer exists, using the default", path);
+        None
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::video::{CompressionConfig, VideoInfo};
+
+/// Small JSON record written next to an output file describing how it was made.
+/// Media managers (and later runs of this app) can read it back.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarRecord {
+    pub app_version: String,
+    pub source_path: String,
+    pub output_path: String,
+    pub result: String, // "Done", "Skipped", "Kept original (no savings)"
+    pub source_size: u64,
+    pub output_size: u64,
+    pub compression_mode: String,
+    pub video_encoder: String,
+    pub audio_encoder: String,
+    pub crf: Option<f32>,
+    pub bitrate_kbps: Option<u32>,
+    pub vmaf: Option<f64>,
+    pub vmaf_model: Option<String>,
+    pub vmaf_device: Option<String>,
+    #[serde(default)]
+    pub ffmpeg_commands: Vec<String>, // One entry per pass, as run
+    #[serde(default)]
+    pub encoder_stats: Option<crate::encstats::EncoderStats>, // Of the final pass
+    #[serde(default)]
+    pub compression_ratio: Option<f64>, // Source size / output size
+    #[serde(default)]
+    pub source_bits_per_pixel: Option<f64>, // Video bits per pixel per frame
+    #[serde(default)]
+    pub output_bits_per_pixel: Option<f64>,
+    pub started_at: u64,  // Unix seconds
+    pub finished_at: u64, // Unix seconds
+    pub config: CompressionConfig,
+}
+
+pub fn now_unix_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Sidecar location: `<output file>.json`, e.g. `movie.mp4.json`
+pub fn sidecar_path(output_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.json", output_path))
+}
+
+pub fn write_sidecar(record: &SidecarRecord) -> Result<(), String> {
+    let path = sidecar_path(&record.output_path);
+    let json = crate::schema::write(record)
+        .map_err(|e| format!("Failed to serialize sidecar: {}", e))?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write sidecar {:?}: {}", path, e))
+}
+
+pub fn read_sidecar(output_path: &str) -> Option<SidecarRecord> {
+    let content = std::fs::read_to_string(sidecar_path(output_path)).ok()?;
+    match crate::schema::read(crate::schema::Kind::Sidecar, &content) {
+        Ok(record) => Some(record),
+        Err(e) => {
+            eprintln!("[WARNING] Ignoring sidecar for {}: {}", output_path, e);
+            None
+        }
+    }
+}
+
+/// Fill in VMAF results once the (queued) post-encode VMAF calculation finishes
+pub fn update_sidecar_vmaf(output_path: &str, info: &VideoInfo) {
+    if let Some(mut record) = read_sidecar(output_path) {
+        record.vmaf = info.vmaf;
+        record.vmaf_model = info.vmaf_model.clone();
+        record.vmaf_device = info.vmaf_device.clone();
+        if let Err(e) = write_sidecar(&record) {
+            eprintln!("[WARNING] {}", e);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Typed SVT-AV1 options (preset, film grain synthesis, tune) kept on the libsvtav1
+//! encoder entry. They are passed the same way to CRF search samples and to the final
+//! encode, so the CRF a search settles on holds for the real thing.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SvtAv1Options {
+    #[serde(default)]
+    pub preset: Option<u8>, // 0 (slowest) .. 13 (fastest)
+    #[serde(default)]
+    pub film_grain: Option<u8>, // Grain synthesis level 0-50, 0 = off
+    #[serde(default)]
+    pub tune: Option<u8>, // 0 = visual quality, 1 = PSNR, 2 = SSIM
+}
+
+pub const MAX_PRESET: u8 = 13;
+const MAX_FILM_GRAIN: u8 = 50;
+const MAX_TUNE: u8 = 2;
+
+pub fn validate(options: Option<&SvtAv1Options>) -> Result<(), String> {
+    let Some(o) = options else { return Ok(()) };
+    if o.preset.is_some_and(|p| p > MAX_PRESET) {
+        return Err(format!("SVT-AV1 preset must be 0-{}", MAX_PRESET));
+    }
+    if o.film_grain.is_some_and(|g| g > MAX_FILM_GRAIN) {
+        return Err(format!("SVT-AV1 film grain must be 0-{}", MAX_FILM_GRAIN));
+    }
+    if o.tune.is_some_and(|t| t > MAX_TUNE) {
+        return Err("SVT-AV1 tune must be 0 (VQ), 1 (PSNR) or 2 (SSIM)".to_string());
+    }
+    Ok(())
+}
+
+/// Encoder args for `options` merged with the encoder's custom params. ffmpeg only keeps
+/// the last `-svtav1-params`, so the typed keys go into a single one ahead of the user's
+/// own keys (later keys win, like a later `-preset` in the custom params).
+pub fn args(options: Option<&SvtAv1Options>, custom: &[String]) -> Vec<String> {
+    let mut params: Vec<String> = Vec::new();
+    let mut args: Vec<String> = Vec::new();
+    if let Some(o) = options {
+        if let Some(preset) = o.preset {
+            args.extend(["-preset".to_string(), preset.to_string()]);
+        }
+        if let Some(tune) = o.tune {
+            params.push(format!("tune={}", tune));
+        }
+        if let Some(grain) = o.film_grain {
+            params.push(format!("film-grain={}", grain));
+        }
+    }
+
+    let mut tokens = custom.iter().flat_map(|p| p.split_whitespace()).map(str::to_string);
+    while let Some(token) = tokens.next() {
+        if token == "-svtav1-params" {
+            params.extend(tokens.next());
+        } else {
+            args.push(token);
+        }
+    }
+    if !params.is_empty() {
+        args.extend(["-svtav1-params".to_string(), params.join(":")]);
+    }
+    args
+}
@@ -0,0 +1,148 @@
+//! Emergency pause: suspends every running ffmpeg process while the GPU overheats or other
+//! programs have pegged the CPU at 100% while someone is using the computer, and resumes
+//! them once the readings are back to normal. Sends `thermal-pause` so the UI can say why.
+//! Off unless enabled in the settings. Only processes the guard itself suspended are
+//! resumed: ones stopped by the user, eco mode or the speed limiter are left to them.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+pub const DEFAULT_GPU_MAX_TEMP: u32 = 90;
+/// Resume only once the GPU is this far below the limit
+const GPU_RESUME_MARGIN: u32 = 10;
+const CPU_PEGGED: f32 = 99.0;
+const CPU_NORMAL: f32 = 90.0;
+const CPU_SUSTAIN: Duration = Duration::from_secs(30);
+/// Input within this many seconds means someone is at the computer
+const USER_ACTIVE_SECS: u64 = 60;
+/// Shortest pause, so a pause doesn't flap with the load it takes away
+const MIN_PAUSE: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ThermalPausePayload {
+    pub paused: bool,
+    pub reason: String, // Empty when resuming
+    pub gpu_temperature: Option<u32>,
+    pub cpu_usage: f32,
+}
+
+struct Pause {
+    since: Instant,
+    suspended: HashMap<String, u32>, // Job key -> pid, only the ones this pause suspended
+}
+
+struct Guard {
+    enabled: bool,
+    gpu_max_temp: u32,
+    cpu_pegged_since: Option<Instant>,
+    pause: Option<Pause>,
+}
+
+static GUARD: Mutex<Guard> = Mutex::new(Guard {
+    enabled: false,
+    gpu_max_temp: DEFAULT_GPU_MAX_TEMP,
+    cpu_pegged_since: None,
+    pause: None,
+});
+
+/// Turn the guard on or off and set the GPU limit (°C, 0 = default). Turning it off
+/// resumes anything it suspended.
+pub fn configure(app: &AppHandle, enabled: bool, gpu_max_temp: u32) {
+    let Ok(mut guard) = GUARD.lock() else { return };
+    guard.enabled = enabled;
+    guard.gpu_max_temp = if gpu_max_temp == 0 { DEFAULT_GPU_MAX_TEMP } else { gpu_max_temp };
+    if !enabled {
+        if let Some(pause) = guard.pause.take() {
+            resume_all(app, pause, None, 0.0);
+        }
+    }
+}
+
+/// Called by the stats thread on every sample; `cpu_usage` leaves out the app's own ffmpeg
+pub fn check(app: &AppHandle, gpu_temperature: Option<u32>, cpu_usage: f32, pids: &Arc<Mutex<HashMap<String, u32>>>) {
+    let Ok(mut guard) = GUARD.lock() else { return };
+    if !guard.enabled {
+        return;
+    }
+    if cpu_usage >= CPU_PEGGED {
+        guard.cpu_pegged_since.get_or_insert_with(Instant::now);
+    } else {
+        guard.cpu_pegged_since = None;
+    }
+    let running = pids.lock().map(|p| p.clone()).unwrap_or_default();
+    let max_temp = guard.gpu_max_temp;
+
+    let Some(pause) = guard.pause.as_mut() else {
+        if running.is_empty() {
+            return;
+        }
+        let user_active = crate::monitor::user_idle_secs().is_some_and(|s| s < USER_ACTIVE_SECS);
+        let reason = match gpu_temperature {
+            Some(t) if t >= max_temp => format!("GPU at {} °C (limit {} °C)", t, max_temp),
+            _ if user_active && guard.cpu_pegged_since.is_some_and(|t| t.elapsed() >= CPU_SUSTAIN) => {
+                format!("CPU at 100% for {} s while the computer is in use", CPU_SUSTAIN.as_secs())
+            }
+            _ => return,
+        };
+        eprintln!("[WARNING] Emergency pause: {}", reason);
+        let mut pause = Pause { since: Instant::now(), suspended: HashMap::new() };
+        suspend_new(&mut pause, &running);
+        guard.pause = Some(pause);
+        let _ = app.emit("thermal-pause", ThermalPausePayload { paused: true, reason, gpu_temperature, cpu_usage });
+        return;
+    };
+
+    // Jobs that start during the pause are held as well
+    suspend_new(pause, &running);
+    let normal = gpu_temperature.is_none_or(|t| t < max_temp.saturating_sub(GPU_RESUME_MARGIN))
+        && cpu_usage < CPU_NORMAL
+        && pause.since.elapsed() >= MIN_PAUSE;
+    if normal {
+        if let Some(pause) = guard.pause.take() {
+            guard.cpu_pegged_since = None;
+            resume_all(app, pause, gpu_temperature, cpu_usage);
+        }
+    }
+}
+
+/// Whether an emergency pause currently holds the job (`key` as in the pid map) suspended
+pub fn is_holding(key: &str) -> bool {
+    GUARD.lock().ok()
+        .and_then(|g| g.pause.as_ref().map(|p| p.suspended.contains_key(key)))
+        .unwrap_or(false)
+}
+
+/// Suspend the running processes not stopped already. One that eco mode or the speed
+/// limiter has suspended is left to it; should it be resumed during the pause, the next
+/// check suspends it here.
+fn suspend_new(pause: &mut Pause, running: &HashMap<String, u32>) {
+    for (key, pid) in running {
+        if pause.suspended.get(key) == Some(pid)
+            || crate::video::is_user_paused(key)
+            || crate::video::is_eco_suspended(key)
+            || crate::throttle::is_suspended(*pid)
+        {
+            continue;
+        }
+        if crate::process::suspend(*pid) {
+            println!("[INFO] Emergency pause: suspended ffmpeg for {}", key);
+            pause.suspended.insert(key.clone(), *pid);
+        }
+    }
+}
+
+/// Wake the processes this pause suspended, except jobs the user paused or eco mode
+/// suspended in the meantime
+fn resume_all(app: &AppHandle, pause: Pause, gpu_temperature: Option<u32>, cpu_usage: f32) {
+    for (key, pid) in pause.suspended {
+        if !crate::video::is_user_paused(&key) && !crate::video::is_eco_suspended(&key) {
+            crate::process::resume(pid);
+        }
+    }
+    println!("[INFO] Emergency pause over after {} s, resumed ffmpeg", pause.since.elapsed().as_secs());
+    let _ = app.emit("thermal-pause", ThermalPausePayload { paused: false, reason: String::new(), gpu_temperature, cpu_usage });
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Length of one run/suspend cycle. Short enough that ffmpeg's progress output
+/// still looks continuous, long enough that the signal overhead is negligible.
+const DUTY_PERIOD_MS: u128 = 1000;
+const TICK: Duration = Duration::from_millis(50);
+
+/// Speed factor per job (keyed by job_key of the input path, like the pid map), 0 < factor < 1
+static LIMITS: Mutex<Option<HashMap<String, f32>>> = Mutex::new(None);
+
+/// Processes the duty cycle has suspended right now, as of its last tick
+static SUSPENDED: Mutex<Option<HashSet<u32>>> = Mutex::new(None);
+
+/// Limit a job to roughly `factor` of its normal speed. The job's ffmpeg is dropped to
+/// below-normal priority and duty-cycled: allowed to run for `factor` of every period
+/// and suspended for the rest. A factor of 1.0 removes the limit (the process keeps its
+/// lowered priority, which only matters while something else wants the CPU).
+///
+/// The limit follows the job across its ffmpeg processes (samples, pass 1, pass 2)
+/// by looking the current pid up in `pids` on every tick.
+pub fn set_job_speed_limit(
+    job_id: &str,
+    factor: f32,
+    pids: Arc<Mutex<HashMap<String, u32>>>,
+) -> Result<(), String> {
+    if !(factor > 0.0 && factor <= 1.0) {
+        return Err(format!("Speed factor must be in (0, 1], got {}", factor));
+    }
+    let job_id = &crate::video::job_key(job_id);
+
+    let start_thread = {
+        let mut guard = LIMITS.lock().map_err(|e| e.to_string())?;
+        let was_running = guard.is_some();
+        let limits = guard.get_or_insert_with(HashMap::new);
+        if factor >= 1.0 {
+            limits.remove(job_id);
+        } else {
+            limits.insert(job_id.to_string(), factor);
+        }
+        !was_running && !limits.is_empty()
+    };
+
+    if factor < 1.0 {
+        if let Some(pid) = pids.lock().ok().and_then(|m| m.get(job_id).cloned()) {
+            crate::process::lower_priority(pid);
+        }
+    }
+
+    if start_thread {
+        std::thread::spawn(move || run_duty_cycle(pids));
+    }
+    println!("[INFO] Speed limit for {} set to {:.0}%", job_id, factor * 100.0);
+    Ok(())
+}
+
+/// Forget a job's limit (when it ends, fails or is cancelled)
+pub fn clear_job_speed_limit(job_id: &str) {
+    if let Ok(mut guard) = LIMITS.lock() {
+        if let Some(limits) = guard.as_mut() {
+            limits.remove(&crate::video::job_key(job_id));
+        }
+    }
+}
+
+/// Whether the duty cycle is holding `pid` suspended (its off part of the period)
+pub fn is_suspended(pid: u32) -> bool {
+    SUSPENDED.lock().ok()
+        .and_then(|g| g.as_ref().map(|set| set.contains(&pid)))
+        .unwrap_or(false)
+}
+
+fn publish_suspended(suspended: &HashSet<u32>) {
+    if let Ok(mut guard) = SUSPENDED.lock() {
+        *guard = (!suspended.is_empty()).then(|| suspended.clone());
+    }
+}
+
+/// Whether something else keeps the job suspended (user pause, eco mode on battery, thermal
+/// guard); the duty cycle must not resume it then
+fn held_elsewhere(job_id: &str) -> bool {
+    crate::video::is_user_paused(job_id)
+        || crate::video::is_eco_suspended(job_id)
+        || crate::thermal::is_holding(job_id)
+}
+
+/// Resume `pid` unless its job is held suspended by something else
+fn release(pid: u32, current_pids: &HashMap<String, u32>) {
+    let held = current_pids.iter().any(|(job_id, p)| *p == pid && held_elsewhere(job_id));
+    if !held {
+        crate::process::resume(pid);
+    }
+}
+
+/// Single background thread driving all limited jobs. Exits (and resumes anything it
+/// suspended, unless held elsewhere) once no limits remain.
+fn run_duty_cycle(pids: Arc<Mutex<HashMap<String, u32>>>) {
+    let epoch = Instant::now();
+    let mut suspended: HashSet<u32> = HashSet::new();
+    let mut lowered: HashSet<u32> = HashSet::new();
+    let mut current_pids: HashMap<String, u32> = HashMap::new();
+
+    loop {
+        let limits = {
+            let mut guard = match LIMITS.lock() {
+                Ok(g) => g,
+                Err(_) => break,
+            };
+            match guard.as_ref() {
+                Some(l) if !l.is_empty() => l.clone(),
+                _ => {
+                    *guard = None;
+                    break;
+                }
+            }
+        };
+        current_pids = match pids.lock() {
+            Ok(m) => m.clone(),
+            Err(_) => break,
+        };
+
+        let phase = epoch.elapsed().as_millis() % DUTY_PERIOD_MS;
+        let mut active: HashSet<u32> = HashSet::new();
+
+        for (job_id, factor) in &limits {
+            let pid = match current_pids.get(job_id) {
+                Some(p) => *p,
+                None => continue, // Between phases; the limit applies to the next process
+            };
+            active.insert(pid);
+
+            // New process for this job (e.g. pass 2 after pass 1)
+            if lowered.insert(pid) {
+                crate::process::lower_priority(pid);
+            }
+
+            // Paused by the user, eco mode or the thermal guard: whoever suspended it resumes it
+            if held_elsewhere(job_id) {
+                suspended.remove(&pid);
+                continue;
+            }
+
+            let run_window = (*factor as f64 * DUTY_PERIOD_MS as f64) as u128;
+            if phase < run_window {
+                if suspended.remove(&pid) {
+                    crate::process::resume(pid);
+                }
+            } else if !suspended.contains(&pid) && crate::process::suspend(pid) {
+                suspended.insert(pid);
+            }
+        }
+
+        // Jobs whose limit was lifted: let them run freely again
+        for pid in suspended.iter().filter(|p| !active.contains(p)) {
+            release(*pid, &current_pids);
+        }
+        suspended.retain(|p| active.contains(p));
+        lowered.retain(|p| active.contains(p));
+        publish_suspended(&suspended);
+
+        std::thread::sleep(TICK);
+    }
+
+    for pid in suspended {
+        release(pid, &current_pids);
+    }
+    publish_suspended(&HashSet::new());
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+use crate::video::CompressionConfig;
+
+/// Named quality presets for users who don't want to pick encoders and targets. A tier
+/// only overrides the encoder and the VMAF/CRF target; everything else in the
+/// CompressionConfig still applies.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityTier {
+    pub id: &'static str,
+    // Preferred encoders, first one the user has enabled wins; empty = keep the selected one
+    pub encoders: &'static [&'static str],
+    // Per resolution class: SD (< 720p), HD (720p-1080p), UHD (above 1080p)
+    pub target_vmaf: [f32; 3],
+    pub target_crf: [f32; 3], // On the x264/x265 scale, mapped to the encoder's range
+}
+
+pub const TIERS: [QualityTier; 3] = [
+    QualityTier {
+        id: "archive",
+        encoders: &["libx265", "hevc_nvenc", "hevc_qsv", "hevc_amf", "hevc_vaapi", "hevc_videotoolbox"],
+        target_vmaf: [97.0, 96.0, 95.0],
+        target_crf: [18.0, 20.0, 22.0],
+    },
+    QualityTier {
+        id: "balanced",
+        encoders: &[],
+        target_vmaf: [94.0, 93.0, 92.0],
+        target_crf: [23.0, 24.0, 26.0],
+    },
+    QualityTier {
+        id: "small",
+        encoders: &["libsvtav1", "av1_nvenc", "av1_qsv", "av1_amf", "av1_vaapi", "libx265", "hevc_nvenc"],
+        target_vmaf: [90.0, 88.0, 86.0],
+        target_crf: [28.0, 30.0, 32.0],
+    },
+];
+
+/// Resolution class index from "WxH" (by the short side, so portrait video counts the same)
+fn resolution_class(resolution: Option<&str>) -> usize {
+    let short_side = resolution
+        .and_then(|r| r.split_once('x'))
+        .and_then(|(w, h)| Some(w.parse::<u32>().ok()?.min(h.parse::<u32>().ok()?)))
+        .unwrap_or(1080);
+    match short_side {
+        0..=719 => 0,
+        720..=1080 => 1,
+        _ => 2,
+    }
+}
+
+/// The config with `quality_tier` applied for a source of the given resolution. VMAF
+/// modes get a target VMAF; other modes become CRF. Copy and custom modes are left alone.
+pub fn apply_tier(config: &CompressionConfig, resolution: Option<&str>) -> CompressionConfig {
+    let mut config = config.clone();
+    if config.quality_tier.is_empty() || config.compression_mode == "copy" || config.compression_mode == "custom" {
+        return config;
+    }
+    let Some(tier) = TIERS.iter().find(|t| t.id == config.quality_tier) else {
+        eprintln!("[WARNING] Unknown quality tier '{}', ignoring it", config.quality_tier);
+        return config;
+    };
+    let class = resolution_class(resolution);
+
+    let enabled = |name: &str| config.available_video_encoders.iter().any(|e| e.value == name && e.visible);
+    if let Some(encoder) = tier.encoders.iter().find(|e| enabled(e)) {
+        config.video_encoder = encoder.to_string();
+    }
+
+    if config.uses_vmaf_search() {
+        config.target_vmaf = tier.target_vmaf[class];
+    } else {
+        // Map from the x264 CRF range onto the encoder's
+        let (min, max) = crate::video::get_crf_range(&config.video_encoder);
+        let position = (tier.target_crf[class] - 18.0) / (46.0 - 18.0);
+        config.compression_mode = "crf".to_string();
+        config.target_crf = (min + position * (max - min)).round();
+    }
+    println!(
+        "[INFO] Quality tier '{}' ({}): {} with {}",
+        tier.id,
+        ["SD", "HD", "UHD"][class],
+        config.video_encoder,
+        if config.uses_vmaf_search() { format!("target VMAF {}", config.target_vmaf) } else { format!("CRF {}", config.target_crf) }
+    );
+    config
+}
@@ -0,0 +1,106 @@
+//! Per-frame VMAF kept next to the output (`<output>.vmaf.json`) and turned into a
+//! per-second series, so quality can be plotted over the video and weak stretches found.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The per-frame scores of one VMAF run: frame number (from the segment start) and score
+/// for each scored segment, as read from the libvmaf JSON logs
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameLog {
+    pub fps: f64,
+    pub segments: Vec<SegmentFrames>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentFrames {
+    pub start: f64, // Seconds into the video
+    pub frames: Vec<(u64, f64)>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelinePoint {
+    pub second: f64,
+    pub mean: f64,
+    pub min: f64,
+}
+
+/// Stretch of consecutive seconds whose mean is below the file's 5% lows
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemSection {
+    pub start: f64,
+    pub end: f64,
+    pub min: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VmafTimeline {
+    pub points: Vec<TimelinePoint>,
+    pub problems: Vec<ProblemSection>,
+}
+
+/// Frame log location: `<output file>.vmaf.json`, e.g. `movie.mp4.vmaf.json`
+pub fn log_path(output_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.vmaf.json", output_path))
+}
+
+/// Frame number and VMAF of every frame of a libvmaf JSON log
+pub fn frame_series(log: &serde_json::Value) -> Vec<(u64, f64)> {
+    log.get("frames")
+        .and_then(|f| f.as_array())
+        .map(|frames| {
+            frames.iter()
+                .filter_map(|f| Some((f.get("frameNum")?.as_u64()?, f.get("metrics")?.get("vmaf")?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn write(output_path: &str, log: &FrameLog) -> Result<(), String> {
+    let path = log_path(output_path);
+    let json = serde_json::to_string(log).map_err(|e| format!("Failed to serialize VMAF log: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write VMAF log {:?}: {}", path, e))
+}
+
+/// Per-second series of the kept frame log of `output_path`
+pub fn timeline(output_path: &str) -> Result<VmafTimeline, String> {
+    let path = log_path(output_path);
+    let content = std::fs::read_to_string(&path).map_err(|_| format!("No per-frame VMAF kept for {}", output_path))?;
+    let log: FrameLog = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+    let fps = if log.fps > 0.0 { log.fps } else { 25.0 };
+
+    // Scores of each whole second, in order (segments may overlap or come unsorted)
+    let mut seconds: std::collections::BTreeMap<u64, Vec<f64>> = std::collections::BTreeMap::new();
+    for segment in &log.segments {
+        for &(frame, vmaf) in &segment.frames {
+            let second = (segment.start + frame as f64 / fps).floor() as u64;
+            seconds.entry(second).or_default().push(vmaf);
+        }
+    }
+    let points: Vec<TimelinePoint> = seconds.into_iter()
+        .map(|(second, scores)| TimelinePoint {
+            second: second as f64,
+            mean: crate::pooling::mean(&scores),
+            min: scores.iter().cloned().fold(f64::INFINITY, f64::min),
+        })
+        .collect();
+
+    let all: Vec<f64> = log.segments.iter().flat_map(|s| s.frames.iter().map(|f| f.1)).collect();
+    let threshold = crate::pooling::pool(&all).map_or(0.0, |p| p.p5);
+    let mut problems: Vec<ProblemSection> = Vec::new();
+    for point in points.iter().filter(|p| p.mean < threshold) {
+        match problems.last_mut() {
+            Some(last) if last.end == point.second => {
+                last.end = point.second + 1.0;
+                last.min = last.min.min(point.min);
+            }
+            _ => problems.push(ProblemSection { start: point.second, end: point.second + 1.0, min: point.min }),
+        }
+    }
+    Ok(VmafTimeline { points, problems })
+}
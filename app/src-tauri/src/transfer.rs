@@ -0,0 +1,85 @@
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Token bucket shared by every throttled copy, so the limit applies to the
+/// whole app rather than per file.
+struct TokenBucket {
+    rate: f64,     // bytes per second
+    capacity: f64, // burst size in bytes
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        let capacity = rate.max(CHUNK_SIZE as f64);
+        TokenBucket { rate, capacity, tokens: capacity, last: Instant::now() }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+}
+
+static BUCKET: Mutex<Option<TokenBucket>> = Mutex::new(None);
+
+/// Block until `bytes` may be transferred under the global limit
+fn acquire(bytes: usize, rate: f64) {
+    loop {
+        let wait = {
+            let mut guard = match BUCKET.lock() {
+                Ok(g) => g,
+                Err(_) => return,
+            };
+            // (Re)create the bucket when the configured rate changes
+            let bucket = match guard.as_mut() {
+                Some(b) if (b.rate - rate).abs() < f64::EPSILON => b,
+                _ => guard.insert(TokenBucket::new(rate)),
+            };
+            bucket.refill();
+            if bucket.tokens >= bytes as f64 {
+                bucket.tokens -= bytes as f64;
+                return;
+            }
+            (bytes as f64 - bucket.tokens) / bucket.rate
+        };
+        std::thread::sleep(Duration::from_secs_f64(wait.clamp(0.001, 1.0)));
+    }
+}
+
+/// Copy a file, capping throughput at `limit_mbps` megabits per second across all
+/// concurrent copies. A limit of 0 falls back to a plain `std::fs::copy`.
+pub fn copy_file(src: &str, dst: &str, limit_mbps: u32) -> std::io::Result<u64> {
+    if limit_mbps == 0 {
+        return std::fs::copy(src, dst);
+    }
+
+    let rate = limit_mbps as f64 * 1_000_000.0 / 8.0;
+    let mut reader = std::fs::File::open(src)?;
+    let mut writer = std::fs::File::create(dst)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total = 0u64;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        acquire(n, rate);
+        writer.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+    writer.flush()?;
+
+    // Match std::fs::copy, which also carries over permissions
+    if let Ok(meta) = std::fs::metadata(src) {
+        let _ = std::fs::set_permissions(dst, meta.permissions());
+    }
+    Ok(total)
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+/// Rough content class used to pick encoder tuning flags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentType {
+    Animation,
+    LiveAction,
+}
+
+/// Share of duplicate frames above which content is treated as animation.
+/// Cel animation is mostly drawn "on twos/threes", so many consecutive frames are identical.
+const ANIMATION_DUPLICATE_RATIO: f64 = 0.3;
+const ANALYSIS_SECONDS: f64 = 10.0;
+
+/// Per-file analysis results, so the CRF search and the final encode agree and
+/// the file is only analyzed once
+static CACHE: Mutex<Option<HashMap<String, ContentType>>> = Mutex::new(None);
+
+/// Classify `input_path` by sampling a short stretch from the middle of the video and
+/// counting near-duplicate frames with mpdecimate. Falls back to live action if the
+/// analysis fails.
+pub fn content_type_for(ffmpeg_path: &str, input_path: &str, duration_sec: f64) -> ContentType {
+    if let Some(cached) = cached_content_type(input_path) {
+        return cached;
+    }
+
+    let start = ((duration_sec - ANALYSIS_SECONDS) / 2.0).max(0.0);
+    let args = [
+        "-hide_banner", "-nostats", "-loglevel", "debug",
+        "-ss", &format!("{:.0}", start),
+        "-t", &format!("{:.0}", ANALYSIS_SECONDS),
+        "-i", input_path,
+        "-an", "-sn",
+        "-vf", "scale=320:-2,mpdecimate",
+        "-f", "null", "-",
+    ];
+
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(args).stdout(Stdio::null()).stderr(Stdio::piped());
+
+    let content = match command.output() {
+        Ok(o) => {
+            // mpdecimate logs one "keep pts:..." / "drop pts:..." line per frame at debug level
+            let stderr = String::from_utf8_lossy(&o.stderr);
+            let kept = stderr.matches("] keep pts:").count();
+            let dropped = stderr.matches("] drop pts:").count();
+            let total = kept + dropped;
+            if total == 0 {
+                ContentType::LiveAction
+            } else {
+                let ratio = dropped as f64 / total as f64;
+                println!("[INFO] Content analysis for {}: {}/{} duplicate frames ({:.0}%)", input_path, dropped, total, ratio * 100.0);
+                if ratio > ANIMATION_DUPLICATE_RATIO { ContentType::Animation } else { ContentType::LiveAction }
+            }
+        }
+        Err(e) => {
+            eprintln!("[WARNING] Content analysis failed for {}: {}", input_path, e);
+            ContentType::LiveAction
+        }
+    };
+
+    if let Ok(mut guard) = CACHE.lock() {
+        guard.get_or_insert_with(HashMap::new).insert(input_path.to_string(), content);
+    }
+    content
+}
+
+pub fn cached_content_type(input_path: &str) -> Option<ContentType> {
+    CACHE.lock().ok()?.as_ref()?.get(input_path).copied()
+}
+
+/// Encoder flags for the content type. Placed before the user's per-encoder custom
+/// params so anything set there explicitly still wins.
+pub fn auto_tune_args(content: ContentType, encoder: &str) -> Vec<String> {
+    let args: &[&str] = match content {
+        ContentType::Animation => {
+            if encoder.contains("libx264") {
+                &["-tune", "animation"]
+            } else if encoder.contains("libx265") {
+                &["-tune", "animation", "-x265-params", "bframes=8:psy-rd=0.4:aq-strength=0.4"]
+            } else if encoder.contains("nvenc") {
+                // Flat areas and hard edges: more B-frames, longer lookahead, no temporal AQ
+                &["-bf", "4", "-rc-lookahead", "32", "-spatial-aq", "1", "-aq-strength", "4"]
+            } else {
+                &[]
+            }
+        }
+        ContentType::LiveAction => {
+            if encoder.contains("libx264") {
+                &["-aq-mode", "3", "-psy-rd", "1.0:0.15"]
+            } else if encoder.contains("libx265") {
+                &["-x265-params", "aq-mode=3:psy-rd=2.0:psy-rdoq=1.0"]
+            } else if encoder.contains("nvenc") {
+                &["-bf", "3", "-rc-lookahead", "20", "-spatial-aq", "1", "-temporal-aq", "1"]
+            } else {
+                &[]
+            }
+        }
+    };
+    args.iter().map(|a| a.to_string()).collect()
+}
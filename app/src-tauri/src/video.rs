@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 use tauri::{AppHandle, Emitter, Manager};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct VideoInfo {
     pub name: String,
@@ -25,12 +27,178 @@ pub struct VideoInfo {
     pub vmaf_detail: Option<Vec<f64>>,
     pub vmaf_total_segments: Option<u32>,
     pub vmaf_model: Option<String>,
+    // GPU vs CPU cross-check: largest per-segment difference (CUDA - CPU), and whether
+    // it exceeded the configured tolerance
+    #[serde(default)]
+    pub vmaf_cpu_delta: Option<f64>,
+    #[serde(default)]
+    pub vmaf_delta_flagged: Option<bool>,
+    // Exact ffmpeg command line of each pass that produced this output
+    #[serde(default)]
+    pub ffmpeg_commands: Option<Vec<String>>,
+    // Bitrate-based "high"/"medium"/"low" guess for skipped or stream-copied files,
+    // which have no meaningful VMAF
+    #[serde(default)]
+    pub estimated_quality: Option<String>,
+    // Only set when the file has more than one video stream (angles, thumbnails):
+    // how many there are and which one (ffmpeg `v:N` index) is treated as the main one
+    #[serde(default)]
+    pub video_stream_count: Option<u32>,
+    #[serde(default)]
+    pub main_video_stream: Option<u32>,
+    // Why the file could not be probed (status "Protected" or "Unreadable")
+    #[serde(default)]
+    pub probe_error: Option<String>,
+    // Main video stream details
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(default)]
+    pub pix_fmt: Option<String>,
+    #[serde(default)]
+    pub bit_depth: Option<u32>,
+    #[serde(default)]
+    pub hdr: Option<bool>, // PQ (HDR10/Dolby Vision) or HLG transfer
+    #[serde(default)]
+    pub color_transfer: Option<String>, // "bt709", "smpte2084", "arib-std-b67", ...
+    #[serde(default)]
+    pub field_order: Option<String>, // "progressive", "tt", "bb", "tb", "bt" or "unknown"
+    #[serde(default)]
+    pub rotation: Option<u32>, // Display rotation, degrees clockwise (90/180/270); phone videos
+    #[serde(default)]
+    pub frame_count: Option<u64>, // From the container, else estimated from duration and fps
+    #[serde(default)]
+    pub audio_tracks: Option<Vec<AudioTrack>>,
+    #[serde(default)]
+    pub subtitle_tracks: Option<Vec<SubtitleTrack>>,
+    // Encoder summary (average QP, frame types, speed) of the encode that made this output
+    #[serde(default)]
+    pub encoder_stats: Option<crate::encstats::EncoderStats>,
+    // Video bits per pixel per frame: an efficiency number that doesn't depend on
+    // resolution or frame rate
+    #[serde(default)]
+    pub bits_per_pixel: Option<f64>,
+    // Outputs only: source size / output size, and the source's bits per pixel
+    #[serde(default)]
+    pub compression_ratio: Option<f64>,
+    #[serde(default)]
+    pub source_bits_per_pixel: Option<f64>,
+    // Pre-analysis (analyze_video): black bars as a `crop=` value "w:h:x:y"
+    #[serde(default)]
+    pub crop: Option<String>,
+    // Pre-analysis: share of frames idet found interlaced
+    #[serde(default)]
+    pub interlaced_ratio: Option<f64>,
+    // Harmonic mean, 5%/1% lows and minimum over the scored frames (`vmaf` is the mean)
+    #[serde(default)]
+    pub vmaf_pooling: Option<crate::pooling::VmafPooling>,
+    // Outputs that replaced their source: where the original can be restored from
+    // (None once it is in the trash)
+    #[serde(default)]
+    pub source_backup: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioTrack {
+    pub codec: String,
+    pub channel_layout: String, // "stereo", "5.1(side)", ... or "N channels" if unnamed
+    #[serde(default)]
+    pub channels: u32,
+    #[serde(default)]
+    pub language: Option<String>, // ISO 639-2 tag, "und" dropped
+    #[serde(default)]
+    pub bitrate_kbps: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleTrack {
+    pub codec: String, // "subrip", "ass", "hdmv_pgs_subtitle", ...
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub forced: bool,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ScanResult {
     pub videos: Vec<VideoInfo>,
     pub errors: Vec<String>,
+    // Files reached through more than one dropped root (e.g. a folder and a file inside it)
+    pub duplicates_collapsed: usize,
+    // Files left out by a ScanFilter
+    pub filtered_out: usize,
+}
+
+/// Narrows a scan of a large library ("only 1080p H.264 over 8 Mbps"). Every found file
+/// gets a quick probe; files that match come back already probed, files that can't be
+/// probed don't match. Zero / empty fields don't filter.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanFilter {
+    pub codecs: Vec<String>, // Source codecs to keep ("h264", "hevc", ...)
+    pub min_resolution: u32, // Shorter side in pixels, 1080 = "1080p" and up
+    pub min_bitrate_kbps: f64,
+    pub min_duration_sec: f64,
+    pub min_size_mb: f64, // Checked from the file size, before any probing
+}
+
+impl ScanFilter {
+    fn is_empty(&self) -> bool {
+        self.min_size_mb <= 0.0 && !self.needs_probe()
+    }
+
+    /// Whether any criterion needs ffprobe
+    fn needs_probe(&self) -> bool {
+        !self.codecs.is_empty() || self.min_resolution > 0 || self.min_bitrate_kbps > 0.0 || self.min_duration_sec > 0.0
+    }
+
+    fn matches(&self, info: &VideoInfo) -> bool {
+        let short_side = info.resolution
+            .split_once('x')
+            .and_then(|(w, h)| Some(w.parse::<u32>().ok()?.min(h.parse::<u32>().ok()?)))
+            .unwrap_or(0);
+        (self.codecs.is_empty() || self.codecs.iter().any(|c| canonical_codec(c) == canonical_codec(&info.encoder)))
+            && short_side >= self.min_resolution
+            && (self.min_bitrate_kbps <= 0.0 || info.bitrate_kbps.is_some_and(|b| b >= self.min_bitrate_kbps))
+            && info.duration_sec >= self.min_duration_sec
+    }
+}
+
+/// Apply `filter` to a scan: the size limit first, then the rest on the remaining files,
+/// probed in parallel
+pub fn filter_scan(mut result: ScanResult, filter: &ScanFilter, ffprobe_path: &str) -> ScanResult {
+    if filter.is_empty() || result.videos.is_empty() {
+        return result;
+    }
+    let total = result.videos.len();
+    let min_size = (filter.min_size_mb.max(0.0) * 1024.0 * 1024.0) as u64;
+    result.videos.retain(|v| v.size >= min_size);
+    if !filter.needs_probe() || result.videos.is_empty() {
+        result.filtered_out = total - result.videos.len();
+        println!("[INFO] Scan filter kept {} of {} file(s)", result.videos.len(), total);
+        return result;
+    }
+
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(16);
+    let chunk_size = result.videos.len().div_ceil(threads);
+    let found = std::mem::take(&mut result.videos);
+    result.videos = std::thread::scope(|scope| {
+        let workers: Vec<_> = found
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter()
+                    .filter_map(|v| get_metadata_quick(&v.path, ffprobe_path).ok())
+                    .filter(|info| info.status == "Pending" && filter.matches(info))
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        workers.into_iter().flat_map(|w| w.join().unwrap_or_default()).collect()
+    });
+    result.filtered_out = total - result.videos.len();
+    crate::metacache::flush();
+    println!("[INFO] Scan filter kept {} of {} file(s)", result.videos.len(), total);
+    result
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -52,8 +220,44 @@ pub struct DetectionReport {
 #[serde(rename_all = "camelCase")]
 pub struct MaxResolution {
     pub enabled: bool,
-    pub width: u32,
-    pub height: u32,
+    pub width: u32, // 0 = no width limit
+    pub height: u32, // 0 = no height limit
+    // Scaler: "lanczos" (sharper) or "bicubic"; "" = bicubic
+    #[serde(default)]
+    pub algorithm: String,
+}
+
+impl MaxResolution {
+    /// Downscale to fit inside width x height, keeping the aspect ratio and even dimensions;
+    /// sources already inside are left at their size. The box is turned with the source, so
+    /// 1920x1080 bounds a portrait video to 1080x1920 (long edge against long edge).
+    fn scale_filter(&self) -> Option<String> {
+        if !self.enabled || (self.width == 0 && self.height == 0) {
+            return None;
+        }
+        // Bounds for a landscape source; a portrait one gets them swapped
+        let (long, short) = if self.width > 0 && self.height > self.width { (self.height, self.width) } else { (self.width, self.height) };
+        let limit = |max: u32, dim: &str| if max > 0 { format!("min({},{})", max, dim) } else { dim.to_string() };
+        let bound = |landscape: u32, portrait: u32, dim: &str| format!("'if(gte(iw,ih),{},{})'", limit(landscape, dim), limit(portrait, dim));
+        let flags = match self.algorithm.as_str() {
+            "lanczos" | "bilinear" | "spline" | "area" => self.algorithm.as_str(),
+            _ => "bicubic",
+        };
+        Some(format!(
+            "scale=w={}:h={}:force_original_aspect_ratio=decrease:force_divisible_by=2:flags={}",
+            bound(long, short, "iw"), bound(short, long, "ih"), flags
+        ))
+    }
+}
+
+/// Subtitle to burn in: an external .srt/.ass/.vtt file, or else a subtitle stream of the source
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BurnInSubtitle {
+    #[serde(default)]
+    pub stream_index: u32, // Source `s:N` index, used when `file` is empty
+    #[serde(default)]
+    pub file: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -63,6 +267,28 @@ pub struct EncoderConfig {
     pub value: String,
     pub visible: bool,
     pub custom_params: Vec<String>,
+    // Typed preset/film grain/tune for libsvtav1
+    #[serde(default)]
+    pub svtav1: Option<crate::svtav1::SvtAv1Options>,
+}
+
+/// Output container for files with a given source extension, e.g. avi -> mkv.
+/// `target_format` may be "original" to keep the source container.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerMapping {
+    pub source_ext: String,
+    pub target_format: String,
+}
+
+/// Same as the UI's default, for configs saved before the option existed (manifests, queue)
+fn default_halt_after_failures() -> u32 {
+    3
+}
+
+/// Same as the UI's default; 0 would search on the first segment only
+fn default_crf_search_segments() -> u32 {
+    3
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -79,6 +305,17 @@ pub struct CompressionConfig {
     pub max_resolution: MaxResolution,
     pub video_encoder: String,
     pub audio_encoder: String,
+    // Audio encoding: bitrate (kbps), channel count (2 = downmix to stereo) and sample rate
+    // (Hz); 0 = the encoder's default / as the source
+    #[serde(default)]
+    pub audio_bitrate_kbps: u32,
+    #[serde(default)]
+    pub audio_channels: u32,
+    #[serde(default)]
+    pub audio_sample_rate: u32,
+    // Two-pass EBU R128 loudness normalization target in LUFS (e.g. -16 or -23); 0 = off
+    #[serde(default)]
+    pub loudnorm_target: f32,
     pub target_format: String,
     pub available_video_encoders: Vec<EncoderConfig>,
     pub available_audio_encoders: Vec<EncoderConfig>,
@@ -111,16 +348,338 @@ pub struct CompressionConfig {
     pub vmaf_auto_config: bool,
     #[serde(default)]
     pub vmaf_use_cuda: bool,
+
+    // GPU kill switch: libvmaf_cuda / NVENC B-frames are avoided on blacklisted drivers
+    // (built-in ranges plus these) unless ignore_gpu_blacklist is set
+    #[serde(default)]
+    pub gpu_blacklist: Vec<crate::gpuquirks::GpuQuirkRule>,
+    #[serde(default)]
+    pub ignore_gpu_blacklist: bool,
     #[serde(default)]
     pub vmaf_neg: bool,
     #[serde(default)]
     pub custom_vmaf_params: Vec<String>,
+    // Score every Nth frame only (libvmaf n_subsample); 0/1 = every frame
+    #[serde(default)]
+    pub vmaf_subsample: u32,
     #[serde(default)]
     #[serde(rename = "vmafSearchOptimization")]
     pub vmaf_search_optimization: bool,
+    // What the CRF search compares with the target: "" = mean, "harmonic_mean", "p5", "p1", "min"
+    #[serde(default)]
+    pub vmaf_search_pooling: String,
+    // Sample segments the CRF search encodes and scores per candidate, spread over the
+    // video: 0/1 = the first only. Adaptive sampling decides this on its own.
+    #[serde(default = "default_crf_search_segments")]
+    pub crf_search_segments: u32,
+    // Encoder preset for CRF search samples only (e.g. "10" for SVT-AV1, "fast" for x265);
+    // "" = the final encode's preset. The found CRF is moved by sample_crf_offset for the
+    // final encode, since a slower preset reaches the same VMAF at a higher CRF.
+    #[serde(default)]
+    pub sample_preset: String,
+    #[serde(default)]
+    pub sample_crf_offset: f32,
     #[serde(default)]
     #[serde(rename = "customCommand")]
     pub custom_command: String,
+
+    // Adaptive VMAF sampling: start with a few segments and only add more
+    // while the scores disagree with each other
+    #[serde(default)]
+    pub vmaf_adaptive_segments: bool,
+    #[serde(default)]
+    pub vmaf_adaptive_initial_segments: u32, // 0 = default (3)
+    #[serde(default)]
+    pub vmaf_adaptive_max_segments: u32, // 0 = default (3x initial)
+    #[serde(default)]
+    pub vmaf_adaptive_variance_threshold: f64, // 0 = default (1.0)
+
+    // "vmaf_abr" mode: VMAF search picks the quality, final encode is a capped two-pass ABR
+    #[serde(default)]
+    pub vmaf_abr_maxrate_percent: u32, // 0 = default (150)
+
+    // Write a <output>.json sidecar describing how each output was made
+    #[serde(default)]
+    pub write_sidecar: bool,
+
+    // Keep the per-frame VMAF scores as <output>.vmaf.json for the quality timeline
+    #[serde(default)]
+    pub keep_vmaf_log: bool,
+
+    // Global throughput cap for file copies (e.g. to network shares), megabits/s, 0 = unlimited
+    #[serde(default)]
+    pub copy_bandwidth_limit_mbps: u32,
+
+    // Eco mode for background compression on laptops: fewer threads, below-normal
+    // priority, no hardware encoding while the GPU is busy, paused while on battery
+    #[serde(default)]
+    pub eco_mode: bool,
+    #[serde(default)]
+    pub eco_max_threads: u32, // 0 = default (half the logical cores)
+    #[serde(default)]
+    pub eco_gpu_threshold: u32, // GPU usage %, 0 = default (50)
+
+    // Writing the output over its own source file must be opted into explicitly;
+    // the original can be kept as a backup (`<file>.bak`), which also keeps VMAF possible
+    #[serde(default)]
+    pub allow_overwrite_source: bool,
+    #[serde(default)]
+    pub backup_before_overwrite: bool,
+
+    // Replace-original workflow: the output takes the source's name (with the output
+    // container's extension) and the original is always backed up for restore_original
+    #[serde(default)]
+    pub replace_original: bool,
+    #[serde(default)]
+    pub backup_location: String, // "" = `<file>.bak` next to the source, "folder" = `.backup/`, "trash" = OS trash
+    #[serde(default)]
+    pub backup_retention_hours: u32, // `.backup` copies older than this are purged, 0 = keep all
+
+    // Output file name normalization: "" = as is, "nfc" = Unicode NFC, "ascii" = NFC
+    // transliterated to ASCII
+    #[serde(default)]
+    pub filename_normalization: String,
+
+    // Source codecs not to re-encode ("av1", "hevc", ...); such files are skipped
+    #[serde(default)]
+    pub skip_codecs: Vec<String>,
+
+    // Per-extension output container, checked before target_format
+    #[serde(default)]
+    pub container_mapping: Vec<ContainerMapping>,
+
+    // Encoder to switch to for the rest of the session if the selected one stops
+    // working mid-batch (driver update, eGPU unplugged). Empty = libx264
+    #[serde(default)]
+    pub fallback_video_encoder: String,
+
+    // Search samples: seek frame-accurately (slower) instead of fast input seeking,
+    // which can start short segments off the requested frame and skew VMAF
+    #[serde(default)]
+    pub accurate_seek: bool,
+
+    // Re-score a share of CUDA VMAF segments on CPU and flag results that disagree
+    #[serde(default)]
+    pub vmaf_cpu_crosscheck_percent: u32, // 0 = off
+    #[serde(default)]
+    pub vmaf_crosscheck_tolerance: f64, // VMAF points, 0 = default (1.0)
+
+    // Don't start new encodes/VMAF runs while free memory is below this floor (MB, 0 = off)
+    #[serde(default)]
+    pub min_free_memory_mb: u32,
+
+    // Pick bframes/AQ/psy-rd/tune flags from a quick animation vs live-action analysis
+    #[serde(default)]
+    pub auto_tune: bool,
+
+    // Typed NVENC preset/tuning/rate control/multipass/AQ/lookahead (None = encoder defaults)
+    #[serde(default)]
+    pub nvenc: Option<crate::nvenc::NvencOptions>,
+
+    // Show a bitrate-based quality estimate for skipped and stream-copied files
+    #[serde(default)]
+    pub estimate_skipped_quality: bool,
+
+    // Video stream to encode (ffmpeg `v:N` index) for files with several; None = pick automatically
+    #[serde(default)]
+    pub video_stream_index: Option<u32>,
+
+    // Audio tracks to keep: "all", "first" or "languages" (audio_languages in that order,
+    // the first track if none match); "" = ffmpeg's own pick, a single track
+    #[serde(default)]
+    pub audio_mapping: String,
+    // ISO 639-2 codes for "languages", e.g. ["jpn", "eng"]
+    #[serde(default)]
+    pub audio_languages: Vec<String>,
+    // Subtitles: "copy" (the tracks the container can hold), "convert" (also text subs
+    // turned into the container's text format), "drop"; "" = ffmpeg's own pick
+    #[serde(default)]
+    pub subtitle_mode: String,
+    // Container metadata: "keep" (global and stream tags, chapters), "strip" (none, and no
+    // encoder tag, for privacy); "" = ffmpeg's default
+    #[serde(default)]
+    pub metadata_mode: String,
+    // MKV outputs: copy attachment streams (fonts that styled ASS subtitles need)
+    #[serde(default)]
+    pub keep_attachments: bool,
+    // Subtitle rendered into the picture; None = no burn-in
+    #[serde(default)]
+    pub burn_in_subtitle: Option<BurnInSubtitle>,
+    // Crop the black bars cropdetect finds before encoding
+    #[serde(default)]
+    pub auto_crop: bool,
+    // Deinterlacer ("yadif"/"bwdif") for sources idet finds interlaced; "" = never deinterlace
+    #[serde(default)]
+    pub deinterlace: String,
+    // Denoise preset: "hqdn3d_light"/"_medium"/"_strong", "nlmeans_light"/"_medium"/"_strong"; "" = none
+    #[serde(default)]
+    pub denoise: String,
+    // Rotated (phone) sources: "bake" turns the pixels upright, "preserve" keeps them as they
+    // are with the rotation flag; "" = ffmpeg's default (encodes bake, copies keep the flag)
+    #[serde(default)]
+    pub rotation_mode: String,
+    // Frame rate cap; sources above it are converted down (e.g. 60 fps recordings to 30); 0 = none
+    #[serde(default)]
+    pub max_fps: f64,
+
+    // Pause the batch after this many consecutive jobs fail with the same systemic error
+    // (disk full, permission denied, encoder missing); 0 = never
+    #[serde(default = "default_halt_after_failures")]
+    pub halt_after_failures: u32,
+    // Keep sample segments in a private scratch folder and overwrite samples and
+    // failed partial outputs with zeros before deleting them
+    #[serde(default)]
+    pub secure_temp_files: bool,
+    // Encodes allowed to run at once across all jobs (0 = no limit, scheduling left to the
+    // caller); the backend queue and manifests start this many workers
+    #[serde(default)]
+    pub max_concurrent_encodes: u32,
+    // Decoder for the source (and the VMAF reference) instead of the automatic choice,
+    // e.g. "hevc" to force software decoding of a stream the GPU decoder mangles; empty = auto
+    #[serde(default)]
+    pub force_decoder: String,
+    // After encoding, compare the output's video frame count with the source's (slower:
+    // both files are read through) to catch encoders that drop trailing frames
+    #[serde(default)]
+    pub verify_frame_count: bool,
+    // Outputs being verified at once (0 = no limit). Verification runs after the encode
+    // slot is released, so the next encode doesn't wait for it
+    #[serde(default)]
+    pub max_concurrent_verifications: u32,
+    // Post-encode VMAF computations at once (0 = 1); CUDA ones still run one per GPU
+    #[serde(default)]
+    pub max_concurrent_vmaf: u32,
+    // "archive" / "balanced" / "small": pick encoder and target per resolution from
+    // tiers::TIERS instead of the settings above; empty = off
+    #[serde(default)]
+    pub quality_tier: String,
+    // Extra environment for every ffmpeg/ffprobe a job starts (CUDA_VISIBLE_DEVICES,
+    // LIBVA_DRIVER_NAME, ...) and flags put first on its ffmpeg command lines (-nostdin)
+    #[serde(default)]
+    pub env: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub global_flags: Vec<String>,
+    // "percent" mode: target size as a percentage of the source; each file gets a bitrate
+    // target from its own bitrate and is then encoded like "bitrate" mode
+    #[serde(default)]
+    pub target_size_percent: u32, // 0 = default (60)
+    // Discard outputs that aren't at least `min_savings_percent` smaller than the source
+    // and keep (copy) the original instead
+    #[serde(default)]
+    pub keep_only_if_smaller: bool,
+    #[serde(default)]
+    pub min_savings_percent: u32, // 0 = any reduction
+}
+
+impl CompressionConfig {
+    /// Modes that run the VMAF-guided CRF search before the final encode
+    pub fn uses_vmaf_search(&self) -> bool {
+        self.compression_mode == "vmaf" || self.compression_mode == "vmaf_abr"
+    }
+
+    /// CRF for the final encode from the one the search found on fast-preset samples
+    fn final_crf(&self, search_crf: f32, v_enc: &str) -> f32 {
+        if sample_preset_override(self, v_enc).is_none() || self.sample_crf_offset == 0.0 {
+            return search_crf;
+        }
+        let (min_crf, max_crf) = get_crf_range(v_enc);
+        (search_crf + self.sample_crf_offset).clamp(min_crf, max_crf)
+    }
+
+    fn vmaf_abr_maxrate_percent(&self) -> u32 {
+        if self.vmaf_abr_maxrate_percent == 0 { 150 } else { self.vmaf_abr_maxrate_percent.max(100) }
+    }
+
+    fn adaptive_initial_segments(&self) -> u32 {
+        if self.vmaf_adaptive_initial_segments == 0 { 3 } else { self.vmaf_adaptive_initial_segments }
+    }
+
+    fn adaptive_variance_threshold(&self) -> f64 {
+        if self.vmaf_adaptive_variance_threshold <= 0.0 { 1.0 } else { self.vmaf_adaptive_variance_threshold }
+    }
+
+    /// Output container for `input_path`: the first matching container mapping,
+    /// otherwise target_format ("original" keeps the source extension)
+    pub fn output_format_for(&self, input_path: &str) -> String {
+        let source_ext = Path::new(input_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let format = self.container_mapping.iter()
+            .find(|m| m.source_ext.trim_start_matches('.').eq_ignore_ascii_case(&source_ext))
+            .map(|m| m.target_format.as_str())
+            .unwrap_or(&self.target_format);
+        if format.is_empty() || format == "original" {
+            if source_ext.is_empty() { "mp4".to_string() } else { source_ext }
+        } else {
+            format.trim_start_matches('.').to_lowercase()
+        }
+    }
+
+    /// Whether segment `idx` of a CUDA VMAF run should also be scored on CPU.
+    /// Spreads the checks evenly, always including the first segment.
+    fn vmaf_crosscheck_segment(&self, idx: usize) -> bool {
+        match self.vmaf_cpu_crosscheck_percent.min(100) {
+            0 => false,
+            percent => idx.is_multiple_of((100.0 / percent as f64).round().max(1.0) as usize),
+        }
+    }
+
+    /// Extra libvmaf options: the frame subsampling, then the user's own params (which win
+    /// on a repeated key)
+    fn vmaf_filter_params(&self) -> Vec<String> {
+        let mut params = Vec::new();
+        if self.vmaf_subsample > 1 {
+            params.push(format!("n_subsample={}", self.vmaf_subsample));
+        }
+        params.extend(self.custom_vmaf_params.iter().cloned());
+        params
+    }
+
+    fn vmaf_crosscheck_tolerance(&self) -> f64 {
+        if self.vmaf_crosscheck_tolerance <= 0.0 { 1.0 } else { self.vmaf_crosscheck_tolerance }
+    }
+
+    /// `-threads` value for encodes (0 = let ffmpeg decide); eco mode caps it
+    fn effective_ffmpeg_threads(&self) -> u32 {
+        if !self.eco_mode {
+            return self.ffmpeg_threads;
+        }
+        let cap = if self.eco_max_threads == 0 {
+            std::thread::available_parallelism().map(|n| n.get() as u32 / 2).unwrap_or(2).max(1)
+        } else {
+            self.eco_max_threads
+        };
+        if self.ffmpeg_threads == 0 { cap } else { self.ffmpeg_threads.min(cap) }
+    }
+
+    fn eco_gpu_threshold(&self) -> f32 {
+        if self.eco_gpu_threshold == 0 { 50.0 } else { self.eco_gpu_threshold.min(100) as f32 }
+    }
+
+    /// Free memory is below the configured floor (unknown counts as fine)
+    fn memory_low(&self, snapshot: &crate::monitor::Snapshot) -> bool {
+        self.min_free_memory_mb > 0
+            && snapshot.available_memory > 0
+            && snapshot.available_memory < self.min_free_memory_mb as u64 * 1024 * 1024
+    }
+
+    /// Workers for the backend queue and manifests: one per encode slot, plus one per
+    /// verification slot so a worker busy verifying doesn't hold back the next encode
+    pub fn job_workers(&self) -> u32 {
+        self.max_concurrent_encodes.max(1) + self.max_concurrent_verifications
+    }
+
+    /// libvmaf_cuda is enabled and not blacklisted for the installed driver
+    pub fn vmaf_cuda(&self) -> bool {
+        self.vmaf_use_cuda && !crate::gpuquirks::blocks(self, crate::gpuquirks::VMAF_CUDA)
+    }
+
+    /// Eco mode: the GPU is considered busy (e.g. a game is running)
+    fn eco_gpu_busy(&self) -> bool {
+        self.eco_mode && crate::monitor::latest().gpu_usage > self.eco_gpu_threshold()
+    }
 }
 
 pub struct VmafTask {
@@ -139,13 +698,68 @@ pub struct VmafTask {
 
 pub struct VmafState {
     pub queue: std::collections::VecDeque<VmafTask>,
-    pub running_task: Option<String>,
+    pub running_tasks: Vec<RunningVmaf>,
     /// Historical CRF-VMAF search results from previous tasks
     /// Used by the optimizer to predict CRF for new tasks
     /// Key: (width, height), Value: List of (crf, vmaf) tuples
     pub crf_history: std::collections::HashMap<(u32, u32), Vec<(f32, f64)>>,
 }
 
+/// A VMAF task taken from the queue and still running
+pub struct RunningVmaf {
+    pub input_path: String,
+    pub distorted_path: String,
+    pub gpu: Option<u32>, // GPU index when it scores with CUDA
+}
+
+/// One VMAF task as shown to the user: running ones first, then the queue in order
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VmafQueueEntry {
+    pub input_path: String,
+    pub distorted_path: String,
+    pub position: Option<usize>, // Place in the queue, None while running
+    pub cuda: bool,
+}
+
+impl VmafState {
+    pub fn entries(&self) -> Vec<VmafQueueEntry> {
+        let running = self.running_tasks.iter().map(|r| VmafQueueEntry {
+            input_path: r.input_path.clone(),
+            distorted_path: r.distorted_path.clone(),
+            position: None,
+            cuda: r.gpu.is_some(),
+        });
+        let queued = self.queue.iter().enumerate().map(|(i, t)| VmafQueueEntry {
+            input_path: t.input_path.clone(),
+            distorted_path: t.distorted_path.clone(),
+            position: Some(i),
+            cuda: t.config.vmaf_cuda(),
+        });
+        running.chain(queued).collect()
+    }
+
+    fn queue_position(&self, path: &str) -> Option<usize> {
+        let key = job_key(path);
+        self.queue.iter().position(|t| job_key(&t.input_path) == key)
+    }
+
+    /// Move the queued task of `path` to `position` (0 = next to run, clamped to the end)
+    pub fn move_task(&mut self, path: &str, position: usize) -> Result<(), String> {
+        let from = self.queue_position(path).ok_or_else(|| format!("{} is not waiting for VMAF", path))?;
+        let task = self.queue.remove(from).ok_or("VMAF queue changed")?;
+        let to = position.min(self.queue.len());
+        self.queue.insert(to, task);
+        Ok(())
+    }
+
+    /// Take the queued task of `path` out of the queue, leaving running ones alone
+    pub fn remove_task(&mut self, path: &str) -> Option<VmafTask> {
+        let pos = self.queue_position(path)?;
+        self.queue.remove(pos)
+    }
+}
+
 const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "avi", "mov", "flv", "wmv", "webm", "m4v", "mpg", "mpeg", "3gp", "ts","asf", "rmvb", "vob","m2ts","f4v","mts","ogv", "divx","xvid","rm"];
 
 /// Check if a path is a video file based on its extension
@@ -187,10 +801,31 @@ pub fn categorize_paths(paths: Vec<String>) -> PathCategorization {
     PathCategorization { videos, directories, invalid }
 }
 
+/// Identity of a scanned file for de-duplication across dropped roots
+fn dedupe_key(path: &Path) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A file found by a scan, before it is probed
+fn scanning_placeholder(path: &Path, size: u64) -> VideoInfo {
+    VideoInfo {
+        name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        path: path.to_string_lossy().to_string(),
+        size,
+        resolution: "...".to_string(),
+        bitrate: "...".to_string(),
+        encoder: "...".to_string(),
+        status: "Scanning".to_string(),
+        ..Default::default()
+    }
+}
+
 /// Scan multiple paths (files and directories) for videos
 pub fn scan_multiple_paths(paths: Vec<String>) -> ScanResult {
     let mut videos = Vec::new();
     let mut errors = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicates_collapsed = 0;
 
     for p in paths {
         let path = Path::new(&p);
@@ -202,25 +837,12 @@ pub fn scan_multiple_paths(paths: Vec<String>) -> ScanResult {
                     Ok(entry) => {
                         let entry_path = entry.path();
                         if is_video_file(entry_path) {
+                            if !seen.insert(dedupe_key(entry_path)) {
+                                duplicates_collapsed += 1;
+                                continue;
+                            }
                             let size = std::fs::metadata(entry_path).map(|m| m.len()).unwrap_or(0);
-                            videos.push(VideoInfo {
-                                name: entry_path.file_name().unwrap_or_default().to_string_lossy().to_string(),
-                                path: entry_path.to_string_lossy().to_string(),
-                                size,
-                                resolution: "...".to_string(),
-                                bitrate: "...".to_string(),
-                                encoder: "...".to_string(),
-                                status: "Scanning".to_string(),
-                                progress: 0,
-                                duration_sec: 0.0,
-                                speed: None,
-                                bitrate_kbps: None,
-                                vmaf: None,
-                                vmaf_device: None,
-                                vmaf_detail: None,
-                                vmaf_total_segments: None,
-                                vmaf_model: None,
-                            });
+                            videos.push(scanning_placeholder(entry_path, size));
                         }
                     }
                     Err(e) => errors.push(format!("Error walking directory: {}", e)),
@@ -228,38 +850,60 @@ pub fn scan_multiple_paths(paths: Vec<String>) -> ScanResult {
             }
         } else if is_video_file(path) {
             // Single video file
+            if !seen.insert(dedupe_key(path)) {
+                duplicates_collapsed += 1;
+                continue;
+            }
             let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-            videos.push(VideoInfo {
-                name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
-                path: path.to_string_lossy().to_string(),
-                size,
-                resolution: "...".to_string(),
-                bitrate: "...".to_string(),
-                encoder: "...".to_string(),
-                status: "Scanning".to_string(),
-                progress: 0,
-                duration_sec: 0.0,
-                speed: None,
-                bitrate_kbps: None,
-                vmaf: None,
-                vmaf_device: None,
-                vmaf_detail: None,
-                vmaf_total_segments: None,
-                vmaf_model: None,
-            });
+            videos.push(scanning_placeholder(path, size));
         } else {
             errors.push(format!("Invalid path (not a video or directory): {}", p));
         }
     }
 
-    ScanResult { videos, errors }
+    if duplicates_collapsed > 0 {
+        println!("[INFO] Scan: collapsed {} duplicate file(s) reached through overlapping paths", duplicates_collapsed);
+    }
+
+    ScanResult { videos, errors, duplicates_collapsed, filtered_out: 0 }
+}
+
+/// A streamed scan sends what it found at least this often...
+const SCAN_BATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// ...or once this many files have piled up
+const SCAN_BATCH_SIZE: usize = 500;
+
+/// Id of the newest streamed scan; an older one stops walking once it is superseded
+static CURRENT_SCAN: AtomicU64 = AtomicU64::new(0);
+
+/// `scan-progress` payload: the files found since the previous event, then one last event
+/// with `done` set, no videos and all walk errors
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgress {
+    pub scan_id: u64,
+    pub videos: Vec<VideoInfo>,
+    pub found: usize, // Files sent so far in this scan
+    pub filtered_out: usize,
+    pub errors: Vec<String>,
+    pub done: bool,
 }
 
-pub fn scan_videos(directory: &str) -> ScanResult {
+/// Walk `directory` and hand the found files to `on_batch` as the walk goes, in batches of
+/// up to SCAN_BATCH_SIZE. Stops early when `on_batch` returns false.
+fn scan_videos_in_batches(directory: &str, mut on_batch: impl FnMut(ScanResult) -> bool) {
     let mut videos = Vec::new();
     let mut errors = Vec::new();
+    let mut last_batch = Instant::now();
 
     for entry in WalkDir::new(directory) {
+        if !videos.is_empty() && (videos.len() >= SCAN_BATCH_SIZE || last_batch.elapsed() >= SCAN_BATCH_INTERVAL) {
+            let batch = ScanResult { videos: std::mem::take(&mut videos), errors: std::mem::take(&mut errors), duplicates_collapsed: 0, filtered_out: 0 };
+            if !on_batch(batch) {
+                return;
+            }
+            last_batch = Instant::now();
+        }
         match entry {
             Ok(entry) => {
                 let path = entry.path();
@@ -267,24 +911,7 @@ pub fn scan_videos(directory: &str) -> ScanResult {
                     if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
                         if VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
                             let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-                            videos.push(VideoInfo {
-                                name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
-                                path: path.to_string_lossy().to_string(),
-                                size,
-                                resolution: "...".to_string(),
-                                bitrate: "...".to_string(),
-                                encoder: "...".to_string(),
-                                status: "Scanning".to_string(),
-                                progress: 0,
-                                duration_sec: 0.0,
-                                speed: None,
-                                bitrate_kbps: None,
-                                vmaf: None,
-                                vmaf_device: None,
-                                vmaf_detail: None,
-                                vmaf_total_segments: None,
-                                vmaf_model: None,
-                            });
+                            videos.push(scanning_placeholder(path, size));
                         }
                     }
                 }
@@ -293,32 +920,247 @@ pub fn scan_videos(directory: &str) -> ScanResult {
         }
     }
 
-    ScanResult { videos, errors }
+    on_batch(ScanResult { videos, errors, duplicates_collapsed: 0, filtered_out: 0 });
 }
 
-#[derive(Debug, Serialize, Clone)]
-pub struct DetectionProgress {
-    pub r#type: String,
-    pub name: String,
-    pub value: String,
-    pub available: bool,
-}
+/// Scan `directory` as `scan_id`, emitting `scan-progress` per batch so the table fills
+/// while a large library is still being walked. The filter, if any, runs on each batch.
+/// A newer streamed scan supersedes this one, which then stops without a final event.
+pub fn stream_scan(app: &AppHandle, directory: &str, filter: Option<&ScanFilter>, ffprobe_path: &str, scan_id: u64) {
+    CURRENT_SCAN.store(scan_id, Ordering::SeqCst);
+    let mut found = 0;
+    let mut filtered_out = 0;
+    let mut errors = Vec::new();
+    let started = Instant::now();
 
-pub fn detect_system_encoders(ffmpeg_path: &str, app: AppHandle) -> DetectionReport {
-    let mut report = DetectionReport {
+    scan_videos_in_batches(directory, |batch| {
+        if CURRENT_SCAN.load(Ordering::SeqCst) != scan_id {
+            return false;
+        }
+        let batch = match filter {
+            Some(filter) => filter_scan(batch, filter, ffprobe_path),
+            None => batch,
+        };
+        found += batch.videos.len();
+        filtered_out += batch.filtered_out;
+        errors.extend(batch.errors);
+        if !batch.videos.is_empty() {
+            let _ = app.emit("scan-progress", ScanProgress {
+                scan_id,
+                videos: batch.videos,
+                found,
+                filtered_out,
+                errors: Vec::new(),
+                done: false,
+            });
+        }
+        true
+    });
+
+    if CURRENT_SCAN.load(Ordering::SeqCst) != scan_id {
+        println!("[INFO] Scan {} of {} superseded after {} file(s)", scan_id, directory, found);
+        return;
+    }
+    println!("[INFO] Scan of {} found {} file(s) in {:.1} s", directory, found, started.elapsed().as_secs_f64());
+    let _ = app.emit("scan-progress", ScanProgress { scan_id, videos: Vec::new(), found, filtered_out, errors, done: true });
+}
+
+/// Id of the newest metadata batch; an older one stops probing once it is superseded
+static CURRENT_METADATA_BATCH: AtomicU64 = AtomicU64::new(0);
+/// Upper bound for `parallelism`, whatever the settings say
+const MAX_PROBE_THREADS: usize = 32;
+
+/// Quick probe of one file of a metadata batch
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataResult {
+    pub path: String,
+    pub info: Option<VideoInfo>,
+    pub error: Option<String>,
+}
+
+/// `metadata-progress` payload: the probes finished since the previous event; the last
+/// event of a batch has `done` set
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataProgress {
+    pub batch_id: u64,
+    pub results: Vec<MetadataResult>,
+    pub completed: usize,
+    pub total: usize,
+    pub done: bool,
+}
+
+/// Quick-probe `paths` on `parallelism` threads (the `ffprobe_threads` setting), emitting
+/// `metadata-progress` with the results in batches, like `stream_scan`. A newer batch
+/// supersedes this one, which then stops without a final event.
+pub fn probe_metadata_batch(app: &AppHandle, paths: Vec<String>, parallelism: usize, ffprobe_path: &str, batch_id: u64) {
+    CURRENT_METADATA_BATCH.store(batch_id, Ordering::SeqCst);
+    let superseded = || CURRENT_METADATA_BATCH.load(Ordering::SeqCst) != batch_id;
+    let total = paths.len();
+    let threads = parallelism.clamp(1, MAX_PROBE_THREADS).min(total.max(1));
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let started = Instant::now();
+    let (tx, rx) = std::sync::mpsc::channel::<MetadataResult>();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let (tx, next, paths) = (tx.clone(), &next, &paths);
+            scope.spawn(move || {
+                while !superseded() {
+                    let Some(path) = paths.get(next.fetch_add(1, Ordering::SeqCst)) else { break };
+                    let result = match get_metadata_quick(path, ffprobe_path) {
+                        Ok(info) => MetadataResult { path: path.clone(), info: Some(info), error: None },
+                        Err(e) => MetadataResult { path: path.clone(), info: None, error: Some(e) },
+                    };
+                    if tx.send(result).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut results = Vec::new();
+        let mut completed = 0;
+        let mut last_batch = Instant::now();
+        loop {
+            match rx.recv_timeout(SCAN_BATCH_INTERVAL) {
+                Ok(result) => results.push(result),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if !results.is_empty() && (results.len() >= SCAN_BATCH_SIZE || last_batch.elapsed() >= SCAN_BATCH_INTERVAL) {
+                if superseded() {
+                    return;
+                }
+                completed += results.len();
+                let _ = app.emit("metadata-progress", MetadataProgress {
+                    batch_id,
+                    results: std::mem::take(&mut results),
+                    completed,
+                    total,
+                    done: false,
+                });
+                last_batch = Instant::now();
+            }
+        }
+        if superseded() {
+            println!("[INFO] Metadata batch {} superseded after {} of {} file(s)", batch_id, completed, total);
+            return;
+        }
+        completed += results.len();
+        crate::metacache::flush();
+        println!("[INFO] Probed {} file(s) on {} thread(s) in {:.1} s", completed, threads, started.elapsed().as_secs_f64());
+        let _ = app.emit("metadata-progress", MetadataProgress { batch_id, results, completed, total, done: true });
+    });
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DetectionProgress {
+    pub r#type: String,
+    pub name: String,
+    pub value: String,
+    pub available: bool,
+}
+
+/// Encode a single synthetic frame to check that a video encoder actually works here
+fn probe_video_encoder(ffmpeg_path: &str, name: &str) -> bool {
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-v", "error"].iter().map(|a| a.to_string()).collect();
+    args.extend(hw_device_args(name));
+    args.extend(with_hw_upload(name, [
+        "-f", "lavfi", "-i", "color=size=1280x720:rate=30",
+        "-frames:v", "1", "-pix_fmt", "yuv420p",
+        "-c:v", name,
+    ].iter().map(|a| a.to_string()).collect()));
+    args.extend(["-f", "null", "-"].iter().map(|a| a.to_string()));
+
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(args);
+
+    matches!(command.output(), Ok(o) if o.status.success())
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderFallbackPayload {
+    pub from: String,
+    pub to: String,
+}
+
+/// Encoders replaced during this session (failed -> substitute), so the rest of the
+/// batch doesn't keep hitting the same error
+static ENCODER_FALLBACKS: std::sync::Mutex<Option<std::collections::HashMap<String, String>>> =
+    std::sync::Mutex::new(None);
+
+/// Error of a CRF search whose samples all failed because the encoder is gone
+const ENCODER_UNAVAILABLE: &str = "Encoder unavailable";
+
+/// Error of a compression attempt that switched to the fallback encoder and must be redone
+const ENCODER_SWITCHED: &str = "Encoder switched";
+
+/// ffmpeg output meaning the encoder itself is gone, as opposed to bad parameters
+fn is_encoder_unavailable_error(stderr: &str) -> bool {
+    [
+        "Unknown encoder",
+        "Encoder not found",
+        "No capable devices found",
+        "No NVENC capable devices found",
+        "Cannot load nvcuda",
+        "Cannot load libcuda",
+    ].iter().any(|m| stderr.contains(m))
+}
+
+/// The selected encoder failed as unavailable: re-check it and, if it's really gone, switch
+/// the session to the configured fallback. Returns the substitute on success.
+fn switch_to_fallback_encoder(app: &AppHandle, ffmpeg_path: &str, failed: &str, config: &CompressionConfig) -> Option<String> {
+    if let Some(existing) = ENCODER_FALLBACKS.lock().ok()?.as_ref().and_then(|m| m.get(failed).cloned()) {
+        return Some(existing);
+    }
+    if probe_video_encoder(ffmpeg_path, failed) {
+        println!("[WARNING] Encoder '{}' reported unavailable but passes detection again", failed);
+        return None;
+    }
+
+    let preferred = if config.fallback_video_encoder.is_empty() { "libx264" } else { config.fallback_video_encoder.as_str() };
+    let substitute = [preferred, "libx264"].into_iter()
+        .filter(|e| *e != failed)
+        .find(|e| probe_video_encoder(ffmpeg_path, e))?
+        .to_string();
+
+    eprintln!("[WARNING] Encoder '{}' is no longer available, using '{}' for the rest of this session", failed, substitute);
+    if let Ok(mut guard) = ENCODER_FALLBACKS.lock() {
+        guard.get_or_insert_with(std::collections::HashMap::new)
+            .insert(failed.to_string(), substitute.clone());
+    }
+    let _ = app.emit("encoder-fallback", EncoderFallbackPayload {
+        from: failed.to_string(),
+        to: substitute.clone(),
+    });
+    Some(substitute)
+}
+
+/// Config with any session encoder substitution applied
+fn with_encoder_fallback(config: &CompressionConfig) -> CompressionConfig {
+    let mut config = config.clone();
+    let substitute = ENCODER_FALLBACKS.lock().ok()
+        .and_then(|g| g.as_ref().and_then(|m| m.get(&config.video_encoder).cloned()));
+    if let Some(substitute) = substitute {
+        config.video_encoder = substitute;
+    }
+    config
+}
+
+pub fn detect_system_encoders(ffmpeg_path: &str, app: AppHandle) -> DetectionReport {
+    let mut report = DetectionReport {
         video: Vec::new(),
         audio: Vec::new(),
         log: Vec::new(),
     };
 
     // 1. Get raw list
-    let mut command = Command::new(ffmpeg_path);
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
     command.arg("-encoders");
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
-    }
     let output = match command.output() {
         Ok(o) => String::from_utf8_lossy(&o.stdout).to_string(),
         Err(e) => {
@@ -349,39 +1191,21 @@ pub fn detect_system_encoders(ffmpeg_path: &str, app: AppHandle) -> DetectionRep
     }
 
     // 2. Filter & Test Video
-    let hw_keywords = ["nvenc", "amf", "qsv", "cuda", "vaapi", "vdpau","d3d12va"];
+    let hw_keywords = ["nvenc", "amf", "qsv", "cuda", "vaapi", "vdpau","d3d12va", "videotoolbox"];
 
     for (name, desc) in all_video {
         let is_hw = hw_keywords.iter().any(|k| name.contains(k));
 
-        let args = [
-            "-y", "-hide_banner", "-v", "error",
-            "-f", "lavfi", "-i", "color=size=1280x720:rate=30",
-            "-frames:v", "1", "-pix_fmt", "yuv420p",
-            "-c:v", &name, "-f", "null", "-"
-        ];
-        
-        let mut command = Command::new(ffmpeg_path);
-        command.args(&args);
-        #[cfg(windows)]
-        {
-            use std::os::windows::process::CommandExt;
-            command.creation_flags(0x08000000);
+        let available = probe_video_encoder(ffmpeg_path, &name);
+        if available {
+            let display_name = if is_hw { format!("{} (HW)", name) } else { format!("{} (CPU)", name) };
+            report.video.push(DetectedEncoder {
+                name: display_name.clone(),
+                value: name.clone(),
+                is_hardware: is_hw,
+                description: desc.clone(),
+            });
         }
-        
-        let available = match command.output() {
-            Ok(o) if o.status.success() => {
-                let display_name = if is_hw { format!("{} (HW)", name) } else { format!("{} (CPU)", name) };
-                report.video.push(DetectedEncoder {
-                    name: display_name.clone(),
-                    value: name.clone(),
-                    is_hardware: is_hw,
-                    description: desc.clone(),
-                });
-                true
-            },
-            _ => false
-        };
 
         // 发送进度事件
         let _ = app.emit("encoder-detection-progress", DetectionProgress {
@@ -400,13 +1224,8 @@ pub fn detect_system_encoders(ffmpeg_path: &str, app: AppHandle) -> DetectionRep
             "-t", "1", "-c:a", &name, "-f", "null", "-"
         ];
 
-        let mut command = Command::new(ffmpeg_path);
+        let mut command = crate::process::ffmpeg_command(ffmpeg_path);
         command.args(&args);
-        #[cfg(windows)]
-        {
-            use std::os::windows::process::CommandExt;
-            command.creation_flags(0x08000000);
-        }
 
         let available = match command.output() {
             Ok(o) if o.status.success() => {
@@ -437,32 +1256,170 @@ pub fn get_metadata(path: &str, ffprobe_path: &str) -> Result<VideoInfo, String>
     get_video_info(Path::new(path), ffprobe_path)
 }
 
-fn get_video_info(path: &Path, ffprobe_path: &str) -> Result<VideoInfo, String> {
-    let mut command = Command::new(ffprobe_path);
-    command.args(&[
-            "-v", "quiet",
+/// Lean probe used while scanning: only reads the fields shown in the file list
+/// (codec, resolution, duration, size, bitrate) from the main video stream.
+/// The full probe is still done when a file is actually processed.
+///
+/// Files that can't be probed come back as an entry with status "Protected" (DRM),
+/// "Still image", "Image sequence" or "Unreadable" and the reason in `probe_error`, so
+/// the list can show them instead of failing later in the encode.
+pub fn get_metadata_quick(path: &str, ffprobe_path: &str) -> Result<VideoInfo, String> {
+    let path = Path::new(path);
+    probe_video_info(path, ffprobe_path, true).or_else(|e| {
+        let status = if e.starts_with(PROTECTED_ERROR_PREFIX) {
+            "Protected"
+        } else if e.starts_with(STILL_IMAGE_PREFIX) {
+            STILL_IMAGE_PREFIX
+        } else if e.starts_with(IMAGE_SEQUENCE_PREFIX) {
+            IMAGE_SEQUENCE_PREFIX
+        } else {
+            "Unreadable"
+        };
+        println!("[WARNING] {} file {}: {}", status, path.display(), e);
+        Ok(VideoInfo {
+            name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            path: path.to_string_lossy().to_string(),
+            size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            resolution: "-".to_string(),
+            bitrate: "-".to_string(),
+            encoder: "-".to_string(),
+            status: status.to_string(),
+            probe_error: Some(e),
+            ..Default::default()
+        })
+    })
+}
+
+/// Probe errors for DRM-protected files start with this
+const PROTECTED_ERROR_PREFIX: &str = "DRM-protected";
+
+/// Probe errors (and statuses) for files that hold one picture, or frames without timing
+const STILL_IMAGE_PREFIX: &str = "Still image";
+const IMAGE_SEQUENCE_PREFIX: &str = "Image sequence";
+
+/// Codecs that store independent pictures rather than video
+const IMAGE_CODECS: &[&str] = &["mjpeg", "png", "bmp", "webp", "tiff", "jpeg2000", "jpegls", "qoi", "gif"];
+
+/// Single-frame files and image streams without a duration (raw MJPEG), which would sit
+/// at 0% forever as a normal job. Image sequences can be encoded with `encode_image_sequence`.
+fn detect_still_image(format: &serde_json::Value, video_stream: &serde_json::Value) -> Option<String> {
+    let codec = video_stream["codec_name"].as_str().unwrap_or("");
+    let frames = video_stream["nb_frames"].as_str().and_then(|n| n.parse::<u64>().ok());
+    let duration = format["duration"].as_str().and_then(|d| d.parse::<f64>().ok()).unwrap_or(0.0);
+    if frames == Some(1) {
+        return Some(format!("{}: a single {} frame", STILL_IMAGE_PREFIX, codec));
+    }
+    if duration <= 0.0 && IMAGE_CODECS.contains(&codec) {
+        return Some(format!("{}: {} frames without timing, encode it with an explicit frame rate", IMAGE_SEQUENCE_PREFIX, codec));
+    }
+    None
+}
+
+/// Sample entry tags of encrypted tracks (FairPlay, Common Encryption)
+const PROTECTED_CODEC_TAGS: &[&str] = &["drms", "drmi", "drac", "encv", "enca"];
+
+/// ffprobe messages (lowercase) that only an encrypted file produces. Not bare "drm" or
+/// "encrypt": those also turn up in file names and in harmless notices.
+const PROTECTED_PROBE_MESSAGES: &[&str] = &[
+    "error decrypting",
+    "encrypted and no key",
+    "drm protected stream detected",
+];
+
+/// Whether ffprobe's stderr or stream tags show the file is encrypted
+fn detect_protection(stderr: &str, streams: &[serde_json::Value]) -> Option<String> {
+    let line = stderr.lines().find(|l| {
+        let l = l.to_lowercase();
+        PROTECTED_PROBE_MESSAGES.iter().any(|m| l.contains(m))
+    });
+    if let Some(line) = line {
+        return Some(format!("{}: {}", PROTECTED_ERROR_PREFIX, line.trim()));
+    }
+    streams.iter()
+        .filter_map(|s| s["codec_tag_string"].as_str())
+        .find(|tag| PROTECTED_CODEC_TAGS.contains(tag))
+        .map(|tag| format!("{}: encrypted '{}' stream", PROTECTED_ERROR_PREFIX, tag))
+}
+
+/// Refuse to start on a source the probe identified as DRM-protected or as a still
+/// image, instead of letting ffmpeg fail part-way with an opaque error (or never finish)
+fn reject_protected_source(app: &AppHandle, input_path: &str, probe: &Result<VideoInfo, String>) -> Result<(), String> {
+    match probe {
+        Err(e) if [PROTECTED_ERROR_PREFIX, STILL_IMAGE_PREFIX, IMAGE_SEQUENCE_PREFIX].iter().any(|p| e.starts_with(p)) => {
+            eprintln!("[ERROR] Not processing '{}': {}", input_path, e);
+            crate::events::emit_progress(app, ProgressPayload {
+                path: input_path.to_string(),
+                progress: 0,
+                status: "Error".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            Err(e.clone())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn get_video_info(path: &Path, ffprobe_path: &str) -> Result<VideoInfo, String> {
+    probe_video_info(path, ffprobe_path, false)
+}
+
+/// Probe through the metadata cache; only successful probes are cached
+fn probe_video_info(path: &Path, ffprobe_path: &str, quick: bool) -> Result<VideoInfo, String> {
+    if let Some(info) = crate::metacache::get(path, !quick) {
+        return Ok(info);
+    }
+    let info = run_ffprobe_info(path, ffprobe_path, quick)?;
+    crate::metacache::put(path, !quick, &info);
+    Ok(info)
+}
+
+fn run_ffprobe_info(path: &Path, ffprobe_path: &str, quick: bool) -> Result<VideoInfo, String> {
+    let path_str = path.to_str().ok_or("Invalid path")?;
+    let mut command = crate::process::ffprobe_command(ffprobe_path);
+    if quick {
+        command.args([
+            "-v", "warning",
+            "-print_format", "json",
+            "-show_entries", "format=size,duration,bit_rate:stream=codec_type,codec_name,codec_tag_string,width,height,avg_frame_rate,nb_frames,bit_rate,pix_fmt,bits_per_raw_sample,color_transfer,field_order,channels,channel_layout:stream_tags=language,title,rotate:stream_side_data=rotation:stream_disposition=default,attached_pic,timed_thumbnails,forced",
+            path_str,
+        ]);
+    } else {
+        command.args([
+            "-v", "warning",
             "-print_format", "json",
             "-show_format",
             "-show_streams",
-            path.to_str().ok_or("Invalid path")?,
+            path_str,
         ]);
-
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
     }
 
+
     let output = command.output()
         .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
 
+    // Drop the path so a file name can't look like an error pattern
+    let stderr = String::from_utf8_lossy(&output.stderr).replace(path_str, "");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let parsed: Option<serde_json::Value> = serde_json::from_str(&stdout).ok();
+    let no_streams = Vec::new();
+    let probed_streams = parsed.as_ref()
+        .and_then(|p| p.get("streams"))
+        .and_then(|v| v.as_array())
+        .unwrap_or(&no_streams);
+    if let Some(reason) = detect_protection(&stderr, probed_streams) {
+        return Err(reason);
+    }
+
     if !output.status.success() {
-        return Err(format!("ffprobe exited with status: {:?}", output.status));
+        let detail = stderr.lines().last().unwrap_or("").trim();
+        return Err(format!("ffprobe exited with status: {:?} {}", output.status, detail));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let parsed: serde_json::Value = serde_json::from_str(&stdout)
-        .map_err(|e| format!("Failed to parse ffprobe json: {}", e))?;
+    let parsed = parsed.ok_or("Failed to parse ffprobe json")?;
 
     let format = parsed.get("format").ok_or("No format info")?;
     let streams = parsed.get("streams").and_then(|v| v.as_array()).ok_or("No streams info")?;
@@ -472,10 +1429,16 @@ fn get_video_info(path: &Path, ffprobe_path: &str) -> Result<VideoInfo, String>
         .and_then(|s| s.parse().ok())
         .unwrap_or(0);
     
-    // Find video stream
-    let video_stream = streams.iter().find(|s| {
-        s["codec_type"].as_str() == Some("video")
-    }).ok_or("No video stream found")?;
+    // Find the main video stream
+    let video_streams: Vec<&serde_json::Value> = streams.iter()
+        .filter(|s| s["codec_type"].as_str() == Some("video"))
+        .collect();
+    let main_index = select_main_video_stream(&video_streams).ok_or("No video stream found")?;
+    let video_stream = video_streams[main_index];
+    let multi_stream = video_streams.len() > 1;
+    if let Some(reason) = detect_still_image(format, video_stream) {
+        return Err(reason);
+    }
 
     let width = video_stream["width"].as_u64().unwrap_or(0);
     let height = video_stream["height"].as_u64().unwrap_or(0);
@@ -496,6 +1459,61 @@ fn get_video_info(path: &Path, ffprobe_path: &str) -> Result<VideoInfo, String>
         .unwrap_or("unknown")
         .to_string();
 
+    let fps = video_stream["avg_frame_rate"].as_str().and_then(|r| {
+        let (num, den) = r.split_once('/')?;
+        let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+        if num > 0.0 && den > 0.0 { Some(num / den) } else { None }
+    });
+    let pix_fmt = video_stream["pix_fmt"].as_str().map(|p| p.to_string());
+    let bit_depth = video_stream["bits_per_raw_sample"].as_str()
+        .and_then(|b| b.parse::<u32>().ok())
+        .or_else(|| pix_fmt.as_deref().map(bit_depth_of_pix_fmt));
+    let color_transfer = video_stream["color_transfer"].as_str().map(|t| t.to_string());
+    let hdr = matches!(color_transfer.as_deref(), Some("smpte2084") | Some("arib-std-b67"));
+    let field_order = video_stream["field_order"].as_str().map(|f| f.to_string());
+    let rotation = display_rotation(video_stream);
+    let duration_sec: f64 = format["duration"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0.0);
+    let frame_count = video_stream["nb_frames"].as_str()
+        .and_then(|n| n.parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .or_else(|| fps.filter(|_| duration_sec > 0.0).map(|f| (duration_sec * f).round() as u64));
+    // The stream's own bitrate when the container records it (MP4 does, MKV usually not)
+    let video_kbps = video_stream["bit_rate"].as_str()
+        .and_then(|b| b.parse::<f64>().ok())
+        .map(|b| b / 1000.0)
+        .or(bitrate_kbps);
+    let bits_per_pixel = match (video_kbps, fps) {
+        (Some(kbps), Some(fps)) if width > 0 && height > 0 => Some(kbps * 1000.0 / (width as f64 * height as f64 * fps)),
+        _ => None,
+    };
+    let language = |s: &serde_json::Value| s["tags"]["language"].as_str()
+        .filter(|l| !l.is_empty() && *l != "und")
+        .map(|l| l.to_string());
+    let audio_tracks: Vec<AudioTrack> = streams.iter()
+        .filter(|s| s["codec_type"].as_str() == Some("audio"))
+        .map(|s| AudioTrack {
+            codec: s["codec_name"].as_str().unwrap_or("unknown").to_string(),
+            channel_layout: s["channel_layout"].as_str()
+                .map(|l| l.to_string())
+                .unwrap_or_else(|| format!("{} channels", s["channels"].as_u64().unwrap_or(0))),
+            channels: s["channels"].as_u64().unwrap_or(0) as u32,
+            language: language(s),
+            bitrate_kbps: s["bit_rate"].as_str().and_then(|b| b.parse::<f64>().ok()).map(|b| b / 1000.0),
+        })
+        .collect();
+    let subtitle_tracks: Vec<SubtitleTrack> = streams.iter()
+        .filter(|s| s["codec_type"].as_str() == Some("subtitle"))
+        .map(|s| SubtitleTrack {
+            codec: s["codec_name"].as_str().unwrap_or("unknown").to_string(),
+            language: language(s),
+            title: s["tags"]["title"].as_str().map(|t| t.to_string()),
+            forced: s["disposition"]["forced"].as_i64() == Some(1),
+        })
+        .collect();
+
     Ok(VideoInfo {
         name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
         path: path.to_string_lossy().to_string(),
@@ -504,21 +1522,307 @@ fn get_video_info(path: &Path, ffprobe_path: &str) -> Result<VideoInfo, String>
         bitrate,
         encoder: codec,
         status: "Pending".to_string(),
-        progress: 0,
-        duration_sec: format["duration"]
-            .as_str()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(0.0),
-        speed: None,
+        duration_sec,
         bitrate_kbps,
-        vmaf: None,
-        vmaf_device: None,
-        vmaf_detail: None,
-        vmaf_total_segments: None,
-        vmaf_model: None,
+        video_stream_count: if multi_stream { Some(video_streams.len() as u32) } else { None },
+        main_video_stream: if multi_stream { Some(main_index as u32) } else { None },
+        fps,
+        pix_fmt,
+        bit_depth,
+        hdr: Some(hdr),
+        color_transfer,
+        field_order,
+        rotation,
+        frame_count,
+        audio_tracks: Some(audio_tracks),
+        subtitle_tracks: Some(subtitle_tracks),
+        bits_per_pixel,
+        ..Default::default()
     })
 }
 
+/// Clockwise display rotation of a video stream: the display matrix side data (which
+/// counts counterclockwise), else the older `rotate` tag. None when upright.
+fn display_rotation(stream: &serde_json::Value) -> Option<u32> {
+    let from_matrix = stream["side_data_list"].as_array()
+        .and_then(|list| list.iter().find_map(|d| d["rotation"].as_f64()))
+        .map(|ccw| -ccw);
+    let degrees = from_matrix.or_else(|| stream["tags"]["rotate"].as_str().and_then(|r| r.parse::<f64>().ok()))?;
+    let clockwise = ((degrees.round() as i64).rem_euclid(360) as u32 + 45) / 90 * 90 % 360;
+    (clockwise != 0).then_some(clockwise)
+}
+
+/// Bits per component from a pixel format name: "yuv420p10le" -> 10, "p016le" -> 16,
+/// "y210le" -> 10, else 8
+fn bit_depth_of_pix_fmt(pix_fmt: &str) -> u32 {
+    let name = pix_fmt.trim_end_matches("le").trim_end_matches("be");
+    match name {
+        "rgb48" | "bgr48" | "rgba64" | "bgra64" => return 16,
+        "nv20" | "xv30" => return 10,
+        "xv36" => return 12,
+        // nv12, nv16, nv21, nv24, nv42: the digits name the chroma layout
+        _ if name.starts_with("nv") => return 8,
+        _ => {}
+    }
+    let digits: String = name.chars().rev().take_while(|c| c.is_ascii_digit()).collect::<Vec<_>>().into_iter().rev().collect();
+    // p010, p216, y210, ...: the layout comes first, the depth is the last two digits
+    let depth = if digits.len() == 3 { &digits[1..] } else { digits.as_str() };
+    match depth.parse::<u32>() {
+        Ok(d) if (9..=16).contains(&d) => d,
+        _ => 8,
+    }
+}
+
+/// Pick the stream that is the actual video among `video_streams` (in ffmpeg `v:N` order):
+/// cover art and thumbnail streams are skipped, then the largest resolution wins, preferring
+/// the default-disposition stream on ties. Falls back to the first stream.
+fn select_main_video_stream(video_streams: &[&serde_json::Value]) -> Option<usize> {
+    if video_streams.is_empty() {
+        return None;
+    }
+    let is_flagged = |s: &serde_json::Value, key: &str| s["disposition"][key].as_i64() == Some(1);
+    video_streams.iter()
+        .enumerate()
+        .filter(|(_, s)| !is_flagged(s, "attached_pic") && !is_flagged(s, "timed_thumbnails"))
+        .max_by_key(|(i, s)| {
+            let area = s["width"].as_u64().unwrap_or(0) * s["height"].as_u64().unwrap_or(0);
+            // Earlier streams win remaining ties
+            (area, is_flagged(s, "default"), std::cmp::Reverse(*i))
+        })
+        .map(|(i, _)| i)
+        .or(Some(0))
+}
+
+/// "percent" mode as "bitrate" mode with this file's target: the percentage of the source's
+/// overall bitrate (audio included, so outputs with re-encoded audio land a little under)
+fn with_size_percent(mut config: CompressionConfig, info: Option<&VideoInfo>) -> Result<CompressionConfig, String> {
+    if config.compression_mode != "percent" {
+        return Ok(config);
+    }
+    let percent = if config.target_size_percent == 0 { 60 } else { config.target_size_percent.min(100) };
+    let source_kbps = info.and_then(|i| i.bitrate_kbps)
+        .ok_or("Percent mode needs the source bitrate, which could not be read")?;
+    config.compression_mode = "bitrate".to_string();
+    config.target_bitrate = ((source_kbps * percent as f64 / 100.0).round() as u32).max(1);
+    println!("[INFO] Reducing to {}% of the source: {:.0} kbps -> {} kbps", percent, source_kbps, config.target_bitrate);
+    Ok(config)
+}
+
+/// Fix the video stream for this file in the config: the user's choice if set, otherwise
+/// the detected main stream when the file has several. Single-stream files are left alone.
+fn with_video_stream(mut config: CompressionConfig, info: Option<&VideoInfo>) -> CompressionConfig {
+    if config.video_stream_index.is_none() {
+        config.video_stream_index = info.and_then(|i| i.main_video_stream);
+    }
+    config
+}
+
+pub fn user_maps_streams(config: &CompressionConfig) -> bool {
+    config.custom_filters.iter().any(|f| f.split_whitespace().any(|t| t == "-map"))
+}
+
+/// Explicit `-map` for the selected video stream alone (sample encodes). Empty when no
+/// stream was selected or the user maps streams in their custom filters.
+fn video_map_args(config: &CompressionConfig) -> Vec<String> {
+    match config.video_stream_index {
+        Some(index) if !user_maps_streams(config) => vec!["-map".to_string(), format!("0:v:{}", index)],
+        _ => Vec::new(),
+    }
+}
+
+/// `-map` arguments of the final encode: the selected video stream, the audio tracks
+/// chosen by `audio_mapping`, the subtitle tracks of `subtitles` (see subtitle_plan) and,
+/// with `attachments`, the attachment streams. Empty (ffmpeg's own pick) when none of
+/// them is set or the user maps streams in their custom filters.
+fn output_map_args(config: &CompressionConfig, info: Option<&VideoInfo>, subtitles: &[(usize, String)], attachments: bool) -> Vec<String> {
+    if user_maps_streams(config) {
+        return Vec::new();
+    }
+    let explicit = config.video_stream_index.is_some() || !subtitles.is_empty() || attachments;
+    let audio = match config.audio_mapping.as_str() {
+        "all" => vec!["0:a?".to_string()],
+        "first" => vec!["0:a:0?".to_string()],
+        "languages" => language_audio_maps(config, info),
+        // Explicit maps for the other streams would otherwise drop the audio
+        _ if explicit => vec!["0:a?".to_string()],
+        _ => return Vec::new(),
+    };
+    let mut args = vec!["-map".to_string(), format!("0:v:{}", config.video_stream_index.unwrap_or(0))];
+    for map in audio {
+        args.push("-map".to_string());
+        args.push(map);
+    }
+    for (index, _) in subtitles {
+        args.push("-map".to_string());
+        args.push(format!("0:s:{}", index));
+    }
+    if attachments {
+        // The fonts are there for the subtitles, which ffmpeg's own pick would have kept
+        if config.subtitle_mode.is_empty() {
+            args.extend(["-map".to_string(), "0:s?".to_string()]);
+        }
+        args.extend(["-map".to_string(), "0:t?".to_string()]);
+    }
+    args
+}
+
+/// Source audio tracks (`0:a:N` indices) in the order output_map_args puts them into the
+/// output, for per-track audio filters. None when that isn't known: the file wasn't probed
+/// or the user maps streams in their custom filters.
+fn output_audio_tracks(config: &CompressionConfig, info: Option<&VideoInfo>, subtitles: &[(usize, String)], attachments: bool) -> Option<Vec<usize>> {
+    if user_maps_streams(config) {
+        return None;
+    }
+    let tracks = info?.audio_tracks.as_ref()?;
+    let explicit = config.video_stream_index.is_some() || !subtitles.is_empty() || attachments;
+    let tracks = match config.audio_mapping.as_str() {
+        "first" => (0..tracks.len().min(1)).collect(),
+        "languages" => language_audio_maps(config, info).iter()
+            .filter_map(|m| m.strip_prefix("0:a:")?.parse().ok())
+            .collect(),
+        "all" => (0..tracks.len()).collect(),
+        _ if explicit => (0..tracks.len()).collect(),
+        // ffmpeg's own pick: the track with the most channels, the first of those
+        _ => tracks.iter().enumerate().rev().max_by_key(|(_, t)| t.channels).map(|(i, _)| i).into_iter().collect(),
+    };
+    Some(tracks)
+}
+
+/// `-map_metadata`/`-map_chapters` for `metadata_mode`. Nothing when the user's custom
+/// filters already decide about metadata.
+fn metadata_args(config: &CompressionConfig) -> Vec<String> {
+    let user_set = config.custom_filters.iter()
+        .any(|f| f.split_whitespace().any(|t| t == "-map_metadata" || t == "-map_chapters"));
+    let args: &[&str] = match config.metadata_mode.as_str() {
+        _ if user_set => &[],
+        "keep" => &["-map_metadata", "0", "-map_chapters", "0"],
+        "strip" => &["-map_metadata", "-1", "-map_chapters", "-1", "-fflags", "+bitexact"],
+        _ => &[],
+    };
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// `subtitles=`/`ass=` filter for `burn_in_subtitle`. Fails for a missing file or stream and
+/// for bitmap subtitle streams (PGS, DVD), which these filters can't render.
+fn burn_in_filter(config: &CompressionConfig, input_path: &str, info: Option<&VideoInfo>) -> Result<Option<String>, String> {
+    let Some(burn_in) = &config.burn_in_subtitle else { return Ok(None) };
+    let file = burn_in.file.trim();
+    if !file.is_empty() {
+        if !Path::new(file).is_file() {
+            return Err(format!("Subtitle file to burn in not found: {}", file));
+        }
+        let is_ass = Path::new(file).extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("ass") || e.eq_ignore_ascii_case("ssa"));
+        let filter = if is_ass { "ass" } else { "subtitles" };
+        return Ok(Some(format!("{}='{}'", filter, escape_path_for_filter(file))));
+    }
+
+    let index = burn_in.stream_index;
+    if let Some(tracks) = info.and_then(|i| i.subtitle_tracks.as_ref()) {
+        let track = tracks.get(index as usize)
+            .ok_or_else(|| format!("No subtitle stream s:{} to burn in ({} found)", index, tracks.len()))?;
+        if !TEXT_SUBTITLE_CODECS.contains(&track.codec.as_str()) {
+            return Err(format!("Burning in {} (bitmap) subtitles is not supported; pick a text subtitle stream", track.codec));
+        }
+    }
+    Ok(Some(format!("subtitles='{}':si={}", escape_path_for_filter(input_path), index)))
+}
+
+/// Subtitle codecs that are plain or styled text and can be converted to another text format
+pub const TEXT_SUBTITLE_CODECS: &[&str] = &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+
+/// Whether `format` can take a `codec` subtitle stream as it is
+pub fn container_holds_subtitle(format: &str, codec: &str) -> bool {
+    match format {
+        "mkv" => codec != "mov_text",
+        "mp4" | "mov" | "m4v" => codec == "mov_text",
+        "webm" => codec == "webvtt",
+        _ => false,
+    }
+}
+
+/// Text subtitle codec of `format`, for the "convert" mode
+pub fn container_text_subtitle(format: &str) -> Option<&'static str> {
+    match format {
+        "mkv" => Some("srt"),
+        "mp4" | "mov" | "m4v" => Some("mov_text"),
+        "webm" => Some("webvtt"),
+        _ => None,
+    }
+}
+
+/// Subtitle tracks of the final encode for `subtitle_mode` "copy"/"convert": input `s:N`
+/// index and output codec ("copy" or the conversion target). Tracks the container can't
+/// take (PGS in MP4, anything in AVI) are left out and logged.
+fn subtitle_plan(config: &CompressionConfig, info: Option<&VideoInfo>, format: &str) -> Vec<(usize, String)> {
+    if !matches!(config.subtitle_mode.as_str(), "copy" | "convert") || user_maps_streams(config) {
+        return Vec::new();
+    }
+    let Some(tracks) = info.and_then(|i| i.subtitle_tracks.as_ref()) else { return Vec::new() };
+    let convert_to = container_text_subtitle(format).filter(|_| config.subtitle_mode == "convert");
+    tracks.iter()
+        .enumerate()
+        .filter_map(|(i, track)| {
+            if container_holds_subtitle(format, &track.codec) {
+                return Some((i, "copy".to_string()));
+            }
+            match convert_to {
+                Some(target) if TEXT_SUBTITLE_CODECS.contains(&track.codec.as_str()) => Some((i, target.to_string())),
+                _ => {
+                    println!("[INFO] Dropping {} subtitle track {}: {} can't hold it", track.codec, i, format);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// `-c:s` per planned subtitle track, or `-sn` when subtitles are dropped or none can be
+/// kept. Nothing when the user maps streams themselves.
+fn subtitle_codec_args(config: &CompressionConfig, subtitles: &[(usize, String)]) -> Vec<String> {
+    if user_maps_streams(config) {
+        return Vec::new();
+    }
+    match config.subtitle_mode.as_str() {
+        "drop" => vec!["-sn".to_string()],
+        "copy" | "convert" if subtitles.is_empty() => vec!["-sn".to_string()],
+        _ => subtitles.iter()
+            .enumerate()
+            .flat_map(|(k, (_, codec))| [format!("-c:s:{}", k), codec.clone()])
+            .collect(),
+    }
+}
+
+/// Audio maps for the "languages" mapping, in the order of `audio_languages`
+fn language_audio_maps(config: &CompressionConfig, info: Option<&VideoInfo>) -> Vec<String> {
+    let wanted: Vec<String> = config.audio_languages.iter()
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let Some(tracks) = info.and_then(|i| i.audio_tracks.as_ref()) else {
+        // Not probed: let ffmpeg match the language tags. Whether any of them matches isn't
+        // known, so the first track is kept too rather than risk an output without audio.
+        println!("[INFO] Audio tracks not probed, keeping the first track besides {}", wanted.join(", "));
+        let mut maps: Vec<String> = wanted.iter().map(|l| format!("0:a:m:language:{}?", l)).collect();
+        maps.push("0:a:0?".to_string());
+        return maps;
+    };
+    let mut maps = Vec::new();
+    for language in &wanted {
+        for (i, track) in tracks.iter().enumerate() {
+            if track.language.as_deref().is_some_and(|l| l.eq_ignore_ascii_case(language)) {
+                maps.push(format!("0:a:{}", i));
+            }
+        }
+    }
+    if maps.is_empty() && !tracks.is_empty() {
+        println!("[INFO] No audio track in {}, keeping the first one", wanted.join(", "));
+        maps.push("0:a:0".to_string());
+    }
+    maps
+}
+
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProgressPayload {
@@ -528,6 +1832,8 @@ pub struct ProgressPayload {
     pub speed: f64,
     pub bitrate_kbps: f64,
     pub output_info: Option<VideoInfo>,
+    pub monotonic_ms: u64, // monitor::monotonic_ms() at emit time, same clock as system-stats
+    pub vmaf_segment: Option<(u32, u32)>, // (segment, of segments), 1-based, while VMAF scores the output
 }
 
 /// Payload for VMAF-guided CRF search progress
@@ -559,7 +1865,7 @@ fn parse_time_str(time_str: &str) -> f64 {
 // --- VMAF-guided CRF Search Functions ---
 
 /// Get CRF range for an encoder
-fn get_crf_range(encoder: &str) -> (f32, f32) {
+pub fn get_crf_range(encoder: &str) -> (f32, f32) {
     if encoder.contains("libx264") || encoder.contains("libx265") {
         (18.0, 46.0) // H.264/H.265 CRF range
     } else if encoder.contains("libsvtav1") {
@@ -568,6 +1874,10 @@ fn get_crf_range(encoder: &str) -> (f32, f32) {
         (18.0, 42.0) // CQ range for NVENC
     } else if encoder.contains("vp9") || encoder.contains("libvpx") {
         (18.0, 42.0) // VP9 CRF range
+    } else if encoder.contains("videotoolbox") {
+        (20.0, 65.0) // VideoToolbox -q:v 80..35, see get_crf_value
+    } else if encoder.contains("vaapi") || encoder.contains("qsv") {
+        (18.0, 42.0) // VAAPI -qp / QSV -global_quality (ICQ)
     } else {
         (1.0, 50.0) // Generic default
     }
@@ -577,16 +1887,32 @@ fn get_crf_range(encoder: &str) -> (f32, f32) {
 fn get_crf_arg(encoder: &str) -> &'static str {
     if encoder.contains("nvenc") {
         "-cq"
+    } else if encoder.contains("vaapi") {
+        "-qp"
+    } else if encoder.contains("qsv") {
+        "-global_quality"
     } else if encoder.contains("libx264") || encoder.contains("libx265") || 
               encoder.contains("libsvtav1") || encoder.contains("vp9") || encoder.contains("libvpx") {
         "-crf"
     } else {
-        "-q:v"
+        "-q:v" // AMF, QSV, VideoToolbox (see get_crf_value)
     }
 }
 
-/// Compute sample segments for VMAF analysis during CRF search
-/// Returns (start_time, duration) pairs for segments to sample
+/// Value for get_crf_arg. The search treats higher as smaller/worse, but VideoToolbox's
+/// -q:v runs the other way (1-100, higher = better), so its value is 100 - crf.
+fn get_crf_value(encoder: &str, crf: f32) -> String {
+    if encoder.contains("videotoolbox") {
+        format!("{}", (100.0 - crf).round().clamp(1.0, 100.0))
+    } else {
+        format!("{}", crf)
+    }
+}
+
+/// Compute sample segments for VMAF analysis (CRF search and post-encode evaluation)
+/// Returns (start_time, duration) pairs for segments to sample.
+/// In adaptive mode the list is the full candidate pool, ordered so that the
+/// first `adaptive_initial_segments` entries are spread evenly over the video.
 fn compute_sample_segments(duration_sec: f64, config: &CompressionConfig) -> Vec<(f64, f64)> {
     if duration_sec < 20.0 {
         // Short video: use full duration
@@ -607,6 +1933,25 @@ fn compute_sample_segments(duration_sec: f64, config: &CompressionConfig) -> Vec
         if count < 1 { count = 1; }
     }
 
+    if config.vmaf_adaptive_segments {
+        let initial = config.adaptive_initial_segments();
+        let max = if config.vmaf_adaptive_max_segments == 0 {
+            (initial * 3).max(count)
+        } else {
+            config.vmaf_adaptive_max_segments.max(initial)
+        };
+        let pool = spread_segments(duration_sec, max, dur);
+        return adaptive_segment_order(pool.len(), initial as usize)
+            .into_iter()
+            .map(|i| pool[i])
+            .collect();
+    }
+
+    spread_segments(duration_sec, count, dur)
+}
+
+/// Place `count` segments of `dur` seconds evenly over the video, with a small pseudo-random jitter
+fn spread_segments(duration_sec: f64, count: u32, dur: f64) -> Vec<(f64, f64)> {
     let mut segments = Vec::new();
     let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_micros();
     
@@ -632,6 +1977,110 @@ fn compute_sample_segments(duration_sec: f64, config: &CompressionConfig) -> Vec
     segments
 }
 
+/// Order pool indices so the first `initial` picks are evenly spaced across the pool,
+/// followed by the remaining indices in timeline order
+fn adaptive_segment_order(pool_len: usize, initial: usize) -> Vec<usize> {
+    let initial = initial.min(pool_len);
+    let mut order: Vec<usize> = Vec::with_capacity(pool_len);
+
+    if initial == 1 {
+        order.push(pool_len / 2);
+    } else if initial > 1 {
+        for i in 0..initial {
+            let idx = ((i * (pool_len - 1)) as f64 / (initial - 1) as f64).round() as usize;
+            if !order.contains(&idx) {
+                order.push(idx);
+            }
+        }
+    }
+
+    for i in 0..pool_len {
+        if !order.contains(&i) {
+            order.push(i);
+        }
+    }
+    order
+}
+
+/// Adaptive sampling stop rule: keep adding segments while the initial batch is
+/// incomplete or the variance of the scores so far exceeds the configured threshold
+fn needs_more_segments(scores: &[f64], config: &CompressionConfig) -> bool {
+    if !config.vmaf_adaptive_segments {
+        return true;
+    }
+    if scores.len() < config.adaptive_initial_segments() as usize {
+        return true;
+    }
+
+    let n = scores.len() as f64;
+    let mean = scores.iter().sum::<f64>() / n;
+    let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+    variance > config.adaptive_variance_threshold()
+}
+
+/// Seconds of pre-roll decoded (and discarded) before an accurately seeked segment
+const ACCURATE_SEEK_PREROLL: f64 = 5.0;
+
+/// Where a sample segment is read from `input_path`.
+/// Fast: whole-second `-ss`/`-t` on the input. Accurate: input-seek to a pre-roll point a
+/// few seconds earlier, then decode-trim the remaining `offset` with exact timestamps.
+struct SampleSeek {
+    accurate: bool,
+    input_args: Vec<String>, // Placed before `-i`
+    offset: f64,             // Decode-trim still to apply after the input (0 in fast mode)
+    duration: f64,
+}
+
+impl SampleSeek {
+    fn new(segment_start: f64, segment_duration: f64, accurate: bool) -> Self {
+        if accurate {
+            let preroll_start = (segment_start - ACCURATE_SEEK_PREROLL).max(0.0);
+            SampleSeek {
+                accurate: true,
+                input_args: vec![
+                    "-accurate_seek".to_string(),
+                    "-ss".to_string(), format!("{:.3}", preroll_start),
+                ],
+                offset: segment_start - preroll_start,
+                duration: segment_duration,
+            }
+        } else {
+            // Round timestamps to integers to avoid frame misalignment
+            let ss = segment_start.round() as i64;
+            let t = segment_duration.round() as i64;
+            SampleSeek {
+                accurate: false,
+                input_args: vec![
+                    "-ss".to_string(), ss.to_string(),
+                    "-t".to_string(), t.to_string(),
+                ],
+                offset: 0.0,
+                duration: t as f64,
+            }
+        }
+    }
+
+    /// Output options trimming the decoded stream to the segment (accurate mode only)
+    fn output_args(&self) -> Vec<String> {
+        if !self.accurate {
+            return Vec::new();
+        }
+        vec![
+            "-ss".to_string(), format!("{:.3}", self.offset),
+            "-t".to_string(), format!("{:.3}", self.duration),
+        ]
+    }
+
+    /// Filter prefix doing the same trim inside a filtergraph, e.g. for the VMAF reference
+    fn trim_filter(&self) -> String {
+        if self.accurate {
+            format!("trim=start={:.3}:duration={:.3},", self.offset, self.duration)
+        } else {
+            String::new()
+        }
+    }
+}
+
 /// Compress a sample segment with a specific CRF and return the output path
 fn compress_sample_with_crf(
     ffmpeg_path: &str,
@@ -643,7 +2092,8 @@ fn compress_sample_with_crf(
     config: &CompressionConfig,
     pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
     input_key: &str,
-) -> Option<String> {
+    source_filters: &[String],
+) -> Result<String, String> {
     let sample_id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_micros();
     // Use the original video's container format (extension) for sample segments
     // This ensures compatibility and avoids format-related issues during VMAF calculation
@@ -657,74 +2107,135 @@ fn compress_sample_with_crf(
     let v_enc = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
     let crf_arg = get_crf_arg(&v_enc);
 
-    // Round timestamps to integers to avoid frame misalignment
-    let ss = segment_start.round() as i64;
-    let t = segment_duration.round() as i64;
+    let seek = SampleSeek::new(segment_start, segment_duration, config.accurate_seek);
 
     let mut args = vec![
         "-y".to_string(),
         "-hide_banner".to_string(),
         "-v".to_string(), "error".to_string(),
-        "-ss".to_string(), ss.to_string(),
-        "-t".to_string(), t.to_string(),
-        "-i".to_string(), input_path.to_string(),
+    ];
+    args.extend(hw_device_args(&v_enc));
+    args.extend(forced_decoder_args(config));
+    args.extend(seek.input_args.iter().cloned());
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+    args.extend(video_map_args(config));
+    args.extend(seek.output_args());
+    // The final encode sees the source through these, so the sample has to as well
+    if !source_filters.is_empty() {
+        args.push("-vf".to_string());
+        args.push(source_filters.join(","));
+    }
+    args.extend([
         "-c:v".to_string(), v_enc.clone(),
-        crf_arg.to_string(), format!("{}", crf),
+        crf_arg.to_string(), get_crf_value(&v_enc, crf),
         "-an".to_string(), // No audio for sample
-    ];
+    ]);
 
-    // Add encoder-specific params
-    if let Some(enc_cfg) = config.available_video_encoders.iter().find(|e| e.value == v_enc) {
-        for param in &enc_cfg.custom_params {
-            let parts: Vec<&str> = param.split_whitespace().collect();
-            for p in parts {
-                args.push(p.to_string());
-            }
+    // Content-based tuning (analyzed once at the start of the search)
+    if config.auto_tune {
+        if let Some(content) = crate::tuning::cached_content_type(input_path) {
+            args.extend(crate::tuning::auto_tune_args(content, &v_enc));
         }
     }
 
-    args.push(sample_output_str.clone());
+    // Add encoder-specific params; samples are constant quality whatever the final mode
+    args.extend(crate::nvenc::args(config.nvenc.as_ref(), &v_enc, "crf", config.auto_tune));
+    args.extend(nvenc_gpu_args(&v_enc));
+    args.extend(video_encoder_params(config, &v_enc));
 
-    let mut command = Command::new(ffmpeg_path);
-    command.args(&args).stdout(Stdio::null()).stderr(Stdio::piped());
-    
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
+    if config.eco_mode {
+        args.push("-threads".to_string());
+        args.push(config.effective_ffmpeg_threads().to_string());
     }
 
+    if let Some(preset) = sample_preset_override(config, &v_enc) {
+        args = with_preset(args, preset);
+    }
+
+    let mut args = crate::gpuquirks::with_bframe_quirk(config, &v_enc, with_hw_upload(&v_enc, args));
+    args.push(sample_output_str.clone());
+
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(&args).stdout(Stdio::null()).stderr(Stdio::piped());
+    
+
     let child = match command.spawn() {
         Ok(c) => c,
         Err(e) => {
             println!("Failed to spawn sample compression: {}", e);
-            return None;
+            return Err(e.to_string());
         }
     };
 
     let pid = child.id();
     {
         if let Ok(mut map) = pids.lock() {
-            map.insert(input_key.to_string(), pid);
+            map.insert(job_key(input_key), pid);
         }
     }
+    if config.eco_mode {
+        crate::process::lower_priority(pid);
+    }
 
     let output = child.wait_with_output();
     
     {
         if let Ok(mut map) = pids.lock() {
-            map.remove(input_key);
+            map.remove(&job_key(input_key));
         }
     }
 
     match output {
-        Ok(o) if o.status.success() => Some(sample_output_str),
-        _ => {
+        Ok(o) if o.status.success() => Ok(sample_output_str),
+        result => {
             // Cleanup failed attempt
-            let _ = std::fs::remove_file(&sample_output);
-            None
+            crate::scratch::remove_scratch_file(&sample_output, config.secure_temp_files);
+            Err(match result {
+                Ok(o) => String::from_utf8_lossy(&o.stderr).into_owned(),
+                Err(e) => e.to_string(),
+            })
+        }
+    }
+}
+
+/// Encode the sample segments at `crf` and return the resulting video bitrate in kbps.
+/// Used by "vmaf_abr" mode to turn the CRF found by the VMAF search into an ABR target.
+fn measure_sample_bitrate(
+    ffmpeg_path: &str,
+    input_path: &str,
+    crf: f32,
+    duration_sec: f64,
+    config: &CompressionConfig,
+    pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    source_filters: &[String],
+) -> Option<u32> {
+    let temp_dir = crate::scratch::scratch_dir(config.secure_temp_files).ok()?;
+    // A few segments are enough to estimate the bitrate
+    let segments: Vec<(f64, f64)> = compute_sample_segments(duration_sec, config).into_iter().take(3).collect();
+
+    let mut total_bits = 0.0;
+    let mut total_duration = 0.0;
+
+    for (seg_start, seg_duration) in segments {
+        let sample_path = compress_sample_with_crf(
+            ffmpeg_path, input_path, &temp_dir, crf, seg_start, seg_duration, config, pids, input_path, source_filters
+        ).ok()?;
+        let size = std::fs::metadata(&sample_path).map(|m| m.len()).unwrap_or(0);
+        crate::scratch::remove_scratch_file(Path::new(&sample_path), config.secure_temp_files);
+        if size == 0 {
+            return None;
         }
+        total_bits += size as f64 * 8.0;
+        total_duration += SampleSeek::new(seg_start, seg_duration, config.accurate_seek).duration.max(1.0);
     }
+
+    if total_duration <= 0.0 {
+        return None;
+    }
+    let kbps = (total_bits / total_duration / 1000.0).round() as u32;
+    println!("Measured sample bitrate at CRF {:.1}: {} kbps", crf, kbps);
+    Some(kbps.max(1))
 }
 
 /// Run VMAF for a sample pair and return the score
@@ -738,19 +2249,27 @@ fn compute_sample_vmaf(
     segment_start: f64,
     segment_duration: f64,
     use_cuda: bool,
+    accurate_seek: bool,
     pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
     input_key: &str,
-    custom_vmaf_params: &[String],
-) -> Option<f64> {
-    // Round timestamps to integers to avoid frame misalignment
-    let ss = segment_start.round() as i64;
-    let t = segment_duration.round() as i64;
+    vmaf_params: &[String],
+    ref_stream: u32,
+    force_decoder: &str,
+    secure_temp_files: bool,
+    ref_filters: &[String],
+) -> Option<Vec<f64>> {
+    // Must read the reference exactly like compress_sample_with_crf read the source
+    let seek = SampleSeek::new(segment_start, segment_duration, accurate_seek);
     
     let model_esc = escape_path_for_filter(model_path);
+    // Per-frame scores for the pooling methods other than the mean
+    let log_path = crate::scratch::scratch_file(secure_temp_files, "vmaf_sample_log", "json")
+        .map_err(|e| eprintln!("[WARNING] {}", e))
+        .ok()?;
     
     // Build vmaf_opts with custom params
-    let mut vmaf_opts = format!("model='path={}'", model_esc);
-    for param in custom_vmaf_params {
+    let mut vmaf_opts = format!("model='path={}':log_fmt=json:log_path='{}'", model_esc, escape_path_for_filter(&log_path.to_string_lossy()));
+    for param in vmaf_params {
         let trimmed = param.trim();
         if !trimmed.is_empty() {
             vmaf_opts.push(':');
@@ -767,43 +2286,30 @@ fn compute_sample_vmaf(
     
     // Input 0: Sample (distorted) - already trimmed, read from start
     if use_cuda {
-        args.push("-hwaccel".to_string()); args.push("cuda".to_string());
-        args.push("-hwaccel_output_format".to_string()); args.push("cuda".to_string());
+        args.extend(cuda_hwaccel_args());
     }
     args.push("-i".to_string());
     args.push(sample_path.to_string());
 
     // Input 1: Reference - apply -ss and -t to match the sample segment
-    if use_cuda {
-        let ref_info = get_metadata(reference_path, ffprobe_path);
-        let mut ref_decoder = None;
-        if let Ok(info) = ref_info {
-            ref_decoder = get_cuda_decoder(&info.encoder);
-        }
-        
-        args.push("-hwaccel".to_string()); args.push("cuda".to_string());
-        args.push("-hwaccel_output_format".to_string()); args.push("cuda".to_string());
-        
-        if let Some(dec) = ref_decoder {
-            args.push("-c:v".to_string()); args.push(dec.to_string());
-        }
-    }
+    let (ref_decode_args, ref_upload) = reference_input(ffprobe_path, reference_path, use_cuda, force_decoder, ref_filters);
+    args.extend(ref_decode_args);
     
-    args.push("-ss".to_string()); args.push(ss.to_string());
-    args.push("-t".to_string()); args.push(t.to_string());
+    args.extend(seek.input_args.iter().cloned());
     args.push("-i".to_string());
     args.push(reference_path.to_string());
 
-    // Filter Complex - note: [0:v] is distorted (sample), [1:v] is reference
+    // Filter Complex - note: [0:v] is distorted (sample), [1:v:N] is reference
+    let ref_trim = seek.trim_filter();
     let filter = if use_cuda {
         format!(
-            "[0:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[dis];[1:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[ref];[dis][ref]libvmaf_cuda={}", 
-            vmaf_opts
+            "[0:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[dis];[1:v:{}]{}{}setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[ref];[dis][ref]libvmaf_cuda={}", 
+            ref_stream, ref_trim, ref_upload, vmaf_opts
         )
     } else {
         format!(
-            "[0:v]setpts=PTS-STARTPTS,format=yuv420p[dis];[1:v]setpts=PTS-STARTPTS,format=yuv420p[ref];[dis][ref]libvmaf={}",
-            vmaf_opts
+            "[0:v]setpts=PTS-STARTPTS,format=yuv420p[dis];[1:v:{}]{}{}setpts=PTS-STARTPTS,format=yuv420p[ref];[dis][ref]libvmaf={}",
+            ref_stream, ref_trim, ref_upload, vmaf_opts
         )
     };
     
@@ -816,21 +2322,16 @@ fn compute_sample_vmaf(
 
     println!("VMAF sample args: {:?}", args);
 
-    let mut command = Command::new(ffmpeg_path);
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
     command.args(&args).stdout(Stdio::piped()).stderr(Stdio::piped());
     
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
-    }
 
     let child = command.spawn().ok()?;
 
     let pid = child.id();
     {
         if let Ok(mut map) = pids.lock() {
-            map.insert(input_key.to_string(), pid);
+            map.insert(job_key(input_key), pid);
         }
     }
 
@@ -838,24 +2339,32 @@ fn compute_sample_vmaf(
     
     {
         if let Ok(mut map) = pids.lock() {
-            map.remove(input_key);
+            map.remove(&job_key(input_key));
         }
     }
 
+    let frames = std::fs::read_to_string(&log_path).ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .map(|json| crate::pooling::frame_scores(&json))
+        .unwrap_or_default();
+    crate::scratch::remove_scratch_file(&log_path, secure_temp_files);
     let o = output.ok()?;
+    if !frames.is_empty() {
+        return Some(frames);
+    }
     let stderr = String::from_utf8_lossy(&o.stderr);
     
     // Debug output
     println!("VMAF stderr length: {} chars", stderr.len());
     
-    // Parse VMAF score from stderr
+    // No log: parse the mean from stderr and treat it as the only score
     // libvmaf outputs something like: "VMAF score: 95.123456"
     // or with Lavfi: "[Parsed_libvmaf_X @ ...] VMAF score: 95.123456"
     if let Some(idx) = stderr.find("VMAF score: ") {
         let rest = &stderr[idx+12..];
         let val_str = rest.split_whitespace().next().unwrap_or("0");
         println!("Found VMAF score string: {}", val_str);
-        return val_str.parse().ok();
+        return val_str.parse().ok().map(|score| vec![score]);
     }
     
     // Alternative pattern: look for mean vmaf in summary lines
@@ -864,7 +2373,7 @@ fn compute_sample_vmaf(
         let rest = &stderr[idx+13..];
         let val_str = rest.split_whitespace().next().unwrap_or("0");
         println!("Found VMAF score (alt pattern): {}", val_str);
-        return val_str.parse().ok();
+        return val_str.parse().ok().map(|score| vec![score]);
     }
 
     println!("No VMAF score found in stderr. First 500 chars: {}", &stderr[..stderr.len().min(500)]);
@@ -999,20 +2508,23 @@ fn search_optimal_crf(
     pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
     cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
     crf_history: &[(f32, f64)],
+    source_filters: &[String], // reference_filter_list of the input
 ) -> Result<(f32, f64), String> {
     let target_vmaf = config.target_vmaf as f64;
     let v_enc = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
     let (min_crf, max_crf) = get_crf_range(&v_enc);
     
-    let temp_dir = std::env::temp_dir();
+    let temp_dir = crate::scratch::scratch_dir(config.secure_temp_files)?;
     let segments = compute_sample_segments(duration_sec, config);
     
     if segments.is_empty() {
         return Err("No segments to sample".to_string());
     }
-
-    // Use first segment for all samples
-    let (seg_start, seg_duration) = segments[0];
+    let segments: Vec<(f64, f64)> = match config.crf_search_segments.max(1) as usize {
+        _ if config.vmaf_adaptive_segments => segments,
+        1 => vec![segments[0]],
+        n => adaptive_segment_order(segments.len(), n).into_iter().take(n).map(|i| segments[i]).collect(),
+    };
 
     // Determine VMAF model based on resolution (same logic as in calculate_vmaf)
     let (width, height) = resolution;
@@ -1027,7 +2539,7 @@ fn search_optimal_crf(
     println!("Selected VMAF model for search: {} (Resolution: {}x{}, Neg: {})", model_filename, width, height, config.vmaf_neg);
     
     let model_path = find_vmaf_model(app, ffmpeg_path, model_filename)
-        .ok_or_else(|| format!("VMAF model {} not found", model_filename))?;
+        .ok_or_else(|| format!("VMAF model {} not found; download the models in Settings", model_filename))?;
 
     let max_iterations = 10u32;
     let mut samples: Vec<(f32, f64)> = Vec::new();
@@ -1035,16 +2547,72 @@ fn search_optimal_crf(
     let mut best_vmaf: Option<f64> = None;
     let mut no_improvement_count = 0;
     let mut search_complete = false;  // Flag to skip iterative search if binary search found optimal
+    let encoder_unavailable = std::cell::Cell::new(false);
 
     // Check cancellation helper
     let check_cancelled = || -> bool {
         if let Ok(set) = cancelled_paths.lock() {
-            set.contains(input_path)
+            set.contains(&job_key(input_path))
         } else {
             false
         }
     };
 
+    // Encode and score one CRF candidate, returning the VMAF over the frames of the sampled
+    // segments, pooled as `vmaf_search_pooling` says (mean by default). Without adaptive
+    // sampling the `crf_search_segments` picked above are all used; with it, segments are
+    // added until the per-segment scores agree (see needs_more_segments) or the pool is exhausted.
+    let evaluate_crf = |crf: f32| -> Option<f64> {
+        let mut scores: Vec<f64> = Vec::new();
+        let mut frames: Vec<f64> = Vec::new();
+
+        for &(seg_start, seg_duration) in &segments {
+            if check_cancelled() {
+                return None;
+            }
+
+            let sample_path = match compress_sample_with_crf(
+                ffmpeg_path, input_path, &temp_dir, crf, seg_start, seg_duration, config, pids, input_path, source_filters
+            ) {
+                Ok(path) => path,
+                Err(stderr) => {
+                    if is_encoder_unavailable_error(&stderr) {
+                        encoder_unavailable.set(true);
+                    }
+                    return None;
+                }
+            };
+            let score = |cuda: bool| compute_sample_vmaf(
+                ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
+                seg_start, seg_duration, cuda, config.accurate_seek,
+                pids, input_path, &config.vmaf_filter_params(), config.video_stream_index.unwrap_or(0), &config.force_decoder, config.secure_temp_files,
+                source_filters,
+            );
+            // libvmaf_cuda needs an NVIDIA GPU; elsewhere score on CPU instead of failing the search
+            let vmaf = if config.vmaf_cuda() && !config.eco_gpu_busy() {
+                score(true).or_else(|| if check_cancelled() { None } else { score(false) })
+            } else {
+                score(false)
+            };
+            crate::scratch::remove_scratch_file(Path::new(&sample_path), config.secure_temp_files);
+            let vmaf = vmaf?;
+            scores.push(crate::pooling::pooled(&vmaf, &config.vmaf_search_pooling)?);
+            frames.extend(vmaf);
+
+            if config.vmaf_adaptive_segments && !needs_more_segments(&scores, config) {
+                break;
+            }
+        }
+
+        if scores.is_empty() {
+            return None;
+        }
+        if scores.len() > 1 {
+            println!("CRF {:.1} scored over {} segments: {:?}", crf, scores.len(), scores);
+        }
+        crate::pooling::pooled(&frames, &config.vmaf_search_pooling)
+    };
+
     // Use search optimization if enabled: predict CRF from history and test it first
     // If the predicted CRF gives VMAF within ±0.5 of target, use it directly
     // Otherwise, use the predicted range for more efficient search
@@ -1064,8 +2632,8 @@ fn search_optimal_crf(
             
             // Test the predicted CRF first
             if check_cancelled() {
-                cleanup_temp_samples(&temp_dir);
-                return Err("Cancelled".to_string());
+                cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+                return Err(CANCELLED.to_string());
             }
             
             let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
@@ -1082,59 +2650,48 @@ fn search_optimal_crf(
             
             println!("Testing predicted CRF: {:.1}", predicted_crf);
             
-            let sample_path = compress_sample_with_crf(
-                ffmpeg_path, input_path, &temp_dir, predicted_crf, seg_start, seg_duration, config, pids, input_path
-            );
-            
-            if let Some(sample_path) = sample_path {
-                let vmaf = compute_sample_vmaf(
-                    ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
-                    seg_start, seg_duration, config.vmaf_use_cuda, pids, input_path, &config.custom_vmaf_params
-                );
-                let _ = std::fs::remove_file(&sample_path);
+            let vmaf = evaluate_crf(predicted_crf);
+            if let Some(score) = vmaf {
+                samples.push((predicted_crf, score));
                 
-                if let Some(score) = vmaf {
-                    samples.push((predicted_crf, score));
-                    
-                    if score >= target_vmaf {
-                        best_crf = Some(predicted_crf);
-                        best_vmaf = Some(score);
-                    }
-                    
-                    let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
-                        path: input_path.to_string(),
-                        iteration: 1,
-                        max_iterations,
-                        current_crf: predicted_crf,
-                        current_vmaf: score,
-                        target_vmaf: config.target_vmaf,
-                        best_crf,
-                        best_vmaf,
-                        samples: samples.clone(),
-                    });
-                    
-                    let vmaf_diff = (score - target_vmaf).abs();
-                    println!("Predicted CRF {:.1} gave VMAF {:.2} (target: {:.1}, diff: {:.2})", 
-                        predicted_crf, score, target_vmaf, vmaf_diff);
-                    
-                    // If within ±0.5 of target, use this CRF directly
-                    if vmaf_diff <= 0.5 {
-                        println!("Prediction successful! VMAF {:.2} is within ±0.5 of target {:.1}", 
-                            score, target_vmaf);
-                        cleanup_temp_samples(&temp_dir);
-                        return Ok((predicted_crf, score));
-                    }
-                    
-                    println!("Prediction not close enough, continuing with optimized search range");
-                    
-                    // Adjust search range based on result
-                    if score > target_vmaf {
-                        // VMAF too high, can use higher CRF
-                        search_min = predicted_crf;
-                    } else {
-                        // VMAF too low, need lower CRF
-                        search_max = predicted_crf;
-                    }
+                if score >= target_vmaf {
+                    best_crf = Some(predicted_crf);
+                    best_vmaf = Some(score);
+                }
+                
+                let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
+                    path: input_path.to_string(),
+                    iteration: 1,
+                    max_iterations,
+                    current_crf: predicted_crf,
+                    current_vmaf: score,
+                    target_vmaf: config.target_vmaf,
+                    best_crf,
+                    best_vmaf,
+                    samples: samples.clone(),
+                });
+                
+                let vmaf_diff = (score - target_vmaf).abs();
+                println!("Predicted CRF {:.1} gave VMAF {:.2} (target: {:.1}, diff: {:.2})", 
+                    predicted_crf, score, target_vmaf, vmaf_diff);
+                
+                // If within ±0.5 of target, use this CRF directly
+                if vmaf_diff <= 0.5 {
+                    println!("Prediction successful! VMAF {:.2} is within ±0.5 of target {:.1}", 
+                        score, target_vmaf);
+                    cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+                    return Ok((predicted_crf, score));
+                }
+                
+                println!("Prediction not close enough, continuing with optimized search range");
+                
+                // Adjust search range based on result
+                if score > target_vmaf {
+                    // VMAF too high, can use higher CRF
+                    search_min = predicted_crf;
+                } else {
+                    // VMAF too low, need lower CRF
+                    search_max = predicted_crf;
                 }
             }
         }
@@ -1155,8 +2712,8 @@ fn search_optimal_crf(
         
         // Test midpoint first
         if check_cancelled() {
-            cleanup_temp_samples(&temp_dir);
-            return Err("Cancelled".to_string());
+            cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+            return Err(CANCELLED.to_string());
         }
         
         let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
@@ -1171,87 +2728,101 @@ fn search_optimal_crf(
             samples: samples.clone(),
         });
         
-        let sample_path = compress_sample_with_crf(
-            ffmpeg_path, input_path, &temp_dir, mid_crf, seg_start, seg_duration, config, pids, input_path
-        );
-        
-        if let Some(sample_path) = sample_path {
-            let vmaf = compute_sample_vmaf(
-                ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
-                seg_start, seg_duration, config.vmaf_use_cuda, pids, input_path, &config.custom_vmaf_params
-            );
-            let _ = std::fs::remove_file(&sample_path);
+        let vmaf = evaluate_crf(mid_crf);
+        if let Some(score) = vmaf {
+            samples.push((mid_crf, score));
             
-            if let Some(score) = vmaf {
-                samples.push((mid_crf, score));
+            // Update best if VMAF >= target
+            if score >= target_vmaf {
+                best_crf = Some(mid_crf);
+                best_vmaf = Some(score);
+            }
+            
+            let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
+                path: input_path.to_string(),
+                iteration,
+                max_iterations,
+                current_crf: mid_crf,
+                current_vmaf: score,
+                target_vmaf: config.target_vmaf,
+                best_crf,
+                best_vmaf,
+                samples: samples.clone(),
+            });
+            
+            // Check if already close enough
+            if (score - target_vmaf).abs() <= 0.5 {
+                println!("Midpoint CRF {} is close enough (VMAF {:.2}, target {:.1})", mid_crf, score, target_vmaf);
+                cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+                return Ok((mid_crf, score));
+            }
+            
+            // Determine search direction based on midpoint result
+            // Higher VMAF than target -> can use higher CRF (more compression)
+            // Lower VMAF than target -> need lower CRF (less compression)
+            if score > target_vmaf {
+                // VMAF too high, search towards higher CRF
+                current_min = mid_crf;
+                println!("Midpoint VMAF {:.2} > target {:.1}, searching higher CRF range [{:.1}, {:.1}]", 
+                    score, target_vmaf, current_min, current_max);
+            } else {
+                // VMAF too low, search towards lower CRF
+                current_max = mid_crf;
+                println!("Midpoint VMAF {:.2} < target {:.1}, searching lower CRF range [{:.1}, {:.1}]", 
+                    score, target_vmaf, current_min, current_max);
+            }
+            
+            // Continue search using interpolation-based prediction
+            while iteration < max_iterations && (current_max - current_min) > 1.0 {
+                if check_cancelled() {
+                    cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+                    return Err(CANCELLED.to_string());
+                }
                 
-                // Update best if VMAF >= target
-                if score >= target_vmaf {
-                    best_crf = Some(mid_crf);
-                    best_vmaf = Some(score);
+                iteration += 1;
+                
+                // Use interpolation to predict next CRF if we have enough samples
+                let mut next_crf = if samples.len() >= 2 {
+                    let predicted = interpolate_crf(&samples, target_vmaf);
+                    // Clamp to current search range
+                    predicted.max(current_min).min(current_max)
+                } else {
+                    // Fallback to midpoint if not enough samples
+                    (current_min + current_max) / 2.0
+                };
+                
+                const MIN_STEP: f32 = 0.8;
+                
+                // Check if we already have a sample too close to this CRF (minimum step: 0.8)
+                if samples.iter().any(|(c, _)| (*c - next_crf).abs() < MIN_STEP) {
+                    // Try midpoint instead
+                    next_crf = (current_min + current_max) / 2.0;
+                    if samples.iter().any(|(c, _)| (*c - next_crf).abs() < MIN_STEP) {
+                        break;
+                    }
                 }
                 
                 let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
                     path: input_path.to_string(),
                     iteration,
                     max_iterations,
-                    current_crf: mid_crf,
-                    current_vmaf: score,
+                    current_crf: next_crf,
+                    current_vmaf: 0.0,
                     target_vmaf: config.target_vmaf,
                     best_crf,
                     best_vmaf,
                     samples: samples.clone(),
                 });
                 
-                // Check if already close enough
-                if (score - target_vmaf).abs() <= 0.5 {
-                    println!("Midpoint CRF {} is close enough (VMAF {:.2}, target {:.1})", mid_crf, score, target_vmaf);
-                    cleanup_temp_samples(&temp_dir);
-                    return Ok((mid_crf, score));
-                }
-                
-                // Determine search direction based on midpoint result
-                // Higher VMAF than target -> can use higher CRF (more compression)
-                // Lower VMAF than target -> need lower CRF (less compression)
-                if score > target_vmaf {
-                    // VMAF too high, search towards higher CRF
-                    current_min = mid_crf;
-                    println!("Midpoint VMAF {:.2} > target {:.1}, searching higher CRF range [{:.1}, {:.1}]", 
-                        score, target_vmaf, current_min, current_max);
-                } else {
-                    // VMAF too low, search towards lower CRF
-                    current_max = mid_crf;
-                    println!("Midpoint VMAF {:.2} < target {:.1}, searching lower CRF range [{:.1}, {:.1}]", 
-                        score, target_vmaf, current_min, current_max);
-                }
-                
-                // Continue search using interpolation-based prediction
-                while iteration < max_iterations && (current_max - current_min) > 1.0 {
-                    if check_cancelled() {
-                        cleanup_temp_samples(&temp_dir);
-                        return Err("Cancelled".to_string());
-                    }
-                    
-                    iteration += 1;
-                    
-                    // Use interpolation to predict next CRF if we have enough samples
-                    let mut next_crf = if samples.len() >= 2 {
-                        let predicted = interpolate_crf(&samples, target_vmaf);
-                        // Clamp to current search range
-                        predicted.max(current_min).min(current_max)
-                    } else {
-                        // Fallback to midpoint if not enough samples
-                        (current_min + current_max) / 2.0
-                    };
-                    
-                    const MIN_STEP: f32 = 0.8;
+                let vmaf = evaluate_crf(next_crf);
+                if let Some(next_score) = vmaf {
+                    samples.push((next_crf, next_score));
                     
-                    // Check if we already have a sample too close to this CRF (minimum step: 0.8)
-                    if samples.iter().any(|(c, _)| (*c - next_crf).abs() < MIN_STEP) {
-                        // Try midpoint instead
-                        next_crf = (current_min + current_max) / 2.0;
-                        if samples.iter().any(|(c, _)| (*c - next_crf).abs() < MIN_STEP) {
-                            break;
+                    // Update best
+                    if next_score >= target_vmaf {
+                        if best_crf.is_none() || next_crf > best_crf.unwrap() {
+                            best_crf = Some(next_crf);
+                            best_vmaf = Some(next_score);
                         }
                     }
                     
@@ -1260,89 +2831,53 @@ fn search_optimal_crf(
                         iteration,
                         max_iterations,
                         current_crf: next_crf,
-                        current_vmaf: 0.0,
+                        current_vmaf: next_score,
                         target_vmaf: config.target_vmaf,
                         best_crf,
                         best_vmaf,
                         samples: samples.clone(),
                     });
                     
-                    let sample_path = compress_sample_with_crf(
-                        ffmpeg_path, input_path, &temp_dir, next_crf, seg_start, seg_duration, config, pids, input_path
-                    );
+                    // Log progress
+                    println!("CRF {} gave VMAF {:.2} (target: {:.1}, diff: {:.2})", 
+                        next_crf, next_score, target_vmaf, (next_score - target_vmaf).abs());
                     
-                    if let Some(sample_path) = sample_path {
-                        let vmaf = compute_sample_vmaf(
-                            ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
-                            seg_start, seg_duration, config.vmaf_use_cuda, pids, input_path, &config.custom_vmaf_params
-                        );
-                        let _ = std::fs::remove_file(&sample_path);
-                        
-                        if let Some(next_score) = vmaf {
-                            samples.push((next_crf, next_score));
-                            
-                            // Update best
-                            if next_score >= target_vmaf {
-                                if best_crf.is_none() || next_crf > best_crf.unwrap() {
-                                    best_crf = Some(next_crf);
-                                    best_vmaf = Some(next_score);
-                                }
-                            }
-                            
-                            let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
-                                path: input_path.to_string(),
-                                iteration,
-                                max_iterations,
-                                current_crf: next_crf,
-                                current_vmaf: next_score,
-                                target_vmaf: config.target_vmaf,
-                                best_crf,
-                                best_vmaf,
-                                samples: samples.clone(),
-                            });
-                            
-                            // Log progress
-                            println!("CRF {} gave VMAF {:.2} (target: {:.1}, diff: {:.2})", 
-                                next_crf, next_score, target_vmaf, (next_score - target_vmaf).abs());
-                            
-                            // Early termination conditions (same as iterative search)
-                            // Condition 1: best_crf exists and best_vmaf is in [target_vmaf, target_vmaf+0.3]
-                            let early_stop_1 = if let (Some(_b_crf), Some(b_vmaf)) = (best_crf, best_vmaf) {
-                                b_vmaf >= target_vmaf - 0.3 && b_vmaf <= target_vmaf + 0.3
-                            } else {
-                                false
-                            };
-                            
-                            // Condition 2: best_crf exists and there's a sample in [best_crf, best_crf+1] with vmaf < target_vmaf
-                            let early_stop_2 = if let Some(b_crf) = best_crf {
-                                samples.iter().any(|(c, v)| {
-                                    *c >= b_crf && *c <= b_crf + 1.0 && *v < target_vmaf
-                                })
-                            } else {
-                                false
-                            };
-                            
-                            if early_stop_1 {
-                                println!("Early stop (binary): best CRF {:.1} has VMAF {:.2} in target range [{:.1}, {:.1}]. Will return closest sample to target.", 
-                                    best_crf.unwrap(), best_vmaf.unwrap(), target_vmaf, target_vmaf + 0.3);
-                                search_complete = true;
-                                break;
-                            }
-                            
-                            if early_stop_2 {
-                                println!("Early stop (binary): found sample with VMAF < target in [best_crf {:.1}, {:.1}] range. Will return closest sample to target.", 
-                                    best_crf.unwrap(), best_crf.unwrap() + 1.0);
-                                search_complete = true;
-                                break;
-                            }
-                            
-                            // Narrow search range
-                            if next_score > target_vmaf {
-                                current_min = next_crf;
-                            } else {
-                                current_max = next_crf;
-                            }
-                        }
+                    // Early termination conditions (same as iterative search)
+                    // Condition 1: best_crf exists and best_vmaf is in [target_vmaf, target_vmaf+0.3]
+                    let early_stop_1 = if let (Some(_b_crf), Some(b_vmaf)) = (best_crf, best_vmaf) {
+                        b_vmaf >= target_vmaf - 0.3 && b_vmaf <= target_vmaf + 0.3
+                    } else {
+                        false
+                    };
+                    
+                    // Condition 2: best_crf exists and there's a sample in [best_crf, best_crf+1] with vmaf < target_vmaf
+                    let early_stop_2 = if let Some(b_crf) = best_crf {
+                        samples.iter().any(|(c, v)| {
+                            *c >= b_crf && *c <= b_crf + 1.0 && *v < target_vmaf
+                        })
+                    } else {
+                        false
+                    };
+                    
+                    if early_stop_1 {
+                        println!("Early stop (binary): best CRF {:.1} has VMAF {:.2} in target range [{:.1}, {:.1}]. Will return closest sample to target.", 
+                            best_crf.unwrap(), best_vmaf.unwrap(), target_vmaf, target_vmaf + 0.3);
+                        search_complete = true;
+                        break;
+                    }
+                    
+                    if early_stop_2 {
+                        println!("Early stop (binary): found sample with VMAF < target in [best_crf {:.1}, {:.1}] range. Will return closest sample to target.", 
+                            best_crf.unwrap(), best_crf.unwrap() + 1.0);
+                        search_complete = true;
+                        break;
+                    }
+                    
+                    // Narrow search range
+                    if next_score > target_vmaf {
+                        current_min = next_crf;
+                    } else {
+                        current_max = next_crf;
                     }
                 }
             }
@@ -1374,8 +2909,8 @@ fn search_optimal_crf(
         
         if let Some(boundary_crf) = boundary_to_test {
             if check_cancelled() {
-                cleanup_temp_samples(&temp_dir);
-                return Err("Cancelled".to_string());
+                cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+                return Err(CANCELLED.to_string());
             }
             
             let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
@@ -1390,41 +2925,30 @@ fn search_optimal_crf(
                 samples: samples.clone(),
             });
             
-            let sample_path = compress_sample_with_crf(
-                ffmpeg_path, input_path, &temp_dir, boundary_crf, seg_start, seg_duration, config, pids, input_path
-            );
-            
-            if let Some(sample_path) = sample_path {
-                let vmaf = compute_sample_vmaf(
-                    ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
-                    seg_start, seg_duration, config.vmaf_use_cuda, pids, input_path, &config.custom_vmaf_params
-                );
-                let _ = std::fs::remove_file(&sample_path);
+            let vmaf = evaluate_crf(boundary_crf);
+            if let Some(score) = vmaf {
+                samples.push((boundary_crf, score));
                 
-                if let Some(score) = vmaf {
-                    samples.push((boundary_crf, score));
-                    
-                    if score >= target_vmaf {
-                        if best_crf.is_none() || boundary_crf > best_crf.unwrap() {
-                            best_crf = Some(boundary_crf);
-                            best_vmaf = Some(score);
-                        }
+                if score >= target_vmaf {
+                    if best_crf.is_none() || boundary_crf > best_crf.unwrap() {
+                        best_crf = Some(boundary_crf);
+                        best_vmaf = Some(score);
                     }
-                    
-                    let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
-                        path: input_path.to_string(),
-                        iteration: sample_start_idx as u32 + 1,
-                        max_iterations,
-                        current_crf: boundary_crf,
-                        current_vmaf: score,
-                        target_vmaf: config.target_vmaf,
-                        best_crf,
-                        best_vmaf,
-                        samples: samples.clone(),
-                    });
-                    
-                    
                 }
+                
+                let _ = app.emit("vmaf-search-progress", VmafSearchPayload {
+                    path: input_path.to_string(),
+                    iteration: sample_start_idx as u32 + 1,
+                    max_iterations,
+                    current_crf: boundary_crf,
+                    current_vmaf: score,
+                    target_vmaf: config.target_vmaf,
+                    best_crf,
+                    best_vmaf,
+                    samples: samples.clone(),
+                });
+                
+                
             }
         }
     }
@@ -1434,8 +2958,8 @@ fn search_optimal_crf(
     if !search_complete {
     for iter in 0..(max_iterations.saturating_sub(initial_samples_count as u32)) {
         if check_cancelled() {
-            cleanup_temp_samples(&temp_dir);
-            return Err("Cancelled".to_string());
+            cleanup_temp_samples(&temp_dir, config.secure_temp_files);
+            return Err(CANCELLED.to_string());
         }
 
         if samples.len() < 2 {
@@ -1474,27 +2998,11 @@ fn search_optimal_crf(
             samples: samples.clone(),
         });
 
-        // Compress sample
-        let sample_path = compress_sample_with_crf(
-            ffmpeg_path, input_path, &temp_dir, crf_guess, seg_start, seg_duration, config, pids, input_path
-        );
-
-        if sample_path.is_none() {
-            println!("Failed to compress sample at CRF {}", crf_guess);
-            no_improvement_count += 1;
-            if no_improvement_count >= 3 { break; }
-            continue;
+        // Compress and score sample(s)
+        let vmaf = evaluate_crf(crf_guess);
+        if vmaf.is_none() {
+            println!("Failed to evaluate sample at CRF {}", crf_guess);
         }
-        let sample_path = sample_path.unwrap();
-
-        // Compute VMAF
-        let vmaf = compute_sample_vmaf(
-            ffmpeg_path, ffprobe_path, input_path, &sample_path, &model_path,
-            seg_start, seg_duration, config.vmaf_use_cuda, pids, input_path, &config.custom_vmaf_params
-        );
-
-        // Cleanup sample
-        let _ = std::fs::remove_file(&sample_path);
 
         if let Some(score) = vmaf {
             let old_best = best_crf;
@@ -1568,13 +3076,16 @@ fn search_optimal_crf(
     }
     }  // end of if !search_complete
 
-    cleanup_temp_samples(&temp_dir);
+    cleanup_temp_samples(&temp_dir, config.secure_temp_files);
 
     // Return results - find the CRF with VMAF closest to target
     // Always return the sample point with the smallest absolute difference from target VMAF
     // This ensures we get the most accurate match, even if it's slightly below target
     
     if samples.is_empty() {
+        if encoder_unavailable.get() {
+            return Err(format!("{}: {}", ENCODER_UNAVAILABLE, v_enc));
+        }
         // No samples at all, use mid CRF
         return Ok(((search_min + search_max) / 2.0, 0.0));
     }
@@ -1601,21 +3112,24 @@ fn search_optimal_crf(
 }
 
 /// Cleanup temporary sample files
-fn cleanup_temp_samples(temp_dir: &std::path::Path) {
+fn cleanup_temp_samples(temp_dir: &std::path::Path, shred: bool) {
     if let Ok(entries) = std::fs::read_dir(temp_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
                 if name.starts_with("vmaf_sample_") {
-                    let _ = std::fs::remove_file(path);
+                    crate::scratch::remove_scratch_file(&path, shred);
                 }
             }
         }
     }
 }
 
-// Helper to resolve ffprobe path
+// Helper to resolve ffprobe path (configured binary, else next to ffmpeg)
 fn resolve_ffprobe_path(ffmpeg_path: &str) -> String {
+    if let Some(custom) = crate::settings::custom_ffprobe_path() {
+        return custom;
+    }
     if let Some(parent_dir) = std::path::Path::new(ffmpeg_path).parent() {
         let ffmpeg_path_buf = std::path::Path::new(ffmpeg_path);
         let ffprobe_name = if let Some(ext) = ffmpeg_path_buf.extension() {
@@ -1643,11 +3157,20 @@ pub fn run_crf_search(
     cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
     vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>>,
 ) -> Result<(f32, f64), String> {
+    let _spawn = crate::process::job_scope(config);
+    crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Queued);
+
     // 0. Resolve ffprobe path
     let ffprobe_path = resolve_ffprobe_path(ffmpeg_path);
 
     // 1. Get Input Info for Resolution
-    let input_info = get_video_info(Path::new(&input_path), &ffprobe_path).ok();
+    crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Probing);
+    let input_probe = get_video_info(Path::new(&input_path), &ffprobe_path);
+    reject_protected_source(&app, &input_path, &input_probe)?;
+    let input_info = input_probe.ok();
+
+    let config = &crate::tiers::apply_tier(config, input_info.as_ref().map(|i| i.resolution.as_str()));
+    let config = &with_video_stream(with_encoder_fallback(config), input_info.as_ref());
     
     // Extract resolution for model selection
     let resolution = if let Some(ref info) = input_info {
@@ -1663,22 +3186,78 @@ pub fn run_crf_search(
         (0, 0)
     };
     
-    // Clear any previous cancellation for this path (allows re-processing after cancel)
-    {
+    // Clear any previous cancellation for this path (allows re-processing after cancel),
+    // unless a pipeline started the search and already did
+    if !is_pipeline_active(&input_path) {
+        if let Ok(mut set) = cancelled_paths.lock() {
+            set.remove(&job_key(&input_path));
+        }
+    }
+    set_user_paused(&input_path, false);
+
+    // An unchanged file searched before with the same settings doesn't need another search
+    let v_enc = if config.video_encoder.is_empty() { "libx264" } else { config.video_encoder.as_str() };
+    let search_key = crate::crfcache::SearchKey {
+        encoder: v_enc.to_string(),
+        target_vmaf: config.target_vmaf,
+        preset: sample_preset(config, v_enc),
+        pooling: config.vmaf_search_pooling.clone(),
+        vmaf_neg: config.vmaf_neg,
+        settings: search_settings_hash(config, v_enc, resolution.0.max(resolution.1) > 2560),
+    };
+    if let Some((crf, vmaf)) = crate::crfcache::get(&input_path, &search_key) {
+        println!("Cached VMAF search result for {}: CRF={}, VMAF={:.2}", input_path, crf, vmaf);
+        crate::events::emit_progress(&app, ProgressPayload {
+            path: input_path.clone(),
+            progress: 50,
+            status: format!("Found CRF {:.0}, Waiting", crf),
+            speed: 0.0,
+            bitrate_kbps: 0.0,
+            output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
+        });
+        return Ok((crf, vmaf));
+    }
+
+    if wait_for_start_conditions(&app, &input_path, config, false, 0, &cancelled_paths).is_err() {
         if let Ok(mut set) = cancelled_paths.lock() {
-            set.remove(&input_path);
+            set.remove(&job_key(&input_path));
         }
+        crate::events::emit_progress(&app, ProgressPayload {
+            path: input_path.clone(),
+            progress: 0,
+            status: "Cancelled".to_string(),
+            speed: 0.0,
+            bitrate_kbps: 0.0,
+            output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
+        });
+        return Err(CANCELLED.to_string());
+    }
+
+    if config.auto_tune {
+        crate::tuning::content_type_for(ffmpeg_path, &input_path, duration_sec);
     }
 
-    let _ = app.emit("video-progress", ProgressPayload {
+    crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Searching);
+    crate::events::emit_progress(&app, ProgressPayload {
         path: input_path.clone(),
         progress: 0,
         status: "Searching CRF".to_string(),
         speed: 0.0,
         bitrate_kbps: 0.0,
         output_info: None,
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
     });
 
+    let source_filters = reference_filter_list(ffmpeg_path, &input_path, input_info.as_ref(), config);
+    if !config.sample_preset.trim().is_empty() && sample_preset_override(config, v_enc).is_none() {
+        eprintln!("[WARNING] Sample preset '{}' doesn't apply to {}; samples use the final encode's preset", config.sample_preset, v_enc);
+    }
+
     // Get historical CRF data for optimization
     let crf_history: Vec<(f32, f64)> = if let Ok(state) = vmaf_state.lock() {
         state.crf_history.get(&resolution).cloned().unwrap_or_default()
@@ -1686,11 +3265,35 @@ pub fn run_crf_search(
         Vec::new()
     };
 
-    match search_optimal_crf(
-        &app, ffmpeg_path, &ffprobe_path, &input_path, config, duration_sec, resolution, &pids, &cancelled_paths, &crf_history
-    ) {
+    // The encoder may have disappeared since detection; search again with the fallback,
+    // from scratch since CRF scales differ between encoders
+    let mut config = config.clone();
+    let mut search_key = search_key;
+    let result = loop {
+        match search_optimal_crf(
+            &app, ffmpeg_path, &ffprobe_path, &input_path, &config, duration_sec, resolution, &pids, &cancelled_paths, &crf_history, &source_filters
+        ) {
+            Err(e) if e.starts_with(ENCODER_UNAVAILABLE) => {
+                eprintln!("[ERROR] VMAF CRF search failed for '{}': {}", input_path, e);
+                let failed = config.video_encoder.clone();
+                match switch_to_fallback_encoder(&app, ffmpeg_path, &failed, &config) {
+                    Some(substitute) if substitute != failed => {
+                        config.video_encoder = substitute;
+                        search_key.encoder = config.video_encoder.clone();
+                        search_key.preset = sample_preset(&config, &config.video_encoder);
+                        search_key.settings = search_settings_hash(&config, &config.video_encoder, resolution.0.max(resolution.1) > 2560);
+                    }
+                    _ => break Err(e),
+                }
+            }
+            result => break result,
+        }
+    };
+
+    match result {
         Ok((crf, vmaf)) => {
             println!("VMAF search complete: CRF={}, VMAF={:.2}", crf, vmaf);
+            crate::crfcache::put(&input_path, search_key, crf, vmaf);
             
             // Update historical CRF data for future task optimization
             if let Ok(mut state) = vmaf_state.lock() {
@@ -1703,28 +3306,32 @@ pub fn run_crf_search(
             }
             
             // Emit "Found CRF" so frontend updates status (and potentially calculates progress)
-            let _ = app.emit("video-progress", ProgressPayload {
+            crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: 50, // Search phase complete
                 status: format!("Found CRF {:.0}, Waiting", crf),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
             
             Ok((crf, vmaf))
         }
         Err(e) => {
             if e == "Cancelled" {
-                let _ = app.emit("video-progress", ProgressPayload {
+                crate::events::emit_progress(&app, ProgressPayload {
                     path: input_path.clone(),
                     progress: 0,
                     status: "Cancelled".to_string(),
                     speed: 0.0,
                     bitrate_kbps: 0.0,
                     output_info: None,
+                    monotonic_ms: crate::monitor::monotonic_ms(),
+                    vmaf_segment: None,
                 });
-                Err("Cancelled during CRF search".to_string())
+                Err(CANCELLED.to_string())
             } else {
                 eprintln!("[ERROR] VMAF CRF search failed for '{}': {}", input_path, e);
                 eprintln!("[INFO] Using default CRF 23 as fallback");
@@ -1768,33 +3375,1089 @@ fn split_custom_command(cmd: &str) -> Vec<String> {
     args
 }
 
-pub fn run_ffmpeg_compression_task(
-    app: AppHandle,
-    ffmpeg_path: &str,
-    input_path: String,
-    output_path: String,
-    config: CompressionConfig,
-    duration_sec: f64,
-    pids: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
-    cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
-    vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>>,
-    // Optionals passed from search phase
-    pass_vmaf_derived_crf: Option<f32>,
-    pass_vmaf_search_score: Option<f64>,
+/// Rough quality class from bits per pixel, normalized to H.264 efficiency.
+/// Thresholds are per second of video, so they assume typical 24-30 fps content.
+fn estimate_quality(info: &VideoInfo) -> Option<String> {
+    let kbps = info.bitrate_kbps?;
+    let mut dims = info.resolution.split('x').filter_map(|p| p.parse::<f64>().ok());
+    let (w, h) = (dims.next()?, dims.next()?);
+    if w <= 0.0 || h <= 0.0 || kbps <= 0.0 {
+        return None;
+    }
+
+    let codec = info.encoder.to_lowercase();
+    let efficiency = if codec.contains("av1") {
+        2.0
+    } else if codec.contains("hevc") || codec.contains("265") || codec.contains("vp9") {
+        1.6
+    } else if codec.contains("mpeg2") || codec.contains("mpeg4") || codec.contains("msmpeg") || codec.contains("wmv") {
+        0.6
+    } else {
+        1.0
+    };
+
+    let bits_per_pixel_second = kbps * 1000.0 / (w * h) * efficiency;
+    let badge = if bits_per_pixel_second >= 3.0 {
+        "high"
+    } else if bits_per_pixel_second >= 1.5 {
+        "medium"
+    } else {
+        "low"
+    };
+    Some(badge.to_string())
+}
+
+fn with_quality_estimate(info: Option<VideoInfo>, config: &CompressionConfig) -> Option<VideoInfo> {
+    info.map(|mut i| {
+        if config.estimate_skipped_quality {
+            i.estimated_quality = estimate_quality(&i);
+        }
+        i
+    })
+}
+
+/// Write the optional JSON sidecar for a finished (or skipped) output.
+/// `rate` is the (CRF, bitrate kbps) actually used, where known.
+fn write_result_sidecar(
+    config: &CompressionConfig,
+    input_path: &str,
+    output_path: &str,
+    result: &str,
+    rate: (Option<f32>, Option<u32>),
+    output_info: Option<&VideoInfo>,
+    started_at: u64,
+) {
+    if !config.write_sidecar {
+        return;
+    }
+
+    let record = crate::sidecar::SidecarRecord {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        source_path: input_path.to_string(),
+        output_path: output_path.to_string(),
+        result: result.to_string(),
+        source_size: std::fs::metadata(input_path).map(|m| m.len()).unwrap_or(0),
+        output_size: output_info.map(|i| i.size).unwrap_or_else(|| {
+            std::fs::metadata(output_path).map(|m| m.len()).unwrap_or(0)
+        }),
+        compression_mode: config.compression_mode.clone(),
+        video_encoder: config.video_encoder.clone(),
+        audio_encoder: config.audio_encoder.clone(),
+        crf: rate.0,
+        bitrate_kbps: rate.1,
+        vmaf: output_info.and_then(|i| i.vmaf),
+        vmaf_model: output_info.and_then(|i| i.vmaf_model.clone()),
+        vmaf_device: output_info.and_then(|i| i.vmaf_device.clone()),
+        ffmpeg_commands: output_info.and_then(|i| i.ffmpeg_commands.clone()).unwrap_or_default(),
+        encoder_stats: output_info.and_then(|i| i.encoder_stats.clone()),
+        compression_ratio: output_info.and_then(|i| i.compression_ratio),
+        source_bits_per_pixel: output_info.and_then(|i| i.source_bits_per_pixel),
+        output_bits_per_pixel: output_info.and_then(|i| i.bits_per_pixel),
+        started_at,
+        finished_at: crate::sidecar::now_unix_secs(),
+        config: config.clone(),
+    };
+
+    if let Err(e) = crate::sidecar::write_sidecar(&record) {
+        eprintln!("[WARNING] {}", e);
+    }
+}
+
+/// Rate control arguments (-crf/-cq/-q:v or -b:v) for the configured compression mode
+fn rate_control_args(
+    config: &CompressionConfig,
+    v_enc: &str,
+    vmaf_derived_crf: Option<f32>,
+    vmaf_abr_bitrate: Option<u32>,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    match config.compression_mode.as_str() {
+        // "percent" only reaches here from validate_encoding_config; jobs convert it first
+        "bitrate" | "percent" => {
+            args.push("-b:v".to_string());
+            args.push(format!("{}k", config.target_bitrate));
+        },
+        "crf" => {
+            if v_enc.contains("libx264") || v_enc.contains("libx265") || v_enc.contains("libsvtav1") || v_enc.contains("vp9") {
+                 args.push("-crf".to_string());
+                 args.push(format!("{}", config.target_crf));
+            } else if v_enc.contains("nvenc") {
+                 args.push("-cq".to_string());
+                 args.push(format!("{}", config.target_crf));
+            } else {
+                 args.push(get_crf_arg(v_enc).to_string());
+                 args.push(get_crf_value(v_enc, config.target_crf));
+            }
+        },
+        "vmaf" => {
+            // Use CRF derived from VMAF search, or fallback to 23
+            let crf_to_use = vmaf_derived_crf.unwrap_or(23.0);
+            let crf_arg = get_crf_arg(v_enc);
+            args.push(crf_arg.to_string());
+            args.push(get_crf_value(v_enc, crf_to_use));
+        },
+        "vmaf_abr" => {
+            if let Some(kbps) = vmaf_abr_bitrate {
+                let maxrate = kbps as u64 * config.vmaf_abr_maxrate_percent() as u64 / 100;
+                args.push("-b:v".to_string());
+                args.push(format!("{}k", kbps));
+                args.push("-maxrate".to_string());
+                args.push(format!("{}k", maxrate));
+                args.push("-bufsize".to_string());
+                args.push(format!("{}k", maxrate * 2));
+            } else {
+                let crf_to_use = vmaf_derived_crf.unwrap_or(23.0);
+                args.push(get_crf_arg(v_enc).to_string());
+                args.push(get_crf_value(v_enc, crf_to_use));
+            }
+        },
+        _ => {}
+    }
+    args
+}
+
+/// The video encoder's own params: its typed options and custom params. Shared by the
+/// final encode and the CRF search samples so both encode with the same settings.
+fn video_encoder_params(config: &CompressionConfig, v_enc: &str) -> Vec<String> {
+    let Some(enc_cfg) = config.available_video_encoders.iter().find(|e| e.value == v_enc) else {
+        return Vec::new();
+    };
+    if v_enc.contains("libsvtav1") {
+        return crate::svtav1::args(enc_cfg.svtav1.as_ref(), &enc_cfg.custom_params);
+    }
+    enc_cfg.custom_params.iter().flat_map(|p| p.split_whitespace()).map(str::to_string).collect()
+}
+
+/// `args` with every `-preset` replaced by a single `-preset preset`
+fn with_preset(args: Vec<String>, preset: &str) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len() + 2);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-preset" {
+            args.next();
+        } else {
+            out.push(arg);
+        }
+    }
+    out.extend(["-preset".to_string(), preset.to_string()]);
+    out
+}
+
+/// Hash of the settings a CRF search result depends on besides the ones spelled out in
+/// crfcache::SearchKey: encoder options, sampled segments and the VMAF model and options
+fn search_settings_hash(config: &CompressionConfig, v_enc: &str, high_res: bool) -> String {
+    use std::hash::{Hash, Hasher};
+    let settings = format!(
+        "{:?}",
+        (
+            video_encoder_params(config, v_enc),
+            crate::nvenc::args(config.nvenc.as_ref(), v_enc, "crf", config.auto_tune),
+            config.auto_tune,
+            (config.crf_search_segments, config.vmaf_segment_count, config.vmaf_segment_duration, config.vmaf_auto_config),
+            (config.vmaf_adaptive_segments, config.vmaf_adaptive_initial_segments, config.vmaf_adaptive_max_segments, config.vmaf_adaptive_variance_threshold),
+            (config.vmaf_filter_params(), high_res, config.accurate_seek),
+            config.sample_crf_offset,
+            (config.auto_crop, config.deinterlace.as_str(), config.max_fps, config.denoise.as_str()),
+        ),
+    );
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    settings.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Preset the CRF search samples of `v_enc` are encoded with ("" = the encoder's default)
+fn sample_preset(config: &CompressionConfig, v_enc: &str) -> String {
+    if let Some(preset) = sample_preset_override(config, v_enc) {
+        return preset.to_string();
+    }
+    let mut args = crate::nvenc::args(config.nvenc.as_ref(), v_enc, "crf", config.auto_tune);
+    args.extend(video_encoder_params(config, v_enc));
+    args.iter().rposition(|a| a == "-preset").and_then(|i| args.get(i + 1)).cloned().unwrap_or_default()
+}
+
+/// `sample_preset` if it is a preset `v_enc` knows; None when it is unset or doesn't apply
+/// (the samples then use the final encode's preset and the CRF offset is not applied)
+fn sample_preset_override<'a>(config: &'a CompressionConfig, v_enc: &str) -> Option<&'a str> {
+    const X26X_PRESETS: &[&str] = &["ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow", "placebo"];
+    const QSV_PRESETS: &[&str] = &["veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"];
+    let preset = config.sample_preset.trim();
+    let fits = if v_enc.contains("libx264") || v_enc.contains("libx265") {
+        X26X_PRESETS.contains(&preset)
+    } else if v_enc.contains("libsvtav1") {
+        preset.parse::<u8>().is_ok_and(|p| p <= crate::svtav1::MAX_PRESET)
+    } else if v_enc.contains("nvenc") {
+        crate::nvenc::PRESETS.contains(&preset)
+    } else if v_enc.contains("qsv") {
+        QSV_PRESETS.contains(&preset)
+    } else {
+        false
+    };
+    (!preset.is_empty() && fits).then_some(preset)
+}
+
+/// Typed options of the video encoder entry, checked before anything runs
+fn check_encoder_options(config: &CompressionConfig, v_enc: &str) -> Result<(), String> {
+    crate::nvenc::validate(config.nvenc.as_ref(), v_enc, &config.compression_mode)?;
+    let enc_cfg = config.available_video_encoders.iter().find(|e| e.value == v_enc);
+    crate::svtav1::validate(enc_cfg.and_then(|e| e.svtav1.as_ref()).filter(|_| v_enc.contains("libsvtav1")))
+}
+
+/// Filter for a `denoise` preset. hqdn3d is fast; nlmeans is much slower but keeps more
+/// detail at the same strength.
+fn denoise_filter(preset: &str) -> Option<&'static str> {
+    match preset {
+        "hqdn3d_light" => Some("hqdn3d=2:1.5:3:2.25"),
+        "hqdn3d_medium" => Some("hqdn3d=4:3:6:4.5"),
+        "hqdn3d_strong" => Some("hqdn3d=8:6:12:9"),
+        "nlmeans_light" => Some("nlmeans=s=1.5:p=7:r=9"),
+        "nlmeans_medium" => Some("nlmeans=s=3:p=7:r=9"),
+        "nlmeans_strong" => Some("nlmeans=s=5:p=7:r=15"),
+        _ => None,
+    }
+}
+
+/// `-vf`/`-filter:v` values from the custom filters, and the remaining arguments
+fn split_custom_filters(config: &CompressionConfig) -> (Vec<String>, Vec<String>) {
+    let mut filters = Vec::new();
+    let mut rest = Vec::new();
+    let mut tokens = config.custom_filters.iter().flat_map(|f| f.split_whitespace());
+    while let Some(token) = tokens.next() {
+        match token {
+            "-vf" | "-filter:v" => filters.extend(tokens.next().map(|f| f.to_string())),
+            _ => rest.push(token.to_string()),
+        }
+    }
+    (filters, rest)
+}
+
+/// The single video filtergraph of an encode: source fix-ups (deinterlace, crop, frame rate
+/// cap) first, so burned-in subtitles land inside the picture that is kept, then denoise,
+/// burn-in, the resolution limit and the user's own filters. Copy mode takes only the
+/// user's (and ffmpeg will refuse them).
+fn video_filter_chain(config: &CompressionConfig, is_copy_mode: bool, source_filters: &[String], burn_in: Option<&str>, user_filters: Vec<String>) -> Vec<String> {
+    let mut chain = Vec::new();
+    if !is_copy_mode {
+        chain.extend(source_filters.iter().cloned());
+        chain.extend(denoise_filter(&config.denoise).map(|f| f.to_string()));
+        chain.extend(burn_in.map(|f| f.to_string()));
+        chain.extend(config.max_resolution.scale_filter());
+    }
+    chain.extend(user_filters);
+    chain
+}
+
+/// Video filtergraph (see video_filter_chain), custom filters/options and per-encoder
+/// custom params for the final encode. `burn_in` (see burn_in_filter) runs before the
+/// scaling, so subtitles are rendered at the source resolution.
+fn encoding_option_args(config: &CompressionConfig, v_enc: &str, a_enc: &str, is_copy_mode: bool, source_filters: &[String], burn_in: Option<&str>) -> Vec<String> {
+    let mut args = Vec::new();
+
+    // Custom Filters (always apply - these can include things like -movflags +faststart).
+    // Their own -vf joins our chain, since a second -vf would replace it.
+    let (user_filters, custom_args) = split_custom_filters(config);
+    let video_filters = video_filter_chain(config, is_copy_mode, source_filters, burn_in, user_filters);
+    if !video_filters.is_empty() {
+        args.push("-vf".to_string());
+        args.push(video_filters.join(","));
+    }
+    args.extend(custom_args);
+    
+    // Encoder Specific Params (skip for copy mode - no encoding)
+    if !is_copy_mode {
+        // Before the encoder's custom params, so a -preset or -gpu there still wins
+        args.extend(crate::nvenc::args(config.nvenc.as_ref(), v_enc, &config.compression_mode, config.auto_tune));
+        args.extend(nvenc_gpu_args(v_enc));
+        args.extend(video_encoder_params(config, v_enc));
+         if let Some(enc_cfg) = config.available_audio_encoders.iter().find(|e| e.value == a_enc) {
+            for param in &enc_cfg.custom_params {
+                 let parts: Vec<&str> = param.split_whitespace().collect();
+                 for p in parts {
+                     args.push(p.to_string());
+                 }
+            }
+        }
+    }
+
+    args
+}
+
+/// Batch warm-up: encode one synthetic frame (and a second of silence) with the exact encoder,
+/// rate control, filters and custom params the batch will use, so a broken configuration
+/// fails in seconds instead of once per file. Custom-command and copy modes are not checked.
+pub fn validate_encoding_config(ffmpeg_path: &str, config: &CompressionConfig) -> Result<(), String> {
+    let _spawn = crate::process::job_scope(config);
+    if config.compression_mode == "custom" || config.compression_mode == "copy" {
+        return Ok(());
+    }
+    let config = with_encoder_fallback(config);
+    let v_enc = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
+    let a_enc = if config.audio_encoder.is_empty() { "aac".to_string() } else { config.audio_encoder.clone() };
+
+    // Mux into the real container when it is known up front, to catch muxer errors too
+    let format = config.target_format.to_lowercase();
+    let container = ["mp4", "mkv", "mov", "webm"].contains(&format.as_str()).then_some(format);
+    if let Some(format) = &container {
+        check_container_compatibility(format, &v_enc, &a_enc)?;
+    }
+    check_audio_settings(&config, &a_enc)?;
+    check_encoder_options(&config, &v_enc)?;
+
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-v", "error"].iter().map(|a| a.to_string()).collect();
+    args.extend(hw_device_args(&v_enc));
+    args.extend([
+        "-f", "lavfi", "-i", "color=size=1280x720:rate=30",
+        "-f", "lavfi", "-i", "anullsrc=r=48000:cl=stereo",
+        "-frames:v", "1", "-t", "1", "-pix_fmt", "yuv420p",
+        "-c:v", &v_enc,
+    ].iter().map(|a| a.to_string()));
+    // Placeholder rate targets; the VMAF modes fill these in per file
+    args.extend(rate_control_args(&config, &v_enc, Some(23.0), Some(2000)));
+    args.push("-c:a".to_string());
+    args.push(a_enc.clone());
+    args.extend(audio_option_args(&config, &a_enc));
+    args.extend(encoding_option_args(&config, &v_enc, &a_enc, false, &[], None));
+    let mut args = crate::gpuquirks::with_bframe_quirk(&config, &v_enc, with_hw_upload(&v_enc, args));
+
+    let temp_output = match &container {
+        Some(format) => Some(crate::scratch::scratch_file(config.secure_temp_files, "encoder_check", format)?),
+        None => None,
+    };
+    match &temp_output {
+        Some(path) => args.push(path.to_string_lossy().to_string()),
+        None => args.extend(["-f".to_string(), "null".to_string(), "-".to_string()]),
+    }
+
+    println!("[INFO] Validating encoding settings: {}", format_command_line(ffmpeg_path, &args));
+
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(&args);
+    let output = command.output();
+    if let Some(path) = &temp_output {
+        crate::scratch::remove_scratch_file(path, config.secure_temp_files);
+    }
+
+    let output = output.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let tail: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    let tail = tail[tail.len().saturating_sub(5)..].join("\n");
+    eprintln!("[ERROR] Encoding settings failed validation:\n{}", tail);
+    Err(format!("Encoder '{}' failed with the current settings:\n{}", v_enc, tail))
+}
+
+/// Render an argument vector as a copy-pasteable command line (quoting where needed)
+fn format_command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(|a| a.as_str()))
+        .map(|a| {
+            if a.is_empty() || a.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+                format!("\"{}\"", a.replace('"', "\\\""))
+            } else {
+                a.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reject encoder/container combinations ffmpeg would fail to mux (after encoding the whole file)
+pub fn check_container_compatibility(format: &str, video_encoder: &str, audio_encoder: &str) -> Result<(), String> {
+    match format {
+        "webm" => {
+            if !["vp8", "vp9", "av1"].iter().any(|c| video_encoder.contains(c)) {
+                return Err(format!("WebM only supports VP8/VP9/AV1 video, not '{}'", video_encoder));
+            }
+            if !["opus", "vorbis"].iter().any(|c| audio_encoder.contains(c)) {
+                return Err(format!("WebM only supports Opus/Vorbis audio, not '{}'", audio_encoder));
+            }
+        }
+        "mp4" | "mov" => {
+            if video_encoder.contains("vp8") {
+                return Err(format!("{} does not support VP8 video", format.to_uppercase()));
+            }
+            if audio_encoder.contains("vorbis") || (format == "mp4" && audio_encoder.starts_with("pcm_")) {
+                return Err(format!("{} does not support '{}' audio", format.to_uppercase(), audio_encoder));
+            }
+        }
+        // Matroska takes practically anything
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Limits of the common audio encoders: (max channels, allowed sample rates, kbps range)
+fn audio_encoder_limits(audio_encoder: &str) -> Option<(u32, &'static [u32], (u32, u32))> {
+    if audio_encoder.contains("opus") {
+        Some((8, &[8000, 12000, 16000, 24000, 48000], (6, 510)))
+    } else if audio_encoder.contains("mp3") {
+        Some((2, &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000], (8, 320)))
+    } else if audio_encoder == "ac3" || audio_encoder == "eac3" {
+        Some((6, &[32000, 44100, 48000], (32, if audio_encoder == "ac3" { 640 } else { 6144 })))
+    } else if audio_encoder.contains("aac") {
+        Some((8, &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000], (8, 512)))
+    } else if audio_encoder.contains("vorbis") {
+        Some((8, &[], (45, 500)))
+    } else {
+        None
+    }
+}
+
+/// Check the audio bitrate, channel count and sample rate against the audio encoder
+fn check_audio_settings(config: &CompressionConfig, audio_encoder: &str) -> Result<(), String> {
+    if audio_encoder == "copy" {
+        return Ok(());
+    }
+    let Some((max_channels, sample_rates, (min_kbps, max_kbps))) = audio_encoder_limits(audio_encoder) else { return Ok(()) };
+    if config.audio_channels > max_channels {
+        return Err(format!("{} supports at most {} audio channels, not {}", audio_encoder, max_channels, config.audio_channels));
+    }
+    if config.audio_sample_rate > 0 && !sample_rates.is_empty() && !sample_rates.contains(&config.audio_sample_rate) {
+        let allowed: Vec<String> = sample_rates.iter().map(|r| r.to_string()).collect();
+        return Err(format!("{} does not support {} Hz audio (use {})", audio_encoder, config.audio_sample_rate, allowed.join(", ")));
+    }
+    if config.audio_bitrate_kbps > 0 && !(min_kbps..=max_kbps).contains(&config.audio_bitrate_kbps) {
+        return Err(format!("{} audio bitrate must be {}-{} kbps, not {}", audio_encoder, min_kbps, max_kbps, config.audio_bitrate_kbps));
+    }
+    Ok(())
+}
+
+/// `-b:a`/`-ac`/`-ar` for the structured audio settings (nothing when copying audio)
+fn audio_option_args(config: &CompressionConfig, audio_encoder: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    if audio_encoder == "copy" {
+        return args;
+    }
+    if config.audio_bitrate_kbps > 0 {
+        args.extend(["-b:a".to_string(), format!("{}k", config.audio_bitrate_kbps)]);
+    }
+    if config.audio_channels > 0 {
+        args.extend(["-ac".to_string(), config.audio_channels.to_string()]);
+    }
+    if config.audio_sample_rate > 0 {
+        args.extend(["-ar".to_string(), config.audio_sample_rate.to_string()]);
+    }
+    args
+}
+
+/// Whether two paths point at the same file, even if the second doesn't exist yet
+/// (case differences on Windows, `..` segments, symlinked directories).
+pub fn resolves_to_same_file(a: &str, b: &str) -> bool {
+    job_key(a) == job_key(b)
+}
+
+/// Error of a job the user cancelled. Every cancel returns exactly this, so callers can
+/// tell it from a failure (no halt count, "Cancelled" rather than "Failed").
+pub const CANCELLED: &str = "Cancelled";
+
+/// Whether a job's error is a user cancel rather than a failure
+pub fn is_cancelled(error: &str) -> bool {
+    error == CANCELLED
+}
+
+/// Canonical form of a path for matching: symlinks, `..` and separators resolved, and
+/// case-folded on Windows, so `D:\Video\a.mp4` and `d:/video/a.mp4` are the same job.
+/// Paths that don't exist (yet) are resolved through their parent directory.
+/// Used as the key of every per-job state map (pids, cancelled paths, VMAF queue, ...),
+/// while events keep reporting the path as the frontend sent it.
+pub fn job_key(p: &str) -> String {
+    let path = Path::new(p);
+    let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| {
+        match (path.parent().and_then(|d| std::fs::canonicalize(d).ok()), path.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => path.to_path_buf(),
+        }
+    });
+    let key = resolved.to_string_lossy().into_owned();
+    if cfg!(windows) {
+        key.trim_start_matches(r"\\?\").replace('/', "\\").to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// Jobs suspended with pause_processing (job keys). Eco mode leaves these suspended
+/// when it would otherwise resume them.
+static USER_PAUSED: std::sync::Mutex<Option<std::collections::HashSet<String>>> = std::sync::Mutex::new(None);
+
+pub fn set_user_paused(path: &str, paused: bool) {
+    if let Ok(mut guard) = USER_PAUSED.lock() {
+        let set = guard.get_or_insert_with(std::collections::HashSet::new);
+        if paused {
+            set.insert(job_key(path));
+        } else {
+            set.remove(&job_key(path));
+        }
+    }
+}
+
+pub fn is_user_paused(path: &str) -> bool {
+    USER_PAUSED.lock().ok()
+        .and_then(|g| g.as_ref().map(|set| set.contains(&job_key(path))))
+        .unwrap_or(false)
+}
+
+/// Jobs (keys) whose ffmpeg eco mode suspended for running on battery
+static ECO_SUSPENDED: std::sync::Mutex<Option<std::collections::HashSet<String>>> = std::sync::Mutex::new(None);
+
+fn set_eco_suspended(path: &str, suspended: bool) {
+    if let Ok(mut guard) = ECO_SUSPENDED.lock() {
+        let set = guard.get_or_insert_with(std::collections::HashSet::new);
+        if suspended {
+            set.insert(job_key(path));
+        } else {
+            set.remove(&job_key(path));
+        }
+    }
+}
+
+pub fn is_eco_suspended(path: &str) -> bool {
+    ECO_SUSPENDED.lock().ok()
+        .and_then(|g| g.as_ref().map(|set| set.contains(&job_key(path))))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputMapping {
+    pub input_path: String,
+    pub output_path: String,
+}
+
+/// Pre-flight check: the inputs whose planned output would replace the source file
+pub fn find_overwrite_hazards(mappings: &[OutputMapping]) -> Vec<String> {
+    mappings.iter()
+        .filter(|m| resolves_to_same_file(&m.input_path, &m.output_path))
+        .map(|m| m.input_path.clone())
+        .collect()
+}
+
+/// Pre-flight check: inputs that can't be read and output directories that can't be
+/// written, as readable messages. Output directories are tested by creating and deleting
+/// a probe file; one that doesn't exist yet is tested at its nearest existing ancestor,
+/// since it will be created there.
+pub fn find_access_problems(mappings: &[OutputMapping]) -> Vec<String> {
+    use std::io::Read;
+
+    let mut problems = Vec::new();
+
+    for m in mappings {
+        let readable = std::fs::File::open(&m.input_path)
+            .and_then(|mut f| f.read(&mut [0u8; 1]).map(|_| ()));
+        if let Err(e) = readable {
+            problems.push(format!("Cannot read {}: {}", m.input_path, e));
+        }
+    }
+
+    let mut output_dirs: Vec<std::path::PathBuf> = mappings.iter()
+        .filter_map(|m| Path::new(&m.output_path).parent().map(|p| p.to_path_buf()))
+        .collect();
+    output_dirs.sort();
+    output_dirs.dedup();
+
+    for dir in output_dirs {
+        let existing = dir.ancestors().find(|a| a.is_dir());
+        let result = match existing {
+            Some(existing) => probe_directory_writable(existing),
+            None => Err("no existing parent directory".to_string()),
+        };
+        if let Err(e) = result {
+            problems.push(format!("Cannot write to {}: {}", dir.display(), e));
+        }
+    }
+
+    problems
+}
+
+fn probe_directory_writable(dir: &Path) -> Result<(), String> {
+    let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_micros()).unwrap_or(0);
+    let probe = dir.join(format!(".write_test_{}_{}", std::process::id(), id));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| e.to_string())?;
+    std::fs::remove_file(&probe).map_err(|e| format!("created a test file but could not delete it: {}", e))
+}
+
+pub fn is_hardware_encoder(encoder: &str) -> bool {
+    ["nvenc", "amf", "qsv", "vaapi", "videotoolbox"].iter().any(|k| encoder.contains(k))
+}
+
+/// Filter chain end that moves frames into VAAPI surfaces (8-bit NV12)
+const VAAPI_UPLOAD: &str = "format=nv12,hwupload";
+
+/// First DRM render node, which is what VAAPI encodes on
+fn vaapi_device() -> String {
+    let mut nodes: Vec<String> = std::fs::read_dir("/dev/dri")
+        .map(|entries| entries.flatten()
+            .map(|e| e.path().to_string_lossy().to_string())
+            .filter(|p| p.contains("renderD"))
+            .collect())
+        .unwrap_or_default();
+    nodes.sort();
+    nodes.into_iter().next().unwrap_or_else(|| "/dev/dri/renderD128".to_string())
+}
+
+/// Hardware device setup that VAAPI/QSV encoders need; goes before the first -i
+fn hw_device_args(v_enc: &str) -> Vec<String> {
+    if v_enc.contains("vaapi") {
+        vec!["-vaapi_device".to_string(), vaapi_device()]
+    } else if v_enc.contains("qsv") {
+        ["-init_hw_device", "qsv=hw", "-filter_hw_device", "hw"].iter().map(|a| a.to_string()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// VAAPI encoders only take hardware frames: end the video filter chain (or add one) with
+/// an upload, and drop -pix_fmt, which would ask for a software format after it. QSV
+/// encoders accept software frames, so their args are left alone.
+fn with_hw_upload(v_enc: &str, args: Vec<String>) -> Vec<String> {
+    if !v_enc.contains("vaapi") {
+        return args;
+    }
+    let mut out = Vec::with_capacity(args.len() + 2);
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-pix_fmt" {
+            iter.next();
+            continue;
+        }
+        out.push(arg);
+    }
+    match out.iter().rposition(|a| a == "-vf" || a == "-filter:v") {
+        Some(i) if i + 1 < out.len() => out[i + 1] = format!("{},{}", out[i + 1], VAAPI_UPLOAD),
+        _ => out.extend(["-vf".to_string(), VAAPI_UPLOAD.to_string()]),
+    }
+    out
+}
+
+/// Hold a job back until it may start: free memory above the configured floor and, in eco
+/// mode, not on battery and the GPU not busy (for hardware encodes). Fails if the job was
+/// cancelled while waiting.
+fn wait_for_start_conditions(
+    app: &AppHandle,
+    input_path: &str,
+    config: &CompressionConfig,
+    hw_encode: bool,
+    progress: u8,
+    cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
 ) -> Result<(), String> {
-    // Clear any previous cancellation for this path (allows re-processing after cancel)
-    {
+    let mut announced = "";
+    // No ffmpeg pid yet: listed with the slot waiters so cancel_processing can reach the job
+    set_waiting_for_slot(input_path, true);
+    let result = loop {
+        if let Ok(set) = cancelled_paths.lock() {
+            if set.contains(&job_key(input_path)) {
+                break Err(CANCELLED.to_string());
+            }
+        }
+
+        let snapshot = crate::monitor::latest();
+        let status = if config.memory_low(&snapshot) {
+            "Deferred: low memory"
+        } else if config.eco_mode && snapshot.on_battery {
+            "Paused (on battery)"
+        } else if config.eco_mode && hw_encode && config.eco_gpu_busy() {
+            "Waiting (GPU busy)"
+        } else {
+            break Ok(());
+        };
+
+        if status != announced {
+            println!("[INFO] {} for {}", status, input_path);
+            crate::phase::emit_job_phase(app, input_path, crate::phase::JobPhase::Queued);
+            crate::events::emit_progress(app, ProgressPayload {
+                path: input_path.to_string(),
+                progress,
+                status: status.to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            announced = status;
+        }
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    };
+    set_waiting_for_slot(input_path, false);
+    result
+}
+
+/// Jobs blocked in wait_for_slot or wait_for_start_conditions; they have no ffmpeg pid, so
+/// cancel_processing checks here to know the job is live and can be marked cancelled
+static WAITING_FOR_SLOT: std::sync::Mutex<Option<std::collections::HashSet<String>>> = std::sync::Mutex::new(None);
+
+pub fn is_waiting_for_slot(input_path: &str) -> bool {
+    WAITING_FOR_SLOT.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.contains(&job_key(input_path))))
+        .unwrap_or(false)
+}
+
+fn set_waiting_for_slot(input_path: &str, waiting: bool) {
+    if let Ok(mut guard) = WAITING_FOR_SLOT.lock() {
+        let set = guard.get_or_insert_with(Default::default);
+        if waiting {
+            set.insert(job_key(input_path));
+        } else {
+            set.remove(&job_key(input_path));
+        }
+    }
+}
+
+/// Block until a slot of `pool` is free under `limit` (ENCODE or VERIFY).
+/// Returns None if the job is cancelled while waiting.
+fn wait_for_slot(
+    app: &AppHandle,
+    input_path: &str,
+    pool: &'static crate::pool::SlotPool,
+    limit: u32,
+    progress: u8,
+    cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+) -> Option<crate::pool::Slot> {
+    let verifying = std::ptr::eq(pool, &crate::pool::VERIFY);
+    let mut announced = false;
+    set_waiting_for_slot(input_path, true);
+    let slot = loop {
+        if let Some(slot) = pool.acquire(limit, std::time::Duration::from_millis(500)) {
+            break Some(slot);
+        }
+        if let Ok(set) = cancelled_paths.lock() {
+            if set.contains(&job_key(input_path)) {
+                break None;
+            }
+        }
+        if !announced {
+            println!("[INFO] Waiting for a free {} slot for {}", if verifying { "verification" } else { "encode" }, input_path);
+            if !verifying {
+                crate::phase::emit_job_phase(app, input_path, crate::phase::JobPhase::Queued);
+            }
+            crate::events::emit_progress(app, ProgressPayload {
+                path: input_path.to_string(),
+                progress,
+                status: if verifying { "Waiting (verification slots full)" } else { "Waiting (encode slots full)" }.to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            announced = true;
+        }
+    };
+    set_waiting_for_slot(input_path, false);
+    slot
+}
+
+/// Eco mode: suspends a running ffmpeg while the machine is on battery and resumes it
+/// on AC power. Stops watching when dropped.
+struct EcoWatcher {
+    done: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    progress: std::sync::Arc<std::sync::atomic::AtomicU8>,
+}
+
+impl EcoWatcher {
+    fn start(app: &AppHandle, input_path: &str, pid: u32) -> Self {
+        use std::sync::atomic::Ordering;
+
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let progress = std::sync::Arc::new(std::sync::atomic::AtomicU8::new(0));
+        crate::process::lower_priority(pid);
+
+        let (app, input_path) = (app.clone(), input_path.to_string());
+        let (done_flag, last_progress) = (done.clone(), progress.clone());
+        std::thread::spawn(move || {
+            let mut suspended = false;
+            while !done_flag.load(Ordering::Relaxed) {
+                let on_battery = crate::monitor::latest().on_battery;
+                if on_battery && !suspended && crate::process::suspend(pid) {
+                    suspended = true;
+                    set_eco_suspended(&input_path, true);
+                    println!("[INFO] Eco mode: on battery, suspended ffmpeg for {}", input_path);
+                    crate::events::emit_progress(&app, ProgressPayload {
+                        path: input_path.clone(),
+                        progress: last_progress.load(Ordering::Relaxed),
+                        status: "Paused (on battery)".to_string(),
+                        speed: 0.0,
+                        bitrate_kbps: 0.0,
+                        output_info: None,
+                        monotonic_ms: crate::monitor::monotonic_ms(),
+                        vmaf_segment: None,
+                    });
+                } else if !on_battery && suspended {
+                    suspended = false;
+                    set_eco_suspended(&input_path, false);
+                    // Left alone if the user paused it meanwhile (resume_processing wakes it
+                    // up) or an emergency pause holds it (resumed when that ends). Otherwise
+                    // ffmpeg's next progress line restores the normal status.
+                    if !is_user_paused(&input_path) && !crate::thermal::is_holding(&job_key(&input_path)) {
+                        crate::process::resume(pid);
+                        println!("[INFO] Eco mode: back on AC, resumed ffmpeg for {}", input_path);
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+            if suspended {
+                set_eco_suspended(&input_path, false);
+            }
+        });
+
+        EcoWatcher { done, progress }
+    }
+
+    fn set_progress(&self, progress: u8) {
+        self.progress.store(progress, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+impl Drop for EcoWatcher {
+    fn drop(&mut self) {
+        self.done.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Status (and sidecar result) of a job whose output was discarded for not saving space
+const KEPT_ORIGINAL: &str = "Kept original (no savings)";
+
+/// Codec name as ffprobe reports it, from the spellings users type ("h265", "x264", ...)
+fn canonical_codec(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.as_str() {
+        "h265" | "x265" => "hevc".to_string(),
+        "avc" | "x264" => "h264".to_string(),
+        "av01" => "av1".to_string(),
+        _ => name,
+    }
+}
+
+/// "Skipped (already AV1)" when the source's codec is in `skip_codecs`
+pub fn skip_codec_status(skip_codecs: &[String], codec: &str) -> Option<String> {
+    let codec = canonical_codec(codec);
+    if !skip_codecs.iter().any(|c| canonical_codec(c) == codec) {
+        return None;
+    }
+    let label = match codec.as_str() {
+        "h264" => "H.264".to_string(),
+        other => other.to_uppercase(),
+    };
+    Some(format!("Skipped (already {})", label))
+}
+
+/// A job the batch pre-filter looks at
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodecSkipJob {
+    pub path: String,
+    pub output_path: String,
+    pub config: CompressionConfig,
+}
+
+/// A file the batch pre-filter takes out of the queue
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodecSkip {
+    pub path: String,
+    pub status: String,
+}
+
+/// Probes at a time in the batch pre-filter
+const CODEC_SKIP_PROBES: u32 = 4;
+
+/// Batch pre-filter: finish the jobs whose source is already in one of their `skip_codecs`
+/// the way the job itself would (see finish_codec_skip), probing a few files at a time.
+/// Returns those files with their "Skipped (already ...)" status. Files that can't be
+/// probed or copied are left to the job.
+pub fn find_codec_skips(app: &AppHandle, ffmpeg_path: &str, jobs: Vec<CodecSkipJob>) -> Vec<CodecSkip> {
+    let ffprobe_path = resolve_ffprobe_path(ffmpeg_path);
+    let jobs = std::sync::Mutex::new(jobs.into_iter().filter(|j| !j.config.skip_codecs.is_empty()).collect::<Vec<_>>());
+    let skips = std::sync::Mutex::new(Vec::new());
+    // Popped in a closure so the lock isn't held while probing
+    let next = || jobs.lock().ok().and_then(|mut j| j.pop());
+    crate::pool::run_workers(CODEC_SKIP_PROBES, || while let Some(job) = next() {
+        let started_at = crate::sidecar::now_unix_secs();
+        let Ok(info) = get_video_info(Path::new(&job.path), &ffprobe_path) else { continue };
+        let Some(status) = skip_codec_status(&job.config.skip_codecs, &info.encoder) else { continue };
+        let output_path = final_output_path(ffmpeg_path, &job.path, &job.output_path, &job.config);
+        let replaces_source = resolves_to_same_file(&job.path, &output_path) || job.config.replace_original;
+        if finish_codec_skip(app, &job.path, &output_path, replaces_source, status.clone(), &job.config, Some(info), started_at).is_ok() {
+            if let Ok(mut s) = skips.lock() {
+                s.push(CodecSkip { path: job.path, status });
+            }
+        }
+    });
+    skips.into_inner().unwrap_or_default()
+}
+
+/// Compress one file, starting over with the session fallback if the encoder turns out to
+/// be gone. A CRF found by the VMAF search is searched again for the substitute encoder.
+#[allow(clippy::too_many_arguments)]
+pub fn run_ffmpeg_compression_task(
+    app: AppHandle,
+    ffmpeg_path: &str,
+    input_path: String,
+    output_path: String,
+    config: CompressionConfig,
+    duration_sec: f64,
+    pids: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+    vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>>,
+    pass_vmaf_derived_crf: Option<f32>,
+    pass_vmaf_search_score: Option<f64>,
+) -> Result<(), String> {
+    let (mut derived_crf, mut search_score) = (pass_vmaf_derived_crf, pass_vmaf_search_score);
+    let result = loop {
+        let result = run_compression_attempt(
+            app.clone(), ffmpeg_path, input_path.clone(), output_path.clone(), config.clone(), duration_sec,
+            pids.clone(), cancelled_paths.clone(), vmaf_state.clone(), derived_crf, search_score,
+        );
+        match result {
+            Err(e) if e == ENCODER_SWITCHED => {
+                if derived_crf.is_some() {
+                    match run_crf_search(
+                        app.clone(), ffmpeg_path, input_path.clone(), &config, duration_sec,
+                        pids.clone(), cancelled_paths.clone(), vmaf_state.clone(),
+                    ) {
+                        Ok((crf, vmaf)) => {
+                            derived_crf = Some(crf);
+                            search_score = Some(vmaf);
+                        }
+                        Err(e) => break Err(e),
+                    }
+                }
+            }
+            result => break result,
+        }
+    };
+    crate::throttle::clear_job_speed_limit(&input_path);
+    // Every way of running a job ends here, so the batch budget and history see them all
+    crate::history::finish(&input_path, &output_path, duration_sec, &result);
+    if result.is_ok() {
+        crate::budget::record_job(&app, &input_path, duration_sec);
+    }
+    result
+}
+
+/// Output path a job actually writes: {height}, {source_codec}, ... naming tokens filled in
+/// before anything uses the path (replace-original mode writes over the source's own name instead)
+fn final_output_path(ffmpeg_path: &str, input_path: &str, output_path: &str, config: &CompressionConfig) -> String {
+    if config.replace_original {
+        crate::backup::replacement_path(input_path, output_path)
+    } else {
+        crate::naming::resolve_output_path(&resolve_ffprobe_path(ffmpeg_path), input_path, output_path, config)
+    }
+}
+
+/// Finish a job whose source is already in a skipped codec: the source is copied to the
+/// output as it is (unless it replaces the source) and the job reports `status`
+#[allow(clippy::too_many_arguments)]
+fn finish_codec_skip(
+    app: &AppHandle,
+    input_path: &str,
+    output_path: &str,
+    replaces_source: bool,
+    status: String,
+    config: &CompressionConfig,
+    input_info: Option<VideoInfo>,
+    started_at: u64,
+) -> Result<(), String> {
+    println!("[INFO] {}: {}", status, input_path);
+    if !replaces_source {
+        if let Some(parent) = Path::new(output_path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = crate::transfer::copy_file(input_path, output_path, config.copy_bandwidth_limit_mbps) {
+            eprintln!("[ERROR] Failed to copy file during codec skip for '{}': {}", input_path, e);
+            return Err(format!("{}, but failed to copy file: {}", status, e));
+        }
+    }
+    write_result_sidecar(config, input_path, output_path, &status, (None, None), input_info.as_ref(), started_at);
+    crate::phase::emit_job_phase(app, input_path, crate::phase::JobPhase::Skipped);
+    crate::events::emit_progress(app, ProgressPayload {
+        path: input_path.to_string(),
+        progress: 100,
+        status,
+        speed: 0.0,
+        bitrate_kbps: input_info.as_ref().and_then(|i| i.bitrate_kbps).unwrap_or(0.0),
+        output_info: with_quality_estimate(input_info, config),
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
+    });
+    Ok(())
+}
+
+fn run_compression_attempt(
+    app: AppHandle,
+    ffmpeg_path: &str,
+    input_path: String,
+    output_path: String,
+    config: CompressionConfig,
+    duration_sec: f64,
+    pids: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+    vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>>,
+    // Optionals passed from search phase
+    pass_vmaf_derived_crf: Option<f32>,
+    pass_vmaf_search_score: Option<f64>,
+) -> Result<(), String> {
+    let started_at = crate::sidecar::now_unix_secs();
+    let _spawn = crate::process::job_scope(&config);
+    let config = if config.quality_tier.is_empty() {
+        config
+    } else {
+        let resolution = get_metadata_quick(&input_path, &resolve_ffprobe_path(ffmpeg_path)).ok().map(|i| i.resolution);
+        crate::tiers::apply_tier(&config, resolution.as_deref())
+    };
+    let config = with_encoder_fallback(&config);
+    crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Queued);
+
+    // Clear any previous cancellation for this path (allows re-processing after cancel).
+    // A pipeline already did that when it started; a cancel since then is meant for this job.
+    if !is_pipeline_active(&input_path) {
         if let Ok(mut set) = cancelled_paths.lock() {
-            set.remove(&input_path);
+            set.remove(&job_key(&input_path));
         }
     }
+    set_user_paused(&input_path, false);
+
+    let output_path = final_output_path(ffmpeg_path, &input_path, &output_path, &config);
+
+    // Refuse to replace the source unless explicitly allowed
+    let overwrites_source = resolves_to_same_file(&input_path, &output_path);
+    let replaces_source = overwrites_source || config.replace_original;
+    if overwrites_source && !config.allow_overwrite_source && !config.replace_original {
+        eprintln!("[ERROR] Output for '{}' resolves to the source file and overwriting is not allowed", input_path);
+        crate::events::emit_progress(&app, ProgressPayload {
+            path: input_path.clone(),
+            progress: 0,
+            status: "Error".to_string(),
+            speed: 0.0,
+            bitrate_kbps: 0.0,
+            output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
+        });
+        return Err("Output path is the source file; enable 'allow overwrite source' to replace it".to_string());
+    }
 
     // 0. Resolve ffprobe path
     let ffprobe_path = resolve_ffprobe_path(ffmpeg_path);
 
     // 1. Get Input Info for Bitrate Analysis
-    let input_info = get_video_info(Path::new(&input_path), &ffprobe_path).ok();
+    crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Probing);
+    let input_probe = get_video_info(Path::new(&input_path), &ffprobe_path);
+    reject_protected_source(&app, &input_path, &input_probe)?;
+    let input_info = input_probe.ok();
     let input_bitrate_kbps = input_info.as_ref().and_then(|i| i.bitrate_kbps);
+    let config = with_video_stream(config, input_info.as_ref());
+    let config = with_size_percent(config, input_info.as_ref())?;
+
+    // 2a. Source already in a codec the user doesn't want to re-encode
+    if let Some(status) = input_info.as_ref().and_then(|i| skip_codec_status(&config.skip_codecs, &i.encoder)) {
+        return finish_codec_skip(&app, &input_path, &output_path, replaces_source, status, &config, input_info, started_at);
+    }
 
     // 2. Bitrate Bypass Check
     if config.compression_mode == "bitrate" && config.min_bitrate_threshold > 0 {
@@ -1802,33 +4465,8 @@ pub fn run_ffmpeg_compression_task(
              if br < config.min_bitrate_threshold as f64 {
                 // SKIP!
                 println!("Skipping {} because bitrate {:.2} < threshold {}", input_path, br, config.min_bitrate_threshold);
-                
-                // Copy if needed
-                if input_path != output_path {
-                     if let Some(parent) = std::path::Path::new(&output_path).parent() {
-                        if !parent.exists() {
-                            let _ = std::fs::create_dir_all(parent);
-                        }
-                    }
-                    if let Err(e) = std::fs::copy(&input_path, &output_path) {
-                        eprintln!("[ERROR] Failed to copy file during bitrate threshold skip for '{}': {}", input_path, e);
-                        eprintln!("[INFO] Source: {}", input_path);
-                        eprintln!("[INFO] Destination: {}", output_path);
-                        return Err(format!("Skipped (bitrate check), but failed to copy file: {}", e));
-                    }
-                }
-
-                // If input == output, we effectively did nothing, which is correct (metadata preserved).
-                
-                let _ = app.emit("video-progress", ProgressPayload {
-                    path: input_path.clone(),
-                    progress: 100,
-                    status: "Skipped".to_string(),
-                    speed: 0.0,
-                    bitrate_kbps: br,
-                    output_info: input_info,
-                });
-                return Ok(());
+                // Copies the source unless it is being replaced, where it simply stays in place
+                return finish_codec_skip(&app, &input_path, &output_path, replaces_source, "Skipped".to_string(), &config, input_info, started_at);
              }
         }
     }
@@ -1836,7 +4474,18 @@ pub fn run_ffmpeg_compression_task(
     // 3. VMAF-guided CRF Search (if compression mode is "vmaf")
     // 3. VMAF-guided CRF Search - Logic removed here, expects passed values
     // Using shadowed variables to map arguments to local logic
-    let vmaf_derived_crf = pass_vmaf_derived_crf;
+    // The bitrate measurement below encodes samples like the search did, so it keeps the
+    // search CRF; the final encode gets it with the sample preset offset applied
+    let vmaf_search_crf = pass_vmaf_derived_crf;
+    let vmaf_derived_crf = vmaf_search_crf.map(|crf| {
+        let v_enc = if config.video_encoder.is_empty() { "libx264" } else { config.video_encoder.as_str() };
+        config.final_crf(crf, v_enc)
+    });
+    if let (Some(search), Some(fin)) = (vmaf_search_crf, vmaf_derived_crf) {
+        if search != fin {
+            println!("[INFO] CRF {:.1} found with preset {}; encoding at CRF {:.1}", search, config.sample_preset.trim(), fin);
+        }
+    }
     let vmaf_search_score = pass_vmaf_search_score;
     // Note: Search was already performed if mode is vmaf
     
@@ -1846,7 +4495,51 @@ pub fn run_ffmpeg_compression_task(
         // Actually, logic below: "let crf_to_use = vmaf_derived_crf.unwrap_or(23.0);" handles None.
     }
 
-    let temp_output_path = format!("{}.tmp.{}", output_path, config.target_format);
+    // 3b. VMAF-targeted ABR: measure what bitrate the found CRF produces on the samples
+    // and use it as a capped two-pass target. Falls back to the plain CRF encode if
+    // the measurement fails.
+    let vmaf_abr_bitrate = if config.compression_mode == "vmaf_abr" {
+        if let Some(crf) = vmaf_search_crf {
+            crate::events::emit_progress(&app, ProgressPayload {
+                path: input_path.clone(),
+                progress: 50,
+                status: "Measuring bitrate".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            let source_filters = reference_filter_list(ffmpeg_path, &input_path, input_info.as_ref(), &config);
+            let measured = measure_sample_bitrate(ffmpeg_path, &input_path, crf, duration_sec, &config, &pids, &source_filters);
+            if measured.is_none() {
+                eprintln!("[WARNING] Failed to measure sample bitrate for '{}', falling back to CRF {:.1}", input_path, crf);
+            }
+            measured
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    let two_pass = (config.compression_mode == "bitrate" && config.two_pass) || vmaf_abr_bitrate.is_some();
+
+    // Rate control actually used, recorded in the sidecar
+    let sidecar_rate: (Option<f32>, Option<u32>) = match config.compression_mode.as_str() {
+        "crf" => (Some(config.target_crf), None),
+        "bitrate" => (None, Some(config.target_bitrate)),
+        "vmaf" | "vmaf_abr" => (vmaf_derived_crf, vmaf_abr_bitrate),
+        _ => (None, None),
+    };
+
+    // The container comes from the output path the frontend built (mirrors output_format_for)
+    let output_format = Path::new(&output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_else(|| config.output_format_for(&input_path));
+    let temp_output_path = format!("{}.tmp.{}", output_path, output_format);
+    let v_enc_name = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
     
     // Ensure output directory exists before starting FFmpeg
     if let Some(parent) = std::path::Path::new(&temp_output_path).parent() {
@@ -1859,6 +4552,56 @@ pub fn run_ffmpeg_compression_task(
         }
     }
 
+    // Progress window (base, span) of each ffmpeg run. Two-pass bitrate uses 0-50 / 50-100;
+    // the VMAF modes already spent 0-50 on the CRF search.
+    let (pass1_range, final_range): ((u32, u32), (u32, u32)) = if config.uses_vmaf_search() && two_pass {
+        ((50, 25), (75, 25))
+    } else if two_pass {
+        ((0, 50), (50, 50))
+    } else if config.uses_vmaf_search() {
+        ((0, 0), (50, 50))
+    } else {
+        ((0, 0), (0, 100))
+    };
+
+    // Source opened exclusively elsewhere (player, sync client): wait a bit, then give up
+    // with "File in use" so the queue can retry the file later
+    if let Err(e) = crate::filelock::wait_until_readable(&input_path) {
+        eprintln!("[WARNING] {} for '{}'", e, input_path);
+        emit_file_in_use(&app, &input_path);
+        return Err(e);
+    }
+
+    // Don't start while memory is low, or (eco mode) on battery / with a busy GPU, then
+    // wait for one of the max_concurrent_encodes slots (held until ffmpeg exits). Taken
+    // before the arguments are built: the loudness measurement, auto-tune and the source
+    // analysis below decode the source too and count as part of the encode.
+    let encode_slot = {
+        let start_progress = if two_pass { pass1_range.0 } else { final_range.0 } as u8;
+        let hw_encode = is_hardware_encoder(&config.video_encoder);
+        let slot = match wait_for_start_conditions(&app, &input_path, &config, hw_encode, start_progress, &cancelled_paths) {
+            Ok(()) => wait_for_slot(&app, &input_path, &crate::pool::ENCODE, config.max_concurrent_encodes, start_progress, &cancelled_paths),
+            Err(_) => None,
+        };
+        let Some(slot) = slot else {
+            if let Ok(mut set) = cancelled_paths.lock() {
+                set.remove(&job_key(&input_path));
+            }
+            crate::events::emit_progress(&app, ProgressPayload {
+                path: input_path.clone(),
+                progress: 0,
+                status: "Cancelled".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            return Err(CANCELLED.to_string());
+        };
+        slot
+    };
+
     let mut args = Vec::new();
 
     if config.compression_mode == "custom" {
@@ -1887,13 +4630,22 @@ pub fn run_ffmpeg_compression_task(
              let _ = std::fs::remove_file(&temp_output_path);
         }
     } else {
+        // Check if we are in copy mode (stream copy, no re-encoding)
+        let is_copy_mode = config.compression_mode == "copy";
+
         args.push("-y".to_string());
         args.push("-hide_banner".to_string());
+        if !is_copy_mode {
+            args.extend(hw_device_args(&config.video_encoder));
+        }
+        args.extend(forced_decoder_args(&config));
+        let rotation = input_info.as_ref().and_then(|i| i.rotation);
+        args.extend(rotation_input_args(&config, rotation, is_copy_mode));
         args.push("-i".to_string());
         args.push(input_path.clone());
-
-        // Check if we are in copy mode (stream copy, no re-encoding)
-        let is_copy_mode = config.compression_mode == "copy";
+        let subtitles = subtitle_plan(&config, input_info.as_ref(), &output_format);
+        let attachments = config.keep_attachments && output_format == "mkv" && !user_maps_streams(&config);
+        args.extend(output_map_args(&config, input_info.as_ref(), &subtitles, attachments));
 
         // Video Encoder
         args.push("-c:v".to_string());
@@ -1908,32 +4660,7 @@ pub fn run_ffmpeg_compression_task(
 
         // Compression Mode (skip for copy mode)
         if !is_copy_mode {
-            match config.compression_mode.as_str() {
-                "bitrate" => {
-                    args.push("-b:v".to_string());
-                    args.push(format!("{}k", config.target_bitrate));
-                },
-                "crf" => {
-                    if v_enc.contains("libx264") || v_enc.contains("libx265") || v_enc.contains("libsvtav1") || v_enc.contains("vp9") {
-                         args.push("-crf".to_string());
-                         args.push(format!("{}", config.target_crf));
-                    } else if v_enc.contains("nvenc") {
-                         args.push("-cq".to_string());
-                         args.push(format!("{}", config.target_crf));
-                    } else {
-                         args.push("-q:v".to_string());
-                         args.push(format!("{}", config.target_crf));
-                    }
-                },
-                "vmaf" => {
-                    // Use CRF derived from VMAF search, or fallback to 23
-                    let crf_to_use = vmaf_derived_crf.unwrap_or(23.0);
-                    let crf_arg = get_crf_arg(&v_enc);
-                    args.push(crf_arg.to_string());
-                    args.push(format!("{}", crf_to_use));
-                },
-                _ => {}
-            }
+            args.extend(rate_control_args(&config, &v_enc, vmaf_derived_crf, vmaf_abr_bitrate));
         }
 
 
@@ -1947,56 +4674,110 @@ pub fn run_ffmpeg_compression_task(
             config.audio_encoder.clone() 
         };
         args.push(a_enc.clone());
-
-        // Resolution (skip for copy mode - cannot scale when copying streams)
-        if !is_copy_mode && config.max_resolution.enabled && config.max_resolution.width > 0 && config.max_resolution.height > 0 {
-            args.push("-vf".to_string());
-            args.push(format!("scale='min({},iw)':-2", config.max_resolution.width));
+        args.extend(audio_option_args(&config, &a_enc));
+        args.extend(subtitle_codec_args(&config, &subtitles));
+        if attachments {
+            args.extend(["-c:t".to_string(), "copy".to_string()]);
         }
-
-        // Custom Filters (always apply - these can include things like -movflags +faststart)
-        for filter in &config.custom_filters {
-            if !filter.trim().is_empty() {
-                 let parts: Vec<&str> = filter.split_whitespace().collect();
-                 for p in parts {
-                     args.push(p.to_string());
-                 }
+        args.extend(metadata_args(&config));
+        args.extend(rotation_output_args(&config, rotation, is_copy_mode));
+
+        let burn_in = if is_copy_mode {
+            // Nothing is re-encoded, so every copied stream must fit the container as it is
+            let problems = input_info.as_ref()
+                .and_then(|info| crate::remux::blocking_summary(&crate::remux::check(info, &config, &output_format)));
+            problems.map_or(Ok(None), Err)
+        } else {
+            check_container_compatibility(&output_format, &v_enc, &a_enc)
+                .and_then(|_| check_audio_settings(&config, &a_enc))
+                .and_then(|_| check_encoder_options(&config, &v_enc))
+                .and_then(|_| burn_in_filter(&config, &input_path, input_info.as_ref()))
+        };
+        let burn_in = match burn_in {
+            Ok(filter) => filter,
+            Err(e) => {
+                eprintln!("[ERROR] Incompatible output for '{}': {}", input_path, e);
+                crate::events::emit_progress(&app, ProgressPayload {
+                    path: input_path.clone(),
+                    progress: 0,
+                    status: "Error".to_string(),
+                    speed: 0.0,
+                    bitrate_kbps: 0.0,
+                    output_info: None,
+                    monotonic_ms: crate::monitor::monotonic_ms(),
+                    vmaf_segment: None,
+                });
+                return Err(e);
             }
-        }
-        
-        // Encoder Specific Params (skip for copy mode - no encoding)
-        if !is_copy_mode {
-            if let Some(enc_cfg) = config.available_video_encoders.iter().find(|e| e.value == v_enc) {
-                for param in &enc_cfg.custom_params {
-                     let parts: Vec<&str> = param.split_whitespace().collect();
-                     for p in parts {
-                         args.push(p.to_string());
-                     }
+        };
+
+        // Two-pass loudness normalization: measure the source now, apply the measured values
+        // in the encode. A failed measurement (e.g. no audio) leaves the audio as it is.
+        if config.loudnorm_target < 0.0 && a_enc != "copy" {
+            crate::events::emit_progress(&app, ProgressPayload {
+                path: input_path.clone(),
+                progress: 0,
+                status: "Measuring loudness".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            // Each output track gets its own measurement; unknown track layout: the first
+            // track's measurement for all of them
+            let tracks = output_audio_tracks(&config, input_info.as_ref(), &subtitles, attachments);
+            let mut normalized = false;
+            for (output_index, track) in tracks.clone().unwrap_or_else(|| vec![0]).into_iter().enumerate() {
+                match crate::loudness::measure(ffmpeg_path, &input_path, track, config.loudnorm_target, &pids) {
+                    Ok(measurement) => {
+                        let option = if tracks.is_some() { format!("-filter:a:{}", output_index) } else { "-af".to_string() };
+                        args.extend([option, crate::loudness::filter(config.loudnorm_target, &measurement)]);
+                        normalized = true;
+                    }
+                    Err(e) => eprintln!("[WARNING] Skipping loudness normalization of audio {} for {}: {}", track, input_path, e),
                 }
             }
-             if let Some(enc_cfg) = config.available_audio_encoders.iter().find(|e| e.value == a_enc) {
-                for param in &enc_cfg.custom_params {
-                     let parts: Vec<&str> = param.split_whitespace().collect();
-                     for p in parts {
-                         args.push(p.to_string());
-                     }
-                }
+            // loudnorm upsamples to 192 kHz internally
+            if normalized && config.audio_sample_rate == 0 {
+                args.extend(["-ar".to_string(), "48000".to_string()]);
             }
         }
 
+        // Before the custom params so the user's own encoder flags take precedence
+        if config.auto_tune && !is_copy_mode {
+            let content = crate::tuning::content_type_for(ffmpeg_path, &input_path, duration_sec);
+            println!("[INFO] Auto-tune for {}: {:?}", input_path, content);
+            args.extend(crate::tuning::auto_tune_args(content, &v_enc));
+        }
+
+        let source_filters = if is_copy_mode {
+            Vec::new()
+        } else {
+            source_filter_list(ffmpeg_path, &input_path, input_info.as_ref(), &config)
+        };
+        args.extend(encoding_option_args(&config, &v_enc, &a_enc, is_copy_mode, &source_filters, burn_in.as_deref()));
+        if !is_copy_mode {
+            args = crate::gpuquirks::with_bframe_quirk(&config, &v_enc, with_hw_upload(&v_enc, args));
+        }
+
         // threads
-        if config.ffmpeg_threads > 0 {
+        let threads = config.effective_ffmpeg_threads();
+        if threads > 0 {
             args.push("-threads".to_string());
-            args.push(format!("{}", config.ffmpeg_threads));
+            args.push(format!("{}", threads));
         }
 
         args.push("-progress".to_string());
         args.push("pipe:2".to_string());
     }
-    
+
+    // Exact command line of every pass, kept with the result for reproducibility
+    let mut ffmpeg_commands: Vec<String> = Vec::new();
+
     // 2-Pass Logic
     let mut pass_log_prefix_opt = None;
-    if config.compression_mode == "bitrate" && config.two_pass {
+    if two_pass {
         let pass_log_prefix = format!("{}.passlog", temp_output_path);
         pass_log_prefix_opt = Some(pass_log_prefix.clone());
         
@@ -2015,27 +4796,29 @@ pub fn run_ffmpeg_compression_task(
              pass1_args.push("/dev/null".to_string());
         }
 
-        let _ = app.emit("video-progress", ProgressPayload {
+        crate::events::emit_progress(&app, ProgressPayload {
             path: input_path.clone(),
-            progress: 0,
+            progress: pass1_range.0 as u8,
             status: "Processing (Pass 1/2)".to_string(),
             speed: 0.0,
             bitrate_kbps: 0.0,
             output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
         });
 
         println!("Starting Pass 1 for {}", input_path);
+        crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Pass1);
+        let pass1_command = format_command_line(ffmpeg_path, &pass1_args);
+        println!("[INFO] Pass 1 command: {}", pass1_command);
+        crate::history::record_command(&input_path, pass1_command.clone());
+        ffmpeg_commands.push(pass1_command);
         
-        let mut command = Command::new(ffmpeg_path);
+        let mut command = crate::process::ffmpeg_command(ffmpeg_path);
         command.args(&pass1_args)
             .stdout(Stdio::null()) // Use null to avoid blocking if we don't read it
             .stderr(Stdio::piped());
 
-        #[cfg(windows)]
-        {
-            use std::os::windows::process::CommandExt;
-            command.creation_flags(0x08000000);
-        }
 
         let mut pass1_child = command.spawn()
             .map_err(|e| {
@@ -2047,9 +4830,10 @@ pub fn run_ffmpeg_compression_task(
         let p1_pid = pass1_child.id();
         {
             if let Ok(mut map) = pids.lock() {
-                map.insert(input_path.clone(), p1_pid);
+                map.insert(job_key(&input_path), p1_pid);
             }
         }
+        let p1_eco = config.eco_mode.then(|| EcoWatcher::start(&app, &input_path, p1_pid));
 
         let p1_stderr = pass1_child.stderr.take().ok_or_else(|| {
             eprintln!("[ERROR] Failed to capture Pass 1 stderr for '{}'", input_path);
@@ -2081,7 +4865,7 @@ pub fn run_ffmpeg_compression_task(
             // Check cancellation
             let is_cancelled = {
                  if let Ok(set) = cancelled_paths.lock() {
-                     set.contains(&input_path)
+                     set.contains(&job_key(&input_path))
                  } else {
                      false
                  }
@@ -2110,15 +4894,20 @@ pub fn run_ffmpeg_compression_task(
                 } else {
                     0
                 };
-                // Pass 1 is 0-50% of total progress
-                let mapped_percent = percent.min(100) / 2;
-                let _ = app.emit("video-progress", ProgressPayload {
+                // Pass 1 covers its own window of total progress
+                let mapped_percent = (pass1_range.0 + percent.min(100) as u32 * pass1_range.1 / 100) as u8;
+                if let Some(eco) = &p1_eco {
+                    eco.set_progress(mapped_percent);
+                }
+                crate::events::emit_progress(&app, ProgressPayload {
                     path: input_path.clone(),
                     progress: mapped_percent,
                     status: "Processing (Pass 1/2)".to_string(),
                     speed: p1_speed,
                     bitrate_kbps: 0.0, // Pass 1 has no meaningful bitrate
                     output_info: None,
+                    monotonic_ms: crate::monitor::monotonic_ms(),
+                    vmaf_segment: None,
                 });
             }
         }
@@ -2127,18 +4916,19 @@ pub fn run_ffmpeg_compression_task(
             eprintln!("[ERROR] Failed to wait on Pass 1 process for '{}': {}", input_path, e);
             format!("Failed to wait on video Pass 1: {}", e)
         })?;
+        drop(p1_eco);
         
         {
             if let Ok(mut map) = pids.lock() {
-                map.remove(&input_path);
+                map.remove(&job_key(&input_path));
             }
         }
 
         // Check cancellation again to be sure
         let is_cancelled = {
              if let Ok(mut set) = cancelled_paths.lock() {
-                 if set.contains(&input_path) {
-                     set.remove(&input_path); // Clear it here since we are stopping early
+                 if set.contains(&job_key(&input_path)) {
+                     set.remove(&job_key(&input_path)); // Clear it here since we are stopping early
                      true
                  } else {
                      false
@@ -2149,20 +4939,22 @@ pub fn run_ffmpeg_compression_task(
         };
 
         if is_cancelled {
-             let _ = app.emit("video-progress", ProgressPayload {
+             crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: 0,
                 status: "Cancelled".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
             // Robust cleanup Pass 1 logs
             std::thread::sleep(std::time::Duration::from_millis(200));
             if let Some(prefix) = &pass_log_prefix_opt {
                  cleanup_pass_logs(prefix, &temp_output_path);
             }
-            return Err("Cancelled during Pass 1".to_string());
+            return Err(CANCELLED.to_string());
         }
 
         if !p1_status.success() {
@@ -2183,7 +4975,14 @@ pub fn run_ffmpeg_compression_task(
              if let Some(prefix) = &pass_log_prefix_opt {
                  cleanup_pass_logs(prefix, &temp_output_path);
              }
-             return Err("Pass 1 failed (unknown error)".to_string());
+
+             if config.compression_mode != "custom"
+                 && is_encoder_unavailable_error(&p1_stderr_lines.join("\n"))
+                 && switch_to_fallback_encoder(&app, ffmpeg_path, &v_enc_name, &config).is_some()
+             {
+                 return Err(ENCODER_SWITCHED.to_string());
+             }
+             return Err(format!("Pass 1 failed{}", stderr_summary(&p1_stderr_lines)));
         }
 
         // Prepare Pass 2 args (modify the original args which will be used for the main spawn)
@@ -2192,21 +4991,23 @@ pub fn run_ffmpeg_compression_task(
         args.push("-passlogfile".to_string());
         args.push(pass_log_prefix);
         
-         let _ = app.emit("video-progress", ProgressPayload {
+         crate::events::emit_progress(&app, ProgressPayload {
             path: input_path.clone(),
-            progress: 50, // Pass 1 complete (50%), starting Pass 2
+            progress: final_range.0 as u8, // Pass 1 complete, starting Pass 2
             status: "Processing (Pass 2/2)".to_string(),
             speed: 0.0,
             bitrate_kbps: 0.0,
             output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
         });
     }
 
     args.push(temp_output_path.clone());
 
-    let status_str = if config.compression_mode == "bitrate" && config.two_pass {
+    let status_str = if two_pass {
         "Processing (Pass 2/2)".to_string()
-    } else if config.compression_mode == "vmaf" {
+    } else if config.uses_vmaf_search() {
         // Use "Found CRF" prefix so frontend can calculate progress as 50-100%
         if let Some(crf) = vmaf_derived_crf {
             format!("Found CRF {:.0}", crf)
@@ -2216,29 +5017,31 @@ pub fn run_ffmpeg_compression_task(
     } else {
         "Processing".to_string()
     };
-    // Initial progress: 50 for phase 2 modes (Pass 2 or VMAF compression), 0 otherwise
-    let is_phase2 = (config.compression_mode == "bitrate" && config.two_pass) || config.compression_mode == "vmaf";
-    let initial_progress = if is_phase2 { 50 } else { 0 };
+    // Initial progress: start of the final encode's window (50 for Pass 2 or VMAF compression)
+    let initial_progress = final_range.0 as u8;
 
-    let _ = app.emit("video-progress", ProgressPayload {
+    crate::events::emit_progress(&app, ProgressPayload {
         path: input_path.clone(),
         progress: initial_progress,
         status: status_str.clone(),
         speed: 0.0,
         bitrate_kbps: 0.0,
         output_info: None,
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
     });
 
-    let mut command = Command::new(ffmpeg_path);
+    crate::phase::emit_job_phase(&app, &input_path, if two_pass { crate::phase::JobPhase::Pass2 } else { crate::phase::JobPhase::Encoding });
+    let final_command = format_command_line(ffmpeg_path, &args);
+    println!("[INFO] FFmpeg command: {}", final_command);
+    crate::history::record_command(&input_path, final_command.clone());
+    ffmpeg_commands.push(final_command);
+
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
     command.args(&args)
         .stdout(Stdio::null())
         .stderr(Stdio::piped());
 
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
-    }
 
     let mut child = command.spawn()
         .map_err(|e| {
@@ -2251,9 +5054,10 @@ pub fn run_ffmpeg_compression_task(
     let pid = child.id();
     {
         if let Ok(mut map) = pids.lock() {
-            map.insert(input_path.clone(), pid);
+            map.insert(job_key(&input_path), pid);
         }
     }
+    let eco = config.eco_mode.then(|| EcoWatcher::start(&app, &input_path, pid));
 
     let stderr = child.stderr.take().ok_or_else(|| {
         eprintln!("[ERROR] Failed to capture stderr for '{}'", input_path);
@@ -2272,6 +5076,8 @@ pub fn run_ffmpeg_compression_task(
     // Collect stderr lines for error reporting
     let mut stderr_lines: Vec<String> = Vec::new();
     let max_stderr_lines = 50; // Keep last 50 lines
+    let mut summary_lines: Vec<String> = Vec::new(); // Encoder end-of-run summary
+    let encode_started = std::time::Instant::now();
 
     println!("Starting ffmpeg for {}, duration: {}s", input_path, duration_sec);
 
@@ -2281,6 +5087,10 @@ pub fn run_ffmpeg_compression_task(
             Err(_) => break,
         };
         
+        if crate::encstats::is_summary_line(&line) {
+            summary_lines.push(line.clone());
+        }
+
         // Collect stderr lines (excluding progress lines which are verbose)
         if !line.contains("progress=") && !line.trim().is_empty() {
             stderr_lines.push(line.clone());
@@ -2348,41 +5158,48 @@ pub fn run_ffmpeg_compression_task(
                                  }
                              }
 
-                             // Handle file copy
-                             if input_path != output_path {
+                             // Handle file copy; a replaced source simply stays in place
+                             if !replaces_source {
                                 if let Some(parent) = std::path::Path::new(&output_path).parent() {
                                     if !parent.exists() {
                                         let _ = std::fs::create_dir_all(parent);
                                     }
                                 }
-                                if let Err(e) = std::fs::copy(&input_path, &output_path) {
+                                if let Err(e) = crate::transfer::copy_file(&input_path, &output_path, config.copy_bandwidth_limit_mbps) {
                                      // Error during copy
                                      eprintln!("[ERROR] Failed to copy file during auto-skip for '{}': {}", input_path, e);
                                      eprintln!("[INFO] Source: {}", input_path);
                                      eprintln!("[INFO] Destination: {}", output_path);
-                                      let _ = app.emit("video-progress", ProgressPayload {
+                                      crate::events::emit_progress(&app, ProgressPayload {
                                         path: input_path.clone(),
                                         progress: 0,
                                         status: "Error".to_string(),
                                         speed: 0.0,
                                         bitrate_kbps: 0.0,
                                         output_info: None,
+                                        monotonic_ms: crate::monitor::monotonic_ms(),
+                                        vmaf_segment: None,
                                     });
                                      return Err(format!("Skipped (CRF check), but failed to copy file: {}", e));
                                 }
                              }
 
-                              let _ = app.emit("video-progress", ProgressPayload {
+                             write_result_sidecar(&config, &input_path, &output_path, "Skipped", (None, None), input_info.as_ref(), started_at);
+
+                              crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Skipped);
+                              crate::events::emit_progress(&app, ProgressPayload {
                                 path: input_path.clone(),
                                 progress: 100,
                                 status: "Skipped".to_string(),
                                 speed: 0.0,
                                 bitrate_kbps: in_br, // Report original bitrate
-                                output_info: input_info, // Report original info
+                                output_info: with_quality_estimate(input_info, &config), // Report original info
+                                monotonic_ms: crate::monitor::monotonic_ms(),
+                                vmaf_segment: None,
                             });
 
                              if let Ok(mut map) = pids.lock() {
-                                map.remove(&input_path);
+                                map.remove(&job_key(&input_path));
                             }
 
                              return Ok(());
@@ -2397,21 +5214,21 @@ pub fn run_ffmpeg_compression_task(
                 0
             };
             
-            // For VMAF mode or 2-pass mode Pass 2, map progress (0-100) to (50-100)
-            let is_two_pass_phase2 = config.compression_mode == "bitrate" && config.two_pass;
-            let final_percent = if config.compression_mode == "vmaf" || is_two_pass_phase2 {
-                50 + (percent.min(100) / 2)
-            } else {
-                percent.min(100)
-            };
+            // For VMAF modes or 2-pass Pass 2, map progress (0-100) into the final encode's window
+            let final_percent = (final_range.0 + percent.min(100) as u32 * final_range.1 / 100) as u8;
+            if let Some(eco) = &eco {
+                eco.set_progress(final_percent);
+            }
             
-            let _ = app.emit("video-progress", ProgressPayload {
+            crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: final_percent,
                 status: status_str.clone(),
                 speed: current_speed,
                 bitrate_kbps: current_bitrate,
                 output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
         }
     }
@@ -2420,38 +5237,119 @@ pub fn run_ffmpeg_compression_task(
         eprintln!("[ERROR] Failed to wait on FFmpeg process for '{}': {}", input_path, e);
         format!("Failed to wait on ffmpeg: {}", e)
     })?;
+    drop(eco);
+    drop(encode_slot);
     
     {
         if let Ok(mut map) = pids.lock() {
-            map.remove(&input_path);
+            map.remove(&job_key(&input_path));
         }
     }
     
     if status.success() {
-        // 1. Verify the output video
-        let verify_result = verify_video(ffmpeg_path, &temp_output_path);
+        // 1. Verify the output video, in a verification slot so the next encode can start
+        let Some(verify_slot) = wait_for_slot(&app, &input_path, &crate::pool::VERIFY, config.max_concurrent_verifications, 100, &cancelled_paths) else {
+            crate::scratch::remove_scratch_file(Path::new(&temp_output_path), config.secure_temp_files);
+            if let Some(prefix) = &pass_log_prefix_opt {
+                cleanup_pass_logs(prefix, &temp_output_path);
+            }
+            if let Ok(mut set) = cancelled_paths.lock() {
+                set.remove(&job_key(&input_path));
+            }
+            crate::events::emit_progress(&app, ProgressPayload {
+                path: input_path.clone(),
+                progress: 0,
+                status: "Cancelled".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            return Err(CANCELLED.to_string());
+        };
+        crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Verifying);
+        crate::events::emit_progress(&app, ProgressPayload {
+            path: input_path.clone(),
+            progress: 100,
+            status: "Verifying".to_string(),
+            speed: 0.0,
+            bitrate_kbps: 0.0,
+            output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
+        });
+        let verify_result = verify_video(ffmpeg_path, &temp_output_path).and_then(|_| {
+            if config.verify_frame_count && config.compression_mode != "custom" {
+                verify_frame_count(&resolve_ffprobe_path(ffmpeg_path), &input_path, &temp_output_path, &config, &args)
+            } else {
+                Ok(())
+            }
+        });
+        drop(verify_slot);
         
         if let Err(e) = verify_result {
             eprintln!("[ERROR] Video validation failed for '{}': {}", input_path, e);
             eprintln!("[INFO] Output file may be corrupted or incomplete: {}", temp_output_path);
             // Cleanup temp file
              if std::path::Path::new(&temp_output_path).exists() {
-                 let _ = std::fs::remove_file(&temp_output_path);
+                 crate::scratch::remove_scratch_file(Path::new(&temp_output_path), config.secure_temp_files);
              }
              if let Some(prefix) = &pass_log_prefix_opt {
                  cleanup_pass_logs(prefix, &temp_output_path);
              }
-             let _ = app.emit("video-progress", ProgressPayload {
+             crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: 0,
                 status: "Error".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
             return Err(format!("Validation failed: {}", e));
         }
 
+        // 1b. Only keep the output if it saves enough space (remuxes are left alone)
+        if config.keep_only_if_smaller && config.compression_mode != "copy" {
+            let source_size = std::fs::metadata(&input_path).map(|m| m.len()).unwrap_or(0);
+            let output_size = std::fs::metadata(&temp_output_path).map(|m| m.len()).unwrap_or(0);
+            let max_size = source_size as f64 * (100.0 - config.min_savings_percent.min(100) as f64) / 100.0;
+            if source_size > 0 && output_size as f64 >= max_size {
+                println!(
+                    "[INFO] Keeping original {}: output {} bytes vs source {} bytes (needs {}% savings)",
+                    input_path, output_size, source_size, config.min_savings_percent
+                );
+                crate::scratch::remove_scratch_file(Path::new(&temp_output_path), config.secure_temp_files);
+                if let Some(prefix) = &pass_log_prefix_opt {
+                    cleanup_pass_logs(prefix, &temp_output_path);
+                }
+                if !replaces_source {
+                    if let Some(parent) = std::path::Path::new(&output_path).parent() {
+                        let _ = std::fs::create_dir_all(parent);
+                    }
+                    if let Err(e) = crate::transfer::copy_file(&input_path, &output_path, config.copy_bandwidth_limit_mbps) {
+                        eprintln!("[ERROR] Failed to copy original for '{}': {}", input_path, e);
+                        return Err(format!("Kept original (no savings), but failed to copy file: {}", e));
+                    }
+                }
+                write_result_sidecar(&config, &input_path, &output_path, KEPT_ORIGINAL, (None, None), input_info.as_ref(), started_at);
+                crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Skipped);
+                crate::events::emit_progress(&app, ProgressPayload {
+                    path: input_path.clone(),
+                    progress: 100,
+                    status: KEPT_ORIGINAL.to_string(),
+                    speed: 0.0,
+                    bitrate_kbps: 0.0,
+                    output_info: with_quality_estimate(input_info, &config),
+                    monotonic_ms: crate::monitor::monotonic_ms(),
+                    vmaf_segment: None,
+                });
+                return Ok(());
+            }
+        }
+
         // 2. Ensure parent directory exists (redundant safety check - should already exist)
         if let Some(parent) = std::path::Path::new(&output_path).parent() {
             if !parent.exists() {
@@ -2464,32 +5362,53 @@ pub fn run_ffmpeg_compression_task(
         }
 
         // 3. Safe overwrite logic
+        crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Moving);
+        let mut source_aside: Option<String> = None;
+        if replaces_source && (config.backup_before_overwrite || config.replace_original) {
+            source_aside = Some(crate::backup::move_aside(&input_path)?);
+        }
         if std::path::Path::new(&output_path).exists() {
-             if let Err(e) = std::fs::remove_file(&output_path) {
+             if let Err(e) = crate::filelock::retry_if_locked(&output_path, || std::fs::remove_file(&output_path)) {
                  eprintln!("[ERROR] Failed to remove existing output file for '{}': {}", input_path, e);
                  eprintln!("[INFO] File to remove: {}", output_path);
-                 return Err(format!("Failed to remove existing output file: {}", e));
+                 return Err(crate::filelock::describe("Failed to remove existing output file", &e));
              }
         }
-        if let Err(e) = std::fs::rename(&temp_output_path, &output_path) {
+        if let Err(e) = crate::filelock::retry_if_locked(&output_path, || std::fs::rename(&temp_output_path, &output_path)) {
+             if let Some(aside) = &source_aside {
+                 let _ = std::fs::rename(aside, &input_path);
+             }
              eprintln!("[ERROR] Failed to move temp file to output for '{}': {}", input_path, e);
              eprintln!("[INFO] Temp file: {}", temp_output_path);
              eprintln!("[INFO] Output file: {}", output_path);
-             return Err(format!("Failed to move temp file to output: {}", e));
+             return Err(crate::filelock::describe("Failed to move temp file to output", &e));
         }
+        let source_backup = source_aside.and_then(|aside| crate::backup::finish_backup(&input_path, &output_path, aside, &config));
 
         // 4. Fetch metadata for the new output file
         let mut output_info = get_video_info(std::path::Path::new(&output_path), &ffprobe_path).ok();
         println!("Output info retrieved: {:?}", output_info.is_some());
+        if let Some(ref mut info) = output_info {
+            info.ffmpeg_commands = Some(ffmpeg_commands.clone());
+            info.encoder_stats = crate::encstats::parse(&summary_lines, &args, encode_started.elapsed().as_secs_f64());
+            info.source_backup = source_backup.clone();
+            if let Some(source) = &input_info {
+                info.compression_ratio = (info.size > 0).then(|| source.size as f64 / info.size as f64);
+                info.source_bits_per_pixel = source.bits_per_pixel;
+            }
+            if config.compression_mode == "copy" && config.estimate_skipped_quality {
+                info.estimated_quality = estimate_quality(info);
+            }
+        }
 
-        // 5. Handle VMAF: In "vmaf" compression mode, use the search score directly
+        // 5. Handle VMAF: In the VMAF search modes, use the search score directly
         //    In other modes with enable_vmaf, queue for post-compression VMAF calculation
-        if config.compression_mode == "vmaf" {
+        if config.uses_vmaf_search() {
             // Use the VMAF score from CRF search directly
             if let Some(vmaf_score) = vmaf_search_score {
                 if let Some(ref mut info) = output_info {
                     info.vmaf = Some(vmaf_score);
-                    info.vmaf_device = if config.vmaf_use_cuda { Some("CUDA".to_string()) } else { Some("CPU".to_string()) };
+                    info.vmaf_device = if config.vmaf_cuda() { Some("CUDA".to_string()) } else { Some("CPU".to_string()) };
                     
                     // Use same resolution-based model selection as in search
                     let parts: Vec<&str> = info.resolution.split('x').collect();
@@ -2510,14 +5429,19 @@ pub fn run_ffmpeg_compression_task(
                     info.vmaf_model = Some(model_filename.to_string());
                 }
             }
+
+            write_result_sidecar(&config, &input_path, &output_path, "Done", sidecar_rate, output_info.as_ref(), started_at);
             
-            let _ = app.emit("video-progress", ProgressPayload {
+            crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Done);
+            crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: 100,
                 status: "Done".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
             if let Some(prefix) = pass_log_prefix_opt {
                 std::thread::sleep(std::time::Duration::from_millis(200));
@@ -2526,6 +5450,8 @@ pub fn run_ffmpeg_compression_task(
             return Ok(());
         } else if config.enable_vmaf {
             // Other modes: queue for separate VMAF calculation
+            // (the sidecar's VMAF fields are filled in once the calculation finishes)
+            write_result_sidecar(&config, &input_path, &output_path, "Done", sidecar_rate, output_info.as_ref(), started_at);
             let app_handle = app.clone();
             let in_p = input_path.clone();
             let ffmpeg_p = ffmpeg_path.to_string();
@@ -2543,7 +5469,8 @@ pub fn run_ffmpeg_compression_task(
                 input_path: in_p.clone(),
                 ffmpeg_path: ffmpeg_p,
                 ffprobe_path: ffprobe_p,
-                reference_path: in_p.clone(),
+                // Overwritten sources are only comparable through their backup
+                reference_path: source_backup.clone().unwrap_or_else(|| in_p.clone()),
                 distorted_path: out_p,
                 config: cfg,
                 duration_sec: dur,
@@ -2558,13 +5485,16 @@ pub fn run_ffmpeg_compression_task(
                 }
             }
 
-            let _ = app.emit("video-progress", ProgressPayload {
+            crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Queued);
+            crate::events::emit_progress(&app, ProgressPayload {
                 path: input_path.clone(),
                 progress: 100,
                 status: "Waiting for VMAF".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: output_info.clone(),
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
 
             schedule_next_vmaf(vmaf_state);
@@ -2572,14 +5502,18 @@ pub fn run_ffmpeg_compression_task(
             return Ok(());
         }
 
+        write_result_sidecar(&config, &input_path, &output_path, "Done", sidecar_rate, output_info.as_ref(), started_at);
 
-         let _ = app.emit("video-progress", ProgressPayload {
+        crate::phase::emit_job_phase(&app, &input_path, crate::phase::JobPhase::Done);
+         crate::events::emit_progress(&app, ProgressPayload {
             path: input_path.clone(),
             progress: 100,
             status: "Done".to_string(),
             speed: 0.0,
             bitrate_kbps: 0.0,
             output_info,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
         });
         if let Some(prefix) = pass_log_prefix_opt {
              // Best effort cleanup of passlog files
@@ -2589,50 +5523,636 @@ pub fn run_ffmpeg_compression_task(
 
         Ok(())
     } else {
-         // Cleanup temp file
-         if std::path::Path::new(&temp_output_path).exists() {
-             let _ = std::fs::remove_file(&temp_output_path);
-         }
-         if let Some(prefix) = &pass_log_prefix_opt {
-             cleanup_pass_logs(prefix, &temp_output_path);
-         }
-         
-         let is_cancelled = {
-             if let Ok(mut set) = cancelled_paths.lock() {
-                 set.remove(&input_path)
-             } else {
-                 false
-             }
-         };
+         // Cleanup temp file
+         if std::path::Path::new(&temp_output_path).exists() {
+             crate::scratch::remove_scratch_file(Path::new(&temp_output_path), config.secure_temp_files);
+         }
+         if let Some(prefix) = &pass_log_prefix_opt {
+             cleanup_pass_logs(prefix, &temp_output_path);
+         }
+         
+         let is_cancelled = {
+             if let Ok(mut set) = cancelled_paths.lock() {
+                 set.remove(&job_key(&input_path))
+             } else {
+                 false
+             }
+         };
+
+         // Selected encoder vanished mid-batch: retry this file with the session fallback
+         if !is_cancelled
+             && config.compression_mode != "custom"
+             && is_encoder_unavailable_error(&stderr_lines.join("\n"))
+             && switch_to_fallback_encoder(&app, ffmpeg_path, &v_enc_name, &config).is_some()
+         {
+             return Err(ENCODER_SWITCHED.to_string());
+         }
+
+         // Read errors because the source got locked mid-encode: retry the file later
+         if !is_cancelled && crate::filelock::is_locked(&input_path) {
+             eprintln!("[WARNING] FFmpeg failed for '{}' while the file is locked by another program", input_path);
+             emit_file_in_use(&app, &input_path);
+             return Err(format!("{}: locked by another program during encoding", crate::filelock::FILE_IN_USE));
+         }
+
+         let status_str = if is_cancelled { "Cancelled" } else { "Error" };
+         
+         if !is_cancelled {
+             eprintln!("[ERROR] FFmpeg compression failed for '{}'", input_path);
+             eprintln!("[INFO] FFmpeg exit status: {:?}", status);
+             eprintln!("[INFO] Compression mode: {}", config.compression_mode);
+             
+             // Print FFmpeg stderr output for debugging
+             if !stderr_lines.is_empty() {
+                 eprintln!("[ERROR] FFmpeg stderr output (last {} lines):", stderr_lines.len());
+                 for stderr_line in &stderr_lines {
+                     eprintln!("  {}", stderr_line);
+                 }
+             } else {
+                 eprintln!("[WARNING] No stderr output captured from FFmpeg");
+             }
+         }
+
+         crate::events::emit_progress(&app, ProgressPayload {
+            path: input_path.clone(),
+            progress: 0,
+            status: status_str.to_string(),
+            speed: 0.0,
+            bitrate_kbps: 0.0,
+            output_info: None,
+            monotonic_ms: crate::monitor::monotonic_ms(),
+            vmaf_segment: None,
+        });
+        if is_cancelled {
+            return Err(CANCELLED.to_string());
+        }
+        Err(format!("FFmpeg exited with status: {:?}{}", status, stderr_summary(&stderr_lines)))
+    }
+}
+
+fn emit_file_in_use(app: &AppHandle, input_path: &str) {
+    crate::events::emit_progress(app, ProgressPayload {
+        path: input_path.to_string(),
+        progress: 0,
+        status: crate::filelock::FILE_IN_USE.to_string(),
+        speed: 0.0,
+        bitrate_kbps: 0.0,
+        output_info: None,
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
+    });
+}
+
+/// Filters fixing up the source before anything else: deinterlacing, auto crop and the
+/// frame rate cap, as far as the config asks for them
+fn source_filter_list(ffmpeg_path: &str, input_path: &str, input_info: Option<&VideoInfo>, config: &CompressionConfig) -> Vec<String> {
+    // Pre-analysis only when something uses it; sources flagged progressive skip idet
+    let wants_deinterlace = !config.deinterlace.is_empty()
+        && input_info.is_some_and(|i| i.field_order.as_deref() != Some("progressive"));
+    let mut source_filters = Vec::new();
+    if let Some(info) = input_info.filter(|_| config.auto_crop || wants_deinterlace) {
+        let analysis = crate::analysis::analyze(ffmpeg_path, input_path, info);
+        if wants_deinterlace && analysis.is_interlaced(info) {
+            println!("[INFO] Deinterlacing {} with {}", input_path, config.deinterlace);
+            source_filters.extend(crate::analysis::deinterlace_filter(&config.deinterlace));
+        }
+        // The crop is measured on the upright picture, which "preserve" doesn't produce
+        if config.auto_crop && !(config.rotation_mode == "preserve" && info.rotation.is_some()) {
+            source_filters.extend(analysis.crop.map(|c| format!("crop={}", c)));
+        }
+    }
+    // Small tolerance so a 59.94 fps source isn't "capped" to 60
+    let source_fps = input_info.and_then(|i| i.fps);
+    if let Some(fps) = source_fps.filter(|fps| config.max_fps > 0.0 && *fps > config.max_fps + 0.01) {
+        let rate = capped_frame_rate(fps, config.max_fps);
+        println!("[INFO] Capping {} from {:.2} to {} fps", input_path, fps, rate);
+        source_filters.push(format!("fps={}", rate));
+    }
+    source_filters
+}
+
+/// The `fps` filter rate for capping `source_fps` at `max_fps`. An NTSC source (59.94 =
+/// 60000/1001) capped at a whole divisor of its nominal rate keeps the 1001 timebase, so
+/// 59.94 capped at 30 becomes 29.97 rather than 30, which would drop frames unevenly.
+fn capped_frame_rate(source_fps: f64, max_fps: f64) -> String {
+    let nominal = (source_fps * 1.001).round();
+    let ntsc = (source_fps * 1.001 - nominal).abs() < 0.01 && (source_fps - source_fps.round()).abs() > 0.01;
+    let whole_divisor = max_fps.fract() == 0.0 && nominal % max_fps == 0.0;
+    if ntsc && whole_divisor {
+        format!("{}/1001", max_fps as u64 * 1000)
+    } else {
+        format!("{}", max_fps)
+    }
+}
+
+/// The part of the encode's filter chain that changes which pixels and frames are scored
+/// (see video_filter_chain): CRF samples are encoded through it and the VMAF reference
+/// read through it, so sample, output and reference line up
+fn reference_filter_list(ffmpeg_path: &str, input_path: &str, input_info: Option<&VideoInfo>, config: &CompressionConfig) -> Vec<String> {
+    let mut filters = source_filter_list(ffmpeg_path, input_path, input_info, config);
+    filters.extend(denoise_filter(&config.denoise).map(|f| f.to_string()));
+    filters
+}
+
+/// ": <last stderr line>" for error messages (ffmpeg prints the actual cause last)
+fn stderr_summary(lines: &[String]) -> String {
+    lines.iter()
+        .rev()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .map(|l| format!(": {}", l))
+        .unwrap_or_default()
+}
+
+/// Job keys with a run_vmaf_pipeline call in flight
+static ACTIVE_PIPELINES: std::sync::Mutex<Option<std::collections::HashSet<String>>> = std::sync::Mutex::new(None);
+
+/// Whether a pipeline for the file is running, even between its ffmpeg processes
+pub fn is_pipeline_active(input_path: &str) -> bool {
+    ACTIVE_PIPELINES.lock().ok()
+        .and_then(|g| g.as_ref().map(|s| s.contains(&job_key(input_path))))
+        .unwrap_or(false)
+}
+
+/// Removes the job from ACTIVE_PIPELINES when the pipeline ends, however it ends
+pub struct PipelineGuard(String);
+
+impl Drop for PipelineGuard {
+    fn drop(&mut self) {
+        if let Ok(mut guard) = ACTIVE_PIPELINES.lock() {
+            if let Some(set) = guard.as_mut() {
+                set.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Start a CRF search + compression pipeline for one file, run as a single job
+/// (process_video) under one job key for cancel and pause. Rejects a second pipeline
+/// for a file whose pipeline is still running instead of repeating the search, and
+/// drops a cancel left over from an earlier run. Hold the guard until the job ends.
+pub fn begin_pipeline(
+    input_path: &str,
+    cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+) -> Result<PipelineGuard, String> {
+    let key = job_key(input_path);
+    {
+        let mut active = ACTIVE_PIPELINES.lock().map_err(|e| e.to_string())?;
+        if !active.get_or_insert_with(std::collections::HashSet::new).insert(key.clone()) {
+            return Err(format!("A pipeline for {} is already running", input_path));
+        }
+    }
+    if let Ok(mut set) = cancelled_paths.lock() {
+        set.remove(&key);
+    }
+    Ok(PipelineGuard(key))
+}
+
+/// Image files a folder sequence is built from
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "webp", "tif", "tiff"];
+
+fn emit_task_status(app: &AppHandle, input_path: &str, progress: u8, status: &str) {
+    crate::events::emit_progress(app, ProgressPayload {
+        path: input_path.to_string(),
+        progress,
+        status: status.to_string(),
+        speed: 0.0,
+        bitrate_kbps: 0.0,
+        output_info: None,
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
+    });
+}
+
+/// Encode an image sequence into a video at `fps`: either a folder of images (in file name
+/// order) or a file of frames without timing (status "Image sequence" from the probe).
+/// Uses the configured encoder and rate control; VMAF modes encode at `target_crf`.
+/// Takes an encode slot like any other job.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_image_sequence(
+    app: AppHandle,
+    ffmpeg_path: &str,
+    input_path: String,
+    output_path: String,
+    fps: f64,
+    config: CompressionConfig,
+    pids: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+) -> Result<(), String> {
+    let _spawn = crate::process::job_scope(&config);
+    if !(fps > 0.0 && fps <= 240.0) {
+        return Err(format!("Invalid frame rate {}", fps));
+    }
+    // Percent mode needs a source bitrate, which a pile of images doesn't have
+    if matches!(config.compression_mode.as_str(), "copy" | "custom" | "percent") {
+        return Err("Image sequences can't be encoded in copy, custom or percent mode".to_string());
+    }
+    if resolves_to_same_file(&input_path, &output_path) && !config.allow_overwrite_source {
+        return Err("Output path is the source file; enable 'allow overwrite source' to replace it".to_string());
+    }
+    if let Ok(mut set) = cancelled_paths.lock() {
+        set.remove(&job_key(&input_path));
+    }
+    let config = with_encoder_fallback(&config);
+    let v_enc = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
+
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-nostats", "-progress", "pipe:2"].iter().map(|a| a.to_string()).collect();
+    args.extend(hw_device_args(&v_enc));
+
+    // Folders go through a concat list, which works with any file names
+    let mut list_file = None;
+    let total_frames = if Path::new(&input_path).is_dir() {
+        let mut images: Vec<std::path::PathBuf> = std::fs::read_dir(&input_path)
+            .map_err(|e| format!("Failed to read {}: {}", input_path, e))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().and_then(|e| e.to_str())
+                .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str())))
+            .collect();
+        images.sort();
+        if images.is_empty() {
+            return Err(format!("No images found in {}", input_path));
+        }
+        let mut list = String::from("ffconcat version 1.0\n");
+        for image in &images {
+            list.push_str(&format!("file '{}'\nduration {}\n", image.to_string_lossy().replace('\'', "'\\''"), 1.0 / fps));
+        }
+        let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_micros()).unwrap_or(0);
+        let path = crate::scratch::scratch_dir(config.secure_temp_files)?.join(format!("image_sequence_{}.ffconcat", id));
+        std::fs::write(&path, list).map_err(|e| format!("Failed to write image list: {}", e))?;
+        args.extend(["-f", "concat", "-safe", "0", "-i"].iter().map(|a| a.to_string()));
+        args.push(path.to_string_lossy().to_string());
+        list_file = Some(path);
+        Some(images.len() as u64)
+    } else {
+        // Input -r gives the untimed frames their timestamps
+        args.extend(["-r".to_string(), fps.to_string(), "-i".to_string(), input_path.clone()]);
+        None
+    };
+
+    // Even dimensions and 4:2:0 for player compatibility (images are often odd-sized RGB)
+    args.extend(["-vf", "scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p", "-r"].iter().map(|a| a.to_string()));
+    args.push(fps.to_string());
+    args.extend(["-an", "-c:v"].iter().map(|a| a.to_string()));
+    args.push(v_enc.clone());
+    args.extend(rate_control_args(&config, &v_enc, Some(config.target_crf), None));
+    let output_format = Path::new(&output_path).extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase();
+    let temp_output_path = format!("{}.tmp.{}", output_path, output_format);
+    let mut args = crate::gpuquirks::with_bframe_quirk(&config, &v_enc, with_hw_upload(&v_enc, args));
+    args.push(temp_output_path.clone());
+
+    let Some(_slot) = wait_for_slot(&app, &input_path, &crate::pool::ENCODE, config.max_concurrent_encodes, 0, &cancelled_paths) else {
+        if let Some(path) = list_file {
+            let _ = std::fs::remove_file(path);
+        }
+        emit_task_status(&app, &input_path, 0, "Cancelled");
+        return Err(CANCELLED.to_string());
+    };
+    println!("[INFO] Encoding image sequence {} at {} fps", input_path, fps);
+    emit_task_status(&app, &input_path, 0, "Processing");
+    let spawned = crate::process::ffmpeg_command(ffmpeg_path)
+        .args(&args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(c) => c,
+        Err(e) => {
+            if let Some(path) = list_file {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(format!("Failed to start ffmpeg: {}", e));
+        }
+    };
+    if let Ok(mut map) = pids.lock() {
+        map.insert(job_key(&input_path), child.id());
+    }
+
+    let mut last_lines: Vec<String> = Vec::new();
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if let (Some(total), Some(frame)) = (total_frames, line.strip_prefix("frame=")) {
+                let done = frame.trim().parse::<u64>().unwrap_or(0);
+                emit_task_status(&app, &input_path, (done * 100 / total).min(99) as u8, "Processing");
+            } else if !line.contains('=') {
+                last_lines.push(line);
+                if last_lines.len() > 10 {
+                    last_lines.remove(0);
+                }
+            }
+        }
+    }
+    let status = child.wait().map_err(|e| format!("Failed to wait on ffmpeg: {}", e));
+    if let Ok(mut map) = pids.lock() {
+        map.remove(&job_key(&input_path));
+    }
+    if let Some(path) = list_file {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let result = status.and_then(|s| if s.success() {
+        std::fs::rename(&temp_output_path, &output_path).map_err(|e| format!("Failed to move temp file to output: {}", e))
+    } else {
+        Err(format!("ffmpeg failed: {}", last_lines.join("\n")))
+    });
+    match &result {
+        Ok(()) => emit_task_status(&app, &input_path, 100, "Done"),
+        Err(e) => {
+            eprintln!("[ERROR] Image sequence {} failed: {}", input_path, e);
+            let _ = std::fs::remove_file(&temp_output_path);
+            emit_task_status(&app, &input_path, 0, "Error");
+        }
+    }
+    result
+}
+
+/// Codec, encoder, extension and quality arguments for an `extract_audio` format
+fn audio_extract_format(format: &str) -> Result<(&'static str, &'static str, &'static str, &'static [&'static str]), String> {
+    match format {
+        "mp3" => Ok(("mp3", "libmp3lame", "mp3", &["-q:a", "2"])),
+        "aac" => Ok(("aac", "aac", "m4a", &["-b:a", "192k"])),
+        "flac" => Ok(("flac", "flac", "flac", &[])),
+        "opus" => Ok(("opus", "libopus", "opus", &["-b:a", "128k"])),
+        _ => Err(format!("Unsupported audio format '{}' (use mp3, aac, flac or opus)", format)),
+    }
+}
+
+/// Extract the audio of `input_path` next to it as `format` (mp3/aac/flac/opus), one file
+/// per track (`name.mp3`, or `name.track1.mp3`, `name.track2.mp3`, ...). Tracks already in
+/// the target codec are copied. Progress is reported on the video's row as "Extracting
+/// audio", which goes back to "Pending" afterwards. Returns the written files.
+pub fn extract_audio(
+    app: AppHandle,
+    ffmpeg_path: &str,
+    input_path: String,
+    format: &str,
+    pids: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+) -> Result<Vec<String>, String> {
+    let (codec, encoder, extension, quality) = audio_extract_format(format)?;
+    let info = get_metadata_quick(&input_path, &resolve_ffprobe_path(ffmpeg_path))?;
+    let tracks = info.audio_tracks.clone().unwrap_or_default();
+    if tracks.is_empty() {
+        return Err(format!("{} has no audio track", input_path));
+    }
+
+    let input = Path::new(&input_path);
+    let stem = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let dir = input.parent().unwrap_or(Path::new("."));
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-nostats", "-progress", "pipe:2", "-i"].iter().map(|a| a.to_string()).collect();
+    args.push(input_path.clone());
+    let mut outputs = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let name = if tracks.len() == 1 {
+            format!("{}.{}", stem, extension)
+        } else {
+            format!("{}.track{}.{}", stem, i + 1, extension)
+        };
+        let output = dir.join(name).to_string_lossy().to_string();
+        if Path::new(&output).exists() {
+            return Err(format!("{} already exists", output));
+        }
+        args.extend(["-map".to_string(), format!("0:a:{}", i), "-vn".to_string(), "-sn".to_string(), "-c:a".to_string()]);
+        // Tracks already in the target codec are copied out as they are
+        if track.codec == codec {
+            args.push("copy".to_string());
+        } else {
+            args.push(encoder.to_string());
+            args.extend(quality.iter().map(|a| a.to_string()));
+        }
+        args.push(format!("{}.tmp.{}", output, extension));
+        outputs.push(output);
+    }
+
+    println!("[INFO] Extracting {} audio track(s) from {} as {}", tracks.len(), input_path, format);
+    emit_task_status(&app, &input_path, 0, "Extracting audio");
+    let mut child = crate::process::ffmpeg_command(ffmpeg_path)
+        .args(&args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    if let Ok(mut map) = pids.lock() {
+        map.insert(job_key(&input_path), child.id());
+    }
+
+    let mut last_lines: Vec<String> = Vec::new();
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if let Some(time) = line.strip_prefix("out_time=") {
+                if info.duration_sec > 0.0 {
+                    let progress = (parse_time_str(time.trim()) / info.duration_sec * 100.0).clamp(0.0, 99.0);
+                    emit_task_status(&app, &input_path, progress as u8, "Extracting audio");
+                }
+            } else if !line.contains('=') {
+                last_lines.push(line);
+                if last_lines.len() > 10 {
+                    last_lines.remove(0);
+                }
+            }
+        }
+    }
+    let status = child.wait().map_err(|e| format!("Failed to wait on ffmpeg: {}", e));
+    if let Ok(mut map) = pids.lock() {
+        map.remove(&job_key(&input_path));
+    }
+
+    let result = status.and_then(|s| if s.success() {
+        outputs.iter().try_for_each(|output| {
+            std::fs::rename(format!("{}.tmp.{}", output, extension), output)
+                .map_err(|e| format!("Failed to move temp file to output: {}", e))
+        })
+    } else {
+        Err(format!("ffmpeg failed: {}", last_lines.join("\n")))
+    });
+    // The video itself is untouched, so its row is still waiting to be compressed
+    emit_task_status(&app, &input_path, 0, "Pending");
+    match result {
+        Ok(()) => Ok(outputs),
+        Err(e) => {
+            eprintln!("[ERROR] Audio extraction from {} failed: {}", input_path, e);
+            for output in &outputs {
+                let _ = std::fs::remove_file(format!("{}.tmp.{}", output, extension));
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Shortest and longest A/B preview window
+const PREVIEW_MIN_SECONDS: f64 = 10.0;
+const PREVIEW_MAX_SECONDS: f64 = 20.0;
+
+/// Original and encoded snippet of the same window, for side-by-side playback
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewClip {
+    pub original: String,
+    pub encoded: String,
+    pub start: f64,
+    pub duration: f64,
+    pub note: Option<String>, // Where the preview differs from the real encode
+}
+
+/// File name prefix of preview clips in the private scratch dir
+const PREVIEW_PREFIX: &str = "preview_";
+
+/// Delete preview clips (the modal closed or generated new ones). Only files that
+/// encode_preview wrote are touched, whatever the caller passes.
+pub fn discard_previews(paths: &[String], shred: bool) {
+    let Ok(dir) = crate::scratch::scratch_dir(true) else { return };
+    for path in paths.iter().map(Path::new) {
+        let is_preview = path.parent() == Some(dir.as_path())
+            && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(PREVIEW_PREFIX));
+        if is_preview && path.is_file() {
+            crate::scratch::remove_scratch_file(path, shred);
+        }
+    }
+}
+
+/// Remove preview clips left from earlier runs (still open in a player when the modal closed)
+pub fn discard_stale_previews() {
+    let Ok(dir) = crate::scratch::scratch_dir(true) else { return };
+    let Ok(entries) = std::fs::read_dir(&dir) else { return };
+    let stale: Vec<String> = entries.flatten()
+        .map(|e| e.path().to_string_lossy().into_owned())
+        .collect();
+    discard_previews(&stale, true);
+}
+
+/// Encode a 10-20 s window of `input_path` from `start` with `config`, plus a visually
+/// lossless H.264 cut of the same window as the reference. Steps that need the whole file
+/// (two-pass, the VMAF search, loudness measurement, burned-in subtitles) are left out.
+/// It takes an encode slot, and its ffmpeg runs are tracked in `pids` under a key of their own,
+/// so a preview never takes the place of a running job of the same file.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_preview(
+    app: &AppHandle,
+    ffmpeg_path: &str,
+    input_path: &str,
+    start: f64,
+    duration: f64,
+    config: &CompressionConfig,
+    pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+) -> Result<PreviewClip, String> {
+    if matches!(config.compression_mode.as_str(), "copy" | "custom") {
+        return Err("Previews need an encoding compression mode".to_string());
+    }
+    let _spawn = crate::process::job_scope(config);
+    let info = get_video_info(Path::new(input_path), &resolve_ffprobe_path(ffmpeg_path))?;
+    let config = if config.quality_tier.is_empty() {
+        config.clone()
+    } else {
+        crate::tiers::apply_tier(config, Some(&info.resolution))
+    };
+    let config = with_encoder_fallback(&config);
+    let config = with_video_stream(config, Some(&info));
+    let config = with_size_percent(config, Some(&info))?;
+    let v_enc = if config.video_encoder.is_empty() { "libx264".to_string() } else { config.video_encoder.clone() };
+    let a_enc = if config.audio_encoder.is_empty() { "aac".to_string() } else { config.audio_encoder.clone() };
+    let container = config.output_format_for(input_path);
+    check_container_compatibility(&container, &v_enc, &a_enc)?;
+    check_audio_settings(&config, &a_enc)?;
+    check_encoder_options(&config, &v_enc)?;
+
+    let duration = duration.clamp(PREVIEW_MIN_SECONDS, PREVIEW_MAX_SECONDS);
+    let start = start.clamp(0.0, (info.duration_sec - duration).max(0.0));
+    let mut notes = Vec::new();
+    if config.uses_vmaf_search() {
+        notes.push("VMAF modes search a CRF per file; the preview uses CRF 23");
+    }
+    if config.compression_mode == "bitrate" && config.two_pass {
+        notes.push("encoded in one pass");
+    }
+    if config.loudnorm_target < 0.0 {
+        notes.push("loudness normalization left out");
+    }
+    if config.burn_in_subtitle.is_some() {
+        notes.push("subtitles not burned in");
+    }
 
-         let status_str = if is_cancelled { "Cancelled" } else { "Error" };
-         
-         if !is_cancelled {
-             eprintln!("[ERROR] FFmpeg compression failed for '{}'", input_path);
-             eprintln!("[INFO] FFmpeg exit status: {:?}", status);
-             eprintln!("[INFO] Compression mode: {}", config.compression_mode);
-             
-             // Print FFmpeg stderr output for debugging
-             if !stderr_lines.is_empty() {
-                 eprintln!("[ERROR] FFmpeg stderr output (last {} lines):", stderr_lines.len());
-                 for stderr_line in &stderr_lines {
-                     eprintln!("  {}", stderr_line);
-                 }
-             } else {
-                 eprintln!("[WARNING] No stderr output captured from FFmpeg");
-             }
-         }
+    // Always in the private scratch dir: the UI may only open preview files from there
+    let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_micros()).unwrap_or(0);
+    let dir = crate::scratch::scratch_dir(true)?;
+    let original = dir.join(format!("{}{}_original.mp4", PREVIEW_PREFIX, id)).to_string_lossy().to_string();
+    let encoded = dir.join(format!("{}{}_encoded.{}", PREVIEW_PREFIX, id, container)).to_string_lossy().to_string();
+    let window = |args: &mut Vec<String>| {
+        args.extend(["-ss".to_string(), format!("{:.3}", start), "-i".to_string(), input_path.to_string()]);
+        args.extend(["-t".to_string(), format!("{:.3}", duration)]);
+        args.extend(video_map_args(&config));
+    };
 
-         let _ = app.emit("video-progress", ProgressPayload {
-            path: input_path.clone(),
-            progress: 0,
-            status: status_str.to_string(),
-            speed: 0.0,
-            bitrate_kbps: 0.0,
-            output_info: None,
-        });
-        Err(format!("FFmpeg exited with status: {:?}{}", status, if is_cancelled { " (Cancelled)" } else { "" }))
+    let mut reference: Vec<String> = ["-y", "-hide_banner", "-v", "error"].iter().map(|a| a.to_string()).collect();
+    window(&mut reference);
+    reference.extend([
+        "-c:v", "libx264", "-preset", "veryfast", "-crf", "12", "-pix_fmt", "yuv420p",
+        "-c:a", "aac", "-b:a", "192k", "-sn", "-dn",
+    ].iter().map(|a| a.to_string()));
+    reference.push(original.clone());
+
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-v", "error"].iter().map(|a| a.to_string()).collect();
+    args.extend(hw_device_args(&v_enc));
+    args.extend(forced_decoder_args(&config));
+    args.extend(rotation_input_args(&config, info.rotation, false));
+    window(&mut args);
+    args.extend(["-c:v".to_string(), v_enc.clone()]);
+    args.extend(rate_control_args(&config, &v_enc, None, None));
+    args.extend(["-c:a".to_string(), a_enc.clone()]);
+    args.extend(audio_option_args(&config, &a_enc));
+    args.push("-sn".to_string());
+    args.extend(rotation_output_args(&config, info.rotation, false));
+    // One encode slot for the content analysis and both cuts, as for a job
+    let preview_key = format!("preview:{}", id);
+    let Some(_slot) = wait_for_slot(app, &preview_key, &crate::pool::ENCODE, config.max_concurrent_encodes, 0, cancelled_paths) else {
+        return Err(CANCELLED.to_string());
+    };
+    if config.auto_tune {
+        let content = crate::tuning::content_type_for(ffmpeg_path, input_path, info.duration_sec);
+        args.extend(crate::tuning::auto_tune_args(content, &v_enc));
+    }
+    let source_filters = source_filter_list(ffmpeg_path, input_path, Some(&info), &config);
+    args.extend(encoding_option_args(&config, &v_enc, &a_enc, false, &source_filters, None));
+    let mut args = crate::gpuquirks::with_bframe_quirk(&config, &v_enc, with_hw_upload(&v_enc, args));
+    let threads = config.effective_ffmpeg_threads();
+    if threads > 0 {
+        args.extend(["-threads".to_string(), threads.to_string()]);
+    }
+    args.push(encoded.clone());
+
+    println!("[INFO] Preview of {} at {:.1}s for {:.0}s: {}", input_path, start, duration, format_command_line(ffmpeg_path, &args));
+    let result = [("reference", &reference), ("encode", &args)].iter().try_for_each(|(what, args)| {
+        let child = crate::process::ffmpeg_command(ffmpeg_path)
+            .args(args.iter())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+        if let Ok(mut map) = pids.lock() {
+            map.insert(job_key(&preview_key), child.id());
+        }
+        let output = child.wait_with_output();
+        if let Ok(mut map) = pids.lock() {
+            map.remove(&job_key(&preview_key));
+        }
+        let output = output.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Preview {} failed: {}", what, stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim()))
+    });
+    if let Err(e) = result {
+        eprintln!("[ERROR] {}", e);
+        crate::scratch::remove_scratch_file(Path::new(&original), config.secure_temp_files);
+        crate::scratch::remove_scratch_file(Path::new(&encoded), config.secure_temp_files);
+        return Err(e);
     }
+    Ok(PreviewClip {
+        original,
+        encoded,
+        start,
+        duration,
+        note: (!notes.is_empty()).then(|| notes.join("; ")),
+    })
 }
 
 pub fn process_video(
@@ -2649,7 +6169,12 @@ pub fn process_video(
     let mut derived_crf = None;
     let mut derived_score = None;
 
-    if config.compression_mode == "vmaf" {
+    // No point searching a CRF for a file the compression task will skip for its codec
+    let codec_skipped = !config.skip_codecs.is_empty()
+        && get_metadata_quick(&input_path, &resolve_ffprobe_path(ffmpeg_path))
+            .is_ok_and(|i| skip_codec_status(&config.skip_codecs, &i.encoder).is_some());
+
+    if config.uses_vmaf_search() && !codec_skipped {
         // Run Search
         match run_crf_search(
             app.clone(),
@@ -2665,7 +6190,32 @@ pub fn process_video(
                 derived_crf = Some(c);
                 derived_score = Some(v);
             }
-            Err(e) => return Err(e),
+            Err(e) => {
+                crate::throttle::clear_job_speed_limit(&input_path);
+                return Err(e);
+            }
+        }
+
+        // A cancel that arrives after the search finished but before ffmpeg starts would
+        // otherwise be cleared by the compression task and lost. Ends the job like a cancel
+        // during the encode does.
+        let cancelled = cancelled_paths.lock()
+            .map(|mut set| set.remove(&job_key(&input_path)))
+            .unwrap_or(false);
+        if cancelled {
+            crate::throttle::clear_job_speed_limit(&input_path);
+            set_user_paused(&input_path, false);
+            crate::events::emit_progress(&app, ProgressPayload {
+                path: input_path.clone(),
+                progress: 0,
+                status: "Cancelled".to_string(),
+                speed: 0.0,
+                bitrate_kbps: 0.0,
+                output_info: None,
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
+            });
+            return Err(CANCELLED.to_string());
         }
     }
 
@@ -2684,7 +6234,16 @@ pub fn process_video(
     )
 }
 
-fn verify_video(ffmpeg_path: &str, file_path: &str) -> Result<(), String> {
+/// Full probe plus the pre-analysis (black bars, interlacing) of one file
+pub fn analyze_video(ffmpeg_path: &str, ffprobe_path: &str, path: &str) -> Result<VideoInfo, String> {
+    let mut info = get_video_info(Path::new(path), ffprobe_path)?;
+    let analysis = crate::analysis::analyze(ffmpeg_path, path, &info);
+    info.crop = analysis.crop;
+    info.interlaced_ratio = analysis.interlaced_ratio;
+    Ok(info)
+}
+
+pub fn verify_video(ffmpeg_path: &str, file_path: &str) -> Result<(), String> {
     // 1. Check if file exists and has size
     let metadata = std::fs::metadata(file_path).map_err(|e| format!("Failed to get metadata: {}", e))?;
     if metadata.len() == 0 {
@@ -2701,13 +6260,8 @@ fn verify_video(ffmpeg_path: &str, file_path: &str) -> Result<(), String> {
         "-"
     ];
 
-    let mut command = Command::new(ffmpeg_path);
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
     command.args(&args);
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000);
-    }
 
     let output = command.output()
         .map_err(|e| format!("Failed to run verification: {}", e))?;
@@ -2720,37 +6274,200 @@ fn verify_video(ffmpeg_path: &str, file_path: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Video packet count (= frames) and frame rate of stream v:`stream`, read with -count_packets
+fn probe_frame_count(ffprobe_path: &str, path: &str, stream: u32) -> Result<(u64, f64), String> {
+    let mut command = crate::process::ffprobe_command(ffprobe_path);
+    command.args([
+        "-v", "error",
+        "-select_streams", &format!("v:{}", stream),
+        "-count_packets",
+        "-show_entries", "stream=nb_read_packets,avg_frame_rate",
+        "-of", "json",
+        path,
+    ]);
+
+    let output = command.output().map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe json: {}", e))?;
+    let stream = parsed["streams"].get(0).ok_or("No video stream")?;
+    let frames = stream["nb_read_packets"].as_str()
+        .and_then(|n| n.parse::<u64>().ok())
+        .ok_or("No packet count")?;
+    let fps = stream["avg_frame_rate"].as_str()
+        .and_then(|r| {
+            let (num, den) = r.split_once('/')?;
+            let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+            if den > 0.0 { Some(num / den) } else { None }
+        })
+        .unwrap_or(0.0);
+    Ok((frames, fps))
+}
+
+/// Completeness check: the output must have about as many frames as the source, scaled
+/// for a frame rate change and capped by a `-t` limit in the encode args. Other trims
+/// (-to, -frames:v, trim filters) make the expected count unknowable, so the check is skipped.
+fn verify_frame_count(ffprobe_path: &str, input_path: &str, output_path: &str, config: &CompressionConfig, encode_args: &[String]) -> Result<(), String> {
+    if encode_args.iter().any(|a| a == "-to" || a == "-frames:v" || a == "-vframes" || a.contains("trim=")) {
+        println!("[INFO] Frame count check skipped for {}: encode is trimmed", input_path);
+        return Ok(());
+    }
+
+    let (source_frames, source_fps) = probe_frame_count(ffprobe_path, input_path, config.video_stream_index.unwrap_or(0))?;
+    let (output_frames, output_fps) = probe_frame_count(ffprobe_path, output_path, 0)?;
+
+    let mut expected = source_frames as f64;
+    if source_fps > 0.0 && output_fps > 0.0 && (output_fps / source_fps - 1.0).abs() > 0.01 {
+        expected *= output_fps / source_fps;
+    }
+    let limit = encode_args.iter().position(|a| a == "-t")
+        .and_then(|i| encode_args.get(i + 1))
+        .and_then(|t| t.parse::<f64>().ok());
+    if let (Some(seconds), true) = (limit, output_fps > 0.0) {
+        expected = expected.min(seconds * output_fps);
+    }
+
+    let tolerance = (expected * 0.005).max(2.0);
+    println!("[INFO] Frame count for {}: {} output vs ~{:.0} expected", input_path, output_frames, expected);
+    if (output_frames as f64) < expected - tolerance {
+        return Err(format!("Output has {} frames, expected about {:.0} (source: {})", output_frames, expected, source_frames));
+    }
+    Ok(())
+}
+
+/// VMAF of a standalone comparison of two files
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub vmaf: f64,
+    pub model: Option<String>,
+    pub device: Option<String>,
+    pub pooling: Option<crate::pooling::VmafPooling>,
+    pub segments: Vec<f64>,
+}
+
+/// Score `task.distorted_path` against `task.reference_path` with the VMAF settings of
+/// `task.config`, for any two files rather than a job's source and output. Skips the
+/// post-encode VMAF queue but waits for a verification slot like the jobs' own checks;
+/// progress goes out under the distorted path, which cancel_processing also takes.
+pub fn compare_files(mut task: VmafTask) -> Result<Comparison, String> {
+    if let Ok(mut set) = task.cancelled_paths.lock() {
+        set.remove(&job_key(&task.distorted_path));
+    }
+    let reference = get_video_info(Path::new(&task.reference_path), &task.ffprobe_path)?;
+    let distorted = get_video_info(Path::new(&task.distorted_path), &task.ffprobe_path)?;
+    if reference.resolution != distorted.resolution {
+        return Err(format!(
+            "VMAF needs the same resolution: reference is {}, distorted is {}",
+            reference.resolution, distorted.resolution
+        ));
+    }
+    task.duration_sec = reference.duration_sec.min(distorted.duration_sec);
+    // Settings meant for jobs: no resolution limit skip, no file written next to the
+    // user's files, the reference's own main stream and no source filters on the reference
+    task.config.max_resolution.enabled = false;
+    task.config.keep_vmaf_log = false;
+    task.config.video_stream_index = None;
+    task.config.auto_crop = false;
+    task.config.deinterlace.clear();
+    task.config.max_fps = 0.0;
+    task.config.denoise.clear();
+    task.output_video_info = Some(distorted);
+
+    let Some(_slot) = wait_for_slot(&task.app, &task.distorted_path, &crate::pool::VERIFY, task.config.max_concurrent_verifications, 0, &task.cancelled_paths) else {
+        if let Ok(mut set) = task.cancelled_paths.lock() {
+            set.remove(&job_key(&task.distorted_path));
+        }
+        return Err(CANCELLED.to_string());
+    };
+    let key = job_key(&task.distorted_path);
+    let cancelled_paths = task.cancelled_paths.clone();
+    calculate_vmaf_score(
+        &task.app,
+        &task.distorted_path,
+        &task.ffmpeg_path,
+        &task.ffprobe_path,
+        &task.reference_path,
+        &task.distorted_path,
+        &task.config,
+        task.duration_sec,
+        task.pids,
+        task.cancelled_paths,
+        &mut task.output_video_info,
+    );
+    if let Ok(mut set) = cancelled_paths.lock() {
+        if set.remove(&key) {
+            return Err(CANCELLED.to_string());
+        }
+    }
+    let info = task.output_video_info.ok_or("VMAF computation failed")?;
+    let vmaf = info.vmaf.ok_or("VMAF computation failed or was cancelled")?;
+    Ok(Comparison {
+        vmaf,
+        model: info.vmaf_model,
+        device: info.vmaf_device,
+        pooling: info.vmaf_pooling,
+        segments: info.vmaf_detail.unwrap_or_default(),
+    })
+}
+
+/// Start queued VMAF tasks while there is room: up to `max_concurrent_vmaf` of them (taken
+/// from the next task's config), with at most one per GPU. A CUDA task waiting for its
+/// GPU lets CPU tasks behind it go first.
 pub fn schedule_next_vmaf(vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>>) {
-    // Check if something is running
-    let mut task_opt = None;
-    
+    let mut started = Vec::new();
     {
         if let Ok(mut state) = vmaf_state.lock() {
-            if state.running_task.is_none() {
-                task_opt = state.queue.pop_front();
-                if let Some(ref t) = task_opt {
-                    state.running_task = Some(t.input_path.clone());
+            while let Some(limit) = state.queue.front().map(|t| t.config.max_concurrent_vmaf.max(1) as usize) {
+                if state.running_tasks.len() >= limit {
+                    break;
                 }
+                let gpu = crate::settings::gpu_index();
+                let gpu_busy = state.running_tasks.iter().any(|r| r.gpu == Some(gpu));
+                let Some(pos) = state.queue.iter().position(|t| !(gpu_busy && t.config.vmaf_cuda())) else {
+                    break;
+                };
+                let Some(task) = state.queue.remove(pos) else { break };
+                state.running_tasks.push(RunningVmaf {
+                    input_path: task.input_path.clone(),
+                    distorted_path: task.distorted_path.clone(),
+                    gpu: task.config.vmaf_cuda().then_some(gpu),
+                });
+                started.push(task);
             }
         }
     }
 
-    if let Some(mut task) = task_opt {
+    for mut task in started {
         let v_state = vmaf_state.clone();
         std::thread::spawn(move || {
-            calculate_vmaf_score(
-                &task.app,
-                &task.input_path,
-                &task.ffmpeg_path,
-                &task.ffprobe_path,
-                &task.reference_path,
-                &task.distorted_path,
-                &task.config,
-                task.duration_sec,
-                task.pids,
-                task.cancelled_paths,
-                &mut task.output_video_info
-            );
+            // libvmaf on 4K is memory hungry; wait for headroom like a new encode would
+            if wait_for_start_conditions(&task.app, &task.input_path, &task.config, false, 100, &task.cancelled_paths).is_ok() {
+                crate::phase::emit_job_phase(&task.app, &task.input_path, crate::phase::JobPhase::Vmaf);
+                calculate_vmaf_score(
+                    &task.app,
+                    &task.input_path,
+                    &task.ffmpeg_path,
+                    &task.ffprobe_path,
+                    &task.reference_path,
+                    &task.distorted_path,
+                    &task.config,
+                    task.duration_sec,
+                    task.pids,
+                    task.cancelled_paths,
+                    &mut task.output_video_info
+                );
+            }
+
+            if task.config.write_sidecar {
+                if let Some(ref info) = task.output_video_info {
+                    crate::sidecar::update_sidecar_vmaf(&task.distorted_path, info);
+                }
+            }
+            if crate::backup::trash_after_vmaf(&task.reference_path, &task.config) {
+                if let Some(ref mut info) = task.output_video_info {
+                    info.source_backup = None;
+                }
+            }
 
             // Determine final status
             // If vmaf is None, it might have failed or been cancelled.
@@ -2767,19 +6484,25 @@ pub fn schedule_next_vmaf(vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>
             // But let's default to Done to satisfy the "cancel" requirement primarily, 
             // relying on external cancellation to kill the process.
 
-            let _ = task.app.emit("video-progress", ProgressPayload {
+            crate::phase::emit_job_phase(&task.app, &task.input_path, crate::phase::JobPhase::Done);
+            crate::events::emit_progress(&task.app, ProgressPayload {
                 path: task.input_path.clone(),
                 progress: 100,
                 status: "Done".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: task.output_video_info.clone(),
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment: None,
             });
 
             // Clear running state
             {
                 if let Ok(mut state) = v_state.lock() {
-                    state.running_task = None;
+                    let key = job_key(&task.input_path);
+                    if let Some(pos) = state.running_tasks.iter().position(|r| job_key(&r.input_path) == key) {
+                        state.running_tasks.remove(pos);
+                    }
                 }
             }
             
@@ -2789,9 +6512,139 @@ pub fn schedule_next_vmaf(vmaf_state: std::sync::Arc<std::sync::Mutex<VmafState>
     }
 }
 
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub input_path: String,
+    pub output_path: String,
+    pub vmaf: Option<f64>, // None if it could not be computed
+    pub below_threshold: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditReport {
+    pub total: usize,
+    pub threshold: f64,
+    pub entries: Vec<AuditEntry>,
+    pub cancelled: bool, // Stopped early; `entries` holds the files scored until then
+}
+
+/// Spot-check a finished batch: pick `sample_count` random input/output pairs, score each
+/// with segment VMAF against its original (the backup for in-place replacements) and
+/// flag those below `threshold`. Meant for batches run without per-file VMAF.
+///
+/// The audit runs as a job of its own under `audit_key` (not a file path, so it can't take
+/// a job's pid): cancel_processing with that key stops it, and each file waits for a
+/// verification slot like any other VMAF run. Scores are pooled per file over the frames
+/// of all its segments, as `vmaf_search_pooling` says.
+#[allow(clippy::too_many_arguments)]
+pub fn audit_outputs(
+    app: &AppHandle,
+    ffmpeg_path: &str,
+    audit_key: &str,
+    mut mappings: Vec<OutputMapping>,
+    sample_count: usize,
+    threshold: f64,
+    config: &CompressionConfig,
+    pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
+    cancelled_paths: &std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+) -> Result<AuditReport, String> {
+    // Registers the audit so a cancel between two ffmpeg runs is seen
+    let _audit = begin_pipeline(audit_key, cancelled_paths)?;
+    let is_cancelled = || cancelled_paths.lock().map(|set| set.contains(&job_key(audit_key))).unwrap_or(false);
+    let _spawn = crate::process::job_scope(config);
+    let ffprobe_path = resolve_ffprobe_path(ffmpeg_path);
+    let total = mappings.len();
+
+    // Partial Fisher-Yates with a time-seeded xorshift; no need for a rand dependency
+    let mut seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0x9E37_79B9_7F4A_7C15) | 1;
+    let picks = sample_count.min(total);
+    for i in 0..picks {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let j = i + (seed % (total - i) as u64) as usize;
+        mappings.swap(i, j);
+    }
+    mappings.truncate(picks);
+
+    let mut entries = Vec::new();
+    let mut cancelled = false;
+    for m in mappings {
+        let Some(_slot) = wait_for_slot(app, audit_key, &crate::pool::VERIFY, config.max_concurrent_verifications, 0, cancelled_paths) else {
+            cancelled = true;
+            break;
+        };
+        let reference = if resolves_to_same_file(&m.input_path, &m.output_path) {
+            crate::backup::latest_backup_for(&m.input_path)
+        } else {
+            Some(m.input_path.clone())
+        };
+        let vmaf = reference.and_then(|reference| {
+            let info = get_video_info(Path::new(&reference), &ffprobe_path).ok()?;
+            let mut dims = info.resolution.split('x').filter_map(|p| p.parse::<u32>().ok());
+            let is_high_res = dims.next().unwrap_or(0).max(dims.next().unwrap_or(0)) > 2560;
+            let model_filename = match (is_high_res, config.vmaf_neg) {
+                (false, false) => "vmaf_v0.6.1.json",
+                (true, false) => "vmaf_4k_v0.6.1.json",
+                (false, true) => "vmaf_v0.6.1neg.json",
+                (true, true) => "vmaf_4k_v0.6.1neg.json",
+            };
+            let model_path = find_vmaf_model(app, ffmpeg_path, model_filename)?;
+            let ref_stream = info.main_video_stream.unwrap_or(0);
+            let ref_filters = reference_filter_list(ffmpeg_path, &reference, Some(&info), config);
+
+            let mut frames: Vec<f64> = Vec::new();
+            for (start, dur) in compute_sample_segments(info.duration_sec, config) {
+                if is_cancelled() {
+                    return None;
+                }
+                let run = |cuda: bool| run_vmaf_instance(
+                    ffmpeg_path, &ffprobe_path, &reference, &m.output_path, &model_path,
+                    cuda, Some(start), Some(dur), pids, audit_key, &config.vmaf_filter_params(), ref_stream,
+                    &config.force_decoder, config.secure_temp_files, &ref_filters, &mut |_| {},
+                ).map(|(_, series)| series.into_iter().map(|(_, score)| score));
+                let scored = if config.vmaf_cuda() {
+                    run(true).or_else(|| if is_cancelled() { None } else { run(false) })
+                } else {
+                    run(false)
+                };
+                frames.extend(scored.into_iter().flatten());
+            }
+            crate::pooling::pooled(&frames, &config.vmaf_search_pooling)
+        });
+        if is_cancelled() {
+            cancelled = true;
+            break;
+        }
+
+        match vmaf {
+            Some(v) => println!("[INFO] Audit: {} scored {:.2}", m.output_path, v),
+            None => eprintln!("[WARNING] Audit: could not score {}", m.output_path),
+        }
+        entries.push(AuditEntry {
+            below_threshold: vmaf.is_some_and(|v| v < threshold),
+            input_path: m.input_path,
+            output_path: m.output_path,
+            vmaf,
+        });
+    }
+
+    if cancelled {
+        println!("[INFO] Audit cancelled after {} files", entries.len());
+        if let Ok(mut set) = cancelled_paths.lock() {
+            set.remove(&job_key(audit_key));
+        }
+    }
+    Ok(AuditReport { total, threshold, entries, cancelled })
+}
+
 // --- VMAF Calculation Logic ---
 
-fn find_vmaf_model(app: &AppHandle, ffmpeg_path: &str, model_filename: &str) -> Option<String> {
+pub fn find_vmaf_model(app: &AppHandle, ffmpeg_path: &str, model_filename: &str) -> Option<String> {
     println!("[DEBUG] Searching for VMAF model: {}", model_filename);
     
     // 1. Check bundled resource directory first (for packaged app)
@@ -2879,10 +6732,107 @@ fn find_vmaf_model(app: &AppHandle, ffmpeg_path: &str, model_filename: &str) ->
         }
     }
 
+    // 6. Models downloaded with download_vmaf_models
+    if let Some(path) = crate::vmafmodels::downloaded(app, model_filename) {
+        println!("[DEBUG] Found downloaded VMAF model: {:?}", path);
+        return Some(path.to_string_lossy().into_owned());
+    }
+
     println!("[DEBUG] VMAF model {} not found in any location", model_filename);
     None
 }
 
+/// Input options for a source with a display rotation under `rotation_mode`. Encodes turn
+/// the pixels upright by default, so "bake" only makes that explicit; "preserve" turns it off.
+fn rotation_input_args(config: &CompressionConfig, rotation: Option<u32>, is_copy_mode: bool) -> Vec<String> {
+    let Some(degrees) = rotation else { return Vec::new() };
+    match config.rotation_mode.as_str() {
+        "preserve" if !is_copy_mode => vec!["-noautorotate".to_string()],
+        "bake" if is_copy_mode => {
+            eprintln!("[WARNING] A {} degree rotation can't be baked in copy mode; keeping it as a flag", degrees);
+            Vec::new()
+        }
+        "bake" => vec!["-autorotate".to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// With autorotation off the rotation is also written as the `rotate` tag, for muxers that
+/// still build the display matrix from it
+fn rotation_output_args(config: &CompressionConfig, rotation: Option<u32>, is_copy_mode: bool) -> Vec<String> {
+    match rotation {
+        Some(degrees) if config.rotation_mode == "preserve" && !is_copy_mode => {
+            vec!["-metadata:s:v:0".to_string(), format!("rotate={}", degrees)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// `-c:v <decoder>` for the job's source input when a decoder is forced
+fn forced_decoder_args(config: &CompressionConfig) -> Vec<String> {
+    let decoder = config.force_decoder.trim();
+    if decoder.is_empty() {
+        Vec::new()
+    } else {
+        vec!["-c:v".to_string(), decoder.to_string()]
+    }
+}
+
+/// Input options for the VMAF reference, plus a filter prefix for its chain. CUDA runs
+/// decode with the cuvid decoder for the codec unless a decoder is forced; a forced
+/// software decoder can't produce CUDA frames, so those are uploaded in the filter.
+fn reference_decoder_args(ffprobe_path: &str, reference_path: &str, use_cuda: bool, force_decoder: &str) -> (Vec<String>, String) {
+    let forced = force_decoder.trim();
+    let mut args = Vec::new();
+    let keeps_cuda_decode = use_cuda && forced.ends_with("_cuvid");
+    if !forced.is_empty() && !keeps_cuda_decode {
+        args.push("-c:v".to_string()); args.push(forced.to_string());
+        let upload = if use_cuda { format!("format=nv12,hwupload_cuda=device={},", crate::settings::gpu_index()) } else { String::new() };
+        return (args, upload);
+    }
+    if use_cuda {
+        let decoder = if forced.is_empty() {
+            get_metadata(reference_path, ffprobe_path).ok().and_then(|info| get_cuda_decoder(&info.encoder)).map(|d| d.to_string())
+        } else {
+            Some(forced.to_string())
+        };
+
+        args.extend(cuda_hwaccel_args());
+
+        if let Some(dec) = decoder {
+            args.push("-c:v".to_string()); args.push(dec);
+        }
+    }
+    (args, String::new())
+}
+
+/// reference_decoder_args plus `ref_filters` in front of any upload. The filters run on
+/// system memory frames, so with them the reference is decoded in software and uploaded after.
+fn reference_input(ffprobe_path: &str, reference_path: &str, use_cuda: bool, force_decoder: &str, ref_filters: &[String]) -> (Vec<String>, String) {
+    if ref_filters.is_empty() {
+        return reference_decoder_args(ffprobe_path, reference_path, use_cuda, force_decoder);
+    }
+    let (args, _) = reference_decoder_args(ffprobe_path, reference_path, false, force_decoder);
+    let upload = if use_cuda { format!("format=nv12,hwupload_cuda=device={},", crate::settings::gpu_index()) } else { String::new() };
+    (args, format!("{},{}", ref_filters.join(","), upload))
+}
+
+/// `-gpu` for NVENC encodes when a GPU other than the first is configured
+fn nvenc_gpu_args(v_enc: &str) -> Vec<String> {
+    let index = crate::settings::gpu_index();
+    if v_enc.contains("nvenc") && index > 0 {
+        vec!["-gpu".to_string(), index.to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Input options to decode on the configured GPU into CUDA frames
+fn cuda_hwaccel_args() -> Vec<String> {
+    let device = crate::settings::gpu_index().to_string();
+    ["-hwaccel", "cuda", "-hwaccel_device", &device, "-hwaccel_output_format", "cuda"].iter().map(|a| a.to_string()).collect()
+}
+
 fn get_cuda_decoder(codec: &str) -> Option<&'static str> {
     match codec {
         "h264" => Some("h264_cuvid"),
@@ -2934,6 +6884,7 @@ fn calculate_vmaf_score(
     cancelled_paths: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
     output_video_info: &mut Option<VideoInfo>
 ) {
+    let _spawn = crate::process::job_scope(config);
     // 0. Logical Checks (Resolution Limit & Overwrite)
     if config.max_resolution.enabled {
         println!("VMAF Calculation skipped: Max Resolution limit enabled.");
@@ -2977,90 +6928,78 @@ fn calculate_vmaf_score(
 
     let model_path_opt = find_vmaf_model(app, ffmpeg_path, model_filename);
     if model_path_opt.is_none() {
-        println!("VMAF Calculation skipped: Model file {} not found.", model_filename);
+        eprintln!("[WARNING] VMAF Calculation skipped: Model file {} not found; download the models in Settings.", model_filename);
         return;
     }
     let model_path = model_path_opt.unwrap();
     
-    let segments: Vec<(f64, f64)>; // (start, duration)
-
-    if config.vmaf_full_computation {
-        segments = vec![(0.0, duration_sec)];
+    let segments: Vec<(f64, f64)> = if config.vmaf_full_computation {
+        vec![(0.0, duration_sec)]
     } else {
-        // Auto config or manual
-        let mut count = config.vmaf_segment_count;
-        let mut dur = config.vmaf_segment_duration as f64;
-        
-        if duration_sec < 20.0 {
-             segments = vec![(0.0, duration_sec)];
-        } else {
-            if config.vmaf_auto_config {
-                 dur = 20.0;
-                 let duration_min = duration_sec / 60.0;
-                 count = (duration_min / 12.0).ceil() as u32;
-                 if count < 1 { count = 1; }
-            } else {
-                if dur > duration_sec { dur = duration_sec; }
-                if dur < 1.0 { dur = 1.0; }
-                if count < 1 { count = 1; }
-            }
-
-            let mut points = Vec::new();
-            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_micros();
-            
-            for i in 0..count {
-                let numerator = (i as f64) + 1.0;
-                let denominator = (count as f64) + 2.0;
-                let base_start = duration_sec * (numerator / denominator);
-                
-                let pseudo_rand = ((now + i as u128 * 12345) % 100) as f64;
-                let offset_sec = (pseudo_rand - 50.0) / 10.0;
-                
-                let mut start = (base_start + offset_sec).round();
-                
-                if start < 0.0 { start = 0.0; }
-                if start + dur > duration_sec {
-                     start = (duration_sec - dur).max(0.0);
-                }
-                start = start.round();
-                if start < 0.0 { start = 0.0; }
-
-                points.push((start, dur));
-            }
-            segments = points;
-        }
-    }
+        compute_sample_segments(duration_sec, config)
+    };
+    let adaptive = config.vmaf_adaptive_segments && !config.vmaf_full_computation && segments.len() > 1;
 
     // Initialize VMAF fields
     if let Some(info) = output_video_info {
-        info.vmaf_total_segments = Some(segments.len() as u32);
+        // In adaptive mode, report only the initial batch; the total grows as segments are added
+        let planned = if adaptive {
+            (config.adaptive_initial_segments() as usize).min(segments.len())
+        } else {
+            segments.len()
+        };
+        info.vmaf_total_segments = Some(planned as u32);
         info.vmaf_detail = Some(Vec::new());
         // Set initial device (optimistic)
-        info.vmaf_device = if config.vmaf_use_cuda { Some("CUDA".to_string()) } else { Some("CPU".to_string()) };
+        info.vmaf_device = if config.vmaf_cuda() { Some("CUDA".to_string()) } else { Some("CPU".to_string()) };
         info.vmaf_model = Some(model_filename.to_string());
     }
 
     // Emit initial status
-    let _ = app.emit("video-progress", ProgressPayload {
+    crate::events::emit_progress(app, ProgressPayload {
         path: input_path.to_string(),
-        progress: 100,
+        progress: 0,
         status: "Evaluating".to_string(),
         speed: 0.0,
         bitrate_kbps: 0.0,
         output_info: output_video_info.clone(),
+        monotonic_ms: crate::monitor::monotonic_ms(),
+        vmaf_segment: None,
     });
 
     let mut scores = Vec::new();
     let mut used_device = "CPU".to_string();
 
-    // Check if we should TRY cuda first
-    let try_cuda = config.vmaf_use_cuda;
+    // Check if we should TRY cuda first (eco mode leaves a busy GPU alone)
+    let try_cuda = config.vmaf_cuda() && !config.eco_gpu_busy();
     let mut cuda_failed_once = false;
 
+    // The output only has the encoded stream; compare it against the same stream of the source
+    let ref_stream = config.video_stream_index.unwrap_or_else(|| {
+        get_video_info(Path::new(reference_path), ffprobe_path).ok()
+            .and_then(|i| i.main_video_stream)
+            .unwrap_or(0)
+    });
+    // The output was cropped, deinterlaced, denoised and fps-capped; the reference has to be too
+    let reference_info = get_video_info(Path::new(reference_path), ffprobe_path).ok();
+    let ref_filters = reference_filter_list(ffmpeg_path, reference_path, reference_info.as_ref(), config);
+    let mut crosscheck_deltas: Vec<f64> = Vec::new();
+    let mut frames: Vec<f64> = Vec::new();
+    let mut frame_log = crate::timeline::FrameLog {
+        fps: output_video_info.as_ref().and_then(|i| i.fps).unwrap_or(0.0),
+        segments: Vec::new(),
+    };
+    let mut progress = 0u8;
+
     for (idx, (start, dur)) in segments.iter().enumerate() {
+        if adaptive && !needs_more_segments(&scores, config) {
+            println!("Adaptive VMAF: scores converged after {} segments: {:?}", scores.len(), scores);
+            break;
+        }
+
         // Check for cancellation before processing segment
         if let Ok(set) = cancelled_paths.lock() {
-            if set.contains(input_path) {
+            if set.contains(&job_key(input_path)) {
                 println!("VMAF Calculation cancelled for {}", input_path);
                 return;
             }
@@ -3069,13 +7008,38 @@ fn calculate_vmaf_score(
         let ss = if config.vmaf_full_computation { None } else { Some(*start) };
         let dt = if config.vmaf_full_computation { None } else { Some(*dur) };
 
+        // Overall percentage from the segments done and ffmpeg's position in this one
+        let total = output_video_info.as_ref().and_then(|i| i.vmaf_total_segments)
+            .map_or(segments.len(), |t| t as usize)
+            .max(idx + 1);
+        let segment_sec = if config.vmaf_full_computation { duration_sec } else { *dur };
+        let vmaf_segment = Some((idx as u32 + 1, total as u32));
+        let mut report = |sec: f64| {
+            let within = if segment_sec > 0.0 { (sec / segment_sec).clamp(0.0, 1.0) } else { 0.0 };
+            let pct = (((idx as f64 + within) / total as f64) * 100.0).min(99.0) as u8;
+            if pct > progress {
+                progress = pct;
+                crate::events::emit_progress(app, ProgressPayload {
+                    path: input_path.to_string(),
+                    progress: pct,
+                    status: "Evaluating".to_string(),
+                    speed: 0.0,
+                    bitrate_kbps: 0.0,
+                    output_info: output_video_info.clone(),
+                    monotonic_ms: crate::monitor::monotonic_ms(),
+                    vmaf_segment,
+                });
+            }
+        };
+
         let mut score = None;
         
         // Try CUDA
         if try_cuda && !cuda_failed_once {
             score = run_vmaf_instance(
                 ffmpeg_path, ffprobe_path, reference_path, distorted_path, 
-                &model_path, true, ss, dt, &pids, input_path, &config.custom_vmaf_params
+                &model_path, true, ss, dt, &pids, input_path, &config.vmaf_filter_params(), ref_stream, &config.force_decoder, config.secure_temp_files,
+                &ref_filters, &mut report,
             );
             if score.is_some() {
                 used_device = "CUDA".to_string();
@@ -3083,13 +7047,27 @@ fn calculate_vmaf_score(
                 println!("VMAF CUDA computation failed for segment {}, falling back to CPU.", idx);
                 cuda_failed_once = true;
             }
+
+            // libvmaf_cuda scales slightly differently; re-score some segments on CPU to compare
+            if let Some((gpu_score, _)) = score {
+                if config.vmaf_crosscheck_segment(idx) {
+                    if let Some((cpu_score, _)) = run_vmaf_instance(
+                        ffmpeg_path, ffprobe_path, reference_path, distorted_path,
+                        &model_path, false, ss, dt, &pids, input_path, &config.vmaf_filter_params(), ref_stream, &config.force_decoder, config.secure_temp_files,
+                        &ref_filters, &mut |_| {},
+                    ) {
+                        println!("VMAF cross-check segment {}: CUDA {:.3} vs CPU {:.3}", idx, gpu_score, cpu_score);
+                        crosscheck_deltas.push(gpu_score - cpu_score);
+                    }
+                }
+            }
         }
 
         // Try CPU
         if score.is_none() {
             // Check for cancellation before fallback
             if let Ok(set) = cancelled_paths.lock() {
-                if set.contains(input_path) {
+                if set.contains(&job_key(input_path)) {
                     println!("VMAF Calculation cancelled during fallback check for {}", input_path);
                     return;
                 }
@@ -3097,28 +7075,40 @@ fn calculate_vmaf_score(
             
             score = run_vmaf_instance(
                 ffmpeg_path, ffprobe_path, reference_path, distorted_path, 
-                &model_path, false, ss, dt, &pids, input_path, &config.custom_vmaf_params
+                &model_path, false, ss, dt, &pids, input_path, &config.vmaf_filter_params(), ref_stream, &config.force_decoder, config.secure_temp_files,
+                &ref_filters, &mut report,
             );
             used_device = "CPU".to_string(); 
         }
         
-        if let Some(s) = score {
+        if let Some((s, segment_frames)) = score {
             scores.push(s);
+            frames.extend(segment_frames.iter().map(|f| f.1));
+            if config.keep_vmaf_log {
+                let start = if config.vmaf_full_computation { 0.0 } else { *start };
+                frame_log.segments.push(crate::timeline::SegmentFrames { start, frames: segment_frames });
+            }
             // Update and emit
             if let Some(info) = output_video_info {
                 if let Some(details) = &mut info.vmaf_detail {
                     details.push(s);
                 }
+                if adaptive && needs_more_segments(&scores, config) && scores.len() < segments.len() {
+                    let total = info.vmaf_total_segments.unwrap_or(0).max(scores.len() as u32 + 1);
+                    info.vmaf_total_segments = Some(total);
+                }
                 // Update device in case fallback happened or it wasn't set correctly
                 info.vmaf_device = Some(used_device.clone());
             }
-            let _ = app.emit("video-progress", ProgressPayload {
+            crate::events::emit_progress(app, ProgressPayload {
                 path: input_path.to_string(),
-                progress: 100,
+                progress,
                 status: "Evaluating".to_string(),
                 speed: 0.0,
                 bitrate_kbps: 0.0,
                 output_info: output_video_info.clone(),
+                monotonic_ms: crate::monitor::monotonic_ms(),
+                vmaf_segment,
             });
         }
     }
@@ -3127,6 +7117,28 @@ fn calculate_vmaf_score(
         let avg = scores.iter().sum::<f64>() / scores.len() as f64;
         if let Some(info) = output_video_info {
             info.vmaf = Some(avg);
+            info.vmaf_pooling = crate::pooling::pool(&frames);
+            if config.keep_vmaf_log && !frame_log.segments.is_empty() {
+                if let Err(e) = crate::timeline::write(distorted_path, &frame_log) {
+                    eprintln!("[WARNING] {}", e);
+                }
+            }
+            if let Some(p) = &info.vmaf_pooling {
+                println!("VMAF pooling for {}: harmonic {:.2}, 5% low {:.2}, 1% low {:.2}, min {:.2}", input_path, p.harmonic_mean, p.p5, p.p1, p.min);
+            }
+            if adaptive {
+                info.vmaf_total_segments = Some(scores.len() as u32);
+            }
+
+            let worst_delta = crosscheck_deltas.iter().cloned().max_by(|a, b| a.abs().total_cmp(&b.abs()));
+            if let Some(delta) = worst_delta {
+                let flagged = delta.abs() > config.vmaf_crosscheck_tolerance();
+                if flagged {
+                    println!("[WARNING] CUDA and CPU VMAF disagree by {:.3} for {}", delta, input_path);
+                }
+                info.vmaf_cpu_delta = Some(delta);
+                info.vmaf_delta_flagged = Some(flagged);
+            }
         }
     }
 }
@@ -3143,19 +7155,24 @@ fn run_vmaf_instance(
     t: Option<f64>,
     pids: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, u32>>>,
     input_key: &str,
-    custom_vmaf_params: &[String],
-) -> Option<f64> {
+    vmaf_params: &[String],
+    ref_stream: u32,
+    force_decoder: &str,
+    secure_temp_files: bool,
+    ref_filters: &[String], // Source filters of the encode (see reference_filter_list)
+    on_progress: &mut dyn FnMut(f64), // Seconds of the segment scored so far
+) -> Option<(f64, Vec<(u64, f64)>)> {
      // Prepare paths
     let model_esc = escape_path_for_filter(model_path);
     // Log file
-    let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_micros();
-    let temp_dir = std::env::temp_dir();
-    let log_path = temp_dir.join(format!("vmaf_log_{}.json", id));
+    let log_path = crate::scratch::scratch_file(secure_temp_files, "vmaf_log", "json")
+        .map_err(|e| eprintln!("[WARNING] {}", e))
+        .ok()?;
     let log_esc = escape_path_for_filter(&log_path.to_string_lossy());
     
     // Build vmaf_opts with custom params
     let mut vmaf_opts = format!("model='path={}':log_fmt=json:log_path='{}'", model_esc, log_esc);
-    for param in custom_vmaf_params {
+    for param in vmaf_params {
         let trimmed = param.trim();
         if !trimmed.is_empty() {
             vmaf_opts.push(':');
@@ -3172,11 +7189,11 @@ fn run_vmaf_instance(
 
     args.push("-v".to_string());
     args.push("info".to_string()); 
+    args.extend(["-progress".to_string(), "pipe:2".to_string(), "-nostats".to_string()]);
     
     // Inputs
     if use_cuda {
-         args.push("-hwaccel".to_string()); args.push("cuda".to_string());
-         args.push("-hwaccel_output_format".to_string()); args.push("cuda".to_string());
+         args.extend(cuda_hwaccel_args());
     }
 
     if let Some(s) = ss { args.push("-ss".to_string()); args.push(s.to_string()); }
@@ -3186,20 +7203,8 @@ fn run_vmaf_instance(
     args.push(dist_path.to_string());
 
     // Reference (Input 1)
-    if use_cuda {
-         let ref_info = get_metadata(ref_path, ffprobe_path);
-         let mut ref_decoder = None;
-         if let Ok(info) = ref_info {
-             ref_decoder = get_cuda_decoder(&info.encoder);
-         }
-         
-         args.push("-hwaccel".to_string()); args.push("cuda".to_string());
-         args.push("-hwaccel_output_format".to_string()); args.push("cuda".to_string());
-         
-         if let Some(dec) = ref_decoder {
-             args.push("-c:v".to_string()); args.push(dec.to_string());
-         }
-    }
+    let (ref_decode_args, ref_upload) = reference_input(ffprobe_path, ref_path, use_cuda, force_decoder, ref_filters);
+    args.extend(ref_decode_args);
     
     if let Some(s) = ss { args.push("-ss".to_string()); args.push(s.to_string()); }
     if let Some(d) = t { args.push("-t".to_string()); args.push(d.to_string()); }
@@ -3210,13 +7215,13 @@ fn run_vmaf_instance(
     // Filter Complex
     let filter = if use_cuda {
         format!(
-            "[0:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[dis];[1:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[ref];[dis][ref]libvmaf_cuda={}", 
-            vmaf_opts
+            "[0:v]setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[dis];[1:v:{}]{}setpts=PTS-STARTPTS,scale_cuda=format=yuv420p[ref];[dis][ref]libvmaf_cuda={}", 
+            ref_stream, ref_upload, vmaf_opts
         )
     } else {
         format!(
-            "[0:v]setpts=PTS-STARTPTS,format=yuv420p[dis];[1:v]setpts=PTS-STARTPTS,format=yuv420p[ref];[dis][ref]libvmaf={}",
-            vmaf_opts
+            "[0:v]setpts=PTS-STARTPTS,format=yuv420p[dis];[1:v:{}]{}setpts=PTS-STARTPTS,format=yuv420p[ref];[dis][ref]libvmaf={}",
+            ref_stream, ref_upload, vmaf_opts
         )
     };
     
@@ -3228,59 +7233,65 @@ fn run_vmaf_instance(
     args.push("-".to_string());
 
     // Spawn
-    let mut command = Command::new(ffmpeg_path);
-    command.args(&args).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut command = crate::process::ffmpeg_command(ffmpeg_path);
+    command.args(&args).stdout(Stdio::null()).stderr(Stdio::piped());
     
-    #[cfg(windows)]
-    let child = {
-        use std::os::windows::process::CommandExt;
-        command.creation_flags(0x08000000); // CREATE_NO_WINDOW if accessible, otherwise standard
-        command.spawn().ok()?
-    };
-    #[cfg(not(windows))]
     let mut child = command.spawn().ok()?;
 
     let pid = child.id();
     {
         if let Ok(mut map) = pids.lock() {
-            map.insert(input_key.to_string(), pid);
+            map.insert(job_key(input_key), pid);
         }
     }
 
-    let output = child.wait_with_output();
+    // Follow -progress output; keep the rest for the "VMAF score:" fallback below
+    let mut stderr = String::new();
+    if let Some(pipe) = child.stderr.take() {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            if let Some(us) = line.strip_prefix("out_time_us=") {
+                if let Ok(us) = us.trim().parse::<f64>() {
+                    on_progress(us / 1_000_000.0);
+                }
+            } else if !line.contains('=') || line.contains(' ') {
+                stderr.push_str(&line);
+                stderr.push('\n');
+            }
+        }
+    }
+    let status = child.wait();
     
     {
         if let Ok(mut map) = pids.lock() {
-            map.remove(input_key);
+            map.remove(&job_key(input_key));
         }
     }
 
-    let o = output.ok()?;
+    status.ok()?;
         
     // Check log file first
     if log_path.exists() {
         if let Ok(content) = std::fs::read_to_string(&log_path) {
             // Parse JSON
             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-                 let _ = std::fs::remove_file(&log_path);
+                 crate::scratch::remove_scratch_file(&log_path, secure_temp_files);
                  if let Some(metrics) = json.get("pooled_metrics") {
                      if let Some(vmaf) = metrics.get("vmaf") {
                          if let Some(mean) = vmaf.get("mean") {
-                              return mean.as_f64();
+                              return mean.as_f64().map(|mean| (mean, crate::timeline::frame_series(&json)));
                          }
                      }
                  }
             }
         }
-        let _ = std::fs::remove_file(&log_path);
+        crate::scratch::remove_scratch_file(&log_path, secure_temp_files);
     }
 
     // Fallback: parse stderr
-     let stderr = String::from_utf8_lossy(&o.stderr);
      if let Some(idx) = stderr.find("VMAF score: ") {
          let rest = &stderr[idx+12..];
          let val_str = rest.split_whitespace().next().unwrap_or("0");
-         return val_str.parse().ok();
+         return val_str.parse().ok().map(|mean| (mean, Vec::new()));
      }
 
     None
@@ -3305,3 +7316,21 @@ fn cleanup_pass_logs(prefix: &str, temp_output_path: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::bit_depth_of_pix_fmt;
+
+    #[test]
+    fn bit_depth_of_pix_fmt_reads_the_depth_not_the_layout() {
+        for (pix_fmt, depth) in [
+            ("yuv420p", 8), ("yuvj420p", 8), ("yuv420p10le", 10), ("yuv444p12be", 12), ("yuv420p9le", 9),
+            ("nv12", 8), ("nv16", 8), ("nv21", 8), ("nv24", 8), ("nv42", 8), ("nv20le", 10),
+            ("p010le", 10), ("p016le", 16), ("p210le", 10), ("p416le", 16), ("y210le", 10),
+            ("gray", 8), ("gray10le", 10), ("rgb24", 8), ("rgb565le", 8), ("rgb48le", 16),
+            ("rgba64be", 16), ("gbrp12le", 12), ("x2rgb10le", 10), ("xv36le", 12),
+        ] {
+            assert_eq!(bit_depth_of_pix_fmt(pix_fmt), depth, "{}", pix_fmt);
+        }
+    }
+}
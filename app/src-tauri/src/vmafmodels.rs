@@ -0,0 +1,111 @@
+//! VMAF models downloaded from the Netflix repository into the app data dir, for installs
+//! whose ffmpeg build came without its `model` folder. There is no HTTP client in the app,
+//! so the files are fetched with the system `curl` (shipped with Windows 10+ and macOS).
+//! Downloads come from a release tag and must match a known SHA-256 before they are used.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+const BASE_URL: &str = "https://raw.githubusercontent.com/Netflix/vmaf/v3.0.0/model/";
+
+/// Every model `calculate_vmaf_score` may pick (HD/4K, with and without NEG)
+pub const MODELS: &[&str] = &["vmaf_v0.6.1.json", "vmaf_4k_v0.6.1.json", "vmaf_v0.6.1neg.json", "vmaf_4k_v0.6.1neg.json"];
+
+/// SHA-256 of each model in MODELS at BASE_URL
+const CHECKSUMS: &[(&str, &str)] = &[
+    ("vmaf_v0.6.1.json", "5950d61fa1f861bd45d8149d80539ed9f3376cfc2495b8f0fa8e9f57cb131ee3"),
+    ("vmaf_4k_v0.6.1.json", "73b187001309703c89d57cf58baab01660bd11e4ea6fac62bc064c5f5da6dac8"),
+    ("vmaf_v0.6.1neg.json", "1fcda193aaa2a3136471b7516b3c5dc344546c05fcde2d58609f8c60a2387392"),
+    ("vmaf_4k_v0.6.1neg.json", "f92048bf94401a5b915f551eb248709275c61df5f20d062a3ae010dcf4d4e8fc"),
+];
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelStatus {
+    pub name: String,
+    pub path: Option<String>, // Where it was found, None if missing
+    pub downloaded: bool, // Found in the download dir
+    pub error: Option<String>, // Why the last download failed
+}
+
+pub fn dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|d| d.join("vmaf_models"))
+}
+
+/// The downloaded copy of `model_filename`, if there is one
+pub fn downloaded(app: &AppHandle, model_filename: &str) -> Option<PathBuf> {
+    let path = dir(app)?.join(model_filename);
+    path.exists().then_some(path)
+}
+
+pub fn status(app: &AppHandle, ffmpeg_path: &str) -> Vec<ModelStatus> {
+    MODELS.iter()
+        .map(|name| {
+            let path = crate::video::find_vmaf_model(app, ffmpeg_path, name);
+            let downloaded = path.as_ref().is_some_and(|p| downloaded(app, name).is_some_and(|d| d.as_os_str() == p.as_str()));
+            ModelStatus { name: name.to_string(), path, downloaded, error: None }
+        })
+        .collect()
+}
+
+/// Download `name` into `target`: to a `.part` file of this process first, moved into place
+/// only once its checksum matches, so `target` never holds a partial or foreign file
+fn fetch(name: &str, target: &std::path::Path) -> Result<(), String> {
+    let expected = CHECKSUMS.iter().find(|(n, _)| *n == name).map(|(_, sum)| *sum)
+        .ok_or_else(|| format!("No checksum for {}", name))?;
+    let part = target.with_extension(format!("json.{}.part", std::process::id()));
+    let output = crate::process::tool_command("curl")
+        .args(["-fsSL", "--max-time", "60", "-o"])
+        .arg(&part)
+        .arg(format!("{}{}", BASE_URL, name))
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => format!(
+                "curl was not found; install it, or copy {} from {} into {:?}",
+                name, BASE_URL, target.parent().unwrap_or(target)
+            ),
+            _ => format!("Failed to run curl: {}", e),
+        })?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&part);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Download failed: {}", stderr.trim()));
+    }
+
+    // A proxy error page or a changed upstream file would otherwise pass for the model
+    let content = std::fs::read(&part).map_err(|e| e.to_string())?;
+    let actual: String = Sha256::digest(&content).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        let _ = std::fs::remove_file(&part);
+        return Err(format!("Checksum mismatch for {} (got {})", name, actual));
+    }
+    std::fs::rename(&part, target).map_err(|e| {
+        let _ = std::fs::remove_file(&part);
+        format!("Failed to save {:?}: {}", target, e)
+    })
+}
+
+/// Download the models that can't be found anywhere; returns the status of all of them
+pub fn download_missing(app: &AppHandle, ffmpeg_path: &str) -> Result<Vec<ModelStatus>, String> {
+    let dir = dir(app).ok_or("No app data directory")?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    let mut statuses = status(app, ffmpeg_path);
+    for model in statuses.iter_mut().filter(|m| m.path.is_none()) {
+        let target = dir.join(&model.name);
+        match fetch(&model.name, &target) {
+            Ok(()) => {
+                println!("[INFO] Downloaded VMAF model {} to {:?}", model.name, target);
+                model.path = Some(target.to_string_lossy().into_owned());
+                model.downloaded = true;
+            }
+            Err(e) => {
+                eprintln!("[WARNING] VMAF model {}: {}", model.name, e);
+                model.error = Some(e);
+            }
+        }
+    }
+    Ok(statuses)
+}
@@ -1,6 +1,55 @@
 
 import { DEFAULT_SETTINGS, type AppSettings } from '../types';
 
+/** Remove every `flag value` pair from custom params; returns the last value (the one ffmpeg used) */
+function takeParam(params: string[], flag: string): { params: string[]; value: string | null } {
+    let value: string | null = null;
+    let valueNext = false;
+    const kept = params.map((item) =>
+        item.split(/\s+/).filter((token) => {
+            if (valueNext) {
+                value = token;
+                valueNext = false;
+                return false;
+            }
+            if (token === flag) {
+                valueNext = true;
+                return false;
+            }
+            return token !== '';
+        }).join(' '),
+    );
+    return { params: kept.filter((item) => item !== ''), value };
+}
+
+// Settings saved before the typed encoder options kept them in the custom params, where
+// they came last on the command line; move them into the typed fields (same as schema.rs)
+function migrateEncoderParams(s: AppSettings): AppSettings {
+    const availableVideoEncoders = s.availableVideoEncoders.map((e) => ({ ...e }));
+    const encoders = [...availableVideoEncoders].sort(
+        (a, b) => Number(a.value === s.videoEncoder) - Number(b.value === s.videoEncoder),
+    );
+    let nvenc = s.nvenc;
+    for (const encoder of encoders.filter((e) => e.value.includes('nvenc'))) {
+        for (const flag of ['preset', 'tune'] as const) {
+            const taken = takeParam(encoder.customParams, `-${flag}`);
+            encoder.customParams = taken.params;
+            if (taken.value !== null) {
+                nvenc = { ...(nvenc ?? DEFAULT_SETTINGS.nvenc!), [flag]: taken.value };
+            }
+        }
+    }
+    for (const encoder of encoders.filter((e) => e.value.includes('libsvtav1'))) {
+        const taken = takeParam(encoder.customParams, '-preset');
+        const preset = taken.value === null ? NaN : Number(taken.value);
+        if (Number.isInteger(preset)) {
+            encoder.customParams = taken.params;
+            encoder.svtav1 = { ...(encoder.svtav1 ?? { preset: null, filmGrain: null, tune: null }), preset };
+        }
+    }
+    return { ...s, availableVideoEncoders, nvenc };
+}
+
 function createSettingsStore() {
     let settings = $state<AppSettings>(DEFAULT_SETTINGS);
     let loaded = false;
@@ -10,7 +59,7 @@ function createSettingsStore() {
         const stored = localStorage.getItem('app_settings');
         if (stored) {
             try {
-                settings = { ...DEFAULT_SETTINGS, ...JSON.parse(stored) };
+                settings = migrateEncoderParams({ ...DEFAULT_SETTINGS, ...JSON.parse(stored) });
             } catch (e) {
                 console.error("Failed to parse settings", e);
             }
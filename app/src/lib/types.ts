@@ -1,8 +1,10 @@
 
 export enum CompressionMode {
     BITRATE = 'bitrate',
+    PERCENT = 'percent', // Bitrate mode with each file's target a share of its own bitrate
     CRF = 'crf',
     VMAF = 'vmaf',
+    VMAF_ABR = 'vmaf_abr', // VMAF search, then capped two-pass ABR at the measured bitrate
     COPY = 'copy',
     CUSTOM = 'custom'
 }
@@ -14,7 +16,7 @@ export interface VideoInfo {
     resolution: string;
     bitrate: string;
     encoder: string;
-    status: string; // "Scanning", "Pending", "Processing", "Done", "Error", "Cancelled"
+    status: string; // "Scanning", "Pending", "Processing", "Done", "Error", "Cancelled", "Protected", "Unreadable"
     progress: number;
     durationSec: number;
     speed?: number;
@@ -24,13 +26,269 @@ export interface VideoInfo {
     vmafDevice?: string; // "CPU" or "CUDA"
     vmafDetail?: number[]; // Detail scores per segment
     vmafTotalSegments?: number;
+    vmafSegment?: [number, number]; // [segment, of segments], 1-based, while "Evaluating"
     vmafModel?: string;
+    vmafCpuDelta?: number; // Largest CUDA - CPU difference among cross-checked segments
+    vmafDeltaFlagged?: boolean; // Cross-check difference exceeded the tolerance
+    ffmpegCommands?: string[]; // Exact ffmpeg command line of each pass
+    estimatedQuality?: string; // "high" | "medium" | "low", for skipped/copied files
+    videoStreamCount?: number; // Set only for files with several video streams
+    mainVideoStream?: number; // ffmpeg v:N index of the stream treated as the video
+    probeError?: string; // Why a "Protected"/"Unreadable"/image file could not be probed or queued
+    fps?: number;
+    pixFmt?: string;
+    bitDepth?: number;
+    hdr?: boolean; // PQ or HLG transfer
+    colorTransfer?: string; // "bt709", "smpte2084", "arib-std-b67", ...
+    fieldOrder?: string; // "progressive", "tt", "bb", "tb", "bt" or "unknown"
+    rotation?: number; // Display rotation, degrees clockwise (phone videos)
+    frameCount?: number; // From the container, else estimated from duration and fps
+    audioTracks?: AudioTrack[];
+    subtitleTracks?: SubtitleTrack[];
+    encoderStats?: EncoderStats; // Encoder summary of the encode that made this output
+    qualityTier?: string; // Per-file override of the batch's quality tier ("" = none)
+    bitsPerPixel?: number; // Video bits per pixel per frame
+    compressionRatio?: number; // Outputs: source size / output size
+    sourceBitsPerPixel?: number; // Outputs: the source's bits per pixel
+    crop?: string; // Pre-analysis: black bars as a crop=w:h:x:y value
+    interlacedRatio?: number; // Pre-analysis: share of frames idet found interlaced
+    vmafPooling?: VmafPooling; // Over the scored frames; vmaf itself is the mean
+    sourceBackup?: string; // Outputs that replaced their source: where the original can be restored from
     vmafSearchEndProgress?: number; // Progress when VMAF search ended (for smooth progress)
     originalOutputDir?: string; // For multi-drop mode: output to original directory
     foundCrf?: number; // Result from VMAF Search
     foundVmafScore?: number; // Result from VMAF Search
 }
 
+export interface VmafPooling {
+    harmonicMean: number;
+    p5: number; // 5% lows
+    p1: number; // 1% lows
+    min: number;
+}
+
+// VMAF of a standalone comparison of two files (compare_files)
+export interface Comparison {
+    vmaf: number;
+    model: string | null;
+    device: string | null;
+    pooling: VmafPooling | null;
+    segments: number[];
+}
+
+// A CRF search result reused for unchanged files (list_crf_cache)
+export interface CachedCrfSearch {
+    path: string;
+    size: number;
+    modifiedMs: number;
+    encoder: string;
+    targetVmaf: number;
+    preset: string;
+    pooling: string;
+    vmafNeg: boolean;
+    settings: string; // Hash of the other search settings
+    crf: number;
+    vmaf: number;
+    cachedAt: number; // Unix seconds
+}
+
+// Where a VMAF model was found (list_vmaf_models / download_vmaf_models)
+export interface VmafModelStatus {
+    name: string;
+    path: string | null; // null if missing
+    downloaded: boolean;
+    error: string | null; // Why its download failed
+}
+
+// A running or waiting VMAF task (list_vmaf_queue)
+export interface VmafQueueEntry {
+    inputPath: string;
+    distortedPath: string;
+    position: number | null; // Place in the queue, null while running
+    cuda: boolean;
+}
+
+// Per-second VMAF of an output, from its kept per-frame scores
+export interface VmafTimeline {
+    points: { second: number; mean: number; min: number }[];
+    problems: { start: number; end: number; min: number }[]; // Below the file's 5% lows
+}
+
+export interface QualityTier {
+    id: string;
+    encoders: string[];
+    targetVmaf: number[]; // SD, HD, UHD
+    targetCrf: number[];
+}
+
+// Named compression settings saved by the backend
+// GPU feature avoided on a driver range: "vmafCuda" or "nvencBframes"
+export interface GpuQuirkRule {
+    feature: string;
+    minDriver: string; // "" = no lower bound
+    maxDriver: string; // First good version, "" = not fixed yet
+    reason: string;
+}
+
+export interface GpuQuirk {
+    feature: string;
+    driverVersion: string;
+    reason: string;
+}
+
+// Narrows scans; files are probed during the scan when any field is set
+export interface ScanFilter {
+    codecs: string[];
+    minResolution: number; // Shorter side in pixels (1080 = 1080p)
+    minBitrateKbps: number;
+    minDurationSec: number;
+    minSizeMb: number; // Applied before probing; size alone never probes
+}
+
+// `scan-progress` event of a folder scan: found files batch by batch, then a final
+// summary with `done` set
+export interface ScanProgress {
+    scanId: number;
+    videos: VideoInfo[];
+    found: number;
+    filteredOut: number;
+    errors: string[];
+    done: boolean;
+}
+
+// `metadata-progress` event of get_video_metadata_batch
+export interface MetadataProgress {
+    batchId: number;
+    results: { path: string; info: VideoInfo | null; error: string | null }[];
+    completed: number;
+    total: number;
+    done: boolean;
+}
+
+// A file the batch pre-filter skips for its codec
+export interface CodecSkip {
+    path: string;
+    status: string; // "Skipped (already AV1)"
+}
+
+// A stream the target container can't hold, from the remux preflight
+export interface Incompatibility {
+    stream: string; // "v:0", "a:1", "s:0" or "encoder"
+    codec: string;
+    reason: string;
+    fix: string;
+    blocking: boolean; // ffmpeg would fail; otherwise the stream is just left out
+}
+
+export interface RemuxReport {
+    path: string;
+    container: string;
+    incompatibilities: Incompatibility[];
+}
+
+// Original and encoded snippet of the same window, for an A/B comparison
+export interface PreviewClip {
+    original: string;
+    encoded: string;
+    start: number;
+    duration: number;
+    note: string | null; // Where the preview differs from the real encode
+}
+
+export interface FinishedOriginal {
+    path: string;
+    output: string;
+}
+
+export interface DeleteFailure {
+    path: string;
+    error: string;
+}
+
+// Post-batch cleanup: originals whose outputs verified, and the ones that must stay
+export interface ReconcileReport {
+    safeToDelete: {
+        sourcePath: string;
+        outputPath: string;
+        sourceSize: number;
+        outputSize: number;
+        vmaf: number | null;
+    }[];
+    reclaimableBytes: number;
+    problems: { sourcePath: string; outputPath: string; reason: string }[];
+    duplicateOutputs: string[];
+    unmatchedOutputs: string[];
+}
+
+export interface Preset {
+    name: string;
+    config: Partial<AppSettings>;
+}
+
+// Background watch-folder service (the app started with --service)
+export interface WatchFolder {
+    path: string;
+    preset: string;
+    outputTemplate: string;
+}
+
+export interface ServiceConfig {
+    watchFolders: WatchFolder[];
+    pollIntervalSecs: number;
+}
+
+export interface ServiceStatus {
+    pid: number;
+    startedMs: number;
+    processed: number;
+    failed: number;
+    lastError: string | null;
+    halted: boolean;
+    current: string[];
+    running: boolean;
+}
+
+export interface BudgetProposal {
+    action: "fasterPreset" | "hardwareEncoder" | "defer";
+    estimatedSecs: number; // Remaining time if the action is taken
+    paths: string[]; // Files to defer (defer only)
+}
+
+// Payload of the budget-status event
+export interface BudgetStatus {
+    deadlineMs: number;
+    availableSecs: number;
+    remainingJobs: number;
+    estimatedRemainingSecs: number | null; // null until the first job has finished
+    fits: boolean | null;
+    proposals: BudgetProposal[];
+}
+
+export interface EncoderStats {
+    preset?: string;
+    avgQp?: number;
+    iFrames?: number;
+    pFrames?: number;
+    bFrames?: number;
+    bFrameRatio?: number; // B-frames / all frames
+    encodeFps?: number;
+}
+
+export interface AudioTrack {
+    codec: string;
+    channelLayout: string; // "stereo", "5.1", ... or "N channels"
+    channels: number;
+    language?: string; // ISO 639-2, absent when undetermined
+    bitrateKbps?: number;
+}
+
+export interface SubtitleTrack {
+    codec: string;
+    language?: string;
+    title?: string;
+    forced: boolean;
+}
+
 // VMAF CRF search progress event payload
 export interface VmafSearchProgress {
     path: string;
@@ -44,6 +302,68 @@ export interface VmafSearchProgress {
     samples: [number, number][]; // [crf, vmaf] pairs
 }
 
+// job-phase-changed event payload
+export type JobPhase =
+    | 'Queued' | 'Probing' | 'Searching' | 'Pass1' | 'Pass2' | 'Encoding'
+    | 'Verifying' | 'Moving' | 'VMAF' | 'Done' | 'Skipped' | 'Error' | 'Cancelled';
+
+export interface JobPhaseChanged {
+    path: string;
+    phase: JobPhase;
+    timestampMs: number; // Unix milliseconds
+    previousPhase?: JobPhase;
+    previousPhaseMs?: number; // Time spent in previousPhase
+}
+
+// batch-halted event payload
+export interface BatchHalted {
+    errorClass: string; // "encoder_missing" | "disk_full" | "permission_denied" | "ffmpeg_missing"
+    diagnosis: string;
+    consecutiveFailures: number;
+    lastError: string;
+}
+
+// audit_batch result
+export interface AuditEntry {
+    inputPath: string;
+    outputPath: string;
+    vmaf?: number; // Missing if it could not be computed
+    belowThreshold: boolean;
+}
+
+export interface AuditReport {
+    total: number;
+    threshold: number;
+    entries: AuditEntry[];
+    cancelled: boolean; // Stopped by cancel_processing(auditKey); entries scored so far
+}
+
+// submit_manifest result
+export interface ManifestResult {
+    submitted: number;
+    succeeded: string[]; // Output paths
+    failed: { inputPath: string; error: string }[];
+    scanErrors: string[];
+    halted: boolean; // Stopped early by haltAfterFailures
+}
+
+// Persistent backend job queue (enqueue_job / list_jobs / remove_job / run_job_queue / requeue_with_changes)
+export type QueuedJobStatus = "Pending" | "Running" | "Done" | "Failed" | "Cancelled";
+
+export interface QueuedJob {
+    id: number;
+    inputPath: string;
+    outputPath: string;
+    config: AppSettings;
+    durationSec: number;
+    status: QueuedJobStatus;
+    error?: string;
+    deferrals: number;
+}
+
+// requeue_with_changes: AppSettings fields (or outputPath) to replace; { delta: n } adjusts a number
+export type JobOverrides = Record<string, unknown | { delta: number }>;
+
 // ... (imports remain same, not restated here)
 
 export interface EncoderConfig {
@@ -52,6 +372,32 @@ export interface EncoderConfig {
     visible: boolean; // Controls visibility in the basic settings dropdown
     customParams: string[]; // Extra arguments specific to this encoder, one per line
     isSupported?: boolean; // Whether the encoder is currently supported/detected
+    svtav1?: SvtAv1Options; // libsvtav1 only
+}
+
+// Typed SVT-AV1 options, used for CRF search samples and the final encode alike
+export interface SvtAv1Options {
+    preset: number | null; // 0 (slowest) - 13 (fastest)
+    filmGrain: number | null; // Grain synthesis level 0-50, 0 = off
+    tune: number | null; // 0 = VQ, 1 = PSNR, 2 = SSIM
+}
+
+// Typed NVENC options; empty/null fields take a default suited to the compression mode
+export interface NvencOptions {
+    preset: string; // 'p1'..'p7'
+    tune: string; // 'hq' | 'll' | 'ull' | 'lossless'
+    rateControl: string; // 'vbr' | 'cbr' (bitrate modes only)
+    multipass: string; // 'disabled' | 'qres' | 'fullres'
+    spatialAq: boolean | null;
+    temporalAq: boolean | null; // Not available on av1_nvenc
+    lookahead: number | null; // Frames, 0-32
+}
+
+// Output container for a source extension, e.g. { sourceExt: 'avi', targetFormat: 'mkv' }.
+// targetFormat 'original' keeps the source container.
+export interface ContainerMapping {
+    sourceExt: string;
+    targetFormat: string;
 }
 
 export interface AppSettings {
@@ -66,11 +412,18 @@ export interface AppSettings {
     ffprobeThreads: number;
     maxResolution: {
         enabled: boolean;
-        width: number;
-        height: number;
+        width: number; // 0 = no width limit
+        height: number; // 0 = no height limit
+        algorithm: string; // Scaler: 'lanczos' | 'bicubic'
     };
     videoEncoder: string;
     audioEncoder: string;
+    // 0 = encoder default / as the source
+    audioBitrateKbps: number;
+    audioChannels: number; // 2 = downmix to stereo
+    audioSampleRate: number; // Hz
+    loudnormTarget: number; // LUFS, two-pass EBU R128; 0 = off
+    audioExtractFormat: string; // 'mp3' | 'aac' | 'flac' | 'opus', for the row's extract audio button
     targetFormat: string;
 
     // Advanced but stored here
@@ -83,6 +436,7 @@ export interface AppSettings {
 
     // Custom VMAF calculation params
     customVmafParams: string[];
+    vmafSubsample: number; // Score every Nth frame only (libvmaf n_subsample), 0/1 = every frame
 
     // Safety lock for manual filter editing
 
@@ -107,12 +461,152 @@ export interface AppSettings {
     vmafUseCuda: boolean;
     vmafNeg: boolean;
 
+    // GPU kill switch: built-in + these driver ranges turn off libvmaf_cuda / NVENC B-frames
+    gpuBlacklist: GpuQuirkRule[];
+    ignoreGpuBlacklist: boolean;
+
+    vmafAdaptiveSegments: boolean; // Start with a few segments, add more only if scores vary
+    vmafAdaptiveInitialSegments: number;
+    vmafAdaptiveMaxSegments: number;
+    vmafAdaptiveVarianceThreshold: number; // Variance (VMAF points squared) that triggers extra segments
+    vmafAbrMaxratePercent: number; // VMAF ABR mode: -maxrate as a percentage of the measured bitrate
+
+    // Write <output>.json next to each output (source, settings, CRF, VMAF, timestamps)
+    writeSidecar: boolean;
+
+    // Keep per-frame VMAF as <output>.vmaf.json for the quality timeline
+    keepVmafLog: boolean;
+
+    // Throughput cap for file copies to the output (Mbps, 0 = unlimited)
+    copyBandwidthLimitMbps: number;
+
+    // Eco mode: fewer threads, low priority, avoid a busy GPU, pause on battery
+    ecoMode: boolean;
+    ecoMaxThreads: number; // 0 = half the logical cores
+    ecoGpuThreshold: number; // GPU usage %
+
+    // Output may replace its own source file only when allowed; optionally keep <file>.bak
+    allowOverwriteSource: boolean;
+    backupBeforeOverwrite: boolean;
+
+    // Replace-original mode: output takes the source's name, the original goes to backupLocation
+    replaceOriginal: boolean;
+    backupLocation: "" | "folder" | "trash"; // "" = <file>.bak next to the source
+    backupRetentionHours: number; // .backup copies older than this are purged, 0 = keep all
+
+    // Delete sources once their output is done (and VMAF-scored, if enabled)
+    deleteSourceAfter: boolean;
+    deleteSourceToTrash: boolean; // Recycle bin instead of permanent deletion
+
+    // Output file names: "" = as is, "nfc" = Unicode NFC, "ascii" = transliterated to ASCII
+    filenameNormalization: "" | "nfc" | "ascii";
+
+    // Source codecs not to re-encode ("av1", "hevc", ...)
+    skipCodecs: string[];
+
+    // Emergency pause of all jobs on an overheating GPU or a CPU pegged by other programs while the computer is in use
+    thermalGuard: boolean;
+    thermalGpuMaxTemp: number; // °C
+
+    // Scan filter (0 / empty = off); see ScanFilter
+    scanFilterCodecs: string[];
+    scanMinResolution: number;
+    scanMinBitrateKbps: number;
+    scanMinDurationSec: number;
+    scanMinSizeMb: number;
+
+    // Per-extension output container, checked before targetFormat
+    containerMapping: ContainerMapping[];
+
+    // Encoder used for the rest of the session if the selected one disappears ('' = libx264)
+    fallbackVideoEncoder: string;
+
+    // Frame-accurate (slower) seeking for CRF search samples
+    accurateSeek: boolean;
+
+    // Re-score a share of CUDA VMAF segments on CPU and flag disagreements
+    vmafCpuCrosscheckPercent: number; // 0 = off
+    vmafCrosscheckTolerance: number; // VMAF points
+
+    // Defer starting new jobs while free memory is below this (MB, 0 = off)
+    minFreeMemoryMb: number;
+
+    // Adjust encoder tuning flags for animation vs live action
+    autoTune: boolean;
+
+    // NVENC preset/tuning/rate control/multipass/AQ/lookahead (null = encoder defaults)
+    nvenc: NvencOptions | null;
+
+    // Bitrate-based quality badge for skipped and stream-copied files
+    estimateSkippedQuality: boolean;
+
+    // Video stream (ffmpeg v:N index) to encode in multi-stream files, null = auto
+    videoStreamIndex: number | null;
+
+    // Audio tracks to keep: "all", "first" or "languages" (audioLanguages in order,
+    // the first track if none match); "" = ffmpeg's pick, a single track
+    audioMapping: string;
+    audioLanguages: string[]; // ISO 639-2 codes, e.g. ["jpn", "eng"]
+    // Subtitles: "copy" (what the container can hold), "convert" (text subs into the
+    // container's text format too), "drop"; "" = ffmpeg's pick
+    subtitleMode: string;
+    // Container metadata: "keep" (tags and chapters), "strip" (none, for privacy); "" = ffmpeg's default
+    metadataMode: string;
+    keepAttachments: boolean; // MKV outputs: copy attachments (fonts for ASS subtitles)
+    // Subtitle rendered into the picture: external file, or else source stream s:N; null = none
+    burnInSubtitle: { streamIndex: number; file: string } | null;
+    autoCrop: boolean; // Crop the black bars cropdetect finds
+    deinterlace: string; // 'yadif' | 'bwdif' for sources found interlaced; '' = never
+    denoise: string; // 'hqdn3d_light' ... 'nlmeans_strong'; '' = none
+    maxFps: number; // Frame rate cap, only for sources above it; 0 = none
+    // Rotated sources: 'bake' turns the pixels upright, 'preserve' keeps the rotation flag; '' = ffmpeg's default
+    rotationMode: string;
+
+    // Pause the batch after this many consecutive jobs fail the same systemic way (0 = never)
+    haltAfterFailures: number;
+
+    // Private scratch folder for samples; shred samples and failed outputs on cleanup
+    secureTempFiles: boolean;
+
+    // Encodes the backend runs at once (0 = no limit); worker count for the backend queue
+    maxConcurrentEncodes: number;
+
+    // Source/VMAF reference decoder instead of the automatic one (e.g. "hevc"), "" = auto
+    forceDecoder: string;
+
+    // Compare output and source frame counts after encoding (reads both files)
+    verifyFrameCount: boolean;
+
+    // Outputs verified at once (0 = no limit); verification doesn't hold an encode slot
+    maxConcurrentVerifications: number;
+
+    // Post-encode VMAF computations at once (0 = 1); CUDA ones still run one at a time
+    maxConcurrentVmaf: number;
+
+    // "archive" | "balanced" | "small": encoder and target from the backend's tier table, "" = off
+    qualityTier: string;
+
+    // Extra environment (e.g. CUDA_VISIBLE_DEVICES) and global ffmpeg flags (e.g. -nostdin) for every ffmpeg/ffprobe a job starts
+    env: Record<string, string>;
+    globalFlags: string[];
+
+    // Percent mode: target size as % of the source
+    targetSizePercent: number;
+
+    // Discard outputs that don't save at least minSavingsPercent and keep the original
+    keepOnlyIfSmaller: boolean;
+    minSavingsPercent: number;
+
     // Encoder filter settings
     showAllEncoders: boolean; // Show all available encoders without filtering
     showOnlyHwEncoders: boolean; // Only show hardware-accelerated video encoders
 
     // VMAF search optimization
     vmafSearchOptimization: boolean; // Enable Bayesian optimization for VMAF CRF search
+    vmafSearchPooling: string; // Score the search compares with the target: '' (mean) | 'harmonic_mean' | 'p5' | 'p1' | 'min'
+    crfSearchSegments: number; // Sample segments scored per CRF candidate (0/1 = first only); ignored with adaptive sampling
+    samplePreset: string; // Faster encoder preset for CRF search samples, '' = the final one
+    sampleCrfOffset: number; // Added to the found CRF for the final encode when samplePreset is set
 
     // Custom Command Mode
     customCommand: string;
@@ -131,18 +625,24 @@ export const DEFAULT_SETTINGS: AppSettings = {
     maxResolution: {
         enabled: false,
         width: 1920,
-        height: 1080
+        height: 1080,
+        algorithm: 'lanczos'
     },
     videoEncoder: 'libx264',
     audioEncoder: 'aac',
+    audioBitrateKbps: 0,
+    audioChannels: 0,
+    audioSampleRate: 0,
+    loudnormTarget: 0,
+    audioExtractFormat: 'mp3',
     targetFormat: 'mp4',
     availableVideoEncoders: [
         { name: 'libx264 (CPU)', value: 'libx264', visible: true, isSupported: true, customParams: ['-preset medium', '-profile:v high'] },
         { name: 'libx265 (CPU)', value: 'libx265', visible: true, isSupported: true, customParams: ['-preset medium', '-tag:v hvc1'] },
-        { name: 'libsvtav1 (CPU)', value: 'libsvtav1', visible: true, isSupported: true, customParams: ['-preset 8', '-svtav1-params tune=0'] },
-        { name: 'h264_nvenc (HW)', value: 'h264_nvenc', visible: true, isSupported: true, customParams: ['-preset p4'] },
-        { name: 'hevc_nvenc (HW)', value: 'hevc_nvenc', visible: true, isSupported: true, customParams: ['-preset p4', '-tag:v hvc1'] },
-        { name: 'av1_nvenc (HW)', value: 'av1_nvenc', visible: true, isSupported: true, customParams: ['-preset p4', '-tune hq'] },
+        { name: 'libsvtav1 (CPU)', value: 'libsvtav1', visible: true, isSupported: true, customParams: [], svtav1: { preset: 8, filmGrain: 0, tune: 0 } },
+        { name: 'h264_nvenc (HW)', value: 'h264_nvenc', visible: true, isSupported: true, customParams: [] },
+        { name: 'hevc_nvenc (HW)', value: 'hevc_nvenc', visible: true, isSupported: true, customParams: ['-tag:v hvc1'] },
+        { name: 'av1_nvenc (HW)', value: 'av1_nvenc', visible: true, isSupported: true, customParams: [] },
     ],
     availableAudioEncoders: [
         { name: 'aac (CPU)', value: 'aac', visible: true, isSupported: true, customParams: ['-b:a 192k'] },
@@ -150,7 +650,8 @@ export const DEFAULT_SETTINGS: AppSettings = {
         { name: 'libopus (CPU)', value: 'libopus', visible: true, isSupported: true, customParams: ['-b:a 128k', '-vbr on'] },
     ],
     customFilters: ['-pix_fmt yuv420p', '-map_metadata 0', '-movflags +faststart'],
-    customVmafParams: ['n_subsample=5'],
+    customVmafParams: [],
+    vmafSubsample: 5,
 
     suffix: '_compressed',
     twoPass: false,
@@ -167,14 +668,122 @@ export const DEFAULT_SETTINGS: AppSettings = {
     vmafUseCuda: true,          // Option 4: Experimental CUDA
     vmafNeg: false,
 
+    gpuBlacklist: [],
+    ignoreGpuBlacklist: false,
+
+    vmafAdaptiveSegments: false,
+    vmafAdaptiveInitialSegments: 3,
+    vmafAdaptiveMaxSegments: 9,
+    vmafAdaptiveVarianceThreshold: 1.0,
+    vmafAbrMaxratePercent: 150,
+
+    writeSidecar: false,
+    keepVmafLog: false,
+    copyBandwidthLimitMbps: 0,
+
+    ecoMode: false,
+    ecoMaxThreads: 0,
+    ecoGpuThreshold: 50,
+
+    allowOverwriteSource: false,
+    backupBeforeOverwrite: true,
+
+    replaceOriginal: false,
+    backupLocation: "",
+    backupRetentionHours: 24,
+
+    deleteSourceAfter: false,
+    deleteSourceToTrash: true,
+
+    filenameNormalization: "",
+
+    skipCodecs: [],
+
+    thermalGuard: false,
+    thermalGpuMaxTemp: 90,
+
+    scanFilterCodecs: [],
+    scanMinResolution: 0,
+    scanMinBitrateKbps: 0,
+    scanMinDurationSec: 0,
+    scanMinSizeMb: 0,
+
+    containerMapping: [],
+
+    fallbackVideoEncoder: '',
+
+    accurateSeek: false,
+
+    vmafCpuCrosscheckPercent: 0,
+    vmafCrosscheckTolerance: 1.0,
+
+    minFreeMemoryMb: 0,
+
+    autoTune: false,
+
+    nvenc: { preset: '', tune: '', rateControl: '', multipass: '', spatialAq: null, temporalAq: null, lookahead: null },
+
+    estimateSkippedQuality: false,
+
+    videoStreamIndex: null,
+
+    audioMapping: "all",
+    audioLanguages: [],
+    subtitleMode: "convert",
+    metadataMode: "keep",
+    keepAttachments: true,
+    burnInSubtitle: null,
+    autoCrop: false,
+    deinterlace: 'bwdif',
+    denoise: '',
+    maxFps: 0,
+    rotationMode: 'bake',
+
+    haltAfterFailures: 3,
+
+    secureTempFiles: false,
+
+    maxConcurrentEncodes: 0,
+
+    forceDecoder: "",
+
+    verifyFrameCount: false,
+    maxConcurrentVerifications: 2,
+    maxConcurrentVmaf: 1,
+    qualityTier: '',
+    env: {},
+    globalFlags: [],
+    targetSizePercent: 60,
+    keepOnlyIfSmaller: false,
+    minSavingsPercent: 0,
+
     // Encoder filter settings
     showAllEncoders: false,
     showOnlyHwEncoders: false,
 
     // VMAF search optimization
     vmafSearchOptimization: false,
+    vmafSearchPooling: '',
+    crfSearchSegments: 3,
+    samplePreset: '',
+    sampleCrfOffset: 0,
 
     // Custom Command default
     customCommand: 'ffmpeg -i %INPUT% %OUTPUT%',
     firstRun: true
 };
+
+// Output container for a file: first matching containerMapping entry, else targetFormat
+// (mirrors CompressionConfig::output_format_for in the backend)
+export function resolveOutputFormat(path: string, settings: AppSettings): string {
+    const dot = path.lastIndexOf('.');
+    const sourceExt = dot >= 0 ? path.substring(dot + 1).toLowerCase() : '';
+    const mapping = (settings.containerMapping ?? []).find(
+        (m) => m.sourceExt.replace(/^\./, '').toLowerCase() === sourceExt
+    );
+    const format = mapping ? mapping.targetFormat : settings.targetFormat;
+    if (!format || format === 'original') {
+        return sourceExt || 'mp4';
+    }
+    return format.replace(/^\./, '').toLowerCase();
+}